@@ -0,0 +1,157 @@
+use crate::chess_board::Color;
+
+/// Rules that end a game early by the scores the engines report, so lost and dead drawn games do not use up the
+/// time of a match. Scores are in centipawns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adjudication {
+    /// A side loses when both engines see it at or below minus this score.
+    pub resign_score: Option<i32>,
+    /// Moves of each side the resign condition has to hold in a row.
+    pub resign_moves: usize,
+    /// A game is drawn when both engines see the score within this distance of 0.
+    pub draw_score: Option<i32>,
+    /// Moves of each side the draw condition has to hold in a row.
+    pub draw_moves: usize,
+    /// The draw condition is only counted after this move number.
+    pub draw_move_number: u32,
+    /// Games longer than this are drawn.
+    pub max_plies: usize,
+}
+
+/// Follows the scores of a game and tells when the adjudication rules end it.
+pub struct Adjudicator {
+    rules: Adjudication,
+    /// The last score of the engine playing White and of the one playing Black, both from White's point of view.
+    scores: [Option<i32>; 2],
+    /// The side both engines see lost and for how many plies in a row.
+    losing: Option<(Color, usize)>,
+    draw_plies: usize,
+}
+
+impl Adjudicator {
+    pub fn new(rules: Adjudication) -> Self {
+        Adjudicator {
+            rules,
+            scores: [None; 2],
+            losing: None,
+            draw_plies: 0,
+        }
+    }
+
+    /// Records the score the engine of the side to move sent for its move, from its own point of view, and returns
+    /// the result of the game from White's point of view if it is adjudicated. A move without a score breaks the
+    /// runs of both conditions.
+    pub fn record(&mut self, color: Color, move_number: u32, score: Option<i32>) -> Option<f64> {
+        self.scores[color as usize] = score.map(|score| match color {
+            Color::White => score,
+            Color::Black => -score,
+        });
+        let scores = match (score, self.scores) {
+            (Some(_), [Some(white), Some(black)]) => Some((white, black)),
+            _ => None,
+        };
+
+        let losing = scores.zip(self.rules.resign_score).and_then(|((white, black), limit)| {
+            if white <= -limit && black <= -limit {
+                Some(Color::White)
+            } else if white >= limit && black >= limit {
+                Some(Color::Black)
+            } else {
+                None
+            }
+        });
+        self.losing = match (losing, self.losing) {
+            (Some(losing), Some((previous, plies))) if losing == previous => Some((losing, plies + 1)),
+            (Some(losing), _) => Some((losing, 1)),
+            (None, _) => None,
+        };
+        if let Some((losing, plies)) = self.losing {
+            if plies >= 2 * self.rules.resign_moves {
+                return Some(match losing {
+                    Color::White => 0.0,
+                    Color::Black => 1.0,
+                });
+            }
+        }
+
+        let drawn = scores
+            .zip(self.rules.draw_score)
+            .is_some_and(|((white, black), limit)| {
+                move_number > self.rules.draw_move_number && white.abs() <= limit && black.abs() <= limit
+            });
+        self.draw_plies = if drawn { self.draw_plies + 1 } else { 0 };
+        (self.draw_plies >= 2 * self.rules.draw_moves).then_some(0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RULES: Adjudication = Adjudication {
+        resign_score: Some(600),
+        resign_moves: 2,
+        draw_score: Some(10),
+        draw_moves: 2,
+        draw_move_number: 40,
+        max_plies: 400,
+    };
+
+    /// Plays scores from the point of view of the side to move, White first, and returns the ply of the
+    /// adjudication with its result.
+    fn adjudicate(rules: Adjudication, move_number: u32, scores: &[Option<i32>]) -> Option<(usize, f64)> {
+        let mut adjudicator = Adjudicator::new(rules);
+        scores.iter().enumerate().find_map(|(ply, &score)| {
+            let color = if ply % 2 == 0 { Color::White } else { Color::Black };
+            let result = adjudicator.record(color, move_number + ply as u32 / 2, score);
+            result.map(|result| (ply, result))
+        })
+    }
+
+    #[test]
+    fn test_resign() {
+        // Black agrees with White that it is lost from its second move on
+        let scores = [
+            Some(700),
+            Some(-200),
+            Some(800),
+            Some(-650),
+            Some(900),
+            Some(-900),
+            Some(950),
+        ];
+        assert_eq!(adjudicate(RULES, 20, &scores), Some((6, 1.0)));
+        let scores = [Some(-700), Some(700), Some(-800), Some(800), Some(-900)];
+        assert_eq!(adjudicate(RULES, 20, &scores), Some((4, 0.0)));
+
+        // The run is broken by a disagreement or a move without a score
+        let scores = [
+            Some(700),
+            Some(-700),
+            Some(700),
+            Some(0),
+            Some(700),
+            Some(-700),
+            Some(700),
+        ];
+        assert_eq!(adjudicate(RULES, 20, &scores), None);
+        let scores = [Some(700), Some(-700), None, Some(-700), Some(700), Some(-700)];
+        assert_eq!(adjudicate(RULES, 20, &scores), None);
+
+        let rules = Adjudication {
+            resign_score: None,
+            ..RULES
+        };
+        assert_eq!(adjudicate(rules, 20, &[Some(-5_000); 20]), None);
+    }
+
+    #[test]
+    fn test_draw() {
+        let scores = [Some(5), Some(-3), Some(0), Some(10), Some(-8)];
+        assert_eq!(adjudicate(RULES, 41, &scores), Some((4, 0.5)));
+        // Only after the move number
+        assert_eq!(adjudicate(RULES, 30, &scores), None);
+        let scores = [Some(5), Some(-3), Some(50), Some(10), Some(-8)];
+        assert_eq!(adjudicate(RULES, 41, &scores), None);
+    }
+}
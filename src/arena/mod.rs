@@ -0,0 +1,218 @@
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::chess_board::game::Game;
+use crate::chess_board::pgn::game_result;
+use crate::chess_board::{ChessBoard, Color, Move};
+use adjudication::{Adjudication, Adjudicator};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use sprt::{MatchScore, Sprt, SprtDecision};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use uci_player::UciPlayer;
+
+pub mod adjudication;
+pub mod pgn;
+pub mod sprt;
+pub mod uci_player;
+
+pub struct ArenaConfig {
+    pub engines: [String; 2],
+    pub options: [Vec<String>; 2],
+    pub max_games: u32,
+    pub move_time: Duration,
+    /// Random plies from the start position, each opening is played with both colors.
+    pub random_plies: usize,
+    pub seed: u64,
+    pub sprt: Option<Sprt>,
+    pub adjudication: Adjudication,
+    /// The games are appended to this PGN file.
+    pub pgn: Option<PathBuf>,
+}
+
+/// The score of White in a game with the result.
+fn white_score(result: &str) -> f64 {
+    match result {
+        "1-0" => 1.0,
+        "0-1" => 0.0,
+        _ => 0.5,
+    }
+}
+
+/// Sets the result of the game and how it ended in the Termination tag.
+fn finish(mut game: Game, result: &str, termination: &str) -> Game {
+    game.result = result.to_string();
+    game.set_tag("Termination", termination);
+    game
+}
+
+/// Plays a game from the opening and returns its record. The moves of the engines are commented with their
+/// evaluation and the time left of the move time.
+fn play_game(
+    white: &mut UciPlayer,
+    black: &mut UciPlayer,
+    opening: &[String],
+    move_time: Duration,
+    adjudication: Adjudication,
+) -> Result<Game, String> {
+    white.new_game()?;
+    black.new_game()?;
+    let mut game = Game::new(INITIAL_POSITION)?;
+    game.set_tag("White", &white.name);
+    game.set_tag("Black", &black.name);
+    game.set_tag("TimeControl", &pgn::time_control(move_time));
+    let mut board = ChessBoard::from_fen(INITIAL_POSITION)?;
+    let mut moves = opening.to_vec();
+    for mv in opening {
+        let mv = Move::from_algebraic(mv);
+        board.make_move(mv);
+        game.push_move(mv);
+    }
+
+    let mut adjudicator = Adjudicator::new(adjudication);
+    while moves.len() < adjudication.max_plies {
+        let result = game_result(&board);
+        if result != "*" {
+            return Ok(finish(game, result, "normal"));
+        }
+        let color = board.active_color;
+        let player = match color {
+            Color::White => &mut *white,
+            Color::Black => &mut *black,
+        };
+        let started = Instant::now();
+        let (best_move, score) = player.best_move(INITIAL_POSITION, &moves, move_time)?;
+        let clock = move_time.saturating_sub(started.elapsed());
+        let Ok(mv) = board.legal_move_from_algebraic(&best_move) else {
+            // An illegal move loses the game
+            println!("{} played the illegal move {}", player.name, best_move);
+            let comment = Some(format!("{} played the illegal move {}", player.name, best_move));
+            match game.moves.last_mut() {
+                Some(last) => last.comment = comment,
+                None => game.comment = comment,
+            }
+            let result = if color == Color::White { "0-1" } else { "1-0" };
+            return Ok(finish(game, result, "rules infraction"));
+        };
+        let adjudicated = adjudicator.record(color, board.fullmove_number, score);
+        board.make_move(mv);
+        moves.push(best_move);
+        game.push_move(mv);
+        let white_score = score.map(|score| if color == Color::White { score } else { -score });
+        game.moves.last_mut().unwrap().comment = Some(pgn::move_comment(white_score, clock));
+        // A game that just ended is scored by the rules
+        if let Some(result) = adjudicated.filter(|_| game_result(&board) == "*") {
+            let result = match result {
+                r if r > 0.5 => "1-0",
+                r if r < 0.5 => "0-1",
+                _ => "1/2-1/2",
+            };
+            return Ok(finish(game, result, "adjudication"));
+        }
+    }
+    let result = game_result(&board);
+    if result != "*" {
+        return Ok(finish(game, result, "normal"));
+    }
+    Ok(finish(game, "1/2-1/2", "adjudication"))
+}
+
+fn append_pgn(path: &Path, game: &Game) -> Result<(), String> {
+    let pgn = game.to_pgn()?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", pgn))
+        .map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+fn random_opening(rng: &mut Pcg64, plies: usize) -> Vec<String> {
+    let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+    let mut opening = Vec::new();
+    for _ in 0..plies {
+        match board.generate_legal_moves().choose(rng) {
+            Some(&mv) => {
+                board.make_move(mv);
+                opening.push(mv.as_algebraic());
+            }
+            None => break,
+        }
+    }
+    opening
+}
+
+fn print_status(config: &ArenaConfig, score: &MatchScore) {
+    let elo = match score.elo() {
+        Some((elo, margin)) => format!("{:+.1} +/- {:.1}", elo, margin),
+        None => String::from("n/a"),
+    };
+    let mut status = format!(
+        "Games: {} W: {} L: {} D: {} Elo: {}",
+        score.games(),
+        score.wins,
+        score.losses,
+        score.draws,
+        elo
+    );
+    if let Some(sprt) = &config.sprt {
+        let (lower, upper) = sprt.bounds();
+        status.push_str(&format!(" LLR: {:.2} ({:.2}, {:.2})", sprt.llr(score), lower, upper));
+    }
+    println!("{}", status);
+}
+
+/// Plays the first engine against the second until the game limit or an SPRT decision and returns the score
+/// of the first engine.
+pub fn run_match(config: &ArenaConfig) -> Result<MatchScore, String> {
+    let mut first = UciPlayer::start(&config.engines[0], &config.options[0])?;
+    let mut second = UciPlayer::start(&config.engines[1], &config.options[1])?;
+    println!("{} vs {}", first.name, second.name);
+
+    let mut rng = Pcg64::seed_from_u64(config.seed);
+    let mut score = MatchScore::default();
+    let mut opening = Vec::new();
+    let date = pgn::date(SystemTime::now());
+    for game in 0..config.max_games {
+        let first_is_white = game % 2 == 0;
+        if first_is_white {
+            opening = random_opening(&mut rng, config.random_plies);
+        }
+        let mut record = if first_is_white {
+            play_game(&mut first, &mut second, &opening, config.move_time, config.adjudication)?
+        } else {
+            play_game(&mut second, &mut first, &opening, config.move_time, config.adjudication)?
+        };
+        if let Some(path) = &config.pgn {
+            record.set_tag("Event", "chic arena");
+            record.set_tag("Date", &date);
+            record.set_tag("Round", &(game + 1).to_string());
+            append_pgn(path, &record)?;
+        }
+        let white_score = white_score(&record.result);
+        let first_score = if first_is_white { white_score } else { 1.0 - white_score };
+        match first_score {
+            s if s > 0.5 => score.wins += 1,
+            s if s < 0.5 => score.losses += 1,
+            _ => score.draws += 1,
+        }
+        print_status(config, &score);
+
+        if let Some(sprt) = &config.sprt {
+            match sprt.decision(&score) {
+                SprtDecision::AcceptH0 => {
+                    println!("SPRT: H0 accepted");
+                    break;
+                }
+                SprtDecision::AcceptH1 => {
+                    println!("SPRT: H1 accepted");
+                    break;
+                }
+                SprtDecision::Continue => {}
+            }
+        }
+    }
+    Ok(score)
+}
@@ -0,0 +1,109 @@
+//! The annotations of the match games in the PGN: the tags of a match and the comments with the evaluation and the
+//! clock of the engine after every move, in the [%eval] and [%clk] commands that PGN viewers show as graph and clock.
+use super::uci_player::MATE_SCORE;
+use std::time::{Duration, SystemTime};
+
+/// Scores up to this far below MATE_SCORE are mates, in moves of the distance to it. Engines that send mates as
+/// centipawns beyond it are shown as they sent them.
+const MATE_DISTANCE_LIMIT: i32 = 1000;
+
+/// The TimeControl tag of a fixed time per move: one move per period of that many seconds.
+pub fn time_control(move_time: Duration) -> String {
+    format!("1/{}", format_seconds(move_time))
+}
+
+fn format_seconds(time: Duration) -> String {
+    let seconds = format!("{:.3}", time.as_secs_f64());
+    seconds.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// The comment of a move: the evaluation from White's point of view in pawns, "#3" for a mate in three and "#-3"
+/// for being mated, and the time left on the clock after the move.
+pub fn move_comment(white_score: Option<i32>, clock: Duration) -> String {
+    let mut comment = String::new();
+    if let Some(score) = white_score {
+        comment.push_str(&format!("[%eval {}] ", format_eval(score)));
+    }
+    comment.push_str(&format!("[%clk {}]", format_clock(clock)));
+    comment
+}
+
+fn format_eval(score: i32) -> String {
+    let distance = MATE_SCORE - score.abs();
+    if (0..MATE_DISTANCE_LIMIT).contains(&distance) {
+        let sign = if score < 0 { "-" } else { "" };
+        format!("#{}{}", sign, distance)
+    } else {
+        format!("{:.2}", score as f64 / 100.0)
+    }
+}
+
+/// Hours, minutes and seconds with milliseconds, the move times of fast matches are below a second.
+fn format_clock(clock: Duration) -> String {
+    let seconds = clock.as_secs();
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        clock.subsec_millis()
+    )
+}
+
+/// The Date tag of the day in UTC, "YYYY.MM.DD".
+pub fn date(time: SystemTime) -> String {
+    let days = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86_400;
+    // The civil calendar from the days since 1970-01-01, by eras of 400 years starting on March 1
+    let days = days as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_comment() {
+        assert_eq!(
+            move_comment(Some(35), Duration::from_millis(40)),
+            "[%eval 0.35] [%clk 0:00:00.040]"
+        );
+        assert_eq!(
+            move_comment(Some(-120), Duration::from_millis(3_725_400)),
+            "[%eval -1.20] [%clk 1:02:05.400]"
+        );
+        assert_eq!(
+            move_comment(Some(MATE_SCORE - 3), Duration::ZERO),
+            "[%eval #3] [%clk 0:00:00.000]"
+        );
+        assert_eq!(format_eval(-(MATE_SCORE - 2)), "#-2");
+        assert_eq!(format_eval(1_000_000), "10000.00");
+        assert_eq!(move_comment(None, Duration::from_secs(2)), "[%clk 0:00:02.000]");
+    }
+
+    #[test]
+    fn test_tags() {
+        assert_eq!(time_control(Duration::from_millis(100)), "1/0.1");
+        assert_eq!(time_control(Duration::from_secs(2)), "1/2");
+        let day = |days: u64| date(SystemTime::UNIX_EPOCH + Duration::from_secs(days * 86_400 + 3600));
+        assert_eq!(day(0), "1970.01.01");
+        assert_eq!(day(11_016), "2000.02.29");
+        assert_eq!(day(20_000), "2024.10.04");
+    }
+}
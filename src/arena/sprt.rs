@@ -0,0 +1,152 @@
+/// Sequential probability ratio test between the hypotheses "Elo difference is elo0" and "is elo1".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtDecision {
+    AcceptH0,
+    AcceptH1,
+    Continue,
+}
+
+/// Wins, draws and losses from the point of view of the first engine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl MatchScore {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Mean score and its variance per game.
+    fn mean_and_variance(&self) -> Option<(f64, f64)> {
+        let games = self.games() as f64;
+        if games == 0.0 {
+            return None;
+        }
+        let win_rate = self.wins as f64 / games;
+        let draw_rate = self.draws as f64 / games;
+        let loss_rate = self.losses as f64 / games;
+        let mean = win_rate + draw_rate / 2.0;
+        let variance = win_rate * (1.0 - mean).powi(2) + draw_rate * (0.5 - mean).powi(2) + loss_rate * mean.powi(2);
+        Some((mean, variance))
+    }
+
+    /// Elo difference with the 95% error margin. None until both a win and a loss happened.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        if self.wins == 0 || self.losses == 0 {
+            return None;
+        }
+        let (mean, variance) = self.mean_and_variance()?;
+        let margin = 1.96 * (variance / self.games() as f64).sqrt();
+        let elo = score_to_elo(mean);
+        let upper = score_to_elo((mean + margin).min(1.0 - f64::EPSILON));
+        let lower = score_to_elo((mean - margin).max(f64::EPSILON));
+        Some((elo, (upper - lower) / 2.0))
+    }
+}
+
+impl Sprt {
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Log likelihood ratio in the normal approximation of the trinomial model.
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        if score.wins == 0 || score.losses == 0 {
+            return 0.0;
+        }
+        let Some((mean, variance)) = score.mean_and_variance() else {
+            return 0.0;
+        };
+        let variance_of_mean = variance / score.games() as f64;
+        let (score0, score1) = (elo_to_score(self.elo0), elo_to_score(self.elo1));
+        (score1 - score0) * (2.0 * mean - score0 - score1) / (2.0 * variance_of_mean)
+    }
+
+    pub fn decision(&self, score: &MatchScore) -> SprtDecision {
+        let (lower, upper) = self.bounds();
+        let llr = self.llr(score);
+        if llr >= upper {
+            SprtDecision::AcceptH1
+        } else if llr <= lower {
+            SprtDecision::AcceptH0
+        } else {
+            SprtDecision::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPRT: Sprt = Sprt {
+        elo0: 0.0,
+        elo1: 10.0,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+
+    #[test]
+    fn test_elo() {
+        let even = MatchScore {
+            wins: 30,
+            draws: 40,
+            losses: 30,
+        };
+        let (elo, margin) = even.elo().unwrap();
+        assert!(elo.abs() < 1e-9);
+        assert!(margin > 0.0);
+
+        let better = MatchScore {
+            wins: 60,
+            draws: 20,
+            losses: 20,
+        };
+        let (elo, _) = better.elo().unwrap();
+        assert!((elo - score_to_elo(0.7)).abs() < 1e-9);
+        assert!(MatchScore::default().elo().is_none());
+    }
+
+    #[test]
+    fn test_sprt() {
+        let (lower, upper) = SPRT.bounds();
+        assert!((lower + 2.944).abs() < 1e-3);
+        assert!((upper - 2.944).abs() < 1e-3);
+
+        assert_eq!(SPRT.decision(&MatchScore::default()), SprtDecision::Continue);
+        let strong = MatchScore {
+            wins: 400,
+            draws: 200,
+            losses: 200,
+        };
+        assert_eq!(SPRT.decision(&strong), SprtDecision::AcceptH1);
+        let weak = MatchScore {
+            wins: 200,
+            draws: 200,
+            losses: 400,
+        };
+        assert_eq!(SPRT.decision(&weak), SprtDecision::AcceptH0);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::engines::uci_client::{SearchLimit, UciClient};
+use std::time::Duration;
+
+pub use crate::engines::uci_client::MATE_SCORE;
+
+/// An external engine playing arena games, searching every move for a fixed time.
+pub struct UciPlayer {
+    pub name: String,
+    client: UciClient,
+}
+
+impl UciPlayer {
+    /// Starts the engine command, e.g. "target/release/chic-engine", and sets the given "Name=Value" options.
+    pub fn start(command: &str, options: &[String]) -> Result<Self, String> {
+        let mut client = UciClient::start(command)?;
+        client.set_options(options)?;
+        Ok(UciPlayer {
+            name: client.name.clone(),
+            client,
+        })
+    }
+
+    pub fn new_game(&mut self) -> Result<(), String> {
+        self.client.new_game()
+    }
+
+    /// Searches the position for a fixed time and returns the best move in coordinate notation with the last score
+    /// the engine sent, in centipawns from the point of view of the side to move.
+    pub fn best_move(
+        &mut self,
+        start_fen: &str,
+        moves: &[String],
+        move_time: Duration,
+    ) -> Result<(String, Option<i32>), String> {
+        let best_move = self
+            .client
+            .search(start_fen, moves, SearchLimit::MoveTime(move_time), |_| {})?;
+        let score = best_move
+            .info
+            .and_then(|info| info.score)
+            .map(|score| score.centipawns());
+        Ok((best_move.mv, score))
+    }
+}
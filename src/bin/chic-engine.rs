@@ -1,11 +1,21 @@
-use crate::engines::uci::run_uci_interface;
-
-#[path = "../engines/mod.rs"]
-mod engines;
-#[path = "../chess_board/mod.rs"]
-mod chess_board;
-
+use chic::config::{self, Config};
+use chic::engines::eval_params::EvalParams;
+use chic::engines::protocol_log;
+use chic::engines::uci::run_uci_interface;
+use clap::{arg, Command};
 
 fn main() {
-            run_uci_interface();
-}
\ No newline at end of file
+    let matches = Command::new("chic-engine")
+        .about("The chic engine speaking UCI")
+        .arg(arg!(--log <FILE> "Records the protocol transcript and search events in this file"))
+        .args(config::args())
+        .get_matches();
+    let config = Config::from_matches_or_default(&matches);
+    if let Some(path) = matches.get_one::<String>("log") {
+        if let Err(e) = protocol_log::open(path) {
+            println!("{}", e);
+            return;
+        }
+    }
+    run_uci_interface(EvalParams::default(), &config.engine);
+}
@@ -0,0 +1,255 @@
+//! The four castlings with the squares they involve, and the castling rights of a position. Move generation, move
+//! validation, making moves, the FEN and the Zobrist hash all read the squares from here, so they cannot disagree.
+use super::{ChessField, Color, Move};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One of the four castlings, in FEN order K, Q, k, q.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Castling {
+    WhiteKingside,
+    WhiteQueenside,
+    BlackKingside,
+    BlackQueenside,
+}
+
+impl Castling {
+    pub const ALL: [Castling; 4] = [
+        Castling::WhiteKingside,
+        Castling::WhiteQueenside,
+        Castling::BlackKingside,
+        Castling::BlackQueenside,
+    ];
+
+    pub fn new(color: Color, kingside: bool) -> Self {
+        match (color, kingside) {
+            (Color::White, true) => Castling::WhiteKingside,
+            (Color::White, false) => Castling::WhiteQueenside,
+            (Color::Black, true) => Castling::BlackKingside,
+            (Color::Black, false) => Castling::BlackQueenside,
+        }
+    }
+
+    /// The castling of a king move from its initial square two squares along the back rank.
+    pub fn from_king_move(color: Color, mv: Move) -> Option<Self> {
+        [Castling::new(color, true), Castling::new(color, false)]
+            .into_iter()
+            .find(|castling| mv.from == castling.king_from() && mv.to == castling.king_to())
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Castling::WhiteKingside | Castling::WhiteQueenside => Color::White,
+            Castling::BlackKingside | Castling::BlackQueenside => Color::Black,
+        }
+    }
+
+    pub fn is_kingside(self) -> bool {
+        matches!(self, Castling::WhiteKingside | Castling::BlackKingside)
+    }
+
+    /// The letter of the castling in the FEN.
+    pub fn letter(self) -> char {
+        ['K', 'Q', 'k', 'q'][self as usize]
+    }
+
+    fn rank(self) -> usize {
+        match self.color() {
+            Color::White => 0,
+            Color::Black => 7,
+        }
+    }
+
+    pub fn king_from(self) -> ChessField {
+        ChessField::new(self.rank(), 4)
+    }
+
+    pub fn king_to(self) -> ChessField {
+        ChessField::new(self.rank(), if self.is_kingside() { 6 } else { 2 })
+    }
+
+    pub fn rook_from(self) -> ChessField {
+        ChessField::new(self.rank(), if self.is_kingside() { 7 } else { 0 })
+    }
+
+    pub fn rook_to(self) -> ChessField {
+        ChessField::new(self.rank(), if self.is_kingside() { 5 } else { 3 })
+    }
+
+    /// The squares between the king and the rook, which have to be empty.
+    pub fn empty_squares(self) -> impl Iterator<Item = ChessField> {
+        let rank = self.rank();
+        let cols = if self.is_kingside() { 5..7 } else { 1..4 };
+        cols.map(move |col| ChessField::new(rank, col))
+    }
+
+    /// The squares the king starts on, passes and lands on, none of them may be attacked. The rook's square and, on
+    /// the queen side, the square next to it may be.
+    pub fn king_path(self) -> [ChessField; 3] {
+        let rank = self.rank();
+        let cols = if self.is_kingside() { [4, 5, 6] } else { [4, 3, 2] };
+        cols.map(|col| ChessField::new(rank, col))
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The castlings both colors still have the right to. A right only says that neither the king nor the rook has moved,
+/// whether the castling is possible in the position depends on the squares in between and the attacks on the king's
+/// path. The rights are serialized as the castling field of a FEN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct CastlingRights(u8);
+
+impl CastlingRights {
+    pub const NONE: CastlingRights = CastlingRights(0);
+    pub const ALL: CastlingRights = CastlingRights(0b1111);
+
+    pub fn has(self, castling: Castling) -> bool {
+        self.0 & castling.bit() != 0
+    }
+
+    pub fn set(&mut self, castling: Castling, allowed: bool) {
+        if allowed {
+            self.0 |= castling.bit();
+        } else {
+            self.0 &= !castling.bit();
+        }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The castlings with the right, in FEN order.
+    pub fn iter(self) -> impl Iterator<Item = Castling> {
+        Castling::ALL.into_iter().filter(move |&castling| self.has(castling))
+    }
+
+    /// Revokes the rights a move ends: the king leaving its initial square, a rook leaving its corner or a piece
+    /// capturing on it.
+    pub fn update(&mut self, mv: Move) {
+        if self.is_empty() {
+            return;
+        }
+        for castling in Castling::ALL {
+            if [castling.king_from(), castling.rook_from()].contains(&mv.from) || mv.to == castling.rook_from() {
+                self.set(castling, false);
+            }
+        }
+    }
+
+    /// The rights with the colors swapped, for the position with colors and ranks flipped.
+    pub fn mirror(self) -> CastlingRights {
+        CastlingRights((self.0 >> 2) | ((self.0 & 0b11) << 2))
+    }
+
+    /// Parses the castling field of a FEN, "-" or letters of KQkq.
+    pub fn from_fen(field: &str) -> Option<CastlingRights> {
+        if field == "-" {
+            return Some(CastlingRights::NONE);
+        }
+        if field.is_empty() {
+            return None;
+        }
+        let mut rights = CastlingRights::NONE;
+        for letter in field.chars() {
+            let castling = Castling::ALL.into_iter().find(|castling| castling.letter() == letter)?;
+            rights.set(castling, true);
+        }
+        Some(rights)
+    }
+}
+
+/// The castling field of a FEN.
+impl fmt::Display for CastlingRights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "-");
+        }
+        self.iter().try_for_each(|castling| write!(f, "{}", castling.letter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squares(fields: impl IntoIterator<Item = ChessField>) -> Vec<String> {
+        fields.into_iter().map(|field| field.as_algebraic()).collect()
+    }
+
+    #[test]
+    fn test_castling_squares() {
+        let castling = Castling::BlackQueenside;
+        assert_eq!(
+            (castling.color(), castling.is_kingside(), castling.letter()),
+            (Color::Black, false, 'q')
+        );
+        assert_eq!(
+            squares([
+                castling.king_from(),
+                castling.king_to(),
+                castling.rook_from(),
+                castling.rook_to()
+            ]),
+            ["e8", "c8", "a8", "d8"]
+        );
+        // b8 has to be empty but may be attacked
+        assert_eq!(squares(castling.empty_squares()), ["b8", "c8", "d8"]);
+        assert_eq!(squares(castling.king_path()), ["e8", "d8", "c8"]);
+        assert_eq!(squares(Castling::WhiteKingside.empty_squares()), ["f1", "g1"]);
+        assert_eq!(squares(Castling::WhiteKingside.king_path()), ["e1", "f1", "g1"]);
+
+        assert_eq!(
+            Castling::from_king_move(Color::White, Move::from_algebraic("e1g1")),
+            Some(Castling::WhiteKingside)
+        );
+        assert_eq!(
+            Castling::from_king_move(Color::Black, Move::from_algebraic("e1g1")),
+            None
+        );
+        assert_eq!(
+            Castling::from_king_move(Color::White, Move::from_algebraic("e1f1")),
+            None
+        );
+        for castling in Castling::ALL {
+            assert_eq!(Castling::new(castling.color(), castling.is_kingside()), castling);
+        }
+    }
+
+    #[test]
+    fn test_castling_rights() {
+        let mut rights = CastlingRights::from_fen("Kq").unwrap();
+        assert!(rights.has(Castling::WhiteKingside) && rights.has(Castling::BlackQueenside));
+        assert!(!rights.has(Castling::WhiteQueenside) && !rights.has(Castling::BlackKingside));
+        assert_eq!(rights.to_string(), "Kq");
+        assert_eq!(rights.mirror().to_string(), "Qk");
+        assert_eq!(CastlingRights::ALL.mirror(), CastlingRights::ALL);
+        assert_eq!(CastlingRights::from_fen("-"), Some(CastlingRights::NONE));
+        assert_eq!(CastlingRights::NONE.to_string(), "-");
+        assert_eq!(CastlingRights::from_fen("KX"), None);
+        assert_eq!(CastlingRights::from_fen(""), None);
+
+        // A capture on a8 ends the queen side castling of Black, a king move both castlings of its color
+        rights.update(Move::from_algebraic("f3a8"));
+        assert_eq!(rights.to_string(), "K");
+        let mut rights = CastlingRights::ALL;
+        rights.update(Move::from_algebraic("e8e7"));
+        rights.update(Move::from_algebraic("h1h5"));
+        assert_eq!(rights.to_string(), "Q");
+        rights.set(Castling::BlackKingside, true);
+        assert_eq!(
+            rights.iter().collect::<Vec<_>>(),
+            [Castling::WhiteQueenside, Castling::BlackKingside]
+        );
+    }
+}
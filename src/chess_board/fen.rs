@@ -1,41 +1,136 @@
-use super::{ChessBoard, ChessField, Color, Piece, PieceType, Square};
+use super::castling::CastlingRights;
+use super::{to_algebraic_square, ChessBoard, ChessField, Color, Piece, PieceType, Square};
+use std::fmt;
 
 pub const INITIAL_POSITION: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-/// Parses a square like "e3" into (file, rank).
-fn parse_square(square: &str) -> Result<ChessField, String> {
-    if square.len() != 2 {
-        return Err(format!("Invalid square: {}", square));
+/// Why a FEN string was rejected. Ranks are numbered 1 to 8 as in the FEN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    InvalidPiece(char),
+    RankTooLong(usize),
+    RankTooShort(usize),
+    InvalidActiveColor(String),
+    InvalidCastlingRights(String),
+    InvalidSquare(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    MissingKing(Color),
+    TooManyKings(Color),
+    TooManyPawns(Color),
+    PawnOnBackRank(ChessField),
+    /// The side that just moved left its king in check.
+    OpponentInCheck,
+    /// A castling right without the king and rook on their initial squares.
+    CastlingWithoutPieces(char),
+    /// An en passant square without the pawn that just made a double step.
+    InvalidEnPassant(ChessField),
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::WrongFieldCount(count) => write!(f, "expected 6 fields, found {}", count),
+            FenError::WrongRankCount(count) => write!(f, "expected 8 ranks, found {}", count),
+            FenError::InvalidPiece(c) => write!(f, "invalid piece character '{}'", c),
+            FenError::RankTooLong(rank) => write!(f, "rank {} has more than 8 squares", rank),
+            FenError::RankTooShort(rank) => write!(f, "rank {} has less than 8 squares", rank),
+            FenError::InvalidActiveColor(color) => write!(f, "invalid active color '{}'", color),
+            FenError::InvalidCastlingRights(rights) => write!(f, "invalid castling rights '{}'", rights),
+            FenError::InvalidSquare(square) => write!(f, "invalid square '{}'", square),
+            FenError::InvalidHalfmoveClock(clock) => write!(f, "halfmove clock is not a valid number: {}", clock),
+            FenError::InvalidFullmoveNumber(number) => {
+                write!(f, "fullmove number is not a valid number: {}", number)
+            }
+            FenError::MissingKing(color) => write!(f, "{:?} has no king", color),
+            FenError::TooManyKings(color) => write!(f, "{:?} has more than one king", color),
+            FenError::TooManyPawns(color) => write!(f, "{:?} has more than 8 pawns", color),
+            FenError::PawnOnBackRank(field) => write!(f, "pawn on {}", to_algebraic_square(field.row(), field.col())),
+            FenError::OpponentInCheck => write!(f, "the side not to move is in check"),
+            FenError::CastlingWithoutPieces(right) => {
+                write!(f, "castling right '{}' without king and rook on their squares", right)
+            }
+            FenError::InvalidEnPassant(field) => write!(
+                f,
+                "en passant square {} does not follow a double pawn step",
+                to_algebraic_square(field.row(), field.col())
+            ),
+        }
     }
-    let file = square.chars().next().unwrap() as usize;
-    let rank = square.chars().nth(1).unwrap() as usize;
-    if ('a'..='h').contains(&(file as u8 as char)) && ('1'..='8').contains(&(rank as u8 as char)) {
-        Ok(ChessField::new(rank - '1' as usize, file - 'a' as usize))
-    } else {
-        Err(format!("Invalid square: {}", square))
+}
+
+impl std::error::Error for FenError {}
+
+impl From<FenError> for String {
+    fn from(error: FenError) -> Self {
+        format!("Invalid FEN string: {}", error)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FenMode {
+    /// Only the syntax is checked. Positions without kings are accepted and the move counters may be missing.
+    Lenient,
+    /// The position must be playable, see validate().
+    Strict,
+}
+
+/// Parses an en passant square like "e3".
+fn parse_square(square: &str) -> Result<ChessField, FenError> {
+    ChessField::parse_algebraic(square).ok_or_else(|| FenError::InvalidSquare(square.to_string()))
+}
+
+/// Parses a FEN string and sets up a ChessBoard, without checking that the position is legal.
+pub fn from_fen(fen: &str) -> Result<ChessBoard, FenError> {
+    from_fen_with_mode(fen, FenMode::Lenient)
+}
+
 /// Parses a FEN string and sets up a ChessBoard.
-pub fn from_fen(fen: &str) -> Result<ChessBoard, String> {
+pub fn from_fen_with_mode(fen: &str, mode: FenMode) -> Result<ChessBoard, FenError> {
+    let mut board = parse(fen, mode)?;
+    if mode == FenMode::Strict {
+        validate(&board)?;
+    }
+    board.clear_unusable_en_passant();
+    Ok(board)
+}
+
+/// Every reason why the FEN is not a playable position, none for a valid one. A syntax error is reported alone, the
+/// position cannot be checked without it.
+pub fn issues(fen: &str) -> Vec<FenError> {
+    match parse(fen, FenMode::Strict) {
+        Ok(board) => position_issues(&board),
+        Err(e) => vec![e],
+    }
+}
+
+/// Reads the fields of the FEN, the position is neither checked nor is an unusable en passant square dropped.
+fn parse(fen: &str, mode: FenMode) -> Result<ChessBoard, FenError> {
     let mut board = ChessBoard::new();
-    let parts: Vec<&str> = fen.split(' ').collect();
-    if parts.len() != 6 {
-        return Err(String::from("Invalid FEN string: must have 6 parts."));
+    let parts: Vec<&str> = fen.split_whitespace().collect();
+    let field_count_ok = match mode {
+        FenMode::Lenient => (4..=6).contains(&parts.len()),
+        FenMode::Strict => parts.len() == 6,
+    };
+    if !field_count_ok {
+        return Err(FenError::WrongFieldCount(parts.len()));
     }
 
     // Parse board squares
     let rows: Vec<&str> = parts[0].split('/').collect();
     if rows.len() != 8 {
-        return Err(String::from("Invalid FEN string: expected 8 rows"));
+        return Err(FenError::WrongRankCount(rows.len()));
     }
 
     for (row_index, row) in rows.iter().enumerate() {
+        let rank = 8 - row_index;
         let mut col_index = 0;
 
         for c in row.chars() {
             if col_index > 7 {
-                return Err(String::from("Invalid FEN string: too many columns"));
+                return Err(FenError::RankTooLong(rank));
             }
             if c.is_ascii_digit() {
                 col_index += c.to_digit(10).unwrap() as usize;
@@ -60,12 +155,15 @@ pub fn from_fen(fen: &str) -> Result<ChessBoard, String> {
                     board.squares[7 - row_index][col_index] = Square::Occupied(Piece { color, kind });
                     col_index += 1;
                 } else {
-                    return Err(format!("Invalid piece character in FEN string: {}", c));
+                    return Err(FenError::InvalidPiece(c));
                 }
             }
         }
         if col_index > 8 {
-            return Err(format!("Too many squares in row {} when parsing FEN", row_index));
+            return Err(FenError::RankTooLong(rank));
+        }
+        if col_index < 8 && mode == FenMode::Strict {
+            return Err(FenError::RankTooShort(rank));
         }
     }
 
@@ -73,16 +171,12 @@ pub fn from_fen(fen: &str) -> Result<ChessBoard, String> {
     board.active_color = match parts[1] {
         "w" => Color::White,
         "b" => Color::Black,
-        _ => return Err(String::from("Invalid FEN string: invalid active color.")),
+        _ => return Err(FenError::InvalidActiveColor(parts[1].to_string())),
     };
 
     // Parse castling rights
-    board.castling_rights = [
-        parts[2].contains('K'), // White king-side castling
-        parts[2].contains('Q'), // White queen-side castling
-        parts[2].contains('k'), // Black king-side castling
-        parts[2].contains('q'), // Black queen-side castling
-    ];
+    board.castling_rights =
+        CastlingRights::from_fen(parts[2]).ok_or_else(|| FenError::InvalidCastlingRights(parts[2].to_string()))?;
 
     // Parse en passant square
     board.en_passant = if parts[3] == "-" {
@@ -92,21 +186,163 @@ pub fn from_fen(fen: &str) -> Result<ChessBoard, String> {
     };
 
     // Parse halfmove clock
-    board.halfmove_clock = parts[4]
-        .parse::<u32>()
-        .map_err(|_| format!("Invalid FEN string: halfmove clock is not a valid number: {}", parts[4]))?;
+    if let Some(halfmove_clock) = parts.get(4) {
+        board.halfmove_clock = halfmove_clock
+            .parse::<u32>()
+            .map_err(|_| FenError::InvalidHalfmoveClock(halfmove_clock.to_string()))?;
+    }
 
     // Parse fullmove number
-    board.fullmove_number = parts[5].parse::<u32>().map_err(|_| {
-        format!(
-            "Invalid FEN string: fullmove number is not a valid number: {}",
-            parts[5]
-        )
-    })?;
+    if let Some(fullmove_number) = parts.get(5) {
+        board.fullmove_number = fullmove_number
+            .parse::<u32>()
+            .map_err(|_| FenError::InvalidFullmoveNumber(fullmove_number.to_string()))?;
+    }
 
+    board.refresh_material();
     Ok(board)
 }
 
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+/// Checks that the position can occur in a game: one king per side, at most 8 pawns per side and none on the first
+/// or last rank, the side that just moved is not in check and castling rights and en passant square match the
+/// pieces.
+pub fn validate(board: &ChessBoard) -> Result<(), FenError> {
+    match position_issues(board).into_iter().next() {
+        Some(issue) => Err(issue),
+        None => Ok(()),
+    }
+}
+
+/// All violations of the rules checked by validate, in the order validate checks them.
+pub fn position_issues(board: &ChessBoard) -> Vec<FenError> {
+    let mut issues = Vec::new();
+    for color in [Color::White, Color::Black] {
+        let count = |kind: PieceType| {
+            board
+                .squares
+                .iter()
+                .flatten()
+                .filter(|&&square| square == Square::Occupied(Piece { color, kind }))
+                .count()
+        };
+        match count(PieceType::King) {
+            0 => issues.push(FenError::MissingKing(color)),
+            1 => {}
+            _ => issues.push(FenError::TooManyKings(color)),
+        }
+        if count(PieceType::Pawn) > 8 {
+            issues.push(FenError::TooManyPawns(color));
+        }
+    }
+    let kings_valid = issues.is_empty();
+
+    for row in [0, 7] {
+        for col in 0..8 {
+            if let Square::Occupied(Piece {
+                kind: PieceType::Pawn, ..
+            }) = board.squares[row][col]
+            {
+                issues.push(FenError::PawnOnBackRank(ChessField::new(row, col)));
+            }
+        }
+    }
+
+    // Without exactly one king per side there is no check to look for
+    if let Some(opponent_king) = board
+        .find_king_position(opponent(board.active_color))
+        .filter(|_| kings_valid)
+    {
+        if !board.attackers_to(opponent_king, board.active_color).is_empty() {
+            issues.push(FenError::OpponentInCheck);
+        }
+    }
+
+    for castling in board.castling_rights.iter() {
+        if !board.has_castling_pieces(castling) {
+            issues.push(FenError::CastlingWithoutPieces(castling.letter()));
+        }
+    }
+
+    if let Some(field) = board.en_passant {
+        // The pawn moved from the row behind the square to the row in front of it
+        let (expected_row, pawn_row, start_row) = match board.active_color {
+            Color::White => (5, 4, 6),
+            Color::Black => (2, 3, 1),
+        };
+        let moved = opponent(board.active_color);
+        if field.row() != expected_row
+            || board.squares[pawn_row][field.col()]
+                != Square::Occupied(Piece {
+                    color: moved,
+                    kind: PieceType::Pawn,
+                })
+            || board.squares[field.row()][field.col()] != Square::Empty
+            || board.squares[start_row][field.col()] != Square::Empty
+        {
+            issues.push(FenError::InvalidEnPassant(field));
+        }
+    }
+    issues
+}
+
+/// Serializes a ChessBoard into a FEN string.
+pub fn to_fen(board: &ChessBoard) -> String {
+    let mut rows = Vec::with_capacity(8);
+    for row in (0..8).rev() {
+        let mut row_string = String::new();
+        let mut empty_squares = 0;
+        for col in 0..8 {
+            match board.squares[row][col] {
+                Square::Empty => empty_squares += 1,
+                Square::Occupied(piece) => {
+                    if empty_squares > 0 {
+                        row_string.push_str(&empty_squares.to_string());
+                        empty_squares = 0;
+                    }
+                    let c = piece.kind.to_string().chars().next().unwrap();
+                    row_string.push(match piece.color {
+                        Color::White => c,
+                        Color::Black => c.to_ascii_lowercase(),
+                    });
+                }
+            }
+        }
+        if empty_squares > 0 {
+            row_string.push_str(&empty_squares.to_string());
+        }
+        rows.push(row_string);
+    }
+
+    let active_color = match board.active_color {
+        Color::White => "w",
+        Color::Black => "b",
+    };
+
+    let castling = board.castling_rights.to_string();
+
+    let en_passant = match board.en_passant {
+        Some(field) => format!("{}{}", (b'a' + field.col() as u8) as char, field.row() + 1),
+        None => String::from("-"),
+    };
+
+    format!(
+        "{} {} {} {} {} {}",
+        rows.join("/"),
+        active_color,
+        castling,
+        en_passant,
+        board.halfmove_clock,
+        board.fullmove_number
+    )
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -121,7 +357,7 @@ mod test {
             }
         }
         assert_eq!(board.active_color, Color::White);
-        assert_eq!(board.castling_rights, [false, false, false, false]);
+        assert_eq!(board.castling_rights, CastlingRights::NONE);
         assert_eq!(board.en_passant, None);
         assert_eq!(board.halfmove_clock, 0);
         assert_eq!(board.fullmove_number, 1);
@@ -199,7 +435,7 @@ mod test {
         assert_eq!(board.squares[3][4], Square::Empty); // Check an empty square
 
         assert_eq!(board.active_color, Color::White);
-        assert_eq!(board.castling_rights, [true, true, true, true]);
+        assert_eq!(board.castling_rights, CastlingRights::ALL);
         assert_eq!(board.en_passant, None);
         assert_eq!(board.halfmove_clock, 0);
         assert_eq!(board.fullmove_number, 1);
@@ -249,6 +485,112 @@ mod test {
         let fen = "8/8/8/8/8/8/8/8 w Kq - 0 1";
         let board = ChessBoard::from_fen(fen).expect("Failed to parse FEN");
 
-        assert_eq!(board.castling_rights, [true, false, false, true]); // White King side, Black Queen side
+        assert_eq!(board.castling_rights.to_string(), "Kq"); // White King side, Black Queen side
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            INITIAL_POSITION,
+            "8/8/8/8/8/8/8/8 w - - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/8/8/8/4pP2/8/8/8 b - f3 0 1",
+            "8/8/8/8/8/8/PPPPPPPP/RNBQKBNR b Kq - 12 34",
+        ] {
+            assert_eq!(ChessBoard::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fen_errors() {
+        let parse = |fen: &str| from_fen_with_mode(fen, FenMode::Strict).unwrap_err();
+        assert_eq!(parse("8/8/8/8/8/8/8/X7 w - - 0 1"), FenError::InvalidPiece('X'));
+        assert_eq!(parse("8/8/8/8/8/8/8 w - - 0 1"), FenError::WrongRankCount(7));
+        assert_eq!(parse("8/8/8/8/8/8/8/8 w - -"), FenError::WrongFieldCount(4));
+        assert_eq!(parse("4k3/8/8/8/8/8/8/4K2 w - - 0 1"), FenError::RankTooShort(1));
+        assert_eq!(
+            parse("4k3/8/8/8/8/8/8/4K3 x - - 0 1"),
+            FenError::InvalidActiveColor("x".to_string())
+        );
+        assert_eq!(
+            parse("4k3/8/8/8/8/8/8/4K3 w X - 0 1"),
+            FenError::InvalidCastlingRights("X".to_string())
+        );
+        assert_eq!(
+            parse("4k3/8/8/8/8/8/8/4K3 w - e9 0 1"),
+            FenError::InvalidSquare("e9".to_string())
+        );
+        assert_eq!(
+            parse("4k3/8/8/8/8/8/8/4K3 w - é 0 1"),
+            FenError::InvalidSquare("é".to_string())
+        );
+        assert_eq!(
+            parse("4k3/8/8/8/8/8/8/4K3 w - - x 1"),
+            FenError::InvalidHalfmoveClock("x".to_string())
+        );
+        assert_eq!(
+            String::from(FenError::MissingKing(Color::White)),
+            "Invalid FEN string: White has no king"
+        );
+    }
+
+    #[test]
+    fn fen_validation() {
+        // The lenient mode would drop the invalid en passant square
+        let validate = |fen: &str| from_fen_with_mode(fen, FenMode::Strict).map(|_| ());
+        assert_eq!(validate(INITIAL_POSITION), Ok(()));
+        assert_eq!(
+            validate("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(FenError::MissingKing(Color::Black))
+        );
+        assert_eq!(
+            validate("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            Err(FenError::TooManyKings(Color::White))
+        );
+        assert_eq!(
+            validate("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"),
+            Err(FenError::PawnOnBackRank(ChessField::from_algebraic("h8")))
+        );
+        assert_eq!(validate("4k3/8/8/8/8/8/8/4K2R w - - 0 1"), Ok(()));
+        assert_eq!(
+            validate("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            Err(FenError::OpponentInCheck)
+        );
+        assert_eq!(
+            validate("4k3/8/8/8/8/8/8/4K3 w K - 0 1"),
+            Err(FenError::CastlingWithoutPieces('K'))
+        );
+        assert_eq!(
+            validate("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            Ok(())
+        );
+        assert_eq!(
+            validate("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq d3 0 1"),
+            Err(FenError::InvalidEnPassant(ChessField::from_algebraic("d3")))
+        );
+        assert_eq!(issues(INITIAL_POSITION), vec![]);
+        assert_eq!(
+            issues("4k2P/8/8/8/8/8/8/4R1K1 w K e6 0 1"),
+            vec![
+                FenError::PawnOnBackRank(ChessField::from_algebraic("h8")),
+                FenError::OpponentInCheck,
+                FenError::CastlingWithoutPieces('K'),
+                FenError::InvalidEnPassant(ChessField::from_algebraic("e6")),
+            ]
+        );
+        assert_eq!(issues("8/8/8/8/8/8/8/4K3 w - -"), vec![FenError::WrongFieldCount(4)]);
+        // The lenient mode accepts missing move counters
+        assert_eq!(from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap().fullmove_number, 1);
+    }
+
+    #[test]
+    fn fen_after_move() {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        board.make_move(crate::chess_board::Move::from_algebraic("e2e4"));
+        // No black pawn can capture on e3
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1"
+        );
     }
 }
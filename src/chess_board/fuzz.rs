@@ -0,0 +1,357 @@
+use super::castling::Castling;
+use super::fen::INITIAL_POSITION;
+use super::zobrist_hash::ZOBRIST;
+use super::{ChessBoard, Color, Move, Piece, PieceType, Square};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use std::fmt;
+
+/// Start positions of the playouts, covering castling, en passant and promotions.
+pub const START_POSITIONS: [&str; 5] = [
+    INITIAL_POSITION,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+];
+
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const PIECE_KINDS: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+/// A position of a playout that failed a check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub start_fen: String,
+    /// The moves from the start position to the failing position.
+    pub moves: Vec<Move>,
+    pub message: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let moves: Vec<String> = self.moves.iter().map(|mv| mv.as_algebraic()).collect();
+        write!(
+            f,
+            "{}\nReproduce with: position fen {} moves {}",
+            self.message,
+            self.start_fen,
+            moves.join(" ")
+        )
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+fn square_at(board: &ChessBoard, row: i32, col: i32) -> Option<Square> {
+    if (0..8).contains(&row) && (0..8).contains(&col) {
+        Some(board.squares[row as usize][col as usize])
+    } else {
+        None
+    }
+}
+
+fn is_piece(square: Option<Square>, color: Color, kinds: &[PieceType]) -> bool {
+    matches!(square, Some(Square::Occupied(piece)) if piece.color == color && kinds.contains(&piece.kind))
+}
+
+/// Whether a piece of the color attacks the square, scanning outwards from the square.
+fn is_attacked(board: &ChessBoard, row: i32, col: i32, by: Color) -> bool {
+    let steps_attack = |steps: &[(i32, i32)], kind| {
+        steps
+            .iter()
+            .any(|&(dr, dc)| is_piece(square_at(board, row + dr, col + dc), by, &[kind]))
+    };
+    let rays_attack = |directions: &[(i32, i32)], kinds: &[PieceType]| {
+        directions.iter().any(|&(dr, dc)| {
+            let (mut r, mut c) = (row + dr, col + dc);
+            while square_at(board, r, c) == Some(Square::Empty) {
+                r += dr;
+                c += dc;
+            }
+            is_piece(square_at(board, r, c), by, kinds)
+        })
+    };
+    // Pawns attack towards the opponent, so they stand one row behind the square
+    let pawn_row = match by {
+        Color::White => row - 1,
+        Color::Black => row + 1,
+    };
+    steps_attack(&KNIGHT_STEPS, PieceType::Knight)
+        || steps_attack(&KING_STEPS, PieceType::King)
+        || is_piece(square_at(board, pawn_row, col - 1), by, &[PieceType::Pawn])
+        || is_piece(square_at(board, pawn_row, col + 1), by, &[PieceType::Pawn])
+        || rays_attack(&ROOK_DIRECTIONS, &[PieceType::Rook, PieceType::Queen])
+        || rays_attack(&BISHOP_DIRECTIONS, &[PieceType::Bishop, PieceType::Queen])
+}
+
+fn add_pawn_move(moves: &mut Vec<Move>, mv: Move) {
+    if mv.to.row() == 0 || mv.to.row() == 7 {
+        moves.extend(PROMOTIONS.iter().map(|&kind| mv.with_promotion(kind)));
+    } else {
+        moves.push(mv);
+    }
+}
+
+fn pawn_moves(board: &ChessBoard, moves: &mut Vec<Move>, row: i32, col: i32, color: Color) {
+    let (direction, start_row) = match color {
+        Color::White => (1, 1),
+        Color::Black => (-1, 6),
+    };
+    let step = |to_row: i32, to_col: i32| Move::new(row as usize, col as usize, to_row as usize, to_col as usize);
+    if square_at(board, row + direction, col) == Some(Square::Empty) {
+        add_pawn_move(moves, step(row + direction, col));
+        if row == start_row && square_at(board, row + 2 * direction, col) == Some(Square::Empty) {
+            moves.push(step(row + 2 * direction, col));
+        }
+    }
+    for to_col in [col - 1, col + 1] {
+        let to_row = row + direction;
+        let en_passant = board
+            .en_passant
+            .is_some_and(|field| field.row() as i32 == to_row && field.col() as i32 == to_col);
+        if is_piece(square_at(board, to_row, to_col), opponent(color), &PIECE_KINDS) || en_passant {
+            add_pawn_move(moves, step(to_row, to_col));
+        }
+    }
+}
+
+fn castling_moves(board: &ChessBoard, moves: &mut Vec<Move>, color: Color) {
+    let row = match color {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    if !is_piece(square_at(board, row, 4), color, &[PieceType::King]) {
+        return;
+    }
+    // Kingside: rook on h, f and g empty and not attacked. Queenside: rook on a, b to d empty, c and d not attacked.
+    for (kingside, rook_col, empty, safe) in [
+        (true, 7, &[5, 6][..], &[4, 5, 6][..]),
+        (false, 0, &[1, 2, 3][..], &[4, 3, 2][..]),
+    ] {
+        if board.castling_rights.has(Castling::new(color, kingside))
+            && is_piece(square_at(board, row, rook_col), color, &[PieceType::Rook])
+            && empty
+                .iter()
+                .all(|&col| square_at(board, row, col) == Some(Square::Empty))
+            && safe.iter().all(|&col| !is_attacked(board, row, col, opponent(color)))
+        {
+            let to_col = if rook_col == 7 { 6 } else { 2 };
+            moves.push(Move::new(row as usize, 4, row as usize, to_col));
+        }
+    }
+}
+
+/// The moves of the side to move by the rules of the pieces, without checking the king's safety.
+fn pseudo_legal_moves(board: &ChessBoard) -> Vec<Move> {
+    let color = board.active_color;
+    let mut moves = Vec::new();
+    for row in 0..8 {
+        for col in 0..8 {
+            let Square::Occupied(piece) = board.squares[row as usize][col as usize] else {
+                continue;
+            };
+            if piece.color != color {
+                continue;
+            }
+            let (directions, range): (&[(i32, i32)], i32) = match piece.kind {
+                PieceType::Pawn => {
+                    pawn_moves(board, &mut moves, row, col, color);
+                    continue;
+                }
+                PieceType::Knight => (&KNIGHT_STEPS, 1),
+                PieceType::King => (&KING_STEPS, 1),
+                PieceType::Bishop => (&BISHOP_DIRECTIONS, 7),
+                PieceType::Rook => (&ROOK_DIRECTIONS, 7),
+                // The queen moves in the directions of the king steps
+                PieceType::Queen => (&KING_STEPS, 7),
+            };
+            for &(dr, dc) in directions {
+                for distance in 1..=range {
+                    let (to_row, to_col) = (row + distance * dr, col + distance * dc);
+                    let mv = || Move::new(row as usize, col as usize, to_row as usize, to_col as usize);
+                    match square_at(board, to_row, to_col) {
+                        Some(Square::Empty) => moves.push(mv()),
+                        Some(Square::Occupied(other)) => {
+                            if other.color != color {
+                                moves.push(mv());
+                            }
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+        }
+    }
+    castling_moves(board, &mut moves, color);
+    moves
+}
+
+fn king_by_scan(board: &ChessBoard, color: Color) -> Option<(usize, usize)> {
+    let king = Square::Occupied(Piece {
+        color,
+        kind: PieceType::King,
+    });
+    (0..64)
+        .map(|i| (i / 8, i % 8))
+        .find(|&(row, col)| board.squares[row][col] == king)
+}
+
+/// The legal moves found by scanning the board square by square, independent of the move generation of ChessBoard.
+/// Only make_move is shared, and its result is checked on the following positions.
+pub fn reference_legal_moves(board: &ChessBoard) -> Vec<Move> {
+    let color = board.active_color;
+    pseudo_legal_moves(board)
+        .into_iter()
+        .filter(|&mv| {
+            let mut after = board.clone();
+            after.make_move(mv);
+            match king_by_scan(&after, color) {
+                Some((row, col)) => !is_attacked(&after, row as i32, col as i32, opponent(color)),
+                None => true,
+            }
+        })
+        .collect()
+}
+
+fn sorted_moves(moves: Vec<Move>) -> Vec<String> {
+    let mut moves: Vec<String> = moves.iter().map(|mv| mv.as_algebraic()).collect();
+    moves.sort();
+    moves
+}
+
+/// Cross-checks the legal moves, the hash, the FEN round trip and the king positions of a position with the
+/// reference implementation, and that the mirrored position has as many legal moves.
+pub fn check_position(board: &ChessBoard) -> Result<(), String> {
+    let moves = sorted_moves(board.generate_legal_moves());
+    let expected = sorted_moves(reference_legal_moves(board));
+    if moves != expected {
+        let missing: Vec<&String> = expected.iter().filter(|mv| !moves.contains(mv)).collect();
+        let extra: Vec<&String> = moves.iter().filter(|mv| !expected.contains(mv)).collect();
+        return Err(format!(
+            "Legal moves of {} differ, missing {:?}, not legal {:?}",
+            board.to_fen(),
+            missing,
+            extra
+        ));
+    }
+
+    let hash = ZOBRIST.calculate_hash(board);
+    if board.zobrist_hash() != hash {
+        return Err(format!(
+            "Hash of {} is {:x}, expected {:x}",
+            board.to_fen(),
+            board.zobrist_hash(),
+            hash
+        ));
+    }
+
+    let fen = board.to_fen();
+    let parsed = ChessBoard::from_fen(&fen).map_err(|e| format!("{} does not parse: {}", fen, e))?;
+    if parsed.squares != board.squares
+        || parsed.active_color != board.active_color
+        || parsed.castling_rights != board.castling_rights
+        || parsed.en_passant != board.en_passant
+        || parsed.halfmove_clock != board.halfmove_clock
+        || parsed.fullmove_number != board.fullmove_number
+        || parsed.to_fen() != fen
+    {
+        return Err(format!("FEN round trip of {} changes the position", fen));
+    }
+
+    for color in [Color::White, Color::Black] {
+        let king = board.find_king_position(color).map(|field| (field.row(), field.col()));
+        if king != king_by_scan(board, color) {
+            return Err(format!("{:?} king of {} found on {:?}", color, fen, king));
+        }
+    }
+
+    let mirrored = board.mirror();
+    if mirrored.generate_legal_moves().len() != moves.len() || mirrored.mirror().to_fen() != fen {
+        return Err(format!("Mirrored position {} of {} differs", mirrored.to_fen(), fen));
+    }
+    Ok(())
+}
+
+/// Plays random games from the start positions and checks every position reached. As every position is checked, a
+/// mismatch reports the shortest sequence of moves of its game that reproduces it. Returns the number of positions
+/// checked.
+pub fn fuzz(seed: u64, games: usize, max_plies: usize, start_positions: &[&str]) -> Result<usize, Mismatch> {
+    let mut rng = Pcg64::seed_from_u64(seed);
+    let mut positions = 0;
+    for game in 0..games {
+        let start_fen = start_positions[game % start_positions.len()];
+        let mut board = ChessBoard::from_fen(start_fen).map_err(|e| Mismatch {
+            start_fen: start_fen.to_string(),
+            moves: Vec::new(),
+            message: e.to_string(),
+        })?;
+        let mut moves = Vec::new();
+        loop {
+            positions += 1;
+            if let Err(message) = check_position(&board) {
+                return Err(Mismatch {
+                    start_fen: start_fen.to_string(),
+                    moves,
+                    message,
+                });
+            }
+            if moves.len() >= max_plies {
+                break;
+            }
+            let Some(&mv) = board.generate_legal_moves().choose(&mut rng) else {
+                break;
+            };
+            board.make_move(mv);
+            moves.push(mv);
+        }
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_random_playouts() {
+        let result = fuzz(1, 25, 150, &START_POSITIONS);
+        assert!(result.is_ok(), "{}", result.unwrap_err());
+        assert!(result.unwrap() > 25);
+    }
+
+    #[test]
+    fn test_check_position_finds_mismatches() {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        assert_eq!(reference_legal_moves(&board).len(), 20);
+        assert_eq!(check_position(&board), Ok(()));
+        board.history.push(0, false);
+        assert!(check_position(&board).unwrap_err().starts_with("Hash"));
+
+        let mismatch = Mismatch {
+            start_fen: INITIAL_POSITION.to_string(),
+            moves: vec![Move::from_algebraic("e2e4"), Move::from_algebraic("e7e5")],
+            message: String::from("Legal moves differ"),
+        };
+        assert!(mismatch
+            .to_string()
+            .ends_with("position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4 e7e5"));
+    }
+}
@@ -0,0 +1,161 @@
+use super::pgn::game_result;
+use super::{ChessBoard, Move};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub(crate) const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// A move of a game with its annotations and the alternatives to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameMove {
+    pub mv: Move,
+    /// Numeric annotation glyphs like 1 for "!" or 4 for "??".
+    pub nags: Vec<u8>,
+    /// The comment after the move.
+    pub comment: Option<String>,
+    /// Lines played instead of this move, each starting in the position before it.
+    pub variations: Vec<Vec<GameMove>>,
+}
+
+impl GameMove {
+    pub fn new(mv: Move) -> Self {
+        GameMove {
+            mv,
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// The record of a game: its start position, the main line with variations and annotations, tags and result. The
+/// ChessBoard only holds a position, the moves played to reach it are kept here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub start_fen: String,
+    /// The comment before the first move.
+    pub comment: Option<String>,
+    pub moves: Vec<GameMove>,
+    pub result: String,
+}
+
+impl Game {
+    /// Creates a game without moves from a start position.
+    pub fn new(start_fen: &str) -> Result<Self, String> {
+        ChessBoard::from_fen(start_fen)?;
+        Ok(Game {
+            tags: SEVEN_TAG_ROSTER
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            start_fen: start_fen.to_string(),
+            comment: None,
+            moves: Vec::new(),
+            result: String::from("*"),
+        })
+    }
+
+    /// Creates a game from a start position and the moves played from there, with the result of the final position.
+    pub fn from_moves(start_fen: &str, moves: Vec<Move>) -> Result<Self, String> {
+        let mut game = Game::new(start_fen)?;
+        game.moves = moves.into_iter().map(GameMove::new).collect();
+        game.update_result()?;
+        Ok(game)
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// The moves of the main line without annotations.
+    pub fn main_line(&self) -> Vec<Move> {
+        self.moves.iter().map(|m| m.mv).collect()
+    }
+
+    /// The position after the first ply moves of the main line.
+    pub fn position_after(&self, ply: usize) -> Result<ChessBoard, String> {
+        let mut board = ChessBoard::from_fen(&self.start_fen)?;
+        for game_move in self.moves.iter().take(ply) {
+            board.make_move(game_move.mv);
+        }
+        Ok(board)
+    }
+
+    /// Replays the main line from the start position.
+    pub fn final_position(&self) -> Result<ChessBoard, String> {
+        self.position_after(self.moves.len())
+    }
+
+    /// Appends a move to the main line.
+    pub fn push_move(&mut self, mv: Move) {
+        self.moves.push(GameMove::new(mv));
+    }
+
+    /// Adds a line played instead of the main line move at the ply. Returns false if there is no such move.
+    pub fn add_variation(&mut self, ply: usize, line: Vec<GameMove>) -> bool {
+        match self.moves.get_mut(ply) {
+            Some(game_move) if !line.is_empty() => {
+                game_move.variations.push(line);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Sets the result to the one of the final position: decided by checkmate or draw, otherwise "*".
+    pub fn update_result(&mut self) -> Result<(), String> {
+        self.result = game_result(&self.final_position()?).to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+
+    #[test]
+    fn test_game_main_line_and_variations() {
+        let moves: Vec<Move> = ["f2f3", "e7e5", "g2g4", "d8h4"]
+            .iter()
+            .map(|m| Move::from_algebraic(m))
+            .collect();
+        let mut game = Game::from_moves(INITIAL_POSITION, moves.clone()).unwrap();
+        assert_eq!(game.result, "0-1");
+        assert_eq!(game.main_line(), moves);
+        assert_eq!(
+            game.position_after(2).unwrap().to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR w KQkq - 0 2"
+        );
+
+        assert!(game.add_variation(2, vec![GameMove::new(Move::from_algebraic("e1f2"))]));
+        assert!(!game.add_variation(4, vec![GameMove::new(Move::from_algebraic("e1f2"))]));
+        assert!(!game.add_variation(0, Vec::new()));
+        assert_eq!(game.moves[2].variations.len(), 1);
+        // Variations do not change the main line
+        assert_eq!(game.main_line(), moves);
+
+        let mut game = Game::new(INITIAL_POSITION).unwrap();
+        game.push_move(Move::from_algebraic("e2e4"));
+        assert_eq!(game.main_line(), vec![Move::from_algebraic("e2e4")]);
+        assert!(Game::new("invalid").is_err());
+    }
+}
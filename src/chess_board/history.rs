@@ -0,0 +1,87 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The hashes of the positions of the game since the last capture or pawn move, the current position last. Earlier
+/// positions cannot occur again, so the history is cut at every irreversible move and otherwise grows with the game.
+/// The board carries it through make_move, so the game, the UCI position and the search count repetitions alike.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct GameHistory {
+    hashes: Vec<u64>,
+}
+
+impl GameHistory {
+    /// A history that starts with the position of the hash.
+    pub fn new(hash: u64) -> Self {
+        GameHistory { hashes: vec![hash] }
+    }
+
+    /// Adds the position reached by a move. After an irreversible move the earlier positions are dropped.
+    pub fn push(&mut self, hash: u64, irreversible: bool) {
+        if irreversible {
+            self.hashes.clear();
+        }
+        self.hashes.push(hash);
+    }
+
+    /// Forgets the game and starts over with the position of the hash.
+    pub fn reset(&mut self, hash: u64) {
+        self.hashes.clear();
+        self.hashes.push(hash);
+    }
+
+    /// The hash of the current position.
+    pub fn current(&self) -> Option<u64> {
+        self.hashes.last().copied()
+    }
+
+    /// How often the current position occurred, counting itself. A repetition needs the same side to move, so only
+    /// every second position is compared.
+    pub fn repetition_count(&self) -> usize {
+        match self.current() {
+            Some(current) => self
+                .hashes
+                .iter()
+                .rev()
+                .step_by(2)
+                .filter(|&&hash| hash == current)
+                .count(),
+            None => 0,
+        }
+    }
+
+    /// Positions in the history, the current one included.
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_history() {
+        let mut history = GameHistory::new(1);
+        for hash in [2, 3, 4, 1, 2, 3, 4, 1] {
+            history.push(hash, false);
+        }
+        assert_eq!(history.len(), 9);
+        assert_eq!(history.repetition_count(), 3);
+        // A position with the other side to move is no repetition even if the hashes collide
+        history.push(1, false);
+        assert_eq!(history.repetition_count(), 1);
+
+        history.push(5, true);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.current(), Some(5));
+        assert_eq!(history.repetition_count(), 1);
+        history.reset(6);
+        assert_eq!(history.current(), Some(6));
+        assert_eq!(GameHistory::default().repetition_count(), 0);
+    }
+}
@@ -0,0 +1,104 @@
+//! The material of a position: the number of pieces of every type per side. The board keeps it up to date while
+//! moves are made, so the evaluation, the endgame recognizers and the draw rules read it instead of counting the
+//! squares.
+use super::{Color, Piece, PieceType, Square};
+
+/// The game phase with all pieces on the board, knights and bishops count 1, rooks 2 and queens 4.
+pub const OPENING_PHASE: i32 = 24;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MaterialSignature {
+    /// By color and piece type
+    counts: [[u8; 6]; 2],
+}
+
+impl MaterialSignature {
+    /// Counts the pieces on the squares.
+    pub fn from_squares(squares: &[[Square; 8]; 8]) -> MaterialSignature {
+        let mut signature = MaterialSignature::default();
+        for square in squares.iter().flatten() {
+            if let Square::Occupied(piece) = *square {
+                signature.add(piece);
+            }
+        }
+        signature
+    }
+
+    pub fn count(&self, color: Color, kind: PieceType) -> u32 {
+        u32::from(self.counts[color as usize][kind as usize])
+    }
+
+    /// All pieces of both sides, kings and pawns included.
+    pub fn pieces(&self) -> u32 {
+        self.counts.iter().flatten().map(|&count| u32::from(count)).sum()
+    }
+
+    /// The game phase, from OPENING_PHASE with all pieces on the board down to 0 with pawns and kings only. Extra
+    /// pieces from promotions do not count beyond OPENING_PHASE.
+    pub fn phase(&self) -> i32 {
+        let phase: u32 = [Color::White, Color::Black]
+            .iter()
+            .map(|&color| {
+                self.count(color, PieceType::Knight)
+                    + self.count(color, PieceType::Bishop)
+                    + 2 * self.count(color, PieceType::Rook)
+                    + 4 * self.count(color, PieceType::Queen)
+            })
+            .sum();
+        (phase as i32).min(OPENING_PHASE)
+    }
+
+    /// Whether the pieces besides the kings are at most one knight or only bishops. Neither side can mate with one
+    /// knight, and with bishops only if they stand on squares of both colors, which the board has to tell.
+    pub fn is_minor_only(&self) -> bool {
+        let count = |kind| self.count(Color::White, kind) + self.count(Color::Black, kind);
+        let heavy = count(PieceType::Pawn) + count(PieceType::Rook) + count(PieceType::Queen);
+        heavy == 0
+            && (count(PieceType::Knight) == 0 || (count(PieceType::Knight) == 1 && count(PieceType::Bishop) == 0))
+    }
+
+    pub(super) fn add(&mut self, piece: Piece) {
+        self.counts[piece.color as usize][piece.kind as usize] += 1;
+    }
+
+    pub(super) fn remove(&mut self, piece: Piece) {
+        self.counts[piece.color as usize][piece.kind as usize] -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+    use crate::chess_board::ChessBoard;
+
+    fn signature(fen: &str) -> MaterialSignature {
+        ChessBoard::from_fen(fen).unwrap().material_signature()
+    }
+
+    #[test]
+    fn test_material_signature() {
+        let initial = signature(INITIAL_POSITION);
+        assert_eq!(initial.count(Color::White, PieceType::Pawn), 8);
+        assert_eq!(initial.count(Color::Black, PieceType::Knight), 2);
+        assert_eq!(initial.count(Color::Black, PieceType::King), 1);
+        assert_eq!(initial.pieces(), 32);
+        assert_eq!(initial.phase(), OPENING_PHASE);
+
+        let rook_ending = signature("4k3/4p3/8/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(rook_ending.phase(), 2);
+        assert_eq!(rook_ending.pieces(), 4);
+        // Three queens are still no more than the opening
+        assert_eq!(signature("qqq1k3/8/8/8/8/8/8/QQQ1K3 w - - 0 1").phase(), OPENING_PHASE);
+    }
+
+    #[test]
+    fn test_minor_only() {
+        assert!(signature("4k3/8/8/8/8/8/8/4K3 w - - 0 1").is_minor_only());
+        assert!(signature("4k3/8/8/8/8/8/8/2N1K3 w - - 0 1").is_minor_only());
+        assert!(signature("3bk3/8/8/8/8/8/8/2B1K3 w - - 0 1").is_minor_only());
+        assert!(!signature("3nk3/8/8/8/8/8/8/2N1K3 w - - 0 1").is_minor_only());
+        assert!(!signature("3bk3/8/8/8/8/8/8/2N1K3 w - - 0 1").is_minor_only());
+        assert!(!signature("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").is_minor_only());
+    }
+}
@@ -1,17 +1,40 @@
+use crate::chess_board::castling::{Castling, CastlingRights};
+use crate::chess_board::fen::{FenError, FenMode};
+use crate::chess_board::history::GameHistory;
+use crate::chess_board::material::MaterialSignature;
+use crate::chess_board::move_list::{sort_by_score, MoveList, MAX_PIECE_MOVES};
 use crate::chess_board::zobrist_hash::ZOBRIST;
-use circular_buffer::CircularBuffer;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
+pub mod castling;
 pub mod fen;
+pub mod fuzz;
+pub mod game;
+pub mod history;
+pub mod material;
+pub mod move_list;
+pub mod perft;
+pub mod pgn;
+pub mod render;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod setup;
+pub mod threats;
 pub mod zobrist_hash;
 
+pub use perft::perft;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Color {
     White,
     Black,
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PieceType {
     Pawn,
     Knight,
@@ -22,24 +45,36 @@ pub enum PieceType {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Piece {
     pub color: Color,
     pub kind: PieceType,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Square {
     Occupied(Piece),
     Empty,
 }
 
+/// A square of the board by its index row * 8 + col, row 0 is the first rank. Only squares on the board can be
+/// created, a missing square is an Option. It is serialized as its name like "e4".
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "String", into = "String")
+)]
+pub struct ChessField(u8);
+
+/// A move in coordinate notation, serialized as such like "e7e8q".
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
-pub struct ChessField {
-    pub row: usize,
-    pub col: usize,
-}
-
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub struct Move {
     pub from: ChessField,
     pub to: ChessField,
@@ -60,15 +95,66 @@ impl fmt::Display for PieceType {
 }
 
 impl ChessField {
+    /// Panics if the row or column is off the board, use try_new for computed coordinates.
     pub fn new(row: usize, col: usize) -> Self {
-        Self { row, col }
+        Self::try_new(row, col).unwrap_or_else(|| panic!("Square {} {} is off the board", row, col))
+    }
+
+    pub fn try_new(row: usize, col: usize) -> Option<Self> {
+        (row < 8 && col < 8).then_some(Self((row * 8 + col) as u8))
+    }
+
+    /// All squares from a1 to h8, rank by rank.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..64).map(Self)
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        (index < 64).then_some(Self(index as u8))
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub fn row(self) -> usize {
+        (self.0 >> 3) as usize
+    }
+
+    pub fn col(self) -> usize {
+        (self.0 & 7) as usize
     }
+
+    /// The square the given number of rows and columns away, if it is on the board.
+    pub fn offset(self, rows: isize, cols: isize) -> Option<Self> {
+        let row = self.row().checked_add_signed(rows)?;
+        let col = self.col().checked_add_signed(cols)?;
+        Self::try_new(row, col)
+    }
+
+    /// Parses a square like "e4".
+    pub fn parse_algebraic(algebraic: &str) -> Option<Self> {
+        match algebraic.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Some(Self::new((rank - b'1') as usize, (file - b'a') as usize))
+            }
+            _ => None,
+        }
+    }
+
+    /// Panics if the text is no square, see parse_algebraic.
     pub fn from_algebraic(algebraic: &str) -> Self {
-        let file = algebraic.chars().next().unwrap();
-        let rank = algebraic.chars().nth(1).unwrap();
-        let col = (file as u8 - b'a') as usize;
-        let row = (rank as u8 - b'1') as usize;
-        Self { row, col }
+        Self::parse_algebraic(algebraic).unwrap_or_else(|| panic!("Invalid square {}", algebraic))
+    }
+
+    pub fn as_algebraic(self) -> String {
+        to_algebraic_square(self.row(), self.col())
+    }
+}
+
+impl fmt::Debug for ChessField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_algebraic())
     }
 }
 
@@ -90,8 +176,8 @@ impl Move {
     pub fn as_algebraic(&self) -> String {
         let base_move = format!(
             "{}{}",
-            to_algebraic_square(self.from.row, self.from.col),
-            to_algebraic_square(self.to.row, self.to.col)
+            to_algebraic_square(self.from.row(), self.from.col()),
+            to_algebraic_square(self.to.row(), self.to.col())
         );
         if let Some(promo) = self.promotion {
             base_move + &promo.to_string().to_lowercase()
@@ -120,6 +206,21 @@ impl Move {
         };
         Self { from, to, promotion }
     }
+
+    /// Parses a move in coordinate notation like from_algebraic, but rejects text that is no move instead of
+    /// panicking. The move is not checked against a position.
+    pub fn parse_algebraic(algebraic: &str) -> Result<Self, String> {
+        let bytes = algebraic.as_bytes();
+        let is_square = |file: u8, rank: u8| (b'a'..=b'h').contains(&file) && (b'1'..=b'8').contains(&rank);
+        let valid = matches!(bytes.len(), 4 | 5)
+            && is_square(bytes[0], bytes[1])
+            && is_square(bytes[2], bytes[3])
+            && bytes.get(4).is_none_or(|promotion| b"qrbnQRBN".contains(promotion));
+        if !valid {
+            return Err(format!("Invalid move {}", algebraic));
+        }
+        Ok(Self::from_algebraic(algebraic))
+    }
 }
 
 fn to_algebraic_square(row: usize, col: usize) -> String {
@@ -128,20 +229,134 @@ fn to_algebraic_square(row: usize, col: usize) -> String {
     format!("{}{}", file, rank) // Combine file and rank into a string
 }
 
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+/// Whether the square lies strictly between the two others on a rank, file or diagonal.
+fn is_between(square: ChessField, from: ChessField, to: ChessField) -> bool {
+    let (dr, dc) = (
+        to.row() as isize - from.row() as isize,
+        to.col() as isize - from.col() as isize,
+    );
+    if dr != 0 && dc != 0 && dr.abs() != dc.abs() {
+        return false;
+    }
+    let steps = dr.abs().max(dc.abs());
+    (1..steps).any(|step| from.offset(dr.signum() * step, dc.signum() * step) == Some(square))
+}
+
+/// Draws by the repetition and move count rules and by insufficient material. The first two need a claim, the others
+/// end the game by themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawRule {
+    ThreefoldRepetition,
+    FiftyMoves,
+    FivefoldRepetition,
+    SeventyFiveMoves,
+    InsufficientMaterial,
+}
+
+impl DrawRule {
+    pub fn is_automatic(self) -> bool {
+        matches!(
+            self,
+            DrawRule::FivefoldRepetition | DrawRule::SeventyFiveMoves | DrawRule::InsufficientMaterial
+        )
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            DrawRule::ThreefoldRepetition => "threefold repetition",
+            DrawRule::FiftyMoves => "fifty-move rule",
+            DrawRule::FivefoldRepetition => "fivefold repetition",
+            DrawRule::SeventyFiveMoves => "seventy-five-move rule",
+            DrawRule::InsufficientMaterial => "insufficient material",
+        }
+    }
+}
+
+/// Whether a move is legal and, if not, the first rule it breaks. Explains rejected moves to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveLegality {
+    Legal,
+    NoPiece,
+    NotYourPiece,
+    CapturesOwnPiece,
+    /// The piece does not move like this.
+    InvalidPieceMove,
+    BlockedPath,
+    /// A pawn moves diagonally without capturing.
+    NothingToCapture,
+    LeavesKingInCheck,
+    /// A promotion piece is given, but the move is no pawn move to the last rank.
+    WrongPromotionRank,
+    MissingPromotion,
+    InvalidPromotionPiece,
+    CastlingWithoutRights,
+    CastlingOutOfCheck,
+    CastlingThroughCheck,
+}
+
+impl MoveLegality {
+    pub fn description(self) -> &'static str {
+        match self {
+            MoveLegality::Legal => "The move is legal",
+            MoveLegality::NoPiece => "There is no piece on that square",
+            MoveLegality::NotYourPiece => "That piece belongs to the opponent",
+            MoveLegality::CapturesOwnPiece => "A piece cannot capture a piece of its own color",
+            MoveLegality::InvalidPieceMove => "The piece does not move like that",
+            MoveLegality::BlockedPath => "The path is blocked",
+            MoveLegality::NothingToCapture => "A pawn only moves diagonally to capture",
+            MoveLegality::LeavesKingInCheck => "The move leaves the king in check",
+            MoveLegality::WrongPromotionRank => "Only a pawn reaching the last rank promotes",
+            MoveLegality::MissingPromotion => "A pawn reaching the last rank must promote",
+            MoveLegality::InvalidPromotionPiece => "A pawn promotes to a queen, rook, bishop or knight",
+            MoveLegality::CastlingWithoutRights => "Castling on that side is no longer allowed",
+            MoveLegality::CastlingOutOfCheck => "The king cannot castle out of check",
+            MoveLegality::CastlingThroughCheck => "The king cannot castle through or into an attacked square",
+        }
+    }
+}
+
+/// A draw the side to move may claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawClaim {
+    pub rule: DrawRule,
+    /// The move announced with the claim when the draw only occurs after it, None if it is on the board already.
+    pub with_move: Option<Move>,
+}
+
+/// A position with the history of the game that leads to it. It is serialized as its FEN and the history, which has to
+/// end with the position.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "serialize::BoardRecord", into = "serialize::BoardRecord")
+)]
 pub struct ChessBoard {
     pub squares: [[Square; 8]; 8],
     pub active_color: Color,
-    pub castling_rights: [bool; 4],
+    pub castling_rights: CastlingRights,
     pub en_passant: Option<ChessField>,
     pub halfmove_clock: u32,
     pub fullmove_number: u32,
-    pub repetition_map: CircularBuffer<32, u64>,
+    pub history: GameHistory,
+    /// Kept up to date by make_move and recounted when the position is set up, code that writes the squares itself
+    /// has to call refresh_material.
+    material: MaterialSignature,
 }
 const NO_CAPTURE: i32 = 0;
 const CAPTURE: i32 = 10000;
 const CAPTURE_BASE: i32 = CAPTURE + 10;
 const CASTLING_SCORE: i32 = 50;
+// Queen promotions come right after the captures that do not lose material, under-promotions after the quiet moves
+const QUEEN_PROMOTION_SCORE: i32 = CAPTURE_BASE - 1;
+const UNDER_PROMOTION_SCORE: i32 = -10;
 
 fn get_piece_value(piece: &PieceType) -> i32 {
     match piece {
@@ -154,60 +369,139 @@ fn get_piece_value(piece: &PieceType) -> i32 {
     }
 }
 
+impl Default for ChessBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ChessBoard {
     /// Creates an empty chess board
     pub fn new() -> Self {
         Self {
             squares: [[Square::Empty; 8]; 8],
-            active_color: Color::White,  // Default active color to White
-            castling_rights: [false; 4], // No castling rights by default
-            en_passant: None,            // No en passant square by default
-            halfmove_clock: 0,           // Halfmove clock starts at 0
+            active_color: Color::White,            // Default active color to White
+            castling_rights: CastlingRights::NONE, // No castling rights by default
+            en_passant: None,                      // No en passant square by default
+            halfmove_clock: 0,                     // Halfmove clock starts at 0
             fullmove_number: 1,
-            repetition_map: CircularBuffer::new(),
+            history: GameHistory::default(),
+            material: MaterialSignature::default(),
         }
     }
 
-    /// Delegates FEN parsing to the `fen` module.
-    pub fn from_fen(fen: &str) -> Result<Self, String> {
-        fen::from_fen(fen).map(|mut board| {
+    /// The number of pieces of every type per side.
+    pub fn material_signature(&self) -> MaterialSignature {
+        self.material
+    }
+
+    /// The game phase, from OPENING_PHASE with all pieces on the board down to 0 with pawns and kings only.
+    pub fn phase(&self) -> i32 {
+        self.material.phase()
+    }
+
+    /// Recounts the material after the squares were written directly.
+    pub fn refresh_material(&mut self) {
+        self.material = MaterialSignature::from_squares(&self.squares);
+    }
+
+    /// Delegates FEN parsing to the `fen` module. Only the syntax is checked, see `from_fen_with_mode`.
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        Self::from_fen_with_mode(fen, FenMode::Lenient)
+    }
+
+    pub fn from_fen_with_mode(fen: &str, mode: FenMode) -> Result<Self, FenError> {
+        fen::from_fen_with_mode(fen, mode).map(|mut board| {
             let zobrist = &*ZOBRIST;
-            board.repetition_map.push_back(zobrist.calculate_hash(&board));
+            board.history.reset(zobrist.calculate_hash(&board));
             board
         })
     }
 
+    /// Checks that the position is legal, see `fen::validate`.
+    pub fn validate(&self) -> Result<(), FenError> {
+        fen::validate(self)
+    }
+
+    /// Delegates FEN serialization to the `fen` module.
+    pub fn to_fen(&self) -> String {
+        fen::to_fen(self)
+    }
+
     pub fn generate_pseudo_moves(&self) -> Vec<(Move, i32)> {
-        let mut all_moves: Vec<(Move, i32)> = Vec::new();
+        let mut all_moves: MoveList = MoveList::new();
+        self.generate_pseudo_moves_into(&mut all_moves);
+        all_moves.to_vec()
+    }
 
+    /// Appends the pseudo-legal moves with their ordering scores to the list, without allocating.
+    pub fn generate_pseudo_moves_into<const N: usize>(&self, moves: &mut MoveList<N>) {
         for row in 0..8 {
             for col in 0..8 {
                 // Only process pieces of the active color
-                all_moves.extend(self.generate_pseudo_moves_from_position(row, col));
+                self.add_pseudo_moves_from_position(row, col, moves);
             }
         }
-        all_moves
+    }
+
+    /// Whether the moved piece attacks the king on its target square. Discovered checks and checks by the rook of
+    /// a castling are not detected, this is only meant for move ordering.
+    pub fn gives_direct_check(&self, mv: Move, king: ChessField) -> bool {
+        let kind = match (mv.promotion, self.squares[mv.from.row()][mv.from.col()]) {
+            (Some(kind), _) => kind,
+            (None, Square::Occupied(piece)) => piece.kind,
+            (None, Square::Empty) => return false,
+        };
+        let dr = king.row() as isize - mv.to.row() as isize;
+        let dc = king.col() as isize - mv.to.col() as isize;
+        let forward = match self.active_color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        let diagonal = dr.abs() == dc.abs();
+        let straight = dr == 0 || dc == 0;
+        match kind {
+            PieceType::Pawn => dr == forward && dc.abs() == 1,
+            PieceType::Knight => dr.abs() * dc.abs() == 2,
+            PieceType::Bishop => diagonal && self.is_ray_clear(mv.to, dr, dc),
+            PieceType::Rook => straight && self.is_ray_clear(mv.to, dr, dc),
+            PieceType::Queen => (diagonal || straight) && self.is_ray_clear(mv.to, dr, dc),
+            PieceType::King => false,
+        }
+    }
+
+    /// Whether the squares between from and from + (dr, dc) are empty, the offset being along a line.
+    fn is_ray_clear(&self, from: ChessField, dr: isize, dc: isize) -> bool {
+        let steps = dr.abs().max(dc.abs());
+        (1..steps).all(|step| {
+            let row = from.row() as isize + dr.signum() * step;
+            let col = from.col() as isize + dc.signum() * step;
+            self.squares[row as usize][col as usize] == Square::Empty
+        })
     }
 
     pub fn generate_pseudo_moves_from_position(&self, row: usize, col: usize) -> Vec<(Move, i32)> {
+        let mut moves: MoveList<MAX_PIECE_MOVES> = MoveList::new();
+        self.add_pseudo_moves_from_position(row, col, &mut moves);
+        moves.to_vec()
+    }
+
+    fn add_pseudo_moves_from_position<const N: usize>(&self, row: usize, col: usize, moves: &mut MoveList<N>) {
         if let Square::Occupied(piece) = self.squares[row][col] {
             if piece.color == self.active_color {
-                let r = match piece.kind {
-                    PieceType::Pawn => self.generate_pawn_moves(row, col),
-                    PieceType::Knight => self.generate_knight_moves(row, col),
-                    PieceType::Bishop => self.generate_bishop_moves(row, col),
-                    PieceType::Rook => self.generate_rook_moves(row, col),
-                    PieceType::Queen => self.generate_queen_moves(row, col),
-                    PieceType::King => self.generate_king_moves(row, col),
-                };
-                return r;
+                match piece.kind {
+                    PieceType::Pawn => self.generate_pawn_moves(row, col, moves),
+                    PieceType::Knight => self.generate_knight_moves(row, col, moves),
+                    PieceType::Bishop => self.generate_bishop_moves(row, col, moves),
+                    PieceType::Rook => self.generate_rook_moves(row, col, moves),
+                    PieceType::Queen => self.generate_queen_moves(row, col, moves),
+                    PieceType::King => self.generate_king_moves(row, col, moves),
+                }
             }
         }
-        Vec::new()
     }
 
-    fn generate_pawn_moves(&self, row: usize, col: usize) -> Vec<(Move, i32)> {
-        let mut moves = Vec::new();
+    fn generate_pawn_moves<const N: usize>(&self, row: usize, col: usize, moves: &mut MoveList<N>) {
         let forward = match self.active_color {
             Color::White => 1,
             Color::Black => -1,
@@ -227,21 +521,13 @@ impl ChessBoard {
 
         // Regular forward move
         if self.squares[new_row][col] == Square::Empty {
-            Self::add_pawn_moves_with_and_without_promotion(
-                row,
-                col,
-                new_row,
-                col,
-                promotion_row,
-                NO_CAPTURE,
-                &mut moves,
-            );
+            Self::add_pawn_moves_with_and_without_promotion(row, col, new_row, col, promotion_row, NO_CAPTURE, moves);
 
             // Double move from start position
             if row == start_row {
                 let two_forward = (row as isize + 2 * forward) as usize;
                 if self.squares[two_forward][col] == Square::Empty {
-                    moves.push((Move::new(row, col, two_forward, col), NO_CAPTURE));
+                    moves.push(Move::new(row, col, two_forward, col), NO_CAPTURE);
                 }
             }
         }
@@ -262,7 +548,7 @@ impl ChessBoard {
                         new_col,
                         promotion_row,
                         CAPTURE_BASE + get_piece_value(&opponent_piece.kind) - 1,
-                        &mut moves,
+                        moves,
                     );
                 }
             }
@@ -270,50 +556,57 @@ impl ChessBoard {
 
         // En passant
         if let Some(en_passant) = self.en_passant {
-            if new_row == en_passant.row && (col as isize - en_passant.col as isize).abs() == 1 {
-                moves.push((Move::new(row, col, en_passant.row, en_passant.col), CAPTURE_BASE));
+            if new_row == en_passant.row() && (col as isize - en_passant.col() as isize).abs() == 1 {
+                moves.push(Move::new(row, col, en_passant.row(), en_passant.col()), CAPTURE_BASE);
             }
         }
-
-        moves
     }
 
-    fn add_pawn_moves_with_and_without_promotion(
+    fn add_pawn_moves_with_and_without_promotion<const N: usize>(
         row: usize,
         col: usize,
         new_row: usize,
         new_col: usize,
         promotion_row: usize,
         score: i32,
-        moves: &mut Vec<(Move, i32)>,
+        moves: &mut MoveList<N>,
     ) {
         if new_row == promotion_row {
             for &promotion_piece in &[PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
-                moves.push((
+                let promotion_score = match promotion_piece {
+                    PieceType::Queen if score == NO_CAPTURE => QUEEN_PROMOTION_SCORE,
+                    PieceType::Queen => score + get_piece_value(&PieceType::Queen) - 1,
+                    _ => score + UNDER_PROMOTION_SCORE,
+                };
+                moves.push(
                     Move::new(row, col, new_row, new_col).with_promotion(promotion_piece),
-                    score + 1,
-                ));
+                    promotion_score,
+                );
             }
         } else {
-            moves.push((Move::new(row, col, new_row, new_col), score));
+            moves.push(Move::new(row, col, new_row, new_col), score);
         }
     }
 
     /// Generate knight moves.
-    fn generate_knight_moves(&self, row: usize, col: usize) -> Vec<(Move, i32)> {
+    fn generate_knight_moves<const N: usize>(&self, row: usize, col: usize, moves: &mut MoveList<N>) {
         const KNIGHT_MOVES: [(isize, isize); 8] =
             [(-2, -1), (-1, -2), (1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1)];
 
-        self.generate_moves_from_directions(row, col, &KNIGHT_MOVES)
+        self.generate_moves_from_directions(row, col, &KNIGHT_MOVES, moves)
     }
 
     /// Generate sliding piece moves (bishop, rook, queen).
-    fn generate_sliding_moves(&self, row: usize, col: usize, directions: &[(isize, isize)]) -> Vec<(Move, i32)> {
-        let mut moves: Vec<(Move, i32)> = Vec::new();
-
+    fn generate_sliding_moves<const N: usize>(
+        &self,
+        row: usize,
+        col: usize,
+        directions: &[(isize, isize)],
+        moves: &mut MoveList<N>,
+    ) {
         let moving_piece = match self.squares[row][col] {
             Square::Occupied(p) => p,
-            _ => return moves,
+            _ => return,
         };
 
         for &(dx, dy) in directions {
@@ -329,161 +622,128 @@ impl ChessBoard {
                 }
 
                 match self.squares[new_row as usize][new_col as usize] {
-                    Square::Empty => moves.push((Move::new(row, col, new_row as usize, new_col as usize), NO_CAPTURE)),
+                    Square::Empty => moves.push(Move::new(row, col, new_row as usize, new_col as usize), NO_CAPTURE),
                     Square::Occupied(p) => {
                         if p.color != self.active_color {
-                            moves.push((
+                            moves.push(
                                 Move::new(row, col, new_row as usize, new_col as usize),
                                 CAPTURE_BASE + get_piece_value(&p.kind) - get_piece_value(&moving_piece.kind),
-                            ));
+                            );
                         }
                         break; // Block sliding
                     }
                 }
             }
         }
-
-        moves
     }
 
     /// Generate bishop moves.
-    fn generate_bishop_moves(&self, row: usize, col: usize) -> Vec<(Move, i32)> {
+    fn generate_bishop_moves<const N: usize>(&self, row: usize, col: usize, moves: &mut MoveList<N>) {
         const BISHOP_DIRECTIONS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
-        self.generate_sliding_moves(row, col, &BISHOP_DIRECTIONS)
+        self.generate_sliding_moves(row, col, &BISHOP_DIRECTIONS, moves)
     }
 
     /// Generate rook moves.
-    fn generate_rook_moves(&self, row: usize, col: usize) -> Vec<(Move, i32)> {
+    fn generate_rook_moves<const N: usize>(&self, row: usize, col: usize, moves: &mut MoveList<N>) {
         const ROOK_DIRECTIONS: [(isize, isize); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
-        self.generate_sliding_moves(row, col, &ROOK_DIRECTIONS)
+        self.generate_sliding_moves(row, col, &ROOK_DIRECTIONS, moves)
     }
 
     /// Generate queen moves.
-    fn generate_queen_moves(&self, row: usize, col: usize) -> Vec<(Move, i32)> {
+    fn generate_queen_moves<const N: usize>(&self, row: usize, col: usize, moves: &mut MoveList<N>) {
         const QUEEN_DIRECTIONS: [(isize, isize); 8] =
             [(-1, -1), (-1, 1), (1, -1), (1, 1), (0, -1), (0, 1), (-1, 0), (1, 0)];
-        self.generate_sliding_moves(row, col, &QUEEN_DIRECTIONS)
+        self.generate_sliding_moves(row, col, &QUEEN_DIRECTIONS, moves)
     }
 
     /// Generate king moves (including castling).
-    fn generate_king_moves(&self, row: usize, col: usize) -> Vec<(Move, i32)> {
+    fn generate_king_moves<const N: usize>(&self, row: usize, col: usize, moves: &mut MoveList<N>) {
         const KING_MOVES: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
 
-        let mut moves = self.generate_moves_from_directions(row, col, &KING_MOVES);
+        self.generate_moves_from_directions(row, col, &KING_MOVES, moves);
 
-        // Castling logic
-        let castling_rank = match self.active_color {
-            Color::White => 0,
-            Color::Black => 7,
-        };
-
-        if row == castling_rank && col == 4 {
-            // Ensure the king is in its starting position (e1/e8)
-            // Kingside castling
-            if self.castling_rights[if self.active_color == Color::White { 0 } else { 2 }]
-                && self.squares[row][5] == Square::Empty
-                && self.squares[row][6] == Square::Empty
-                && !self.is_square_attacked(row, 4)
-                && !self.is_square_attacked(row, 5)
-                && !self.is_square_attacked(row, 6)
-            {
-                moves.push((Move::new(row, 4, row, 6), CASTLING_SCORE)); // Move King: e1->g1 or e8->g8
-            }
-
-            // Queenside castling
-            if self.castling_rights[if self.active_color == Color::White { 1 } else { 3 }]
-                && self.squares[row][3] == Square::Empty
-                && self.squares[row][2] == Square::Empty
-                && self.squares[row][1] == Square::Empty
-                && !self.is_square_attacked(row, 4)
-                && !self.is_square_attacked(row, 3)
-                && !self.is_square_attacked(row, 2)
+        // Castling, only from the king's initial square
+        for kingside in [true, false] {
+            let castling = Castling::new(self.active_color, kingside);
+            if ChessField::new(row, col) == castling.king_from()
+                && self.castling_legality(castling) == MoveLegality::Legal
             {
-                moves.push((Move::new(row, 4, row, 2), CASTLING_SCORE)); // Move King: e1->c1 or e8->c8
+                moves.push(
+                    Move {
+                        from: castling.king_from(),
+                        to: castling.king_to(),
+                        promotion: None,
+                    },
+                    CASTLING_SCORE,
+                );
             }
         }
-        moves
     }
 
     pub fn make_move(&mut self, mv: Move) {
-        let piece = self.squares[mv.from.row][mv.from.col];
+        let piece = self.squares[mv.from.row()][mv.from.col()];
 
         match piece {
             Square::Empty => {
                 self.en_passant = None;
             }
             Square::Occupied(p) => {
-                self.squares[mv.from.row][mv.from.col] = Square::Empty;
-                self.squares[mv.to.row][mv.to.col] = piece;
+                let captured = self.squares[mv.to.row()][mv.to.col()];
+                let is_capture = matches!(captured, Square::Occupied(_));
+                if let Square::Occupied(captured) = captured {
+                    self.material.remove(captured);
+                }
+                self.squares[mv.from.row()][mv.from.col()] = Square::Empty;
+                self.squares[mv.to.row()][mv.to.col()] = piece;
 
                 if let Some(en_passant) = self.en_passant {
                     if mv.to == en_passant && p.kind == PieceType::Pawn {
                         //Remove piece from en passant
-                        self.squares[mv.from.row][mv.to.col] = Square::Empty;
+                        self.squares[mv.from.row()][mv.to.col()] = Square::Empty;
+                        let color = match p.color {
+                            Color::White => Color::Black,
+                            Color::Black => Color::White,
+                        };
+                        self.material.remove(Piece {
+                            color,
+                            kind: PieceType::Pawn,
+                        });
                     }
                 }
                 self.en_passant = None;
 
-                // Check if the move is a castling move and if castling is allowed
+                // The rook moves along if the king castles
                 if p.kind == PieceType::King {
-                    if mv.from.col == 4 && mv.to.col == 6 && mv.from.row == mv.to.row {
-                        if self.castling_rights[if self.active_color == Color::White { 0 } else { 2 }] {
-                            let rook_col = 7;
-                            self.squares[mv.from.row][5] = self.squares[mv.from.row][rook_col];
-                            self.squares[mv.from.row][rook_col] = Square::Empty;
-                        }
-                    } else if mv.from.col == 4 && mv.to.col == 2 && mv.from.row == mv.to.row {
-                        // Queenside castling
-                        if self.castling_rights[if self.active_color == Color::White { 1 } else { 3 }] {
-                            let rook_col = 0;
-                            self.squares[mv.from.row][3] = self.squares[mv.from.row][rook_col];
-                            self.squares[mv.from.row][rook_col] = Square::Empty;
-                        }
+                    if let Some(castling) =
+                        Castling::from_king_move(p.color, mv).filter(|&castling| self.castling_rights.has(castling))
+                    {
+                        let (rook_from, rook_to) = (castling.rook_from(), castling.rook_to());
+                        self.squares[rook_to.row()][rook_to.col()] = self.squares[rook_from.row()][rook_from.col()];
+                        self.squares[rook_from.row()][rook_from.col()] = Square::Empty;
                     }
                 }
-                if mv.from.row == 0 && mv.from.col == 0 {
-                    self.castling_rights[1] = false;
-                } else if mv.from.row == 7 && mv.from.col == 0 {
-                    self.castling_rights[3] = false;
-                } else if mv.from.row == 0 && mv.from.col == 7 {
-                    self.castling_rights[0] = false;
-                } else if mv.from.row == 7 && mv.from.col == 7 {
-                    self.castling_rights[2] = false;
-                } else if mv.from.row == 0 && mv.from.col == 4 {
-                    self.castling_rights[0] = false;
-                    self.castling_rights[1] = false;
-                } else if mv.from.row == 7 && mv.from.col == 4 {
-                    self.castling_rights[2] = false;
-                    self.castling_rights[3] = false;
-                }
-                //capture of the rooks
-                if mv.to.row == 0 && mv.to.col == 0 {
-                    self.castling_rights[1] = false;
-                } else if mv.to.row == 7 && mv.to.col == 0 {
-                    self.castling_rights[3] = false;
-                } else if mv.to.row == 0 && mv.to.col == 7 {
-                    self.castling_rights[0] = false;
-                } else if mv.to.row == 7 && mv.to.col == 7 {
-                    self.castling_rights[2] = false;
-                }
+                // Moves of the king or a rook and captures of a rook end castling rights
+                self.castling_rights.update(mv);
 
-                if p.kind == PieceType::Pawn || matches!(self.squares[mv.to.row][mv.to.col], Square::Occupied(_)) {
+                if p.kind == PieceType::Pawn || is_capture {
                     self.halfmove_clock = 0;
                 } else {
                     self.halfmove_clock += 1;
                 }
 
                 if p.kind == PieceType::Pawn {
-                    if p.color == Color::White && mv.from.row == 1 && mv.to.row == 3 {
-                        self.en_passant = Some(ChessField::new(2, mv.from.col));
-                    } else if p.color == Color::Black && mv.from.row == 6 && mv.to.row == 4 {
-                        self.en_passant = Some(ChessField::new(5, mv.from.col));
-                    } else if mv.promotion.is_some() {
+                    if p.color == Color::White && mv.from.row() == 1 && mv.to.row() == 3 {
+                        self.en_passant = Some(ChessField::new(2, mv.from.col()));
+                    } else if p.color == Color::Black && mv.from.row() == 6 && mv.to.row() == 4 {
+                        self.en_passant = Some(ChessField::new(5, mv.from.col()));
+                    } else if let Some(kind) = mv.promotion {
                         // Handle promotion
-                        self.squares[mv.to.row][mv.to.col] = Square::Occupied(Piece {
+                        self.squares[mv.to.row()][mv.to.col()] = Square::Occupied(Piece {
                             color: p.color,
-                            kind: mv.promotion.unwrap(), // Replace the pawn with the promoted piece
+                            kind, // Replace the pawn with the promoted piece
                         });
+                        self.material.remove(p);
+                        self.material.add(Piece { color: p.color, kind });
                     }
                 }
             }
@@ -498,9 +758,10 @@ impl ChessBoard {
         if self.active_color == Color::White {
             self.fullmove_number += 1;
         }
+        self.clear_unusable_en_passant();
 
-        let zobrist = &*ZOBRIST;
-        self.repetition_map.push_back(zobrist.calculate_hash(self));
+        let hash = ZOBRIST.calculate_hash(self);
+        self.history.push(hash, self.halfmove_clock == 0);
     }
 
     pub fn is_square_attacked(&self, row: usize, col: usize) -> bool {
@@ -581,6 +842,130 @@ impl ChessBoard {
         false
     }
 
+    /// Returns the squares of all pieces of the given color attacking the square, including pinned pieces.
+    /// Sliding pieces behind other attackers (x-rays) are not included.
+    pub fn attackers_to(&self, square: ChessField, color: Color) -> Vec<ChessField> {
+        const KNIGHT_MOVES: [(isize, isize); 8] =
+            [(-2, -1), (-1, -2), (1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1)];
+        const KING_MOVES: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+        const DIRECTIONS: [(isize, isize); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+        let pawn_attacks = match color {
+            Color::Black => [(1, -1), (1, 1)],
+            Color::White => [(-1, -1), (-1, 1)],
+        };
+        let mut attackers = Vec::new();
+        for (directions, piece_type) in [
+            (&pawn_attacks[..], PieceType::Pawn),
+            (&KNIGHT_MOVES[..], PieceType::Knight),
+            (&KING_MOVES[..], PieceType::King),
+        ] {
+            for &(dx, dy) in directions {
+                if let Some(field) = square.offset(dx, dy) {
+                    if let Square::Occupied(piece) = self.squares[field.row()][field.col()] {
+                        if piece.color == color && piece.kind == piece_type {
+                            attackers.push(field);
+                        }
+                    }
+                }
+            }
+        }
+
+        for &(dx, dy) in &DIRECTIONS {
+            let is_diagonal = dx != 0 && dy != 0;
+            let mut next = square.offset(dx, dy);
+            while let Some(field) = next {
+                if let Square::Occupied(piece) = self.squares[field.row()][field.col()] {
+                    let attacks = match piece.kind {
+                        PieceType::Rook => !is_diagonal,
+                        PieceType::Bishop => is_diagonal,
+                        PieceType::Queen => true,
+                        _ => false,
+                    };
+                    if piece.color == color && attacks {
+                        attackers.push(field);
+                    }
+                    break;
+                }
+                next = field.offset(dx, dy);
+            }
+        }
+        attackers
+    }
+
+    /// Returns the least valuable piece of the given color attacking the square, the one an exchange on the square
+    /// continues with. Knights go before bishops, pinned pieces and x-rays are handled like in
+    /// [`ChessBoard::attackers_to`], but nothing is allocated.
+    pub fn least_valuable_attacker(&self, square: ChessField, color: Color) -> Option<(ChessField, PieceType)> {
+        const KNIGHT_MOVES: [(isize, isize); 8] =
+            [(-2, -1), (-1, -2), (1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1)];
+        const KING_MOVES: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+        const DIRECTIONS: [(isize, isize); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+        let pawn_attacks = match color {
+            Color::Black => [(1, -1), (1, 1)],
+            Color::White => [(-1, -1), (-1, 1)],
+        };
+        let stepping_attacker = |directions: &[(isize, isize)], piece_type: PieceType| {
+            directions.iter().find_map(|&(dx, dy)| {
+                let field = square.offset(dx, dy)?;
+                match self.squares[field.row()][field.col()] {
+                    Square::Occupied(piece) if piece.color == color && piece.kind == piece_type => {
+                        Some((field, piece_type))
+                    }
+                    _ => None,
+                }
+            })
+        };
+        let attacker = stepping_attacker(&pawn_attacks, PieceType::Pawn);
+        if attacker.is_some() {
+            return attacker;
+        }
+        let attacker = stepping_attacker(&KNIGHT_MOVES, PieceType::Knight);
+        if attacker.is_some() {
+            return attacker;
+        }
+
+        // The first piece in every direction, bishops before rooks before queens
+        let slider_rank = |kind: PieceType| match kind {
+            PieceType::Bishop => 0,
+            PieceType::Rook => 1,
+            _ => 2,
+        };
+        let mut slider: Option<(ChessField, PieceType)> = None;
+        for &(dx, dy) in &DIRECTIONS {
+            let is_diagonal = dx != 0 && dy != 0;
+            let mut next = square.offset(dx, dy);
+            while let Some(field) = next {
+                if let Square::Occupied(piece) = self.squares[field.row()][field.col()] {
+                    let attacks = match piece.kind {
+                        PieceType::Rook => !is_diagonal,
+                        PieceType::Bishop => is_diagonal,
+                        PieceType::Queen => true,
+                        _ => false,
+                    };
+                    if piece.color == color
+                        && attacks
+                        && slider.is_none_or(|(_, kind)| slider_rank(piece.kind) < slider_rank(kind))
+                    {
+                        slider = Some((field, piece.kind));
+                    }
+                    break;
+                }
+                next = field.offset(dx, dy);
+            }
+        }
+        if slider.is_some() {
+            return slider;
+        }
+        stepping_attacker(&KING_MOVES, PieceType::King)
+    }
+
+    /// Returns true if a piece of the given color attacks the square, so a piece captured there can be recaptured.
+    pub fn defended(&self, square: ChessField, color: Color) -> bool {
+        self.is_square_attacked_by_color(square.row(), square.col(), color)
+    }
+
     fn check_attack(
         &self,
         row: usize,
@@ -604,17 +989,16 @@ impl ChessBoard {
         false
     }
 
-    fn generate_moves_from_directions(
+    fn generate_moves_from_directions<const N: usize>(
         &self,
         row: usize,
         col: usize,
         directions: &[(isize, isize)],
-    ) -> Vec<(Move, i32)> {
-        let mut moves = Vec::new();
-
+        moves: &mut MoveList<N>,
+    ) {
         let moving_piece = match self.squares[row][col] {
             Square::Occupied(p) => p,
-            _ => return moves,
+            _ => return,
         };
 
         for &(dx, dy) in directions {
@@ -623,219 +1007,494 @@ impl ChessBoard {
 
             if new_row < 8 && new_col < 8 {
                 match self.squares[new_row][new_col] {
-                    Square::Empty => moves.push((Move::new(row, col, new_row, new_col), NO_CAPTURE)),
+                    Square::Empty => moves.push(Move::new(row, col, new_row, new_col), NO_CAPTURE),
                     Square::Occupied(p) => {
                         if p.color != self.active_color {
-                            moves.push((
+                            moves.push(
                                 Move::new(row, col, new_row, new_col),
                                 CAPTURE_BASE + get_piece_value(&p.kind) - get_piece_value(&moving_piece.kind),
-                            ));
+                            );
                         }
                     }
                 }
             }
         }
-        moves
+    }
+
+    pub fn square(&self, field: ChessField) -> Square {
+        self.squares[field.row()][field.col()]
     }
 
     pub fn find_king_position(&self, color: Color) -> Option<ChessField> {
-        for row in 0..8 {
-            for col in 0..8 {
-                if let Square::Occupied(Piece {
-                                            color: piece_color,
-                                            kind: PieceType::King,
-                                        }) = self.squares[row][col]
-                {
-                    if piece_color == color {
-                        return Some(ChessField::new(row, col));
-                    }
-                }
-            }
-        }
-        None // Should never occur in a valid chess position
+        let king = Square::Occupied(Piece {
+            color,
+            kind: PieceType::King,
+        });
+        // None should never occur in a valid chess position
+        ChessField::all().find(|&field| self.square(field) == king)
     }
 
     pub fn generate_legal_moves(&self) -> Vec<Move> {
-        let mut legal_moves = Vec::new();
-
-        // Generate all pseudo-legal moves
-        let pseudo_moves = self.generate_pseudo_moves();
-
-        // For each pseudo-legal move, check if it leaves the king in check
-        for mv in pseudo_moves {
-            let mut board_clone = self.clone();
-            board_clone.make_move(mv.0);
+        let mut legal_moves: MoveList = MoveList::new();
+        let checkers = self.checkers();
 
-            let king_position = board_clone.find_king_position(self.active_color);
+        // Generate all pseudo-legal moves, in a double check only those of the king
+        self.generate_candidate_moves_into(&checkers, &mut legal_moves);
 
-            // if mv.0.from.col == 1 && mv.0.from.row == 3 {
-            //     println!("{:?}", mv);
-            // }
-            if let Some(king_pos) = king_position {
-                if !board_clone.is_square_attacked_by_color(king_pos.row, king_pos.col, board_clone.active_color) {
-                    legal_moves.push(mv); // Add move to legal moves if not leaving the king in check
-                }
-            }
-        }
-        legal_moves.sort_by(|a, b| b.1.cmp(&a.1));
+        // Keep the moves that do not leave the king in check
+        legal_moves.retain(|mv| self.may_evade(mv, &checkers) && self.is_pseudo_move_legal(mv));
+        sort_by_score(&mut legal_moves);
         legal_moves.iter().map(|m| m.0).collect()
     }
 
-    pub fn generate_capture_moves(&self) -> Vec<Move> {
-        let mut capture_moves = Vec::new();
-
-        for row in 0..8 {
-            for col in 0..8 {
-                // Only process pieces of the active color
-                if let Square::Occupied(piece) = self.squares[row][col] {
-                    if piece.color == self.active_color {
-                        let piece_moves = self.generate_pseudo_moves_from_position(row, col);
-
-                        // Filter for capture moves
-                        for mv in piece_moves {
-                            if let Square::Occupied(target_piece) = self.squares[mv.0.to.row][mv.0.to.col] {
-                                if target_piece.color != self.active_color {
-                                    capture_moves.push(mv);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    /// The pseudo-legal moves that can be legal with these checkers: all of them out of check, the king moves only
+    /// in a double check.
+    pub fn generate_candidate_moves_into<const N: usize>(&self, checkers: &[ChessField], moves: &mut MoveList<N>) {
+        match self.find_king_position(self.active_color) {
+            Some(king) if checkers.len() >= 2 => self.add_pseudo_moves_from_position(king.row(), king.col(), moves),
+            _ => self.generate_pseudo_moves_into(moves),
         }
+    }
 
-        capture_moves.sort_by(|a, b| b.1.cmp(&a.1));
+    pub fn generate_capture_moves(&self) -> Vec<Move> {
+        let mut capture_moves: MoveList = MoveList::new();
+        self.generate_capture_moves_into(&mut capture_moves);
         capture_moves.iter().map(|m| m.0).collect()
     }
 
+    /// Replaces the moves of the list by the pseudo-legal captures, en passant included, best first.
+    fn generate_capture_moves_into<const N: usize>(&self, moves: &mut MoveList<N>) {
+        moves.clear();
+        self.generate_pseudo_moves_into(moves);
+        moves.retain(|mv| self.is_capture(mv));
+        sort_by_score(moves);
+    }
+
     pub fn generate_legal_capture_moves(&self) -> Vec<Move> {
-        let mut legal_moves = Vec::new();
+        let mut legal_moves: MoveList = MoveList::new();
+        self.generate_legal_capture_moves_into(&mut legal_moves);
+        legal_moves.iter().map(|m| m.0).collect()
+    }
 
-        let pseudo_moves = self.generate_capture_moves();
+    /// Replaces the moves of the list by the legal captures, best first, without allocating.
+    pub fn generate_legal_capture_moves_into<const N: usize>(&self, moves: &mut MoveList<N>) {
+        self.generate_capture_moves_into(moves);
+        let checkers = self.checkers();
+        moves.retain(|mv| self.may_evade(mv, &checkers) && self.is_pseudo_move_legal(mv));
+    }
 
-        for mv in pseudo_moves {
-            let mut board_clone = self.clone(); // Clone the board to simulate the move
-            board_clone.make_move(mv); // Make the move on the cloned board
+    /// Checks whether the move is legal in this position, for moves that were not generated from it (user input,
+    /// TT or killer moves).
+    pub fn is_legal(&self, mv: Move) -> bool {
+        let mut moves: MoveList<MAX_PIECE_MOVES> = MoveList::new();
+        self.add_pseudo_moves_from_position(mv.from.row(), mv.from.col(), &mut moves);
+        let generated = moves.iter().any(|&(generated, _)| generated == mv);
+        generated && self.is_pseudo_move_legal(mv)
+    }
 
-            // Locate the king of the current player
-            let king_position = board_clone.find_king_position(self.active_color);
+    /// Parses a move in coordinate notation and checks that it is legal, the error tells which rule it breaks.
+    pub fn legal_move_from_algebraic(&self, algebraic: &str) -> Result<Move, String> {
+        let mv = Move::parse_algebraic(algebraic)?;
+        match self.classify_move(mv) {
+            MoveLegality::Legal => Ok(mv),
+            legality => Err(format!("Illegal move {}: {}", algebraic, legality.description())),
+        }
+    }
 
-            // Check if the king is under attack after the move
-            if let Some(king_pos) = king_position {
-                if !board_clone.is_square_attacked_by_color(king_pos.row, king_pos.col, board_clone.active_color) {
-                    legal_moves.push(mv); // Add move to legal moves if not leaving the king in check
+    /// Parses a move of a GUI and checks that it is legal. Besides coordinate notation castling is accepted as the
+    /// king capturing its own rook (e1h1, as Chess960 GUIs send it) and in SAN (O-O, 0-0-0 with an optional check
+    /// sign), both become the king move e1g1 or e1c1 of the side to move.
+    pub fn legal_move_from_uci(&self, notation: &str) -> Result<Move, String> {
+        let row = match self.active_color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        let castling_col = match notation.trim_end_matches(['+', '#']) {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => Move::parse_algebraic(notation)
+                .ok()
+                .and_then(|mv| self.king_takes_rook_castling(mv, row)),
+        };
+        let Some(col) = castling_col else {
+            return self.legal_move_from_algebraic(notation);
+        };
+        match self.classify_move(Move::new(row, 4, row, col)) {
+            MoveLegality::Legal => Ok(Move::new(row, 4, row, col)),
+            legality => Err(format!("Illegal move {}: {}", notation, legality.description())),
+        }
+    }
+
+    /// The column the king castles to if the move is the king on its start square taking its own rook in a corner.
+    fn king_takes_rook_castling(&self, mv: Move, row: usize) -> Option<usize> {
+        let own = |kind| {
+            Square::Occupied(Piece {
+                color: self.active_color,
+                kind,
+            })
+        };
+        let king_on_start = mv.from == ChessField::new(row, 4) && self.square(mv.from) == own(PieceType::King);
+        let takes_rook = mv.to.row() == row && self.square(mv.to) == own(PieceType::Rook);
+        if !king_on_start || !takes_rook || mv.promotion.is_some() {
+            return None;
+        }
+        match mv.to.col() {
+            7 => Some(6),
+            0 => Some(2),
+            _ => None,
+        }
+    }
+
+    /// Classifies the move like is_legal, but tells which rule an illegal move breaks.
+    pub fn classify_move(&self, mv: Move) -> MoveLegality {
+        let piece = match self.squares[mv.from.row()][mv.from.col()] {
+            Square::Occupied(piece) => piece,
+            Square::Empty => return MoveLegality::NoPiece,
+        };
+        if piece.color != self.active_color {
+            return MoveLegality::NotYourPiece;
+        }
+        if mv.from == mv.to {
+            return MoveLegality::InvalidPieceMove;
+        }
+        let target = self.squares[mv.to.row()][mv.to.col()];
+        if matches!(target, Square::Occupied(p) if p.color == piece.color) {
+            return MoveLegality::CapturesOwnPiece;
+        }
+        let (forward, start_row, promotion_row) = match piece.color {
+            Color::White => (1, 1, 7),
+            Color::Black => (-1, 6, 0),
+        };
+        let reaches_last_rank = piece.kind == PieceType::Pawn && mv.to.row() == promotion_row;
+        match mv.promotion {
+            Some(_) if !reaches_last_rank => return MoveLegality::WrongPromotionRank,
+            Some(PieceType::Pawn | PieceType::King) => return MoveLegality::InvalidPromotionPiece,
+            None if reaches_last_rank => return MoveLegality::MissingPromotion,
+            _ => {}
+        }
+
+        let d_row = mv.to.row() as isize - mv.from.row() as isize;
+        let d_col = mv.to.col() as isize - mv.from.col() as isize;
+        let legality = match piece.kind {
+            PieceType::Pawn => {
+                if d_col == 0 && (d_row == forward || (d_row == 2 * forward && mv.from.row() == start_row)) {
+                    if self.is_path_clear(mv) && target == Square::Empty {
+                        MoveLegality::Legal
+                    } else {
+                        MoveLegality::BlockedPath
+                    }
+                } else if d_col.abs() == 1 && d_row == forward {
+                    if target != Square::Empty || self.en_passant == Some(mv.to) {
+                        MoveLegality::Legal
+                    } else {
+                        MoveLegality::NothingToCapture
+                    }
+                } else {
+                    MoveLegality::InvalidPieceMove
                 }
             }
+            PieceType::King if d_row == 0 && d_col.abs() == 2 => return self.classify_castling(mv),
+            PieceType::King if d_row.abs() <= 1 && d_col.abs() <= 1 => MoveLegality::Legal,
+            PieceType::Knight if d_row.abs() * d_col.abs() == 2 => MoveLegality::Legal,
+            PieceType::Bishop if d_row.abs() == d_col.abs() => self.sliding_legality(mv),
+            PieceType::Rook if d_row == 0 || d_col == 0 => self.sliding_legality(mv),
+            PieceType::Queen if d_row.abs() == d_col.abs() || d_row == 0 || d_col == 0 => self.sliding_legality(mv),
+            _ => MoveLegality::InvalidPieceMove,
+        };
+        if legality == MoveLegality::Legal && !self.is_pseudo_move_legal(mv) {
+            return MoveLegality::LeavesKingInCheck;
         }
+        legality
+    }
 
-        legal_moves
+    fn sliding_legality(&self, mv: Move) -> MoveLegality {
+        if self.is_path_clear(mv) {
+            MoveLegality::Legal
+        } else {
+            MoveLegality::BlockedPath
+        }
     }
 
-    #[allow(dead_code)]
-    pub fn is_stalemate(&self) -> bool {
-        if let Some(king_pos) = self.find_king_position(self.active_color) {
-            if self.is_square_attacked(king_pos.row, king_pos.col) {
+    /// Whether the squares between the start and the target of a straight or diagonal move are empty.
+    fn is_path_clear(&self, mv: Move) -> bool {
+        let d_row = (mv.to.row() as isize - mv.from.row() as isize).signum();
+        let d_col = (mv.to.col() as isize - mv.from.col() as isize).signum();
+        let (mut row, mut col) = (mv.from.row() as isize + d_row, mv.from.col() as isize + d_col);
+        while (row, col) != (mv.to.row() as isize, mv.to.col() as isize) {
+            if self.squares[row as usize][col as usize] != Square::Empty {
                 return false;
             }
-        } else {
-            return false;
+            row += d_row;
+            col += d_col;
         }
-        self.generate_legal_moves().is_empty()
+        true
     }
 
-    #[allow(dead_code)]
-    pub fn is_checkmate(&self) -> bool {
-        // Step 1: Ensure the active player's king is in check
-        if let Some(king_pos) = self.find_king_position(self.active_color) {
-            if !self.is_square_attacked(king_pos.row, king_pos.col) {
-                return false;
+    /// Classifies a king move by two squares along its rank, which is only legal as castling.
+    fn classify_castling(&self, mv: Move) -> MoveLegality {
+        match Castling::from_king_move(self.active_color, mv) {
+            Some(castling) => self.castling_legality(castling),
+            None => MoveLegality::InvalidPieceMove,
+        }
+    }
+
+    /// Whether the side to move can castle: it has the right, the squares between king and rook are empty and the
+    /// king does not start on, pass or land on an attacked square.
+    fn castling_legality(&self, castling: Castling) -> MoveLegality {
+        if !self.castling_rights.has(castling) {
+            return MoveLegality::CastlingWithoutRights;
+        }
+        if castling
+            .empty_squares()
+            .any(|field| self.square(field) != Square::Empty)
+        {
+            return MoveLegality::BlockedPath;
+        }
+        let [king, path @ ..] = castling.king_path();
+        if self.is_square_attacked(king.row(), king.col()) {
+            return MoveLegality::CastlingOutOfCheck;
+        }
+        if path
+            .iter()
+            .any(|field| self.is_square_attacked(field.row(), field.col()))
+        {
+            return MoveLegality::CastlingThroughCheck;
+        }
+        MoveLegality::Legal
+    }
+
+    /// Checks whether a pseudo-legal move does not leave the own king in check.
+    pub fn is_pseudo_move_legal(&self, mv: Move) -> bool {
+        let mut board_clone = self.clone(); // Clone the board to simulate the move
+        board_clone.make_move(mv);
+
+        // Locate the king of the current player and check if it is under attack after the move
+        match board_clone.find_king_position(self.active_color) {
+            Some(king_pos) => {
+                !board_clone.is_square_attacked_by_color(king_pos.row(), king_pos.col(), board_clone.active_color)
             }
-        } else {
-            return false;
+            None => false,
         }
+    }
 
-        self.generate_legal_moves().is_empty()
+    /// The en passant captures of the side to move, whether they leave the king in check or not.
+    fn en_passant_moves(&self) -> impl Iterator<Item = Move> + '_ {
+        let forward: isize = match self.active_color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        self.en_passant.into_iter().flat_map(move |field| {
+            let row = field.row() as isize - forward;
+            [-1, 1].into_iter().filter_map(move |dc| {
+                let col = field.col() as isize + dc;
+                let pawn = Square::Occupied(Piece {
+                    color: self.active_color,
+                    kind: PieceType::Pawn,
+                });
+                ((0..8).contains(&row) && (0..8).contains(&col) && self.squares[row as usize][col as usize] == pawn)
+                    .then(|| Move::new(row as usize, col as usize, field.row(), field.col()))
+            })
+        })
     }
 
-    #[allow(dead_code)]
-    pub fn is_draw(&self) -> bool {
-        self.is_draw_by_fifty_move_rule() || self.is_threefold_repetition()
+    /// Whether a pawn of the side to move stands next to the pawn that can be captured en passant.
+    pub fn has_pseudo_legal_en_passant(&self) -> bool {
+        self.en_passant_moves().next().is_some()
     }
-    #[allow(dead_code)]
-    pub fn is_draw_by_fifty_move_rule(&self) -> bool {
-        self.halfmove_clock >= 100
+
+    /// Whether an en passant capture is legal. Without a king of the side to move, as in some test positions, the
+    /// pseudo-legal captures count.
+    pub fn has_legal_en_passant(&self) -> bool {
+        let has_king = self.find_king_position(self.active_color).is_some();
+        self.en_passant_moves()
+            .any(|mv| !has_king || self.is_pseudo_move_legal(mv))
     }
 
-    pub fn is_threefold_repetition(&self) -> bool {
-        let mut repetition_count = 0;
+    /// Drops an en passant square no capture can use. Positions that only differ by such a square are the same
+    /// position, so they get the same hash, FEN and repetition count.
+    pub fn clear_unusable_en_passant(&mut self) {
+        if self.en_passant.is_some() && !self.has_legal_en_passant() {
+            self.en_passant = None;
+        }
+    }
 
-        if let Some(&current_hash) = self.repetition_map.back() {
-            for &stored_hash in self.repetition_map.iter() {
-                if stored_hash == current_hash {
-                    repetition_count += 1;
-                }
-                if repetition_count >= 3 {
-                    return true;
-                }
+    /// A move capturing a piece, including en passant.
+    pub fn is_capture(&self, mv: Move) -> bool {
+        match self.squares[mv.to.row()][mv.to.col()] {
+            Square::Occupied(_) => true,
+            Square::Empty => {
+                self.en_passant == Some(mv.to)
+                    && matches!(
+                        self.squares[mv.from.row()][mv.from.col()],
+                        Square::Occupied(Piece {
+                            kind: PieceType::Pawn,
+                            ..
+                        })
+                    )
             }
         }
+    }
 
-        false
+    #[allow(dead_code)]
+    pub fn is_stalemate(&self) -> bool {
+        self.find_king_position(self.active_color).is_some() && !self.is_in_check() && !self.has_legal_move()
     }
 
-    pub(crate) fn render_to_string(&self) -> String {
-        let mut board_representation = String::new();
-        board_representation.push_str("    a   b   c   d   e   f   g   h  \n");
-        board_representation.push_str("  ┌───┬───┬───┬───┬───┬───┬───┬───┐\n");
+    /// Tests the attacks on the king's square only, no moves are generated.
+    pub fn is_in_check(&self) -> bool {
+        match self.find_king_position(self.active_color) {
+            Some(king_pos) => self.is_square_attacked(king_pos.row(), king_pos.col()),
+            None => false,
+        }
+    }
 
-        for row in (0..8).rev() { // Render rows from top (8) to bottom (1)
-            board_representation.push_str(&format!("{} │", row + 1)); // Row number on the left
-            for col in 0..8 {
-                let square = match &self.squares[row][col] {
-                    Square::Empty => ' ',
-                    Square::Occupied(piece) => match piece.kind {
-                        PieceType::Pawn => if piece.color == Color::White { 'P' } else { 'p' },
-                        PieceType::Knight => if piece.color == Color::White { 'N' } else { 'n' },
-                        PieceType::Bishop => if piece.color == Color::White { 'B' } else { 'b' },
-                        PieceType::Rook => if piece.color == Color::White { 'R' } else { 'r' },
-                        PieceType::Queen => if piece.color == Color::White { 'Q' } else { 'q' },
-                        PieceType::King => if piece.color == Color::White { 'K' } else { 'k' },
-                    },
-                };
-                board_representation.push_str(&format!(" {} │", square));
-            }
-            board_representation.push_str(&format!(" {}\n", row + 1));
+    /// The pieces giving check to the side to move: none, one, or two in a double check, which leaves only king
+    /// moves. Discovered checks count like direct ones, the piece that moved away is not among them.
+    pub fn checkers(&self) -> Vec<ChessField> {
+        match self.find_king_position(self.active_color) {
+            Some(king) => self.attackers_to(king, opponent(self.active_color)),
+            None => Vec::new(),
+        }
+    }
 
-            // Add horizontal grid border between rows
-            if row > 0 {
-                board_representation.push_str("  ├───┼───┼───┼───┼───┼───┼───┼───┤\n");
-            }
+    /// Whether a pseudo-legal move can get the king out of the check of these checkers: a king move, and against a
+    /// single checker a capture of it or a move onto the line between it and the king. Any move may evade without
+    /// checkers. Pins are left to is_pseudo_move_legal, this only spares it the moves that cannot help.
+    pub fn may_evade(&self, mv: Move, checkers: &[ChessField]) -> bool {
+        let Square::Occupied(piece) = self.square(mv.from) else {
+            return false;
+        };
+        if checkers.is_empty() || piece.kind == PieceType::King {
+            return true;
         }
+        let (&[checker], Some(king)) = (checkers, self.find_king_position(self.active_color)) else {
+            return false;
+        };
+        // A pawn that just made a double step and gives check can be captured en passant
+        let en_passant = piece.kind == PieceType::Pawn
+            && Some(mv.to) == self.en_passant
+            && checker == ChessField::new(mv.from.row(), mv.to.col());
+        mv.to == checker || en_passant || is_between(mv.to, checker, king)
+    }
 
-        board_representation.push_str("  └───┴───┴───┴───┴───┴───┴───┴───┘\n");
-        board_representation.push_str("    a   b   c   d   e   f   g   h  \n");
+    #[allow(dead_code)]
+    pub fn is_checkmate(&self) -> bool {
+        let checkers = self.checkers();
+        !checkers.is_empty() && !self.has_legal_move_with(&checkers)
+    }
 
-        board_representation
+    /// Stops at the first legal move. Together with is_in_check it tells checkmate and stalemate apart with a single
+    /// move generation.
+    pub fn has_legal_move(&self) -> bool {
+        self.has_legal_move_with(&self.checkers())
+    }
+
+    fn has_legal_move_with(&self, checkers: &[ChessField]) -> bool {
+        let mut moves: MoveList = MoveList::new();
+        self.generate_candidate_moves_into(checkers, &mut moves);
+        moves
+            .iter()
+            .any(|&(mv, _)| self.may_evade(mv, checkers) && self.is_pseudo_move_legal(mv))
+    }
+
+    #[allow(dead_code)]
+    pub fn is_draw(&self) -> bool {
+        self.is_draw_by_fifty_move_rule() || self.is_threefold_repetition() || self.is_insufficient_material()
+    }
+
+    /// Whether neither side can mate any more: kings alone, with a single knight or with bishops all on squares of
+    /// the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        if !self.material.is_minor_only() {
+            return false;
+        }
+        let mut bishop_colors = self
+            .squares
+            .iter()
+            .enumerate()
+            .flat_map(|(row, squares)| squares.iter().enumerate().map(move |(col, &square)| (row, col, square)))
+            .filter(|&(_, _, square)| matches!(square, Square::Occupied(piece) if piece.kind == PieceType::Bishop))
+            .map(|(row, col, _)| (row + col) % 2);
+        match bishop_colors.next() {
+            Some(first) => bishop_colors.all(|color| color == first),
+            None => true,
+        }
+    }
+    pub fn is_draw_by_fifty_move_rule(&self) -> bool {
+        self.halfmove_clock >= 100
     }
-}
 
-pub fn perft(board: &ChessBoard, depth: u8) -> u64 {
-    let mut node_count = 0u64;
+    /// Halfmoves without capture or pawn move left until a draw by the fifty-move rule may be claimed.
+    pub fn halfmoves_until_fifty_move_draw(&self) -> u32 {
+        100u32.saturating_sub(self.halfmove_clock)
+    }
 
-    if depth == 0 {
-        return 1u64;
+    /// The Zobrist hash of the position, maintained by make_move.
+    pub fn zobrist_hash(&self) -> u64 {
+        match self.history.current() {
+            Some(hash) => hash,
+            None => ZOBRIST.calculate_hash(self),
+        }
     }
 
-    let moves = board.generate_legal_moves();
-    if moves.is_empty() {
-        return 0u64;
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
     }
-    for mv in moves {
-        let mut new_board = board.clone();
-        new_board.make_move(mv);
-        node_count += perft(&new_board, depth - 1);
+
+    /// How often the current position occurred, counting itself.
+    pub fn repetition_count(&self) -> usize {
+        self.history.repetition_count()
+    }
+
+    /// The draw that ends the game without a claim. A checkmate on the last move takes precedence.
+    pub fn automatic_draw(&self) -> Option<DrawRule> {
+        let rule = if self.repetition_count() >= 5 {
+            DrawRule::FivefoldRepetition
+        } else if self.halfmove_clock >= 150 {
+            DrawRule::SeventyFiveMoves
+        } else if self.is_insufficient_material() {
+            DrawRule::InsufficientMaterial
+        } else {
+            return None;
+        };
+        (!self.is_checkmate()).then_some(rule)
+    }
+
+    /// The draw the side to move may claim, either on the board already or by announcing a move: a move to a position
+    /// occurring the third time, or a move without capture or pawn move when the halfmove clock is at 99.
+    pub fn claimable_draw(&self) -> Option<DrawClaim> {
+        let moves = self.generate_legal_moves();
+        if moves.is_empty() && self.is_in_check() {
+            return None;
+        }
+        if self.is_threefold_repetition() {
+            return Some(DrawClaim {
+                rule: DrawRule::ThreefoldRepetition,
+                with_move: None,
+            });
+        }
+        if self.is_draw_by_fifty_move_rule() {
+            return Some(DrawClaim {
+                rule: DrawRule::FiftyMoves,
+                with_move: None,
+            });
+        }
+        moves.into_iter().find_map(|mv| {
+            let mut new_board = self.clone();
+            new_board.make_move(mv);
+            let rule = if new_board.is_threefold_repetition() {
+                DrawRule::ThreefoldRepetition
+            } else if new_board.is_draw_by_fifty_move_rule() && !new_board.is_checkmate() {
+                DrawRule::FiftyMoves
+            } else {
+                return None;
+            };
+            Some(DrawClaim {
+                rule,
+                with_move: Some(mv),
+            })
+        })
     }
-    node_count
 }
 
 #[cfg(test)]
@@ -846,7 +1505,7 @@ mod tests {
     impl ChessBoard {
         /// Creates an empty chess board
         pub fn generate_pseudo_moves_from_chess_field(&self, pos: ChessField) -> Vec<Move> {
-            self.generate_pseudo_moves_from_position(pos.row, pos.col)
+            self.generate_pseudo_moves_from_position(pos.row(), pos.col())
                 .into_iter()
                 .map(|m| m.0)
                 .collect()
@@ -857,12 +1516,6 @@ mod tests {
         }
     }
 
-    impl ChessField {
-        pub fn as_algebraic(&self) -> String {
-            to_algebraic_square(self.row, self.col)
-        }
-    }
-
     fn assert_moves(generated: Vec<Move>, mut expected: Vec<&str>) {
         let mut generated_converted: Vec<_> = generated.iter().map(|m| m.as_algebraic()).collect();
         generated_converted.sort();
@@ -875,9 +1528,27 @@ mod tests {
     fn test_convertion_method() {
         assert_eq!(ChessField::from_algebraic("b2"), ChessField::new(1, 1));
         assert_eq!(ChessField::from_algebraic("b2").as_algebraic(), "b2");
+        assert_eq!(ChessField::parse_algebraic("i2"), None);
+        assert_eq!(ChessField::parse_algebraic("b"), None);
+        assert_eq!(ChessField::from_index(63), Some(ChessField::new(7, 7)));
+        assert_eq!(ChessField::from_index(64), None);
+        assert_eq!(ChessField::try_new(8, 0), None);
+        let field = ChessField::from_algebraic("b2");
+        assert_eq!((field.row(), field.col(), field.index()), (1, 1, 9));
+        assert_eq!(field.offset(2, -1), Some(ChessField::from_algebraic("a4")));
+        assert_eq!(field.offset(-2, 0), None);
+        assert_eq!(field.offset(0, 7), None);
+        assert_eq!(ChessField::all().count(), 64);
+        assert_eq!(ChessField::all().nth(12), Some(ChessField::from_algebraic("e2")));
 
         assert_eq!(Move::from_algebraic("e2e4").as_algebraic(), "e2e4");
     }
+
+    #[test]
+    #[should_panic(expected = "off the board")]
+    fn test_off_board_field() {
+        ChessField::new(0, 8);
+    }
     #[test]
     fn test_generate_pawn_moves_pseudo_legal() {
         // Test simple pawn moves. Pawn at e4 can move forward to e5
@@ -1177,6 +1848,60 @@ mod tests {
         ); //f7,f8 are pseudo legal moves
     }
 
+    #[test]
+    fn test_promotion_ordering() {
+        // Queen promotion first, the under-promotions after the quiet moves
+        let board = ChessBoard::from_fen("4k3/1P6/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let moves: Vec<String> = board.generate_legal_moves().iter().map(|m| m.as_algebraic()).collect();
+        assert_eq!(moves[0], "b7b8q");
+        assert_eq!(moves[moves.len() - 3..], ["b7b8r", "b7b8b", "b7b8n"]);
+    }
+
+    #[test]
+    fn test_gives_direct_check() {
+        let checks = |fen: &str, moves: &[&str]| {
+            let board = ChessBoard::from_fen(fen).unwrap();
+            let king = ChessField::from_algebraic("e8");
+            moves
+                .iter()
+                .map(|mv| board.gives_direct_check(Move::from_algebraic(mv), king))
+                .collect::<Vec<_>>()
+        };
+        // The bishop on g6 is blocked by the pawn
+        let fen = "4k3/5p2/8/8/8/3B4/1P6/4K1N1 w - - 0 1";
+        let moves = ["d3b5", "d3g6", "g1f3", "e1e2"];
+        assert_eq!(checks(fen, &moves), vec![true, false, false, false]);
+        let fen = "4k3/1P6/8/5N2/8/8/8/4K3 w - - 0 1";
+        let moves = ["f5d6", "f5g7", "f5h4", "b7b8r", "b7b8n"];
+        assert_eq!(checks(fen, &moves), vec![true, true, false, true, false]);
+    }
+
+    #[test]
+    fn test_en_passant_square_only_when_capturable() {
+        // No black pawn next to e4
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        board.make_move(Move::from_algebraic("e2e4"));
+        assert_eq!(board.en_passant, None);
+
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1").unwrap();
+        board.make_move(Move::from_algebraic("e2e4"));
+        assert_eq!(board.en_passant, Some(ChessField::from_algebraic("e3")));
+        assert!(board.has_pseudo_legal_en_passant() && board.has_legal_en_passant());
+
+        // Capturing en passant would expose the king on h4 to the rook on a4
+        let mut board = ChessBoard::from_fen("8/8/8/8/R2p3k/8/4P3/4K3 w - - 0 1").unwrap();
+        board.make_move(Move::from_algebraic("e2e4"));
+        assert_eq!(board.en_passant, None);
+        board.en_passant = Some(ChessField::from_algebraic("e3"));
+        assert!(board.has_pseudo_legal_en_passant() && !board.has_legal_en_passant());
+
+        // The same position with and without the unusable square
+        let with_square = ChessBoard::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        let without_square = ChessBoard::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(with_square.zobrist_hash(), without_square.zobrist_hash());
+        assert_eq!(with_square.to_fen(), without_square.to_fen());
+    }
+
     #[test]
     fn test_make_move_set_en_passant_legal() {
         let mut board = ChessBoard::from_fen("8/4p3/8/3P4/8/8/8/8 b - - 0 1").unwrap();
@@ -1214,7 +1939,7 @@ mod tests {
             ChessBoard::from_fen("r3k2r/p2pqpb1/bn2pnp1/2pPN3/1pB1P3/2N2Q1p/PPPB1PPP/R3K2R w KQkq c6 0 2").unwrap();
         board.make_move(Move::from_algebraic("d5c6"));
         let field = ChessField::from_algebraic("c5");
-        assert_eq!(board.squares[field.row][field.col], Square::Empty);
+        assert_eq!(board.squares[field.row()][field.col()], Square::Empty);
         let expected_moves = vec!["e7c5", "e7d6", "e7d8", "e7f8"];
         assert_moves(board.generate_pseudo_moves_from_algebraic("e7"), expected_moves.clone());
 
@@ -1260,7 +1985,7 @@ mod tests {
     fn test_make_move_capture_rook_invalidates_castling() {
         let mut board = ChessBoard::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/P7/1PP1NnPP/RNBQK2R b KQ - 0 8").unwrap();
         board.make_move(Move::from_algebraic("f2h1"));
-        assert_eq!(board.castling_rights[0], false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), false);
     }
 
     #[test]
@@ -1281,10 +2006,10 @@ mod tests {
                 kind: PieceType::Rook
             })
         );
-        assert_eq!(board.castling_rights[0], false);
-        assert_eq!(board.castling_rights[1], false);
-        assert_eq!(board.castling_rights[2], true);
-        assert_eq!(board.castling_rights[3], true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), true);
         assert_eq!(board.en_passant, None);
 
         let mut board = ChessBoard::from_fen("rnbqk2r/ppp2pbp/3p1np1/4p3/8/8/PPPPPPPP/R3K2R w KQkq - 0 1").unwrap();
@@ -1303,10 +2028,10 @@ mod tests {
                 kind: PieceType::Rook
             })
         );
-        assert_eq!(board.castling_rights[0], false);
-        assert_eq!(board.castling_rights[1], false);
-        assert_eq!(board.castling_rights[2], true);
-        assert_eq!(board.castling_rights[3], true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), true);
         assert_eq!(board.en_passant, None);
 
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
@@ -1325,10 +2050,10 @@ mod tests {
                 kind: PieceType::Rook
             })
         );
-        assert_eq!(board.castling_rights[0], true);
-        assert_eq!(board.castling_rights[1], true);
-        assert_eq!(board.castling_rights[2], false);
-        assert_eq!(board.castling_rights[3], false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), false);
         assert_eq!(board.en_passant, None);
 
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
@@ -1347,37 +2072,37 @@ mod tests {
                 kind: PieceType::Rook
             })
         );
-        assert_eq!(board.castling_rights[0], true);
-        assert_eq!(board.castling_rights[1], true);
-        assert_eq!(board.castling_rights[2], false);
-        assert_eq!(board.castling_rights[3], false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), false);
         assert_eq!(board.en_passant, None);
 
         //white a rook moved
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
         board.make_move(Move::from_algebraic("a1b1"));
-        assert_eq!(board.castling_rights[0], true);
-        assert_eq!(board.castling_rights[1], false);
-        assert_eq!(board.castling_rights[2], true);
-        assert_eq!(board.castling_rights[3], true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), true);
         assert_eq!(board.en_passant, None);
 
         //black a rook moved
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
         board.make_move(Move::from_algebraic("a8b8"));
-        assert_eq!(board.castling_rights[0], true);
-        assert_eq!(board.castling_rights[1], true);
-        assert_eq!(board.castling_rights[2], true);
-        assert_eq!(board.castling_rights[3], false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), false);
         assert_eq!(board.en_passant, None);
 
         //white h rook moved
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
         board.make_move(Move::from_algebraic("h1g1"));
-        assert_eq!(board.castling_rights[0], false);
-        assert_eq!(board.castling_rights[1], true);
-        assert_eq!(board.castling_rights[2], true);
-        assert_eq!(board.castling_rights[3], true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), true);
         assert_eq!(board.en_passant, None);
         board.make_move(Move::from_algebraic("a7a6"));
         board.make_move(Move::from_algebraic("e1c1"));
@@ -1399,28 +2124,28 @@ mod tests {
         //black h rook moved
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
         board.make_move(Move::from_algebraic("h8g8"));
-        assert_eq!(board.castling_rights[0], true);
-        assert_eq!(board.castling_rights[1], true);
-        assert_eq!(board.castling_rights[2], false);
-        assert_eq!(board.castling_rights[3], true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), true);
         assert_eq!(board.en_passant, None);
 
         //white king moved
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
         board.make_move(Move::from_algebraic("e1d1"));
-        assert_eq!(board.castling_rights[0], false);
-        assert_eq!(board.castling_rights[1], false);
-        assert_eq!(board.castling_rights[2], true);
-        assert_eq!(board.castling_rights[3], true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), true);
         assert_eq!(board.en_passant, None);
 
         //black king moved
         let mut board = ChessBoard::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R b KQkq - 0 1").unwrap();
         board.make_move(Move::from_algebraic("e8d8"));
-        assert_eq!(board.castling_rights[0], true);
-        assert_eq!(board.castling_rights[1], true);
-        assert_eq!(board.castling_rights[2], false);
-        assert_eq!(board.castling_rights[3], false);
+        assert_eq!(board.castling_rights.has(Castling::WhiteKingside), true);
+        assert_eq!(board.castling_rights.has(Castling::WhiteQueenside), true);
+        assert_eq!(board.castling_rights.has(Castling::BlackKingside), false);
+        assert_eq!(board.castling_rights.has(Castling::BlackQueenside), false);
         assert_eq!(board.en_passant, None);
     }
 
@@ -1466,6 +2191,21 @@ mod tests {
         assert_eq!(board.is_stalemate(), false);
     }
 
+    #[test]
+    fn test_has_legal_move() {
+        let board = ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(board.has_legal_move());
+        // Mated and stalemated
+        let board = ChessBoard::from_fen("1k6/8/8/8/8/8/PPn5/KN6 w - - 0 1").unwrap();
+        assert!(!board.has_legal_move());
+        let board = ChessBoard::from_fen("1k6/8/8/8/8/1r6/7r/K7 w - - 0 1").unwrap();
+        assert!(!board.has_legal_move());
+        // The king captures the checking queen
+        let board = ChessBoard::from_fen("1k6/8/8/8/8/8/PP6/Kq6 w - - 0 1").unwrap();
+        assert!(board.has_legal_move());
+        assert!(!board.is_checkmate());
+    }
+
     #[test]
     fn test_three_fold_repetition() {
         let mut board =
@@ -1484,108 +2224,315 @@ mod tests {
     }
 
     #[test]
-    fn test_perft() {
-        let board = ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
-        assert_eq!(perft(&board, 3), 8902u64);
-        assert_eq!(perft(&board, 4), 197281u64);
-        assert_eq!(perft(&board, 5), 4865609u64);
-        //assert_eq!(perft(&board, 6), 119060324u64);
+    fn test_repetition_over_long_history() {
+        let mut board = ChessBoard::from_fen(fen::INITIAL_POSITION).unwrap();
+        let play = |board: &mut ChessBoard, moves: &str| {
+            for mv in moves.split_whitespace() {
+                board.make_move(Move::from_algebraic(mv));
+            }
+        };
+        play(&mut board, "g1f3 g8f6 f3g1 f6g8");
+        assert_eq!(board.repetition_count(), 2);
+        // 36 plies pass before the start position occurs for the third time
+        play(&mut board, "g1f3 g8f6");
+        for _ in 0..8 {
+            play(&mut board, "b1c3 b8c6 c3b1 c6b8");
+        }
+        play(&mut board, "f3g1 f6g8");
+        assert_eq!(board.repetition_count(), 3);
+        assert_eq!(board.history.len(), 41);
+
+        // Positions before a pawn move cannot repeat
+        play(&mut board, "e2e4");
+        assert_eq!(board.history.len(), 1);
+        play(&mut board, "g8f6 g1f3 f6g8 f3g1");
+        assert_eq!(board.repetition_count(), 2);
     }
 
     #[test]
-    fn test_perft2() {
-        let board =
-            ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
-        assert_eq!(perft(&board, 1), 48);
-        assert_eq!(perft(&board, 2), 2039);
-        assert_eq!(perft(&board, 3), 97862);
-        assert_eq!(perft(&board, 4), 4085603);
-        //assert_eq!(perft(&board, 5), 193690690);
+    fn test_fifty_move_rule() {
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 98 80").unwrap();
+        board.make_move(Move::from_algebraic("a1a2"));
+        assert_eq!(board.halfmove_clock, 99);
+        assert!(!board.is_draw_by_fifty_move_rule());
+        board.make_move(Move::from_algebraic("e8d8"));
+        assert!(board.is_draw_by_fifty_move_rule());
+        board.make_move(Move::from_algebraic("e2e4"));
+        assert_eq!(board.halfmove_clock, 0);
     }
 
     #[test]
-    fn test_perft3() {
-        let board = ChessBoard::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
-        assert_eq!(perft(&board, 1,), 14);
-        assert_eq!(perft(&board, 2), 191);
-        assert_eq!(perft(&board, 3), 2812);
-        assert_eq!(perft(&board, 4), 43238);
-        assert_eq!(perft(&board, 5), 674624);
-        assert_eq!(perft(&board, 6), 11030083);
+    fn test_draw_claims() {
+        let mut board =
+            ChessBoard::from_fen("1rb2rk1/p4ppp/1p1qp1n1/3n2N1/2pP4/2P3P1/PPQ2PBP/R1B1R1K1 w - - 4 17").unwrap();
+        for mv in ["e1e2", "g8h8", "e2e1", "h8g8", "e1e2", "g8h8", "e2e1"] {
+            assert_eq!(board.claimable_draw(), None);
+            board.make_move(Move::from_algebraic(mv));
+        }
+        // Black repeats the position a third time with h8g8
+        assert_eq!(
+            board.claimable_draw(),
+            Some(DrawClaim {
+                rule: DrawRule::ThreefoldRepetition,
+                with_move: Some(Move::from_algebraic("h8g8")),
+            })
+        );
+        board.make_move(Move::from_algebraic("h8g8"));
+        assert_eq!(board.repetition_count(), 3);
+        assert_eq!(board.claimable_draw().unwrap().with_move, None);
+        assert_eq!(board.automatic_draw(), None);
+        for mv in ["e1e2", "g8h8", "e2e1", "h8g8", "e1e2", "g8h8", "e2e1", "h8g8"] {
+            board.make_move(Move::from_algebraic(mv));
+        }
+        assert_eq!(board.automatic_draw(), Some(DrawRule::FivefoldRepetition));
+
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80").unwrap();
+        let claim = board.claimable_draw().unwrap();
+        assert_eq!(claim.rule, DrawRule::FiftyMoves);
+        // Only a pawn move would reset the clock
+        assert_ne!(claim.with_move.unwrap().from, ChessField::from_algebraic("e2"));
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 150 80").unwrap();
+        assert_eq!(board.automatic_draw(), Some(DrawRule::SeventyFiveMoves));
+        assert!(DrawRule::SeventyFiveMoves.is_automatic());
+        assert!(!DrawRule::FiftyMoves.is_automatic());
     }
 
     #[test]
-    fn test_perft4w() {
-        let board = ChessBoard::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
-        assert_eq!(perft(&board, 1), 6);
-        assert_eq!(perft(&board, 2), 264);
-        assert_eq!(perft(&board, 3), 9467);
-        assert_eq!(perft(&board, 4), 422333);
-        assert_eq!(perft(&board, 5), 15833292);
+    fn test_insufficient_material() {
+        // The rook is captured and a bishop is left
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/3r4/2B1K3 w - - 0 1").unwrap();
+        assert_eq!(board.automatic_draw(), None);
+        board.make_move(Move::from_algebraic("e1d2"));
+        assert!(board.is_insufficient_material());
+        assert_eq!(board.automatic_draw(), Some(DrawRule::InsufficientMaterial));
+        assert!(board.is_draw());
+
+        let insufficient = |fen| ChessBoard::from_fen(fen).unwrap().is_insufficient_material();
+        // Bishops on squares of one color only
+        assert!(insufficient("2b1k3/8/8/8/8/8/8/3BK3 w - - 0 1"));
+        assert!(!insufficient("3bk3/8/8/8/8/8/8/3BK3 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"));
     }
 
     #[test]
-    fn test_perft4b() {
-        let board = ChessBoard::from_fen("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1").unwrap();
-        assert_eq!(perft(&board, 1), 6);
-        assert_eq!(perft(&board, 2), 264);
-        assert_eq!(perft(&board, 3), 9467);
-        assert_eq!(perft(&board, 4), 422333);
-        assert_eq!(perft(&board, 5), 15833292);
+    fn test_checkers() {
+        let fields = |names: &[&str]| {
+            names
+                .iter()
+                .map(|&name| ChessField::from_algebraic(name))
+                .collect::<Vec<_>>()
+        };
+        let board = ChessBoard::from_fen(fen::INITIAL_POSITION).unwrap();
+        assert!(board.checkers().is_empty());
+
+        // Double check by the knight on f6 and the rook on e1: only the king may move
+        let board = ChessBoard::from_fen("r3k2r/8/5N2/8/8/8/8/4RK2 b kq - 0 1").unwrap();
+        let mut checkers = board.checkers();
+        checkers.sort();
+        assert_eq!(checkers, fields(&["e1", "f6"]));
+        let moves = board.generate_legal_moves();
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|mv| mv.from == ChessField::from_algebraic("e8")));
+
+        // The pawn gives check after its double step and is captured en passant
+        let board = ChessBoard::from_fen("8/8/8/2Pp4/2K5/8/8/4k3 w - d6 0 2").unwrap();
+        assert_eq!(board.checkers(), fields(&["d5"]));
+        assert!(board.generate_legal_moves().contains(&Move::from_algebraic("c5d6")));
+
+        // Blocking a rook check
+        let board = ChessBoard::from_fen("4r1k1/8/8/8/8/8/3B4/4K3 w - - 0 1").unwrap();
+        let bishop_moves: Vec<Move> = board
+            .generate_legal_moves()
+            .into_iter()
+            .filter(|mv| mv.from == ChessField::from_algebraic("d2"))
+            .collect();
+        assert_eq!(bishop_moves, vec![Move::from_algebraic("d2e3")]);
+
+        // Mate by a double check although the pawn on c7 could take the knight
+        let board = ChessBoard::from_fen("3rkr2/2pp1p2/3N4/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(board.checkers().len(), 2);
+        assert!(board.is_checkmate());
     }
 
     #[test]
-    fn test_perft_pos5() {
-        let board = ChessBoard::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
-        assert_eq!(perft(&board, 1), 44u64);
-        assert_eq!(perft(&board, 2), 1486u64);
-        assert_eq!(perft(&board, 3), 62379u64);
-        assert_eq!(perft(&board, 4), 2103487u64);
-        //assert_eq!(perft(&board, 5), 89941194u64);
+    fn test_material_follows_moves() {
+        // Captures, en passant, castling and a promotion with capture
+        let mut board = ChessBoard::from_fen("r3k2r/1P6/8/8/3p4/8/4P3/R3K2R w KQkq - 0 1").unwrap();
+        for mv in ["e2e4", "d4e3", "e1g1", "e8g8", "b7a8q", "g8h7"] {
+            board.make_move(Move::from_algebraic(mv));
+            assert_eq!(
+                board.material_signature(),
+                MaterialSignature::from_squares(&board.squares),
+                "{}",
+                mv
+            );
+        }
+        assert_eq!(board.material_signature().count(Color::White, PieceType::Queen), 1);
+        assert_eq!(board.material_signature().count(Color::Black, PieceType::Rook), 1);
+        assert_eq!(board.phase(), 10);
+
+        let mut edited = board.clone();
+        edited.remove_piece(ChessField::from_algebraic("a8")).unwrap();
+        assert_eq!(edited.phase(), 6);
+        let mirrored = board.mirror();
+        assert_eq!(mirrored.material_signature().count(Color::Black, PieceType::Queen), 1);
     }
 
     #[test]
-    fn test_perft_pos6() {
-        let board =
-            ChessBoard::from_fen("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10").unwrap();
-        assert_eq!(perft(&board, 1), 46u64);
-        assert_eq!(perft(&board, 2), 2079u64);
-        assert_eq!(perft(&board, 3), 89890u64);
-        assert_eq!(perft(&board, 4), 3894594u64);
-        //assert_eq!(perft(&board, 5), 164075551u64);
+    fn test_move_counters() {
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/1r6/R3K3 w - - 97 300").unwrap();
+        assert_eq!(board.halfmoves_until_fifty_move_draw(), 3);
+        board.make_move(Move::from_algebraic("a1a2"));
+        assert_eq!(board.halfmove_clock, 98);
+        // The capture resets the clock
+        board.make_move(Move::from_algebraic("b2a2"));
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 301);
+        assert_eq!(board.halfmoves_until_fifty_move_draw(), 100);
+
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 300 1000").unwrap();
+        assert_eq!(board.halfmoves_until_fifty_move_draw(), 0);
+        assert!(board.is_draw_by_fifty_move_rule());
     }
 
     #[test]
-    fn test_perft_pos_cf() {
-        let board = ChessBoard::from_fen("r3k2r/1pb2N2/2P5/3N3b/P2n4/1qB2pp1/5np1/R1Q1K2R w KQkq - 0 1").unwrap();
-        assert_eq!(perft(&board, 1), 40);
-        assert_eq!(perft(&board, 2), 2143);
-        assert_eq!(perft(&board, 3), 75353);
-        assert_eq!(perft(&board, 4), 3958794);
-        //assert_eq!(perft(&board, 5), 140774393);
+    fn test_is_in_check() {
+        let board = ChessBoard::from_fen("rnbqkbnr/ppppp2p/5p2/6pQ/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 3").unwrap();
+        assert!(board.is_in_check());
+        let board = ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert!(!board.is_in_check());
     }
 
     #[test]
-    fn test_perft_pos_web() {
-        //https://github.com/elcabesa/vajolet/blob/master/tests/perft.txt
-        let board =
-            ChessBoard::from_fen("rnbqkbnr/1p4p1/3pp2p/p1p2p2/7P/2PP1P1N/PP1NP1P1/R1BQKB1R b Qkq - 0 1").unwrap();
-        assert_eq!(perft(&board, 1), 30);
-        assert_eq!(perft(&board, 2), 784);
-        assert_eq!(perft(&board, 3), 23151);
-        assert_eq!(perft(&board, 4), 638663);
-        //assert_eq!(perft(&board, 5), 19171633);
+    fn test_attackers_to() {
+        let board = ChessBoard::from_fen("3rk3/8/2n5/3p4/4P3/8/3R4/3QK3 w - - 0 1").unwrap();
+        let mut white_attackers = board.attackers_to(ChessField::from_algebraic("d5"), Color::White);
+        white_attackers.sort();
+        // The queen on d1 is behind the rook
+        assert_eq!(white_attackers, vec![ChessField::from_algebraic("d2"), ChessField::from_algebraic("e4")]);
+        let black_attackers = board.attackers_to(ChessField::from_algebraic("e4"), Color::Black);
+        assert_eq!(black_attackers, vec![ChessField::from_algebraic("d5")]);
+        assert!(board.attackers_to(ChessField::from_algebraic("a1"), Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_least_valuable_attacker() {
+        let d5 = ChessField::from_algebraic("d5");
+        let board = ChessBoard::from_fen("4k3/8/8/3p4/8/1B3Q2/8/3RK3 w - - 0 1").unwrap();
+        assert_eq!(
+            board.least_valuable_attacker(d5, Color::White),
+            Some((ChessField::from_algebraic("b3"), PieceType::Bishop))
+        );
+        assert!(board.defended(d5, Color::White));
+        assert_eq!(board.least_valuable_attacker(d5, Color::Black), None);
+        assert!(!board.defended(d5, Color::Black));
+
+        let board = ChessBoard::from_fen("4k3/8/8/3p4/8/5Q2/8/3RK3 w - - 0 1").unwrap();
+        assert_eq!(
+            board.least_valuable_attacker(d5, Color::White),
+            Some((ChessField::from_algebraic("d1"), PieceType::Rook))
+        );
+        let board = ChessBoard::from_fen("4k3/8/2n5/3p4/4P3/8/3R4/3QK3 w - - 0 1").unwrap();
+        assert_eq!(
+            board.least_valuable_attacker(ChessField::from_algebraic("e4"), Color::Black),
+            Some((d5, PieceType::Pawn))
+        );
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            board.least_valuable_attacker(ChessField::from_algebraic("d2"), Color::White),
+            Some((ChessField::from_algebraic("e1"), PieceType::King))
+        );
     }
 
     #[test]
-    fn test_perft_pos_web2() {
-        //http://www.rocechess.ch/perft.html
-        let board = ChessBoard::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap();
-        assert_eq!(perft(&board, 1), 24);
-        assert_eq!(perft(&board, 2), 496);
-        assert_eq!(perft(&board, 3), 9483);
-        assert_eq!(perft(&board, 4), 182838);
-        assert_eq!(perft(&board, 5), 3605103);
-        //assert_eq!(perft(&board, 6), 71179139);
+    fn test_is_legal() {
+        let board = ChessBoard::from_fen("4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1").unwrap();
+        assert!(board.is_legal(Move::from_algebraic("e1f2")));
+        // The knight is pinned
+        assert!(!board.is_legal(Move::from_algebraic("d2f3")));
+        // No piece moves like this
+        assert!(!board.is_legal(Move::from_algebraic("e1e3")));
+        assert!(!board.is_legal(Move::from_algebraic("a1a2")));
+        assert!(!board.is_legal(Move::from_algebraic("b4c3")));
+    }
+
+    #[test]
+    fn test_classify_move() {
+        let classify = |fen: &str, mv: &str| {
+            ChessBoard::from_fen(fen)
+                .unwrap()
+                .classify_move(Move::from_algebraic(mv))
+        };
+        let fen = "4k3/8/8/8/1b6/8/3N4/4K3 w - - 0 1";
+        assert_eq!(classify(fen, "e1f2"), MoveLegality::Legal);
+        assert_eq!(classify(fen, "d2f3"), MoveLegality::LeavesKingInCheck);
+        assert_eq!(classify(fen, "e1e3"), MoveLegality::InvalidPieceMove);
+        assert_eq!(classify(fen, "a1a2"), MoveLegality::NoPiece);
+        assert_eq!(classify(fen, "b4c3"), MoveLegality::NotYourPiece);
+        assert_eq!(classify(fen, "e1d2"), MoveLegality::CapturesOwnPiece);
+        assert_eq!(classify(fen, "e1g1"), MoveLegality::CastlingWithoutRights);
+
+        let fen = "r3k2r/1P6/8/8/8/4p3/P3P3/R3KB1R w KQkq - 0 1";
+        assert_eq!(classify(fen, "e1g1"), MoveLegality::BlockedPath);
+        assert_eq!(classify(fen, "a1a3"), MoveLegality::BlockedPath);
+        assert_eq!(classify(fen, "e2e3"), MoveLegality::BlockedPath);
+        assert_eq!(classify(fen, "a2b3"), MoveLegality::NothingToCapture);
+        assert_eq!(classify(fen, "a2a5"), MoveLegality::InvalidPieceMove);
+        assert_eq!(classify(fen, "b7b8"), MoveLegality::MissingPromotion);
+        let board = ChessBoard::from_fen(fen).unwrap();
+        let to_king = Move::from_algebraic("b7b8").with_promotion(PieceType::King);
+        assert_eq!(board.classify_move(to_king), MoveLegality::InvalidPromotionPiece);
+        assert_eq!(classify(fen, "a2a3q"), MoveLegality::WrongPromotionRank);
+        assert_eq!(classify(fen, "b7a8n"), MoveLegality::Legal);
+        assert_eq!(classify(fen, "e1c1"), MoveLegality::Legal);
+
+        assert_eq!(
+            classify("4k3/8/8/8/8/8/8/R3K2r w Q - 0 1", "e1c1"),
+            MoveLegality::CastlingOutOfCheck
+        );
+        assert_eq!(
+            classify("4k3/8/8/8/8/8/3r4/R3K3 w Q - 0 1", "e1c1"),
+            MoveLegality::CastlingThroughCheck
+        );
+
+        // Agrees with the move generation on every move between any two squares
+        let board =
+            ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        for from in 0..64 {
+            for to in 0..64 {
+                let mv = Move::new(from / 8, from % 8, to / 8, to % 8);
+                assert_eq!(
+                    board.classify_move(mv) == MoveLegality::Legal,
+                    board.is_legal(mv),
+                    "{}",
+                    mv.as_algebraic()
+                );
+            }
+        }
+    }
+    #[test]
+    fn test_legal_move_from_uci() {
+        let board = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let parse = |notation| board.legal_move_from_uci(notation).map(|mv| mv.as_algebraic());
+        assert_eq!(parse("e1g1"), Ok(String::from("e1g1")));
+        assert_eq!(parse("e1h1"), Ok(String::from("e1g1")));
+        assert_eq!(parse("e1a1"), Ok(String::from("e1c1")));
+        assert_eq!(parse("O-O"), Ok(String::from("e1g1")));
+        assert_eq!(parse("0-0-0+"), Ok(String::from("e1c1")));
+        assert_eq!(parse("a1a8"), Ok(String::from("a1a8")));
+        for garbage in ["", "e", "e1", "e1e", "O-O-O-O", "e1i1", "e1e2xx", "o-o"] {
+            assert!(parse(garbage).is_err(), "{}", garbage);
+        }
+
+        let board = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b Qk - 0 1").unwrap();
+        assert_eq!(
+            board.legal_move_from_uci("e8h8").map(|mv| mv.as_algebraic()),
+            Ok(String::from("e8g8"))
+        );
+        assert!(board.legal_move_from_uci("O-O-O").unwrap_err().contains("Castling"));
+        // A king that already moved does not castle by taking its rook
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/3RK3 w - - 0 1").unwrap();
+        assert!(board.legal_move_from_uci("e1d1").is_err());
     }
 }
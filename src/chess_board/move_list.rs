@@ -0,0 +1,133 @@
+use crate::chess_board::Move;
+use std::ops::{Deref, DerefMut};
+
+/// More than the pseudo-legal moves of any position.
+pub const MAX_MOVES: usize = 256;
+
+/// More than the pseudo-legal moves of a single piece, a queen has at most 27.
+pub const MAX_PIECE_MOVES: usize = 28;
+
+/// Moves with their ordering scores in a fixed array, so generating the moves of a node does not allocate. The search
+/// keeps a list per ply and reuses it at every node.
+#[derive(Clone)]
+pub struct MoveList<const N: usize = MAX_MOVES> {
+    moves: [(Move, i32); N],
+    len: usize,
+}
+
+impl<const N: usize> MoveList<N> {
+    pub fn new() -> Self {
+        MoveList {
+            moves: [(Move::new(0, 0, 0, 0), 0); N],
+            len: 0,
+        }
+    }
+
+    /// Panics if the list is full, which the moves of a position never fill.
+    pub fn push(&mut self, mv: Move, score: i32) {
+        self.moves[self.len] = (mv, score);
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Keeps the moves for which keep returns true, in their order.
+    pub fn retain(&mut self, mut keep: impl FnMut(Move) -> bool) {
+        let mut kept = 0;
+        for index in 0..self.len {
+            if keep(self.moves[index].0) {
+                self.moves[kept] = self.moves[index];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+impl<const N: usize> Default for MoveList<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for MoveList<N> {
+    type Target = [(Move, i32)];
+
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
+
+impl<const N: usize> DerefMut for MoveList<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.moves[..self.len]
+    }
+}
+
+/// Sorts moves by descending score, moves of equal score keep their order. An insertion sort, which does not allocate
+/// like the stable sort of the standard library and is fast for the few moves of a position.
+pub fn sort_by_score(moves: &mut [(Move, i32)]) {
+    for index in 1..moves.len() {
+        let entry = moves[index];
+        let mut position = index;
+        while position > 0 && moves[position - 1].1 < entry.1 {
+            moves[position] = moves[position - 1];
+            position -= 1;
+        }
+        moves[position] = entry;
+    }
+}
+
+/// Moves the moves for which first returns true before the others, both keep their order. Returns the number of
+/// moves moved to the front.
+pub fn partition_stable(moves: &mut [(Move, i32)], mut first: impl FnMut(Move) -> bool) -> usize {
+    let mut end = 0;
+    for index in 0..moves.len() {
+        if first(moves[index].0) {
+            moves[end..=index].rotate_right(1);
+            end += 1;
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(moves: &[(Move, i32)]) -> Vec<(String, i32)> {
+        moves.iter().map(|(mv, score)| (mv.as_algebraic(), *score)).collect()
+    }
+
+    fn expected(moves: &[(&str, i32)]) -> Vec<(String, i32)> {
+        moves.iter().map(|&(mv, score)| (mv.to_string(), score)).collect()
+    }
+
+    #[test]
+    fn test_move_list() {
+        let mut list: MoveList = MoveList::new();
+        for (mv, score) in [("e2e4", 0), ("d2d4", 5), ("g1f3", 5), ("b1c3", -3), ("a2a3", 7)] {
+            list.push(Move::from_algebraic(mv), score);
+        }
+        sort_by_score(&mut list[1..]);
+        let sorted = [("e2e4", 0), ("a2a3", 7), ("d2d4", 5), ("g1f3", 5), ("b1c3", -3)];
+        assert_eq!(moves(&list), expected(&sorted));
+
+        let pawn_moves = partition_stable(&mut list, |mv| mv.from.row() == 1);
+        assert_eq!(pawn_moves, 3);
+        let partitioned = [("e2e4", 0), ("a2a3", 7), ("d2d4", 5), ("g1f3", 5), ("b1c3", -3)];
+        assert_eq!(moves(&list), expected(&partitioned));
+        let knight_moves = partition_stable(&mut list, |mv| mv.from.row() == 0);
+        assert_eq!(knight_moves, 2);
+        let partitioned = [("g1f3", 5), ("b1c3", -3), ("e2e4", 0), ("a2a3", 7), ("d2d4", 5)];
+        assert_eq!(moves(&list), expected(&partitioned));
+
+        list.retain(|mv| mv.from.col() != 3);
+        let retained = [("g1f3", 5), ("b1c3", -3), ("e2e4", 0), ("a2a3", 7)];
+        assert_eq!(moves(&list), expected(&retained));
+        list.clear();
+        assert!(list.is_empty());
+    }
+}
@@ -0,0 +1,398 @@
+use super::{ChessBoard, Move};
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::Relaxed;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bytes of a cache entry.
+const ENTRY_SIZE: usize = 16;
+
+/// Node counts of positions already counted to a depth, so transpositions are counted once.
+///
+/// The table is shared by the threads without a lock: an entry stores the node count and depth next to the hash
+/// xor that data, so an entry torn by two threads writing at once fails the check and is ignored.
+pub struct PerftCache {
+    entries: Vec<[AtomicU64; 2]>,
+}
+
+impl PerftCache {
+    /// A cache of at most size_mb megabytes, rounded down to a power of two entries.
+    pub fn new(size_mb: usize) -> Self {
+        let entries = (size_mb * 1024 * 1024 / ENTRY_SIZE).max(1);
+        let entries = 1 << entries.ilog2();
+        PerftCache {
+            entries: (0..entries).map(|_| [AtomicU64::new(0), AtomicU64::new(0)]).collect(),
+        }
+    }
+
+    fn entry(&self, hash: u64, depth: u8) -> &[AtomicU64; 2] {
+        // Different depths of a position go to different entries
+        let index = hash ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        &self.entries[index as usize & (self.entries.len() - 1)]
+    }
+
+    fn get(&self, hash: u64, depth: u8) -> Option<u64> {
+        let entry = self.entry(hash, depth);
+        let check = entry[0].load(Relaxed);
+        let data = entry[1].load(Relaxed);
+        (check ^ data == hash && data as u8 == depth).then_some(data >> 8)
+    }
+
+    fn insert(&self, hash: u64, depth: u8, node_count: u64) {
+        let entry = self.entry(hash, depth);
+        let data = node_count << 8 | depth as u64;
+        entry[0].store(hash ^ data, Relaxed);
+        entry[1].store(data, Relaxed);
+    }
+}
+
+/// Counts the leaf nodes of the legal move tree to a depth, to test the move generation against known counts.
+pub fn perft(board: &ChessBoard, depth: u8) -> u64 {
+    let mut node_count = 0u64;
+
+    if depth == 0 {
+        return 1u64;
+    }
+
+    let moves = board.generate_legal_moves();
+    if moves.is_empty() {
+        return 0u64;
+    }
+    for mv in moves {
+        let mut new_board = board.clone();
+        new_board.make_move(mv);
+        node_count += perft(&new_board, depth - 1);
+    }
+    node_count
+}
+
+/// How far a perft_divide count got, reported while it counts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerftProgress {
+    /// Leaf nodes counted so far, including those of the root moves that are not finished yet.
+    pub nodes: u64,
+    pub root_moves_done: usize,
+    pub root_moves: usize,
+    pub elapsed: Duration,
+}
+
+/// Receives the progress of a count from all its threads, after every root move and every PROGRESS_INTERVAL nodes.
+/// Returning false stops the count.
+pub type PerftCallback<'a> = &'a (dyn Fn(&PerftProgress) -> bool + Sync);
+
+/// Leaf nodes between two progress reports.
+const PROGRESS_INTERVAL: u64 = 1 << 20;
+
+/// The state of a count shared by its threads.
+struct PerftRun<'a> {
+    nodes: AtomicU64,
+    root_moves_done: AtomicUsize,
+    root_moves: usize,
+    start: Instant,
+    stopped: AtomicBool,
+    callback: PerftCallback<'a>,
+}
+
+impl PerftRun<'_> {
+    fn count(&self, nodes: u64) {
+        let before = self.nodes.fetch_add(nodes, Relaxed);
+        if before / PROGRESS_INTERVAL != (before + nodes) / PROGRESS_INTERVAL {
+            self.report();
+        }
+    }
+
+    fn report(&self) {
+        let progress = PerftProgress {
+            nodes: self.nodes.load(Relaxed),
+            root_moves_done: self.root_moves_done.load(Relaxed),
+            root_moves: self.root_moves,
+            elapsed: self.start.elapsed(),
+        };
+        if !(self.callback)(&progress) {
+            self.stopped.store(true, Relaxed);
+        }
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Relaxed)
+    }
+}
+
+/// The perft of every legal root move in move generation order, the root moves split over the threads. A count
+/// stopped by the callback returns the root moves counted before, a partly counted move is left out.
+pub fn perft_divide(
+    board: &ChessBoard,
+    depth: u8,
+    threads: usize,
+    cache: Option<&PerftCache>,
+    callback: PerftCallback,
+) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let moves = board.generate_legal_moves();
+    let next = AtomicUsize::new(0);
+    let run = PerftRun {
+        nodes: AtomicU64::new(0),
+        root_moves_done: AtomicUsize::new(0),
+        root_moves: moves.len(),
+        start: Instant::now(),
+        stopped: AtomicBool::new(false),
+        callback,
+    };
+    let mut counts = vec![None; moves.len()];
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.clamp(1, moves.len().max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut counted = Vec::new();
+                    // The threads take the next root move until none is left, so one slow move does not idle them
+                    while !run.is_stopped() {
+                        let index = next.fetch_add(1, Relaxed);
+                        let Some(&mv) = moves.get(index) else {
+                            break;
+                        };
+                        let mut new_board = board.clone();
+                        new_board.make_move(mv);
+                        let node_count = perft_cached(&new_board, depth - 1, cache, &run);
+                        if run.is_stopped() {
+                            break;
+                        }
+                        counted.push((index, node_count));
+                        run.root_moves_done.fetch_add(1, Relaxed);
+                        run.report();
+                    }
+                    counted
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (index, node_count) in worker.join().unwrap() {
+                counts[index] = Some(node_count);
+            }
+        }
+    });
+    moves
+        .into_iter()
+        .zip(counts)
+        .filter_map(|(mv, node_count)| Some((mv, node_count?)))
+        .collect()
+}
+
+/// The same count as perft, on several threads and with an optional cache.
+pub fn perft_parallel(
+    board: &ChessBoard,
+    depth: u8,
+    threads: usize,
+    cache: Option<&PerftCache>,
+    callback: PerftCallback,
+) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    perft_divide(board, depth, threads, cache, callback)
+        .iter()
+        .map(|&(_, node_count)| node_count)
+        .sum()
+}
+
+fn perft_cached(board: &ChessBoard, depth: u8, cache: Option<&PerftCache>, run: &PerftRun) -> u64 {
+    if depth == 0 {
+        run.count(1);
+        return 1;
+    }
+    let moves = board.generate_legal_moves();
+    // The leaves are counted without making the moves
+    if depth == 1 {
+        run.count(moves.len() as u64);
+        return moves.len() as u64;
+    }
+    let hash = board.zobrist_hash();
+    if let Some(node_count) = cache.and_then(|cache| cache.get(hash, depth)) {
+        run.count(node_count);
+        return node_count;
+    }
+    let mut node_count = 0;
+    for mv in moves {
+        if run.is_stopped() {
+            // The partial count must not get into the cache
+            return node_count;
+        }
+        let mut new_board = board.clone();
+        new_board.make_move(mv);
+        node_count += perft_cached(&new_board, depth - 1, cache, run);
+    }
+    if let Some(cache) = cache {
+        cache.insert(hash, depth, node_count);
+    }
+    node_count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The deeper counts are checked with the cache on all cores, perft itself only to the shallow depths.
+    fn fast_perft(board: &ChessBoard, depth: u8) -> u64 {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        perft_parallel(board, depth, threads, Some(&PerftCache::new(16)), &|_| true)
+    }
+
+    #[test]
+    fn test_perft() {
+        let board = ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&board, 3), 8902u64);
+        assert_eq!(fast_perft(&board, 4), 197281u64);
+        assert_eq!(fast_perft(&board, 5), 4865609u64);
+        //assert_eq!(perft(&board, 6), 119060324u64);
+    }
+
+    #[test]
+    fn test_perft2() {
+        let board =
+            ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&board, 1), 48);
+        assert_eq!(perft(&board, 2), 2039);
+        assert_eq!(perft(&board, 3), 97862);
+        assert_eq!(fast_perft(&board, 4), 4085603);
+        //assert_eq!(perft(&board, 5), 193690690);
+    }
+
+    #[test]
+    fn test_perft3() {
+        let board = ChessBoard::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(&board, 1,), 14);
+        assert_eq!(perft(&board, 2), 191);
+        assert_eq!(perft(&board, 3), 2812);
+        assert_eq!(fast_perft(&board, 4), 43238);
+        assert_eq!(fast_perft(&board, 5), 674624);
+        assert_eq!(fast_perft(&board, 6), 11030083);
+    }
+
+    #[test]
+    fn test_perft4w() {
+        let board = ChessBoard::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        assert_eq!(perft(&board, 1), 6);
+        assert_eq!(perft(&board, 2), 264);
+        assert_eq!(perft(&board, 3), 9467);
+        assert_eq!(fast_perft(&board, 4), 422333);
+        assert_eq!(fast_perft(&board, 5), 15833292);
+    }
+
+    #[test]
+    fn test_perft4b() {
+        let board = ChessBoard::from_fen("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1").unwrap();
+        assert_eq!(perft(&board, 1), 6);
+        assert_eq!(perft(&board, 2), 264);
+        assert_eq!(perft(&board, 3), 9467);
+        assert_eq!(fast_perft(&board, 4), 422333);
+        assert_eq!(fast_perft(&board, 5), 15833292);
+    }
+
+    #[test]
+    fn test_perft_pos5() {
+        let board = ChessBoard::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        assert_eq!(perft(&board, 1), 44u64);
+        assert_eq!(perft(&board, 2), 1486u64);
+        assert_eq!(perft(&board, 3), 62379u64);
+        assert_eq!(fast_perft(&board, 4), 2103487u64);
+        //assert_eq!(perft(&board, 5), 89941194u64);
+    }
+
+    #[test]
+    fn test_perft_pos6() {
+        let board =
+            ChessBoard::from_fen("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10").unwrap();
+        assert_eq!(perft(&board, 1), 46u64);
+        assert_eq!(perft(&board, 2), 2079u64);
+        assert_eq!(perft(&board, 3), 89890u64);
+        assert_eq!(fast_perft(&board, 4), 3894594u64);
+        //assert_eq!(perft(&board, 5), 164075551u64);
+    }
+
+    #[test]
+    fn test_perft_pos_cf() {
+        let board = ChessBoard::from_fen("r3k2r/1pb2N2/2P5/3N3b/P2n4/1qB2pp1/5np1/R1Q1K2R w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&board, 1), 40);
+        assert_eq!(perft(&board, 2), 2143);
+        assert_eq!(perft(&board, 3), 75353);
+        assert_eq!(fast_perft(&board, 4), 3958794);
+        //assert_eq!(perft(&board, 5), 140774393);
+    }
+
+    #[test]
+    fn test_perft_pos_web() {
+        //https://github.com/elcabesa/vajolet/blob/master/tests/perft.txt
+        let board =
+            ChessBoard::from_fen("rnbqkbnr/1p4p1/3pp2p/p1p2p2/7P/2PP1P1N/PP1NP1P1/R1BQKB1R b Qkq - 0 1").unwrap();
+        assert_eq!(perft(&board, 1), 30);
+        assert_eq!(perft(&board, 2), 784);
+        assert_eq!(perft(&board, 3), 23151);
+        assert_eq!(fast_perft(&board, 4), 638663);
+        //assert_eq!(perft(&board, 5), 19171633);
+    }
+
+    #[test]
+    fn test_perft_pos_web2() {
+        //http://www.rocechess.ch/perft.html
+        let board = ChessBoard::from_fen("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1").unwrap();
+        assert_eq!(perft(&board, 1), 24);
+        assert_eq!(perft(&board, 2), 496);
+        assert_eq!(perft(&board, 3), 9483);
+        assert_eq!(fast_perft(&board, 4), 182838);
+        assert_eq!(fast_perft(&board, 5), 3605103);
+        //assert_eq!(perft(&board, 6), 71179139);
+    }
+
+    #[test]
+    fn test_perft_divide() {
+        let board =
+            ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let divide = perft_divide(&board, 3, 3, None, &|_| true);
+        assert_eq!(divide.len(), 48);
+        for &(mv, node_count) in &divide {
+            let mut new_board = board.clone();
+            new_board.make_move(mv);
+            assert_eq!(node_count, perft(&new_board, 2));
+        }
+        // A single entry cache is overwritten all the time but still gives the right counts
+        let cache = PerftCache::new(0);
+        assert_eq!(perft_parallel(&board, 3, 100, Some(&cache), &|_| true), 97862);
+        assert_eq!(perft_parallel(&board, 3, 1, Some(&cache), &|_| true), 97862);
+        assert_eq!(perft_parallel(&board, 0, 2, None, &|_| true), 1);
+        let mate = ChessBoard::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(perft_parallel(&mate, 2, 2, None, &|_| true), 0);
+    }
+
+    #[test]
+    fn test_perft_progress() {
+        let board =
+            ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        let reports = std::sync::Mutex::new(Vec::new());
+        let divide = perft_divide(&board, 4, 2, None, &|progress| {
+            reports.lock().unwrap().push(*progress);
+            true
+        });
+        assert_eq!(divide.iter().map(|&(_, node_count)| node_count).sum::<u64>(), 4085603);
+        let reports = reports.into_inner().unwrap();
+        // One report after every root move and at least one for every PROGRESS_INTERVAL nodes
+        assert!(reports.len() >= 48 + 3);
+        assert!(reports.iter().all(|progress| progress.root_moves == 48));
+        let last = reports.iter().max_by_key(|progress| progress.root_moves_done).unwrap();
+        assert_eq!((last.root_moves_done, last.nodes), (48, 4085603));
+
+        // Stopped after ten root moves, the moves still counting are left out
+        let cache = PerftCache::new(1);
+        let divide = perft_divide(&board, 4, 1, Some(&cache), &|progress| progress.root_moves_done < 10);
+        assert_eq!(divide.len(), 10);
+        for &(mv, node_count) in &divide {
+            let mut new_board = board.clone();
+            new_board.make_move(mv);
+            assert_eq!(node_count, perft(&new_board, 3));
+        }
+        // No partial count got into the cache
+        assert_eq!(perft_parallel(&board, 4, 1, Some(&cache), &|_| true), 4085603);
+    }
+}
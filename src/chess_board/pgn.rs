@@ -0,0 +1,681 @@
+use super::fen::INITIAL_POSITION;
+use super::game::{Game, GameMove, SEVEN_TAG_ROSTER};
+use super::{ChessBoard, ChessField, Move, PieceType, Square};
+
+/// The suffix annotations with their numeric annotation glyphs.
+const SUFFIX_ANNOTATIONS: [(&str, u8); 6] = [("!!", 3), ("??", 4), ("!?", 5), ("?!", 6), ("!", 1), ("?", 2)];
+
+/// The parts of PGN movetext.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(String),
+}
+
+impl Game {
+    /// Writes the game as PGN with the seven tag roster first and SetUp/FEN tags for non-standard starts.
+    pub fn to_pgn(&self) -> Result<String, String> {
+        let mut tags: Vec<(String, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|(name, default)| {
+                let value = match *name {
+                    "Result" => self.result.as_str(),
+                    _ => self.get_tag(name).unwrap_or(default),
+                };
+                (name.to_string(), value.to_string())
+            })
+            .collect();
+        if self.start_fen != INITIAL_POSITION {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), self.start_fen.clone()));
+        }
+        for (name, value) in &self.tags {
+            if !tags.iter().any(|(n, _)| n == name) {
+                tags.push((name.clone(), value.clone()));
+            }
+        }
+
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            pgn.push_str(&format!(
+                "[{} \"{}\"]\n",
+                name,
+                value.replace('\\', "\\\\").replace('"', "\\\"")
+            ));
+        }
+        pgn.push('\n');
+
+        // Export format limits lines to 80 characters
+        let mut line = String::new();
+        for token in self.movetext_tokens()? {
+            if !line.is_empty() && line.len() + token.len() + 1 > 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        Ok(pgn)
+    }
+
+    /// The movetext on a single line: the moves in SAN with annotations, variations and the result.
+    pub fn movetext(&self) -> Result<String, String> {
+        Ok(self.movetext_tokens()?.join(" "))
+    }
+
+    fn movetext_tokens(&self) -> Result<Vec<String>, String> {
+        let mut tokens = Vec::new();
+        if let Some(comment) = &self.comment {
+            tokens.push(format_comment(comment));
+        }
+        write_line(&ChessBoard::from_fen(&self.start_fen)?, &self.moves, &mut tokens);
+        tokens.push(self.result.clone());
+        Ok(tokens)
+    }
+
+    /// Reads the first game of a PGN text with its comments, variations and NAGs. Comments at the start of a
+    /// variation are skipped.
+    pub fn from_pgn(pgn: &str) -> Result<Self, String> {
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        for line in pgn.lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                if !movetext.trim().is_empty() {
+                    // Tag pair section of the next game
+                    break;
+                }
+                tags.push(parse_tag(line)?);
+            } else if line.starts_with('%') {
+                // Escape mechanism of the PGN standard
+                continue;
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+
+        let start_fen = tags
+            .iter()
+            .find(|(name, _)| name == "FEN")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_else(|| INITIAL_POSITION.to_string());
+        let board = ChessBoard::from_fen(&start_fen)?;
+
+        let mut tokens = tokenize_movetext(&movetext)?.into_iter().peekable();
+        let mut comment: Option<String> = None;
+        while let Some(Token::Comment(text)) = tokens.peek() {
+            comment = Some(match comment {
+                Some(comment) => format!("{} {}", comment, text),
+                None => text.clone(),
+            });
+            tokens.next();
+        }
+        let mut result = None;
+        let moves = parse_line(&board, &mut tokens, &mut result, 0)?;
+
+        let result = result
+            .or_else(|| {
+                tags.iter()
+                    .find(|(name, _)| name == "Result")
+                    .map(|(_, value)| value.clone())
+            })
+            .unwrap_or_else(|| String::from("*"));
+
+        Ok(Game {
+            tags,
+            start_fen,
+            comment,
+            moves,
+            result,
+        })
+    }
+}
+
+/// Writes the moves of a line played from the board, the variations of a move right after it.
+fn write_line(board: &ChessBoard, moves: &[GameMove], tokens: &mut Vec<String>) {
+    let mut board = board.clone();
+    // Black moves get a number at the start of a line and after comments and variations
+    let mut needs_number = true;
+    for game_move in moves {
+        if board.active_color == super::Color::White {
+            tokens.push(format!("{}.", board.fullmove_number));
+        } else if needs_number {
+            tokens.push(format!("{}...", board.fullmove_number));
+        }
+        tokens.push(move_to_san(&board, game_move.mv));
+        tokens.extend(game_move.nags.iter().map(|nag| format!("${}", nag)));
+        needs_number = false;
+        if let Some(comment) = &game_move.comment {
+            tokens.push(format_comment(comment));
+            needs_number = true;
+        }
+        for variation in game_move.variations.iter().filter(|v| !v.is_empty()) {
+            // The parentheses are written without spaces inside
+            let start = tokens.len();
+            write_line(&board, variation, tokens);
+            tokens[start].insert(0, '(');
+            tokens.last_mut().unwrap().push(')');
+            needs_number = true;
+        }
+        board.make_move(game_move.mv);
+    }
+}
+
+fn format_comment(comment: &str) -> String {
+    // Brace comments cannot contain a closing brace
+    format!("{{{}}}", comment.replace('}', ")"))
+}
+
+/// Parses the moves of a line played from the board until the end of the variation or the game.
+fn parse_line(
+    board: &ChessBoard,
+    tokens: &mut std::iter::Peekable<std::vec::IntoIter<Token>>,
+    result: &mut Option<String>,
+    depth: usize,
+) -> Result<Vec<GameMove>, String> {
+    let mut board = board.clone();
+    let mut previous_board = None;
+    let mut moves: Vec<GameMove> = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Move(san) => {
+                let (text, nag) = strip_suffix_annotation(&san);
+                let mv = san_to_move(&board, text).map_err(|e| format!("{} (after {} moves)", e, moves.len()))?;
+                let mut game_move = GameMove::new(mv);
+                game_move.nags.extend(nag);
+                previous_board = Some(board.clone());
+                board.make_move(mv);
+                moves.push(game_move);
+            }
+            Token::Nag(nag) => {
+                if let Some(game_move) = moves.last_mut() {
+                    game_move.nags.push(nag);
+                }
+            }
+            Token::Comment(text) => {
+                if let Some(game_move) = moves.last_mut() {
+                    game_move.comment = Some(match game_move.comment.take() {
+                        Some(comment) => format!("{} {}", comment, text),
+                        None => text,
+                    });
+                }
+            }
+            Token::VariationStart => {
+                let (Some(game_move), Some(previous_board)) = (moves.last_mut(), &previous_board) else {
+                    return Err(String::from("Variation without a move in PGN"));
+                };
+                let variation = parse_line(previous_board, tokens, result, depth + 1)?;
+                game_move.variations.push(variation);
+            }
+            Token::VariationEnd => {
+                if depth == 0 {
+                    return Err(String::from("Unbalanced variation in PGN"));
+                }
+                return Ok(moves);
+            }
+            Token::Result(token) => {
+                if depth > 0 {
+                    return Err(String::from("Unbalanced variation in PGN"));
+                }
+                *result = Some(token);
+                return Ok(moves);
+            }
+        }
+    }
+    if depth > 0 {
+        return Err(String::from("Unbalanced variation in PGN"));
+    }
+    Ok(moves)
+}
+
+fn strip_suffix_annotation(san: &str) -> (&str, Option<u8>) {
+    for (suffix, nag) in SUFFIX_ANNOTATIONS {
+        if let Some(text) = san.strip_suffix(suffix) {
+            return (text, Some(nag));
+        }
+    }
+    (san, None)
+}
+
+/// Returns the PGN result token for a position: decided games by checkmate or draw, otherwise "*".
+pub fn game_result(board: &ChessBoard) -> &'static str {
+    if !board.has_legal_move() {
+        match (board.is_in_check(), board.active_color) {
+            (false, _) => "1/2-1/2",
+            (true, super::Color::White) => "0-1",
+            (true, super::Color::Black) => "1-0",
+        }
+    } else if board.is_draw() {
+        "1/2-1/2"
+    } else {
+        "*"
+    }
+}
+
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let inner = line
+        .strip_prefix('[')
+        .and_then(|l| l.strip_suffix(']'))
+        .ok_or_else(|| format!("Invalid PGN tag: {}", line))?;
+    let (name, value) = inner
+        .split_once(' ')
+        .ok_or_else(|| format!("Invalid PGN tag: {}", line))?;
+    let value = value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .ok_or_else(|| format!("Invalid PGN tag value: {}", line))?;
+    Ok((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+fn tokenize_movetext(movetext: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = movetext.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                // Brace comments do not nest
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(String::from("Unterminated comment in PGN")),
+                    }
+                }
+                push_movetext_token(&mut tokens, &mut current)?;
+                tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" ")));
+                continue;
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                push_movetext_token(&mut tokens, &mut current)?;
+                tokens.push(Token::Comment(comment.trim().to_string()));
+                continue;
+            }
+            '(' | ')' => {
+                push_movetext_token(&mut tokens, &mut current)?;
+                tokens.push(if c == '(' {
+                    Token::VariationStart
+                } else {
+                    Token::VariationEnd
+                });
+                continue;
+            }
+            _ if c.is_whitespace() => {}
+            _ => {
+                current.push(c);
+                continue;
+            }
+        }
+        push_movetext_token(&mut tokens, &mut current)?;
+    }
+    push_movetext_token(&mut tokens, &mut current)?;
+    Ok(tokens)
+}
+
+fn push_movetext_token(tokens: &mut Vec<Token>, current: &mut String) -> Result<(), String> {
+    let token = std::mem::take(current);
+    if token.is_empty() {
+        return Ok(());
+    }
+    if let Some(nag) = token.strip_prefix('$') {
+        let nag = nag.parse().map_err(|_| format!("Invalid NAG in PGN: {}", token))?;
+        tokens.push(Token::Nag(nag));
+        return Ok(());
+    }
+    if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+        tokens.push(Token::Result(token));
+        return Ok(());
+    }
+    // Strip move number indications like "12." or "12..." glued to the move
+    let token = if token.chars().next().unwrap().is_ascii_digit() && token.contains('.') {
+        token
+            .trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+            .to_string()
+    } else {
+        token
+    };
+    if !token.is_empty() {
+        tokens.push(Token::Move(token));
+    }
+    Ok(())
+}
+
+fn piece_letter(kind: PieceType) -> Option<char> {
+    match kind {
+        PieceType::Pawn => None,
+        _ => kind.to_string().chars().next(),
+    }
+}
+
+fn is_castling_move(board: &ChessBoard, mv: Move) -> bool {
+    matches!(board.squares[mv.from.row()][mv.from.col()], Square::Occupied(p) if p.kind == PieceType::King)
+        && mv.from.row() == mv.to.row()
+        && (mv.from.col() as isize - mv.to.col() as isize).abs() == 2
+}
+
+/// Converts a legal move into Standard Algebraic Notation (SAN) for the given position.
+pub fn move_to_san(board: &ChessBoard, mv: Move) -> String {
+    let piece = match board.squares[mv.from.row()][mv.from.col()] {
+        Square::Occupied(p) => p,
+        Square::Empty => return mv.as_algebraic(),
+    };
+
+    let mut san = String::new();
+    if is_castling_move(board, mv) {
+        san.push_str(if mv.to.col() == 6 { "O-O" } else { "O-O-O" });
+    } else {
+        let is_capture = board.squares[mv.to.row()][mv.to.col()] != Square::Empty
+            || (piece.kind == PieceType::Pawn && mv.from.col() != mv.to.col());
+
+        match piece_letter(piece.kind) {
+            None => {
+                if is_capture {
+                    san.push((b'a' + mv.from.col() as u8) as char);
+                }
+            }
+            Some(letter) => {
+                san.push(letter);
+                let rivals: Vec<ChessField> = board
+                    .generate_legal_moves()
+                    .into_iter()
+                    .filter(|m| {
+                        m.to == mv.to
+                            && m.from != mv.from
+                            && board.squares[m.from.row()][m.from.col()] == Square::Occupied(piece)
+                    })
+                    .map(|m| m.from)
+                    .collect();
+                if !rivals.is_empty() {
+                    if rivals.iter().all(|f| f.col() != mv.from.col()) {
+                        san.push((b'a' + mv.from.col() as u8) as char);
+                    } else if rivals.iter().all(|f| f.row() != mv.from.row()) {
+                        san.push((b'1' + mv.from.row() as u8) as char);
+                    } else {
+                        san.push((b'a' + mv.from.col() as u8) as char);
+                        san.push((b'1' + mv.from.row() as u8) as char);
+                    }
+                }
+            }
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push((b'a' + mv.to.col() as u8) as char);
+        san.push((b'1' + mv.to.row() as u8) as char);
+        if let Some(promotion) = mv.promotion {
+            san.push('=');
+            san.push_str(&promotion.to_string());
+        }
+    }
+
+    let mut new_board = board.clone();
+    new_board.make_move(mv);
+    if new_board.is_checkmate() {
+        san.push('#');
+    } else if let Some(king_pos) = new_board.find_king_position(new_board.active_color) {
+        if new_board.is_square_attacked(king_pos.row(), king_pos.col()) {
+            san.push('+');
+        }
+    }
+    san
+}
+
+/// Parses a move in SAN or coordinate notation and returns the matching legal move.
+pub fn san_to_move(board: &ChessBoard, san: &str) -> Result<Move, String> {
+    let legal_moves = board.generate_legal_moves();
+    let text = san.trim_end_matches(['+', '#', '!', '?']);
+
+    if (4..=5).contains(&text.len()) && text.is_ascii() {
+        let bytes = text.as_bytes();
+        if (b'a'..=b'h').contains(&bytes[0])
+            && (b'1'..=b'8').contains(&bytes[1])
+            && (b'a'..=b'h').contains(&bytes[2])
+            && (b'1'..=b'8').contains(&bytes[3])
+        {
+            let mv = Move::from_algebraic(text);
+            return if legal_moves.contains(&mv) {
+                Ok(mv)
+            } else {
+                Err(format!("Illegal move: {}", san))
+            };
+        }
+    }
+
+    if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+        let target_col = if text.len() == 3 { 6 } else { 2 };
+        return legal_moves
+            .into_iter()
+            .find(|&m| is_castling_move(board, m) && m.to.col() == target_col)
+            .ok_or_else(|| format!("Illegal move: {}", san));
+    }
+
+    let mut chars: Vec<char> = text.chars().filter(|&c| c != 'x' && c != '=').collect();
+    let kind = match chars.first() {
+        Some('N') => PieceType::Knight,
+        Some('B') => PieceType::Bishop,
+        Some('R') => PieceType::Rook,
+        Some('Q') => PieceType::Queen,
+        Some('K') => PieceType::King,
+        Some(_) => PieceType::Pawn,
+        None => return Err(String::from("Empty move")),
+    };
+    if kind != PieceType::Pawn {
+        chars.remove(0);
+    }
+
+    let promotion = match chars.last() {
+        Some('Q') | Some('q') if kind == PieceType::Pawn && chars.len() > 2 => Some(PieceType::Queen),
+        Some('R') | Some('r') if kind == PieceType::Pawn && chars.len() > 2 => Some(PieceType::Rook),
+        Some('B') | Some('b') if kind == PieceType::Pawn && chars.len() > 2 => Some(PieceType::Bishop),
+        Some('N') | Some('n') if kind == PieceType::Pawn && chars.len() > 2 => Some(PieceType::Knight),
+        _ => None,
+    };
+    if promotion.is_some() {
+        chars.pop();
+    }
+
+    if chars.len() < 2 || chars.len() > 4 {
+        return Err(format!("Invalid move: {}", san));
+    }
+    let (disambiguation, target) = chars.split_at(chars.len() - 2);
+    if !('a'..='h').contains(&target[0]) || !('1'..='8').contains(&target[1]) {
+        return Err(format!("Invalid move: {}", san));
+    }
+    let to = ChessField::new(target[1] as usize - '1' as usize, target[0] as usize - 'a' as usize);
+
+    let mut from_col = None;
+    let mut from_row = None;
+    for &c in disambiguation {
+        match c {
+            'a'..='h' => from_col = Some(c as usize - 'a' as usize),
+            '1'..='8' => from_row = Some(c as usize - '1' as usize),
+            _ => return Err(format!("Invalid move: {}", san)),
+        }
+    }
+
+    let candidates: Vec<Move> = legal_moves
+        .into_iter()
+        .filter(|m| {
+            m.to == to
+                && m.promotion == promotion
+                && matches!(board.squares[m.from.row()][m.from.col()], Square::Occupied(p) if p.kind == kind)
+                && from_col.is_none_or(|c| m.from.col() == c)
+                && from_row.is_none_or(|r| m.from.row() == r)
+                && !is_castling_move(board, *m)
+        })
+        .collect();
+
+    match candidates.len() {
+        0 => Err(format!("Illegal move: {}", san)),
+        1 => Ok(candidates[0]),
+        _ => Err(format!("Ambiguous move: {}", san)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(board: &mut ChessBoard, san: &str) -> Move {
+        let mv = san_to_move(board, san).unwrap();
+        board.make_move(mv);
+        mv
+    }
+
+    #[test]
+    fn test_move_to_san() {
+        let board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        assert_eq!(move_to_san(&board, Move::from_algebraic("e2e4")), "e4");
+        assert_eq!(move_to_san(&board, Move::from_algebraic("g1f3")), "Nf3");
+
+        // Two knights can reach d2
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&board, Move::from_algebraic("b1d2")), "Nbd2");
+
+        // Two rooks on the same file
+        let board = ChessBoard::from_fen("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&board, Move::from_algebraic("a1a3")), "R1a3");
+
+        // Castling and checks
+        let board = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(move_to_san(&board, Move::from_algebraic("e1g1")), "O-O");
+        assert_eq!(move_to_san(&board, Move::from_algebraic("e1c1")), "O-O-O");
+        assert_eq!(move_to_san(&board, Move::from_algebraic("a1a8")), "Rxa8+");
+
+        // Promotion with mate
+        let board = ChessBoard::from_fen("k7/4P3/1K6/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(move_to_san(&board, Move::from_algebraic("e7e8q")), "e8=Q#");
+
+        // En passant
+        let board = ChessBoard::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        assert_eq!(move_to_san(&board, Move::from_algebraic("e5d6")), "exd6");
+    }
+
+    #[test]
+    fn test_san_to_move() {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        assert_eq!(play(&mut board, "e4"), Move::from_algebraic("e2e4"));
+        assert_eq!(play(&mut board, "e7e5"), Move::from_algebraic("e7e5"));
+        assert_eq!(play(&mut board, "Nf3"), Move::from_algebraic("g1f3"));
+        assert!(san_to_move(&board, "Ke6").is_err());
+        assert!(san_to_move(&board, "Nf3").is_err());
+
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+        assert!(san_to_move(&board, "Nd2").is_err());
+        assert_eq!(san_to_move(&board, "Nfd2").unwrap(), Move::from_algebraic("f1d2"));
+
+        let board = ChessBoard::from_fen("k7/4P3/1K6/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(san_to_move(&board, "e8=N").unwrap(), Move::from_algebraic("e7e8n"));
+        assert_eq!(san_to_move(&board, "e8Q#").unwrap(), Move::from_algebraic("e7e8q"));
+
+        let board = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert_eq!(san_to_move(&board, "O-O-O").unwrap(), Move::from_algebraic("e8c8"));
+        assert_eq!(san_to_move(&board, "0-0").unwrap(), Move::from_algebraic("e8g8"));
+    }
+
+    #[test]
+    fn test_pgn_round_trip() {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        let moves: Vec<Move> = ["f3", "e5", "g4", "Qh4#"]
+            .iter()
+            .map(|san| play(&mut board, san))
+            .collect();
+
+        let mut game = Game::from_moves(INITIAL_POSITION, moves.clone()).unwrap();
+        game.set_tag("White", "Human");
+        assert_eq!(game.result, "0-1");
+
+        let pgn = game.to_pgn().unwrap();
+        assert!(pgn.contains("[White \"Human\"]"));
+        assert!(pgn.contains("1. f3 e5 2. g4 Qh4# 0-1"));
+
+        let parsed = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(parsed.main_line(), moves);
+        assert_eq!(parsed.result, "0-1");
+        assert_eq!(parsed.get_tag("White"), Some("Human"));
+    }
+
+    #[test]
+    fn test_pgn_from_position_with_comments_and_variations() {
+        let pgn = r#"[Event "Test"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 10"]
+
+10... Kd7 {a comment} (10... Ke7 11. e4) 11.e4 $1 Kd6 ; rest of line
+12. Kd2 *
+"#;
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.start_fen, "4k3/8/8/8/8/8/4P3/4K3 b - - 0 10");
+        assert_eq!(game.moves.len(), 4);
+        assert_eq!(game.result, "*");
+        assert_eq!(game.moves[0].comment.as_deref(), Some("a comment"));
+        assert_eq!(game.moves[0].variations.len(), 1);
+        assert_eq!(game.moves[0].variations[0].len(), 2);
+        assert_eq!(game.moves[1].nags, vec![1]);
+        assert_eq!(game.moves[2].comment.as_deref(), Some("rest of line"));
+        assert!(game
+            .final_position()
+            .unwrap()
+            .to_fen()
+            .starts_with("8/8/3k4/8/4P3/8/3K4/8 b - -"));
+
+        let written = game.to_pgn().unwrap();
+        assert!(written.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 10\"]"));
+        assert!(written.contains("10... Kd7 {a comment} (10... Ke7 11. e4) 11. e4 $1 Kd6 {rest of line} 12. Kd2 *"));
+        assert_eq!(Game::from_pgn(&written).unwrap().moves, game.moves);
+    }
+
+    #[test]
+    fn test_pgn_reads_first_game_only() {
+        let pgn = r#"[Event "First"]
+
+1. e4 e5 2. Nf3
+
+[Event "Second"]
+
+1. d4 d5 1-0
+"#;
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.get_tag("Event"), Some("First"));
+        assert_eq!(game.moves.len(), 3);
+        assert_eq!(game.result, "*");
+    }
+
+    #[test]
+    fn test_pgn_invalid_move() {
+        assert!(Game::from_pgn("1. e4 e5 2. Ke3 *").is_err());
+        assert!(Game::from_pgn("1. e4 {unterminated").is_err());
+        assert!(Game::from_pgn("1. e4 (1. d4 *").is_err());
+        assert!(Game::from_pgn("1. e4 e5) *").is_err());
+        assert!(Game::from_pgn("(1. e4) *").is_err());
+    }
+
+    #[test]
+    fn test_pgn_nested_variations_and_annotations() {
+        let pgn = "{Start} 1. e4!? e5 (1... c5 2. Nf3 (2. c3 d5) 2... d6) (1... e6) 2. Nf3?! $14 *";
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.comment.as_deref(), Some("Start"));
+        assert_eq!(game.moves[0].nags, vec![5]);
+        assert_eq!(game.moves[1].variations.len(), 2);
+        assert_eq!(game.moves[1].variations[0][1].variations[0].len(), 2);
+        assert_eq!(game.moves[2].nags, vec![6, 14]);
+        assert_eq!(
+            game.movetext().unwrap(),
+            "{Start} 1. e4 $5 e5 (1... c5 2. Nf3 (2. c3 d5) 2... d6) (1... e6) 2. Nf3 $6 $14 *"
+        );
+    }
+}
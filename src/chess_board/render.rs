@@ -0,0 +1,226 @@
+use super::{ChessBoard, Color, PieceType, Square};
+
+/// How large the squares of a board diagram are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BoardSize {
+    /// One character per square without a grid.
+    Compact,
+    /// A grid of squares three characters wide.
+    #[default]
+    Standard,
+    /// A grid of squares five characters wide and three lines high.
+    Large,
+}
+
+impl BoardSize {
+    pub const NAMES: [&'static str; 3] = ["compact", "standard", "large"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "compact" => Some(BoardSize::Compact),
+            "standard" => Some(BoardSize::Standard),
+            "large" => Some(BoardSize::Large),
+            _ => None,
+        }
+    }
+
+    /// Characters per square and lines per rank, the piece is in the middle.
+    fn square(self) -> (usize, usize) {
+        match self {
+            BoardSize::Compact => (2, 1),
+            BoardSize::Standard => (3, 1),
+            BoardSize::Large => (5, 3),
+        }
+    }
+}
+
+/// How ChessBoard::render draws a position. The default is the plain ASCII grid of render_to_string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderOptions {
+    /// Chess glyphs instead of the letters of the FEN.
+    pub unicode: bool,
+    /// Light and dark squares as ANSI background colors, for terminals only.
+    pub colors: bool,
+    pub size: BoardSize,
+    /// A line with the side to move, the castling rights and the en passant square below the board.
+    pub footer: bool,
+}
+
+const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+const RESET: &str = "\x1b[0m";
+
+impl ChessBoard {
+    /// The board as plain ASCII grid with coordinates.
+    pub fn render_to_string(&self) -> String {
+        self.render(&RenderOptions::default())
+    }
+
+    /// The board with the coordinates around it, White at the bottom.
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let (width, height) = options.size.square();
+        let grid = options.size != BoardSize::Compact;
+        let border = |left: &str, middle: &str, right: &str| {
+            format!("  {}{}{}\n", left, vec!["─".repeat(width); 8].join(middle), right)
+        };
+        let files: String = ('a'..='h')
+            .map(|file| format!("{}{:^width$}", if grid { " " } else { "" }, file))
+            .collect();
+        let files = format!("  {}", files);
+        let files = if grid {
+            format!("{} \n", files)
+        } else {
+            format!("{}\n", files.trim_end())
+        };
+
+        let mut diagram = files.clone();
+        if grid {
+            diagram.push_str(&border("┌", "┬", "┐"));
+        }
+        for row in (0..8).rev() {
+            for line in 0..height {
+                let middle = line == height / 2;
+                diagram.push_str(&if middle {
+                    format!("{} ", row + 1)
+                } else {
+                    String::from("  ")
+                });
+                if grid {
+                    diagram.push('│');
+                }
+                for col in 0..8 {
+                    let square = self.squares[row][col];
+                    let symbol = match square {
+                        Square::Occupied(_) if middle => self.symbol(square, options.unicode),
+                        Square::Empty if middle && !grid && !options.colors => '.',
+                        _ => ' ',
+                    };
+                    let cell = if grid {
+                        format!("{:^width$}", symbol)
+                    } else {
+                        format!("{:<width$}", symbol)
+                    };
+                    if options.colors {
+                        let background = if (row + col) % 2 == 1 {
+                            LIGHT_SQUARE
+                        } else {
+                            DARK_SQUARE
+                        };
+                        let foreground = match square {
+                            Square::Occupied(piece) if piece.color == Color::Black => BLACK_PIECE,
+                            _ => WHITE_PIECE,
+                        };
+                        diagram.push_str(&format!("{}{}{}{}", background, foreground, cell, RESET));
+                    } else {
+                        diagram.push_str(&cell);
+                    }
+                    if grid {
+                        diagram.push('│');
+                    }
+                }
+                if middle {
+                    diagram.push_str(&format!("{}{}", if grid { " " } else { "" }, row + 1));
+                }
+                diagram.push('\n');
+            }
+            if grid && row > 0 {
+                diagram.push_str(&border("├", "┼", "┤"));
+            }
+        }
+        if grid {
+            diagram.push_str(&border("└", "┴", "┘"));
+        }
+        diagram.push_str(&files);
+        if options.footer {
+            diagram.push_str(&self.footer());
+            diagram.push('\n');
+        }
+        diagram
+    }
+
+    /// The side to move, the castling rights and the en passant square as in the FEN.
+    fn footer(&self) -> String {
+        let fen = self.to_fen();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        format!(
+            "{:?} to move, castling {}, en passant {}",
+            self.active_color, fields[2], fields[3]
+        )
+    }
+
+    fn symbol(&self, square: Square, unicode: bool) -> char {
+        let Square::Occupied(piece) = square else {
+            return ' ';
+        };
+        if unicode {
+            return match (piece.color, piece.kind) {
+                (Color::White, PieceType::King) => '♔',
+                (Color::White, PieceType::Queen) => '♕',
+                (Color::White, PieceType::Rook) => '♖',
+                (Color::White, PieceType::Bishop) => '♗',
+                (Color::White, PieceType::Knight) => '♘',
+                (Color::White, PieceType::Pawn) => '♙',
+                (Color::Black, PieceType::King) => '♚',
+                (Color::Black, PieceType::Queen) => '♛',
+                (Color::Black, PieceType::Rook) => '♜',
+                (Color::Black, PieceType::Bishop) => '♝',
+                (Color::Black, PieceType::Knight) => '♞',
+                (Color::Black, PieceType::Pawn) => '♟',
+            };
+        }
+        let letter = piece.kind.to_string().chars().next().unwrap();
+        match piece.color {
+            Color::White => letter,
+            Color::Black => letter.to_ascii_lowercase(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+
+    #[test]
+    fn test_render() {
+        let board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        let standard = board.render_to_string();
+        assert!(standard.starts_with("    a   b   c   d   e   f   g   h  \n  ┌───┬───┬───┬"));
+        assert!(standard.contains("\n8 │ r │ n │ b │ q │ k │ b │ n │ r │ 8\n"));
+        assert!(standard.contains("\n1 │ R │ N │ B │ Q │ K │ B │ N │ R │ 1\n"));
+        assert_eq!(standard.lines().count(), 19);
+
+        let compact = board.render(&RenderOptions {
+            size: BoardSize::Compact,
+            ..Default::default()
+        });
+        assert_eq!(compact.lines().next(), Some("  a b c d e f g h"));
+        assert!(compact.contains("\n8 r n b q k b n r 8\n7 p p p p p p p p 7\n6 . . . . . . . . 6\n"));
+        assert_eq!(compact.lines().count(), 10);
+
+        let board = ChessBoard::from_fen("4k3/8/8/8/4Pp2/8/8/R3K2R b KQ e3 0 1").unwrap();
+        let large = board.render(&RenderOptions {
+            unicode: true,
+            size: BoardSize::Large,
+            footer: true,
+            ..Default::default()
+        });
+        assert!(large.contains("\n1 │  ♖  │     │     │     │  ♔  │     │     │  ♖  │ 1\n"));
+        assert!(large.contains("\n8 │     │     │     │     │  ♚  │     │     │     │ 8\n"));
+        assert_eq!(large.lines().count(), 3 + 8 * 3 + 7 + 1 + 1);
+        assert!(large.ends_with("  └─────┴─────┴─────┴─────┴─────┴─────┴─────┴─────┘\n     a     b     c     d     e     f     g     h   \nBlack to move, castling KQ, en passant e3\n"));
+
+        let colored = board.render(&RenderOptions {
+            colors: true,
+            ..Default::default()
+        });
+        // a1 is a dark square, b1 a light one
+        assert!(colored.contains(&format!(
+            "1 │{}{} R {}│{}{}   {}│",
+            DARK_SQUARE, WHITE_PIECE, RESET, LIGHT_SQUARE, WHITE_PIECE, RESET
+        )));
+        assert!(colored.contains(&format!("{}{} k {}", LIGHT_SQUARE, BLACK_PIECE, RESET)));
+    }
+}
@@ -0,0 +1,138 @@
+//! The conversions behind the serde feature. Squares, moves and castling rights are serialized in the notation of
+//! the UCI protocol and the FEN, so the JSON of a game reads like the protocol. A board is its FEN and the history of
+//! its game, which is checked against the FEN when it is read.
+use super::castling::CastlingRights;
+use super::history::GameHistory;
+use super::{ChessBoard, ChessField, Move};
+use serde::{Deserialize, Serialize};
+
+impl TryFrom<String> for ChessField {
+    type Error = String;
+
+    fn try_from(algebraic: String) -> Result<Self, Self::Error> {
+        ChessField::parse_algebraic(&algebraic).ok_or_else(|| format!("Invalid square {}", algebraic))
+    }
+}
+
+impl From<ChessField> for String {
+    fn from(field: ChessField) -> Self {
+        field.as_algebraic()
+    }
+}
+
+impl TryFrom<String> for Move {
+    type Error = String;
+
+    fn try_from(algebraic: String) -> Result<Self, Self::Error> {
+        Move::parse_algebraic(&algebraic)
+    }
+}
+
+impl From<Move> for String {
+    fn from(mv: Move) -> Self {
+        mv.as_algebraic()
+    }
+}
+
+impl TryFrom<String> for CastlingRights {
+    type Error = String;
+
+    fn try_from(field: String) -> Result<Self, Self::Error> {
+        CastlingRights::from_fen(&field).ok_or_else(|| format!("Invalid castling rights {}", field))
+    }
+}
+
+impl From<CastlingRights> for String {
+    fn from(rights: CastlingRights) -> Self {
+        rights.to_string()
+    }
+}
+
+/// The serialized form of a board.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardRecord {
+    pub fen: String,
+    pub history: GameHistory,
+}
+
+impl From<ChessBoard> for BoardRecord {
+    fn from(board: ChessBoard) -> Self {
+        BoardRecord {
+            fen: board.to_fen(),
+            history: board.history,
+        }
+    }
+}
+
+impl TryFrom<BoardRecord> for ChessBoard {
+    type Error = String;
+
+    /// Fails if the FEN is invalid or the history does not end with its position.
+    fn try_from(record: BoardRecord) -> Result<Self, Self::Error> {
+        let mut board = ChessBoard::from_fen(&record.fen)?;
+        if record.history.current() != Some(board.zobrist_hash()) {
+            return Err(format!("The history does not end with the position {}", record.fen));
+        }
+        board.history = record.history;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+    use crate::chess_board::game::Game;
+    use crate::chess_board::{Color, Piece, PieceType, Square};
+
+    #[test]
+    fn test_serialize_notation() {
+        let mv = Move::from_algebraic("e7e8n");
+        assert_eq!(serde_json::to_string(&mv).unwrap(), "\"e7e8n\"");
+        assert_eq!(serde_json::from_str::<Move>("\"e7e8n\"").unwrap(), mv);
+        assert!(serde_json::from_str::<Move>("\"e7e9\"").is_err());
+        assert_eq!(
+            serde_json::from_str::<ChessField>("\"h8\"").unwrap(),
+            ChessField::new(7, 7)
+        );
+        assert!(serde_json::from_str::<ChessField>("\"i1\"").is_err());
+        assert_eq!(
+            serde_json::from_str::<CastlingRights>("\"Kq\"").unwrap().to_string(),
+            "Kq"
+        );
+        let piece = Square::Occupied(Piece {
+            color: Color::Black,
+            kind: PieceType::Queen,
+        });
+        let json = serde_json::to_string(&piece).unwrap();
+        assert_eq!(json, r#"{"Occupied":{"color":"Black","kind":"Queen"}}"#);
+        assert_eq!(serde_json::from_str::<Square>(&json).unwrap(), piece);
+    }
+
+    #[test]
+    fn test_serialize_board() {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        for mv in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+            board.make_move(Move::from_algebraic(mv));
+        }
+        let json = serde_json::to_string(&board).unwrap();
+        let restored: ChessBoard = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, board);
+        // The history survives, so the repetition is still counted
+        assert_eq!(restored.history.repetition_count(), 2);
+
+        let mut record: BoardRecord = serde_json::from_str(&json).unwrap();
+        record.fen = String::from("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(ChessBoard::try_from(record).is_err());
+        assert!(serde_json::from_str::<ChessBoard>(r#"{"fen":"no fen","history":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_serialize_game() {
+        let mut game = Game::from_pgn("1. e4 (1. d4 d5) 1... e5 {Open game} 2. Nf3 *").unwrap();
+        game.moves[0].nags.push(1);
+        let json = serde_json::to_string(&game).unwrap();
+        assert!(json.contains(r#""mv":"e2e4""#), "{}", json);
+        assert_eq!(serde_json::from_str::<Game>(&json).unwrap(), game);
+    }
+}
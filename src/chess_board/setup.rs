@@ -0,0 +1,295 @@
+use super::castling::Castling;
+use super::fen::{FenError, INITIAL_POSITION};
+use super::zobrist_hash::ZOBRIST;
+use super::{ChessBoard, ChessField, Color, Piece, PieceType, Square};
+
+/// Odds the engine gives the user, who plays White. Material odds take a piece of the engine off the initial
+/// position. With move odds the engine gives up the first move: the user plays Black and moves first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handicap {
+    None,
+    Pawn,
+    Knight,
+    Rook,
+    Queen,
+    Move,
+}
+
+/// The handicaps in the order of the new game dialog.
+pub const HANDICAPS: [Handicap; 6] = [
+    Handicap::None,
+    Handicap::Pawn,
+    Handicap::Knight,
+    Handicap::Rook,
+    Handicap::Queen,
+    Handicap::Move,
+];
+
+impl Handicap {
+    pub fn name(self) -> &'static str {
+        match self {
+            Handicap::None => "No odds",
+            Handicap::Pawn => "Pawn odds",
+            Handicap::Knight => "Knight odds",
+            Handicap::Rook => "Rook odds",
+            Handicap::Queen => "Queen odds",
+            Handicap::Move => "Move odds",
+        }
+    }
+
+    /// The square of the piece the engine gives, the f-pawn and the queen-side knight and rook as is customary.
+    fn removed_square(self) -> Option<&'static str> {
+        match self {
+            Handicap::Pawn => Some("f7"),
+            Handicap::Knight => Some("b8"),
+            Handicap::Rook => Some("a8"),
+            Handicap::Queen => Some("d8"),
+            Handicap::None | Handicap::Move => None,
+        }
+    }
+
+    /// The start position of a game with the handicap.
+    pub fn start_position(self) -> ChessBoard {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        if let Some(square) = self.removed_square() {
+            board.remove_piece(ChessField::from_algebraic(square));
+        }
+        if self == Handicap::Move {
+            board.set_side_to_move(Color::Black);
+        }
+        board
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+/// Editing a position. Every edit keeps the board consistent on its own: pawns stay off the back ranks, there is at
+/// most one king per color, castling rights need their king and rook. Whether the position as a whole can be played,
+/// e.g. both kings are on the board, is checked by validate.
+impl ChessBoard {
+    /// Puts the piece on the square, replacing the piece standing there.
+    pub fn place_piece(&mut self, square: ChessField, piece: Piece) -> Result<(), FenError> {
+        let count = |board: &ChessBoard| {
+            board
+                .squares
+                .iter()
+                .flatten()
+                .filter(|&&s| s == Square::Occupied(piece))
+                .count()
+        };
+        let replaced = self.squares[square.row()][square.col()] == Square::Occupied(piece);
+        match piece.kind {
+            PieceType::Pawn if square.row() == 0 || square.row() == 7 => return Err(FenError::PawnOnBackRank(square)),
+            PieceType::Pawn if !replaced && count(self) >= 8 => return Err(FenError::TooManyPawns(piece.color)),
+            PieceType::King if !replaced && count(self) >= 1 => return Err(FenError::TooManyKings(piece.color)),
+            _ => {}
+        }
+        self.squares[square.row()][square.col()] = Square::Occupied(piece);
+        self.position_edited();
+        Ok(())
+    }
+
+    /// Takes the piece off the square and returns it.
+    pub fn remove_piece(&mut self, square: ChessField) -> Option<Piece> {
+        match std::mem::replace(&mut self.squares[square.row()][square.col()], Square::Empty) {
+            Square::Occupied(piece) => {
+                self.position_edited();
+                Some(piece)
+            }
+            Square::Empty => None,
+        }
+    }
+
+    pub fn set_side_to_move(&mut self, color: Color) {
+        self.active_color = color;
+        self.position_edited();
+    }
+
+    /// Grants or revokes a castling right. It is only granted with the king and the rook on their initial squares.
+    pub fn set_castling(&mut self, color: Color, kingside: bool, allowed: bool) -> Result<(), FenError> {
+        let castling = Castling::new(color, kingside);
+        if allowed && !self.has_castling_pieces(castling) {
+            return Err(FenError::CastlingWithoutPieces(castling.letter()));
+        }
+        self.castling_rights.set(castling, allowed);
+        self.position_edited();
+        Ok(())
+    }
+
+    /// Removes all pieces and castling rights.
+    pub fn clear(&mut self) {
+        self.squares = [[Square::Empty; 8]; 8];
+        self.position_edited();
+    }
+
+    /// Whether the king and the rook of the castling are on their initial squares.
+    pub(super) fn has_castling_pieces(&self, castling: Castling) -> bool {
+        let color = castling.color();
+        self.square(castling.king_from())
+            == Square::Occupied(Piece {
+                color,
+                kind: PieceType::King,
+            })
+            && self.square(castling.rook_from())
+                == Square::Occupied(Piece {
+                    color,
+                    kind: PieceType::Rook,
+                })
+    }
+
+    /// The position with colors and ranks flipped: White's pieces become Black's on the mirrored squares, Black is to
+    /// move instead of White, castling rights and the en passant square change sides. An evaluation that does not
+    /// favor a color scores it as the negated original.
+    pub fn mirror(&self) -> ChessBoard {
+        let mut mirrored = self.clone();
+        for (row, squares) in self.squares.iter().enumerate() {
+            mirrored.squares[7 - row] = squares.map(|square| match square {
+                Square::Occupied(piece) => Square::Occupied(Piece {
+                    color: opponent(piece.color),
+                    kind: piece.kind,
+                }),
+                Square::Empty => Square::Empty,
+            });
+        }
+        mirrored.active_color = opponent(self.active_color);
+        mirrored.castling_rights = self.castling_rights.mirror();
+        mirrored.en_passant = self
+            .en_passant
+            .map(|field| ChessField::new(7 - field.row(), field.col()));
+        mirrored.refresh_material();
+        mirrored.history.reset(ZOBRIST.calculate_hash(&mirrored));
+        mirrored
+    }
+
+    /// An edited position has no en passant square and no history, and castling rights are dropped with their pieces.
+    fn position_edited(&mut self) {
+        for castling in Castling::ALL {
+            if !self.has_castling_pieces(castling) {
+                self.castling_rights.set(castling, false);
+            }
+        }
+        self.en_passant = None;
+        self.halfmove_clock = 0;
+        self.refresh_material();
+        self.history.reset(ZOBRIST.calculate_hash(self));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror() {
+        let board = ChessBoard::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K3 w Qkq d6 0 3").unwrap();
+        let mirrored = board.mirror();
+        assert_eq!(mirrored.to_fen(), "r3k3/8/8/8/3Pp3/8/8/R3K2R b KQq d3 0 3");
+        assert_eq!(mirrored.zobrist_hash(), ZOBRIST.calculate_hash(&mirrored));
+        assert_eq!(
+            mirrored.generate_legal_moves().len(),
+            board.generate_legal_moves().len()
+        );
+        assert_eq!(mirrored.mirror().to_fen(), board.to_fen());
+
+        let initial = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        assert_eq!(
+            initial.mirror().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"
+        );
+    }
+
+    fn piece(color: Color, kind: PieceType) -> Piece {
+        Piece { color, kind }
+    }
+
+    #[test]
+    fn test_edit_position() {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        board.make_move(crate::chess_board::Move::from_algebraic("e2e4"));
+        assert_eq!(
+            board.remove_piece(ChessField::from_algebraic("h1")),
+            Some(piece(Color::White, PieceType::Rook))
+        );
+        assert_eq!(board.remove_piece(ChessField::from_algebraic("h1")), None);
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBN1 b Qkq - 0 1"
+        );
+        assert_eq!(board.repetition_count(), 1);
+        assert_eq!(board.zobrist_hash(), ZOBRIST.calculate_hash(&board));
+
+        board
+            .place_piece(ChessField::from_algebraic("h1"), piece(Color::White, PieceType::Rook))
+            .unwrap();
+        assert_eq!(board.set_castling(Color::White, true, true), Ok(()));
+        assert!(board.castling_rights.has(Castling::WhiteKingside));
+        board.set_side_to_move(Color::White);
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1"
+        );
+
+        board.remove_piece(ChessField::from_algebraic("e1"));
+        assert_eq!(
+            board.set_castling(Color::White, false, true),
+            Err(FenError::CastlingWithoutPieces('Q'))
+        );
+        assert!(
+            !board.castling_rights.has(Castling::WhiteKingside) && !board.castling_rights.has(Castling::WhiteQueenside)
+        );
+    }
+
+    #[test]
+    fn test_handicap() {
+        let fens: Vec<String> = HANDICAPS
+            .iter()
+            .map(|handicap| handicap.start_position().to_fen())
+            .collect();
+        assert_eq!(
+            fens,
+            [
+                INITIAL_POSITION,
+                "rnbqkbnr/ppppp1pp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1",
+                "rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
+            ]
+        );
+        for handicap in HANDICAPS {
+            assert_eq!(handicap.start_position().validate(), Ok(()), "{}", handicap.name());
+        }
+    }
+
+    #[test]
+    fn test_edit_validation() {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        let a8 = ChessField::from_algebraic("a8");
+        assert_eq!(
+            board.place_piece(a8, piece(Color::White, PieceType::Pawn)),
+            Err(FenError::PawnOnBackRank(a8))
+        );
+        assert_eq!(
+            board.place_piece(ChessField::from_algebraic("d4"), piece(Color::White, PieceType::King)),
+            Err(FenError::TooManyKings(Color::White))
+        );
+        // Placing a king on its own square is not a second king
+        assert_eq!(
+            board.place_piece(ChessField::from_algebraic("e1"), piece(Color::White, PieceType::King)),
+            Ok(())
+        );
+        assert_eq!(
+            board.place_piece(ChessField::from_algebraic("d4"), piece(Color::White, PieceType::Pawn)),
+            Err(FenError::TooManyPawns(Color::White))
+        );
+
+        board.clear();
+        assert_eq!(board.to_fen(), "8/8/8/8/8/8/8/8 w - - 0 1");
+        assert_eq!(board.validate(), Err(FenError::MissingKing(Color::White)));
+    }
+}
@@ -0,0 +1,379 @@
+//! Simple tactical motifs read off the attacks of both colors: pieces that can be won, squares from which a knight
+//! attacks two pieces at once, and pieces pinned or skewered by a sliding piece. The detectors are static, they see
+//! no exchange sequences and no x-rays, so they are hints for the evaluation and the UI, not a replacement for search.
+use super::{ChessBoard, ChessField, Color, Piece, PieceType, Square};
+
+const KNIGHT_MOVES: [(isize, isize); 8] = [(-2, -1), (-1, -2), (1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1)];
+const KING_MOVES: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+const ROOK_DIRECTIONS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_DIRECTIONS: [(isize, isize); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+/// A square a knight can jump to from which it attacks at least two pieces it wins material against: the king, a
+/// rook or queen, or an undefended piece other than a pawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnightFork {
+    pub knight: ChessField,
+    pub square: ChessField,
+    pub targets: Vec<ChessField>,
+}
+
+/// A sliding piece attacking a piece with another piece of the same color behind it on the line. In a pin the piece
+/// behind is the more valuable one, so the front piece cannot move away without losing it; in a skewer the front
+/// piece is the more valuable one and has to move, leaving the piece behind to be taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pin {
+    pub attacker: ChessField,
+    pub front: ChessField,
+    pub behind: ChessField,
+    pub skewer: bool,
+}
+
+/// The number of pieces of each color attacking every square and the least valuable of them. Pinned pieces attack
+/// like all others, sliding pieces behind other attackers (x-rays) are not counted.
+#[derive(Debug, Clone)]
+pub struct AttackMap<'a> {
+    board: &'a ChessBoard,
+    /// The squares of the pieces, the first `piece_count` ones.
+    occupied: [ChessField; 64],
+    piece_count: usize,
+    /// Sliding pieces with an enemy piece in their line and another piece of that color behind it: the attacker, the
+    /// front piece and the piece behind, the candidates of pins and skewers.
+    lines: Vec<(ChessField, ChessField, ChessField)>,
+    counts: [[u8; 64]; 2],
+    least_valuable: [[Option<PieceType>; 64]; 2],
+}
+
+impl<'a> AttackMap<'a> {
+    pub fn new(board: &'a ChessBoard) -> Self {
+        let mut map = AttackMap {
+            board,
+            occupied: [ChessField(0); 64],
+            piece_count: 0,
+            lines: Vec::new(),
+            counts: [[0; 64]; 2],
+            least_valuable: [[None; 64]; 2],
+        };
+        for from in ChessField::all() {
+            if let Square::Occupied(piece) = board.squares[from.row()][from.col()] {
+                map.occupied[map.piece_count] = from;
+                map.piece_count += 1;
+                map.add_attacks(from, piece);
+            }
+        }
+        map
+    }
+
+    fn add_attacks(&mut self, from: ChessField, piece: Piece) {
+        let (row, col) = (from.row() as isize, from.col() as isize);
+        let steps: &[(isize, isize)] = match piece.kind {
+            PieceType::Pawn => match piece.color {
+                Color::White => &[(1, -1), (1, 1)],
+                Color::Black => &[(-1, -1), (-1, 1)],
+            },
+            PieceType::Knight => &KNIGHT_MOVES,
+            PieceType::King => &KING_MOVES,
+            PieceType::Bishop | PieceType::Rook | PieceType::Queen => {
+                for &(rows, cols) in directions(piece.kind) {
+                    let Some(front) = self.walk(row, col, rows, cols, |map, field| map.add(field, piece)) else {
+                        continue;
+                    };
+                    let front_color = self.piece(front).map(|front| front.color);
+                    if front_color == Some(piece.color) {
+                        continue;
+                    }
+                    let (front_row, front_col) = (front.row() as isize, front.col() as isize);
+                    if let Some(behind) = self.walk(front_row, front_col, rows, cols, |_, _| {}) {
+                        if self.piece(behind).map(|behind| behind.color) == front_color {
+                            self.lines.push((from, front, behind));
+                        }
+                    }
+                }
+                return;
+            }
+        };
+        for &(rows, cols) in steps {
+            if let Some(field) = square(row + rows, col + cols) {
+                self.add(field, piece);
+            }
+        }
+    }
+
+    /// Walks from the square in the direction up to the first piece, calling the function with every square on the
+    /// way including the one of the piece. Returns the square of the piece.
+    fn walk(
+        &mut self,
+        row: isize,
+        col: isize,
+        rows: isize,
+        cols: isize,
+        mut f: impl FnMut(&mut Self, ChessField),
+    ) -> Option<ChessField> {
+        let (mut row, mut col) = (row + rows, col + cols);
+        while let Some(field) = square(row, col) {
+            f(self, field);
+            if self.board.squares[row as usize][col as usize] != Square::Empty {
+                return Some(field);
+            }
+            row += rows;
+            col += cols;
+        }
+        None
+    }
+
+    fn add(&mut self, square: ChessField, piece: Piece) {
+        let color = piece.color as usize;
+        self.counts[color][square.index()] += 1;
+        let least = &mut self.least_valuable[color][square.index()];
+        if least.is_none_or(|kind| value(piece.kind) < value(kind)) {
+            *least = Some(piece.kind);
+        }
+    }
+
+    /// The number of pieces of the color attacking the square.
+    pub fn attackers(&self, square: ChessField, color: Color) -> usize {
+        self.counts[color as usize][square.index()] as usize
+    }
+
+    /// The least valuable piece of the color attacking the square.
+    pub fn least_valuable_attacker(&self, square: ChessField, color: Color) -> Option<PieceType> {
+        self.least_valuable[color as usize][square.index()]
+    }
+
+    /// The pieces on the board with their squares.
+    fn pieces(&self) -> impl Iterator<Item = (ChessField, Piece)> + '_ {
+        self.occupied[..self.piece_count]
+            .iter()
+            .filter_map(|&square| self.piece(square).map(|piece| (square, piece)))
+    }
+
+    fn piece(&self, square: ChessField) -> Option<Piece> {
+        match self.board.squares[square.row()][square.col()] {
+            Square::Occupied(piece) => Some(piece),
+            Square::Empty => None,
+        }
+    }
+
+    /// The pieces of the color but the king the opponent attacks and that are either undefended or attacked by a
+    /// less valuable piece, the ones the opponent wins material capturing.
+    pub fn threatened_pieces(&self, color: Color) -> Vec<ChessField> {
+        self.pieces()
+            .filter(|&(square, piece)| {
+                if piece.color != color || piece.kind == PieceType::King {
+                    return false;
+                }
+                match self.least_valuable_attacker(square, opponent(color)) {
+                    Some(attacker) => self.attackers(square, color) == 0 || value(attacker) < value(piece.kind),
+                    None => false,
+                }
+            })
+            .map(|(square, _)| square)
+            .collect()
+    }
+
+    /// The squares the knights of the color can jump to safely and fork two pieces from. A square is safe if the
+    /// opponent does not attack it, or if it is defended and no pawn attacks it.
+    pub fn knight_forks(&self, color: Color) -> Vec<KnightFork> {
+        let enemy = opponent(color);
+        let mut forks = Vec::new();
+        for (knight, piece) in self.pieces() {
+            if piece.color != color || piece.kind != PieceType::Knight {
+                continue;
+            }
+            for square in knight_moves(knight) {
+                if self.piece(square).is_some_and(|piece| piece.color == color) {
+                    continue;
+                }
+                let safe = match self.least_valuable_attacker(square, enemy) {
+                    None => true,
+                    Some(attacker) => attacker != PieceType::Pawn && self.attackers(square, color) > 1,
+                };
+                if !safe {
+                    continue;
+                }
+                let targets = knight_moves(square).filter(|&target| match self.piece(target) {
+                    Some(piece) if piece.color == enemy => match piece.kind {
+                        PieceType::King | PieceType::Rook | PieceType::Queen => true,
+                        PieceType::Knight | PieceType::Bishop => self.attackers(target, enemy) == 0,
+                        PieceType::Pawn => false,
+                    },
+                    _ => false,
+                });
+                if targets.clone().count() >= 2 {
+                    forks.push(KnightFork {
+                        knight,
+                        square,
+                        targets: targets.collect(),
+                    });
+                }
+            }
+        }
+        forks
+    }
+
+    /// The pins and skewers of the pieces of the color by the sliding pieces of the opponent. A pinned piece that can
+    /// capture the attacker along the line is not pinned.
+    pub fn pins(&self, color: Color) -> Vec<Pin> {
+        let mut pins = Vec::new();
+        for &(attacker, front, behind) in &self.lines {
+            let (Some(piece), Some(front_piece), Some(behind_piece)) =
+                (self.piece(attacker), self.piece(front), self.piece(behind))
+            else {
+                continue;
+            };
+            if front_piece.color != color {
+                continue;
+            }
+            // The direction from the front piece back to the attacker
+            let back = (
+                (attacker.row() as isize - front.row() as isize).signum(),
+                (attacker.col() as isize - front.col() as isize).signum(),
+            );
+            let (front_value, behind_value) = (value(front_piece.kind), value(behind_piece.kind));
+            let behind_undefended = self.attackers(behind, color) == 0;
+            let pinned = front_value < behind_value
+                && (behind_value > value(piece.kind) || behind_undefended)
+                && !directions(front_piece.kind).contains(&back);
+            let skewered = front_value > behind_value
+                && behind_piece.kind != PieceType::Pawn
+                && (front_value > value(piece.kind) || self.attackers(front, color) == 0)
+                && (behind_value >= value(piece.kind) || behind_undefended);
+            if pinned || skewered {
+                pins.push(Pin {
+                    attacker,
+                    front,
+                    behind,
+                    skewer: skewered,
+                });
+            }
+        }
+        pins
+    }
+}
+
+impl ChessBoard {
+    pub fn attack_map(&self) -> AttackMap<'_> {
+        AttackMap::new(self)
+    }
+
+    /// The pieces of the color the opponent wins material capturing, see [`AttackMap::threatened_pieces`].
+    pub fn threatened_pieces(&self, color: Color) -> Vec<ChessField> {
+        self.attack_map().threatened_pieces(color)
+    }
+}
+
+fn knight_moves(from: ChessField) -> impl Iterator<Item = ChessField> + Clone {
+    let (row, col) = (from.row() as isize, from.col() as isize);
+    KNIGHT_MOVES
+        .iter()
+        .filter_map(move |&(rows, cols)| square(row + rows, col + cols))
+}
+
+/// The square at the coordinates, if they are on the board.
+fn square(row: isize, col: isize) -> Option<ChessField> {
+    ((0..8).contains(&row) && (0..8).contains(&col)).then_some(ChessField((row * 8 + col) as u8))
+}
+
+/// The directions the piece slides in, none for the pieces that do not slide.
+fn directions(kind: PieceType) -> &'static [(isize, isize)] {
+    const QUEEN_DIRECTIONS: [(isize, isize); 8] =
+        [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)];
+    match kind {
+        PieceType::Bishop => &BISHOP_DIRECTIONS,
+        PieceType::Rook => &ROOK_DIRECTIONS,
+        PieceType::Queen => &QUEEN_DIRECTIONS,
+        PieceType::Pawn | PieceType::Knight | PieceType::King => &[],
+    }
+}
+
+/// Piece values in pawns, the king above all others.
+fn value(kind: PieceType) -> i32 {
+    match kind {
+        PieceType::Pawn => 1,
+        PieceType::Knight | PieceType::Bishop => 3,
+        PieceType::Rook => 5,
+        PieceType::Queen => 9,
+        PieceType::King => 100,
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squares(names: &[&str]) -> Vec<ChessField> {
+        names.iter().map(|name| ChessField::from_algebraic(name)).collect()
+    }
+
+    #[test]
+    fn test_threatened_pieces() {
+        // The knight on c6 and the rook on a8 are undefended, the bishop attacking the pawn on d5 is worth more than
+        // its defender
+        let board = ChessBoard::from_fen("r3k3/8/2n1p3/1P1p4/8/8/6B1/R3K3 w - - 0 1").unwrap();
+        let map = board.attack_map();
+        assert_eq!(map.attackers(ChessField::from_algebraic("d5"), Color::White), 1);
+        assert_eq!(
+            map.least_valuable_attacker(ChessField::from_algebraic("d5"), Color::Black),
+            Some(PieceType::Pawn)
+        );
+        assert_eq!(board.threatened_pieces(Color::Black), squares(&["c6", "a8"]));
+        assert_eq!(board.threatened_pieces(Color::White), squares(&["a1"]));
+
+        // Defended by the rook, the knight attacked by the pawn is still threatened
+        let board = ChessBoard::from_fen("4k3/8/2n5/1P6/8/8/8/2r1K3 b - - 0 1").unwrap();
+        assert_eq!(board.threatened_pieces(Color::Black), squares(&["c6"]));
+    }
+
+    #[test]
+    fn test_knight_forks() {
+        // Nc7 forks the king and the rook, Nf6 would fork the king and the queen but the pawn takes it
+        let board = ChessBoard::from_fen("r3k3/6pq/8/3N4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            board.attack_map().knight_forks(Color::White),
+            vec![KnightFork {
+                knight: ChessField::from_algebraic("d5"),
+                square: ChessField::from_algebraic("c7"),
+                targets: squares(&["a8", "e8"]),
+            }]
+        );
+        assert!(board.attack_map().knight_forks(Color::Black).is_empty());
+    }
+
+    #[test]
+    fn test_pins() {
+        // The bishop pins the knight to the king
+        let board = ChessBoard::from_fen("4k3/3n4/8/1B6/8/8/q6r/4K2R w - - 0 1").unwrap();
+        assert_eq!(
+            board.attack_map().pins(Color::Black),
+            vec![Pin {
+                attacker: ChessField::from_algebraic("b5"),
+                front: ChessField::from_algebraic("d7"),
+                behind: ChessField::from_algebraic("e8"),
+                skewer: false,
+            }]
+        );
+        assert!(board.attack_map().pins(Color::White).is_empty());
+
+        // The rook skewers the queen to the rook
+        let board = ChessBoard::from_fen("7k/8/8/8/8/K7/8/R2q3r w - - 0 1").unwrap();
+        assert_eq!(
+            board.attack_map().pins(Color::Black),
+            vec![Pin {
+                attacker: ChessField::from_algebraic("a1"),
+                front: ChessField::from_algebraic("d1"),
+                behind: ChessField::from_algebraic("h1"),
+                skewer: true,
+            }]
+        );
+
+        // A rook pinned on the file by a rook captures it
+        let board = ChessBoard::from_fen("4k3/4r3/8/8/8/8/8/4RK2 w - - 0 1").unwrap();
+        assert!(board.attack_map().pins(Color::Black).is_empty());
+    }
+}
@@ -1,4 +1,5 @@
-use super::{ChessBoard, Color, PieceType, Square};
+use super::castling::Castling;
+use super::{ChessBoard, ChessField, Color, PieceType, Square};
 use lazy_static::lazy_static;
 use rand::{Rng, SeedableRng};
 use rand_pcg::Pcg64;
@@ -6,10 +7,16 @@ use std::sync::Arc;
 
 const BOARD_SIZE: usize = 8;
 
+/// Color index and rook column of a castling. The keys belong to the rook a king may castle with, as in Chess960,
+/// where the rooks do not start in the corners.
+fn castling_rook(castling: Castling) -> (usize, usize) {
+    (castling.color() as usize, castling.rook_from().col())
+}
+
 pub struct ZobristHash {
     piece_keys: [[[u64; BOARD_SIZE * BOARD_SIZE]; 6]; 2],
     side_to_move_key: u64,
-    castling_keys: [u64; 4],
+    castling_keys: [[u64; BOARD_SIZE]; 2],
     en_passant_keys: [u64; BOARD_SIZE],
 }
 
@@ -31,10 +38,10 @@ impl ZobristHash {
         // Random number for side-to-move
         let side_to_move_key = rng.gen();
 
-        // Random numbers for castling rights
-        let mut castling_keys = [0; 4];
-        for key in &mut castling_keys {
-            *key = rng.gen();
+        // Random numbers for castling with the rooks of the standard start position
+        let mut castling_keys = [[0; BOARD_SIZE]; 2];
+        for (color_index, col) in Castling::ALL.map(castling_rook) {
+            castling_keys[color_index][col] = rng.gen();
         }
 
         // Random numbers for en passant file
@@ -43,6 +50,15 @@ impl ZobristHash {
             *file = rng.gen();
         }
 
+        // The keys of the other rook columns are drawn last, so the hashes of standard positions stay the same
+        for (color_index, color_keys) in castling_keys.iter_mut().enumerate() {
+            for (col, key) in color_keys.iter_mut().enumerate() {
+                if !Castling::ALL.map(castling_rook).contains(&(color_index, col)) {
+                    *key = rng.gen();
+                }
+            }
+        }
+
         ZobristHash {
             piece_keys,
             side_to_move_key,
@@ -55,24 +71,21 @@ impl ZobristHash {
         let mut hash = 0;
 
         // Hash pieces on squares
-        for row in 0..BOARD_SIZE {
-            for col in 0..BOARD_SIZE {
-                if let Square::Occupied(piece) = board.squares[row][col] {
-                    let color_index = match piece.color {
-                        Color::White => 0,
-                        Color::Black => 1,
-                    };
-                    let piece_index = match piece.kind {
-                        PieceType::Pawn => 0,
-                        PieceType::Knight => 1,
-                        PieceType::Bishop => 2,
-                        PieceType::Rook => 3,
-                        PieceType::Queen => 4,
-                        PieceType::King => 5,
-                    };
-                    let square_index = row * BOARD_SIZE + col;
-                    hash ^= self.piece_keys[color_index][piece_index][square_index];
-                }
+        for field in ChessField::all() {
+            if let Square::Occupied(piece) = board.square(field) {
+                let color_index = match piece.color {
+                    Color::White => 0,
+                    Color::Black => 1,
+                };
+                let piece_index = match piece.kind {
+                    PieceType::Pawn => 0,
+                    PieceType::Knight => 1,
+                    PieceType::Bishop => 2,
+                    PieceType::Rook => 3,
+                    PieceType::Queen => 4,
+                    PieceType::King => 5,
+                };
+                hash ^= self.piece_keys[color_index][piece_index][field.index()];
             }
         }
 
@@ -82,15 +95,13 @@ impl ZobristHash {
         }
 
         // Hash castling rights
-        for (i, castling) in board.castling_rights.iter().enumerate() {
-            if *castling {
-                hash ^= self.castling_keys[i];
-            }
+        for (color_index, col) in board.castling_rights.iter().map(castling_rook) {
+            hash ^= self.castling_keys[color_index][col];
         }
 
-        // Hash en passant
-        if let Some(en_passant) = board.en_passant {
-            hash ^= self.en_passant_keys[en_passant.col];
+        // Hash en passant, a square no pawn can legally capture on does not make a different position
+        if let Some(en_passant) = board.en_passant.filter(|_| board.has_legal_en_passant()) {
+            hash ^= self.en_passant_keys[en_passant.col()];
         }
 
         hash
@@ -100,3 +111,41 @@ impl ZobristHash {
 lazy_static! {
     pub static ref ZOBRIST: Arc<ZobristHash> = Arc::new(ZobristHash::new(42));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_en_passant_hash() {
+        let capturable = ChessBoard::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let not_capturable = ChessBoard::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(capturable.zobrist_hash(), not_capturable.zobrist_hash());
+
+        // A square set without a pawn to capture on it is ignored
+        let mut board = ChessBoard::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        let hash = ZOBRIST.calculate_hash(&board);
+        board.en_passant = Some(ChessField::from_algebraic("e3"));
+        assert_eq!(ZOBRIST.calculate_hash(&board), hash);
+    }
+
+    #[test]
+    fn test_castling_keys() {
+        let mut keys: Vec<u64> = ZOBRIST.castling_keys.iter().flatten().copied().collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), 16);
+        assert!(!keys.contains(&0));
+
+        let all = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let none = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+        let rooks = Castling::ALL.map(|castling| {
+            let (color_index, col) = castling_rook(castling);
+            ZOBRIST.castling_keys[color_index][col]
+        });
+        assert_eq!(
+            all.zobrist_hash() ^ none.zobrist_hash(),
+            rooks.iter().fold(0, |hash, key| hash ^ key)
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use crate::chess_board::fen::{FenMode, INITIAL_POSITION};
+use crate::chess_board::pgn::{move_to_san, san_to_move};
+use crate::chess_board::{ChessBoard, Color, Move};
+use crate::commands::Subcommand;
+use crate::engines::analysis::{format_evaluation, Analysis, AnalysisDepth, AnalysisStep};
+use crate::engines::analysis_db::AnalysisDb;
+use crate::engines::engine_alpha_beta::MAX_PLY;
+use clap::{arg, ArgMatches, Command};
+use std::io::{stdin, BufRead};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "analyze",
+    command,
+    run,
+};
+
+/// How long the analysis runs before it looks for new input.
+const STEP_TIME: Duration = Duration::from_millis(50);
+
+fn command() -> Command {
+    Command::new("analyze")
+        .about("Analyze a position until quit, entering a move (SAN or e2e4) restarts the analysis after it")
+        .arg(arg!(-f --fen <FEN> "Board position").default_value(INITIAL_POSITION))
+        .arg(
+            arg!(--multipv <LINES> "Number of lines shown per depth")
+                .default_value("3")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(-x --depth <DEPTH> "Stop every analysis after this depth instead of the maximum depth")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(arg!(--db <FILE> "Analysis database, by default analysis.db in the user's configuration directory"))
+        .arg(arg!(--"no-db" "Neither resume stored analyses nor store this one").conflicts_with("db"))
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let fen = arg_matches.get_one::<String>("fen").unwrap();
+    let multi_pv = *arg_matches.get_one::<usize>("multipv").unwrap();
+    let max_depth = arg_matches.get_one::<usize>("depth").copied().unwrap_or(MAX_PLY);
+
+    let mut board = match ChessBoard::from_fen_with_mode(fen, FenMode::Strict) {
+        Ok(board) => board,
+        Err(e) => {
+            println!("Invalid FEN {}: {}", fen, e);
+            return;
+        }
+    };
+
+    let mut db = open_db(arg_matches);
+
+    // Input is read on its own thread, so the analysis keeps running while waiting for a move
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in stdin().lock().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let start_analysis = |board: &ChessBoard, db: &Option<AnalysisDb>| {
+        println!("Analyzing {}", board.to_fen());
+        let known = db.as_ref().and_then(|db| db.get(board));
+        if let Some(known) = &known {
+            println!("Resuming the stored analysis of depth {}", known.depth);
+        }
+        let mut analysis = Analysis::resume(board.clone(), multi_pv, known);
+        analysis.set_max_depth(max_depth);
+        analysis
+    };
+    let mut analysis = start_analysis(&board, &db);
+    let mut finished = false;
+    let mut input_closed = false;
+    loop {
+        let input = if finished && !input_closed {
+            receiver.recv().map_err(|_| TryRecvError::Disconnected)
+        } else {
+            receiver.try_recv()
+        };
+        match input {
+            Ok(line) => match line.trim() {
+                "" => {}
+                "quit" => return,
+                text => match parse_move(&board, text) {
+                    Ok(mv) => {
+                        board.make_move(mv);
+                        analysis = start_analysis(&board, &db);
+                        finished = false;
+                    }
+                    Err(e) => println!("{}", e),
+                },
+            },
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => input_closed = true,
+        }
+        if finished {
+            if input_closed {
+                return;
+            }
+            continue;
+        }
+        match analysis.step(STEP_TIME) {
+            AnalysisStep::Running => {}
+            AnalysisStep::Depth(depth) => {
+                for line in format_depth(&board, &depth) {
+                    println!("{}", line);
+                }
+                if let Some(db) = &mut db {
+                    if let Err(e) = db.record(&board, &depth) {
+                        println!("Could not store the analysis: {}", e);
+                    }
+                }
+            }
+            AnalysisStep::Finished => {
+                println!("Analysis finished");
+                finished = true;
+            }
+        }
+    }
+}
+
+/// The database of the --db option or the default one, None with --no-db or if it cannot be read.
+fn open_db(arg_matches: &ArgMatches) -> Option<AnalysisDb> {
+    if arg_matches.get_flag("no-db") {
+        return None;
+    }
+    let path = match arg_matches.get_one::<String>("db") {
+        Some(path) => PathBuf::from(path),
+        None => AnalysisDb::default_path()?,
+    };
+    match AnalysisDb::open(&path) {
+        Ok(db) => Some(db),
+        Err(e) => {
+            println!("Could not read the analysis database {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Accepts SAN like Nf3 and coordinate moves like g1f3.
+fn parse_move(board: &ChessBoard, text: &str) -> Result<Move, String> {
+    san_to_move(board, text).or_else(|_| {
+        board
+            .generate_legal_moves()
+            .into_iter()
+            .find(|mv| mv.as_algebraic() == text)
+            .ok_or_else(|| format!("Illegal move {} in {}", text, board.to_fen()))
+    })
+}
+
+/// One line per principal variation in SAN, scores in pawns from White's point of view.
+fn format_depth(board: &ChessBoard, depth: &AnalysisDepth) -> Vec<String> {
+    depth
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(index, (score, pv))| {
+            let white_score = if board.active_color == Color::White {
+                *score
+            } else {
+                -*score
+            };
+            let mut line_board = board.clone();
+            let mut san_moves = Vec::new();
+            for &mv in pv {
+                san_moves.push(move_to_san(&line_board, mv));
+                line_board.make_move(mv);
+            }
+            format!(
+                "Depth {} line {} score {} nodes {} time {} ms: {}",
+                depth.depth,
+                index + 1,
+                format_evaluation(white_score),
+                depth.nodes,
+                depth.elapsed.as_millis(),
+                san_moves.join(" ")
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_move() {
+        let board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        assert_eq!(parse_move(&board, "Nf3"), Ok(Move::from_algebraic("g1f3")));
+        assert_eq!(parse_move(&board, "e2e4"), Ok(Move::from_algebraic("e2e4")));
+        assert!(parse_move(&board, "e2e5").is_err());
+        assert!(parse_move(&board, "x").is_err());
+    }
+
+    #[test]
+    fn test_format_depth() {
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1").unwrap();
+        let depth = AnalysisDepth {
+            depth: 2,
+            nodes: 100,
+            elapsed: Duration::from_millis(5),
+            lines: vec![(-250, vec![Move::from_algebraic("g8f8"), Move::from_algebraic("a1a8")])],
+        };
+        assert_eq!(
+            format_depth(&board, &depth),
+            vec!["Depth 2 line 1 score +0.25 nodes 100 time 5 ms: Kf8 Ra8+"]
+        );
+    }
+}
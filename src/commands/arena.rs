@@ -0,0 +1,133 @@
+use crate::arena::adjudication::Adjudication;
+use crate::arena::sprt::Sprt;
+use crate::arena::{run_match, ArenaConfig};
+use crate::commands::Subcommand;
+use clap::{arg, Arg, ArgMatches, Command};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "arena",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("arena")
+        .about("Play a match between two UCI engines with optional SPRT termination")
+        .arg(arg!(--engine1 <COMMAND> "First engine, the Elo difference is from its point of view").required(true))
+        .arg(arg!(--engine2 <COMMAND> "Second engine").required(true))
+        .arg(arg!(--option1 <OPTION> "UCI option Name=Value of the first engine").num_args(1..))
+        .arg(arg!(--option2 <OPTION> "UCI option Name=Value of the second engine").num_args(1..))
+        .arg(
+            arg!(-g --games <N> "Maximum number of games")
+                .default_value("1000")
+                .value_parser(clap::value_parser!(u32)),
+        )
+        .arg(
+            arg!(--movetime <MS> "Time per move in milliseconds")
+                .default_value("100")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            arg!(--plies <N> "Random opening plies")
+                .default_value("4")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(--seed <N> "Seed of the random openings")
+                .default_value("1")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(arg!(--sprt "Stop the match by a sequential probability ratio test"))
+        .arg(
+            arg!(--elo0 <ELO> "SPRT H0 Elo")
+                .default_value("0")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--elo1 <ELO> "SPRT H1 Elo")
+                .default_value("10")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--alpha <P> "SPRT alpha")
+                .default_value("0.05")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--beta <P> "SPRT beta")
+                .default_value("0.05")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(arg!(--pgn <FILE> "Append the games to this PGN file").value_parser(clap::value_parser!(PathBuf)))
+        .args(adjudication_args())
+}
+
+/// The arguments of the rules that end a game early, shared with the self-play of gen-data.
+pub fn adjudication_args() -> [Arg; 6] {
+    [
+        arg!(--"resign-score" <CP> "Adjudicate a loss when both engines see a side at or below minus this score")
+            .value_parser(clap::value_parser!(i32)),
+        arg!(--"resign-moves" <N> "Moves of each side the resign score has to hold")
+            .default_value("3")
+            .value_parser(clap::value_parser!(usize)),
+        arg!(--"draw-score" <CP> "Adjudicate a draw when both engines see the score within this distance of 0")
+            .value_parser(clap::value_parser!(i32)),
+        arg!(--"draw-moves" <N> "Moves of each side the draw score has to hold")
+            .default_value("8")
+            .value_parser(clap::value_parser!(usize)),
+        arg!(--"draw-movenumber" <N> "Move number after which draws are adjudicated")
+            .default_value("40")
+            .value_parser(clap::value_parser!(u32)),
+        arg!(--"max-plies" <N> "Games longer than this are drawn")
+            .default_value("400")
+            .value_parser(clap::value_parser!(usize)),
+    ]
+}
+
+/// Reads the arguments of [`adjudication_args`].
+pub fn adjudication(arg_matches: &ArgMatches) -> Adjudication {
+    Adjudication {
+        resign_score: arg_matches.get_one::<i32>("resign-score").copied(),
+        resign_moves: *arg_matches.get_one::<usize>("resign-moves").unwrap(),
+        draw_score: arg_matches.get_one::<i32>("draw-score").copied(),
+        draw_moves: *arg_matches.get_one::<usize>("draw-moves").unwrap(),
+        draw_move_number: *arg_matches.get_one::<u32>("draw-movenumber").unwrap(),
+        max_plies: *arg_matches.get_one::<usize>("max-plies").unwrap(),
+    }
+}
+
+fn options(arg_matches: &ArgMatches, id: &str) -> Vec<String> {
+    arg_matches
+        .get_many::<String>(id)
+        .unwrap_or_default()
+        .cloned()
+        .collect()
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let sprt = arg_matches.get_flag("sprt").then(|| Sprt {
+        elo0: *arg_matches.get_one::<f64>("elo0").unwrap(),
+        elo1: *arg_matches.get_one::<f64>("elo1").unwrap(),
+        alpha: *arg_matches.get_one::<f64>("alpha").unwrap(),
+        beta: *arg_matches.get_one::<f64>("beta").unwrap(),
+    });
+    let config = ArenaConfig {
+        engines: [
+            arg_matches.get_one::<String>("engine1").unwrap().clone(),
+            arg_matches.get_one::<String>("engine2").unwrap().clone(),
+        ],
+        options: [options(arg_matches, "option1"), options(arg_matches, "option2")],
+        max_games: *arg_matches.get_one::<u32>("games").unwrap(),
+        move_time: Duration::from_millis(*arg_matches.get_one::<u64>("movetime").unwrap()),
+        random_plies: *arg_matches.get_one::<usize>("plies").unwrap(),
+        seed: *arg_matches.get_one::<u64>("seed").unwrap(),
+        sprt,
+        adjudication: adjudication(arg_matches),
+        pgn: arg_matches.get_one::<PathBuf>("pgn").cloned(),
+    };
+    if let Err(e) = run_match(&config) {
+        println!("Match aborted: {}", e);
+    }
+}
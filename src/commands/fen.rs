@@ -0,0 +1,125 @@
+use crate::chess_board::fen::{self, FenError};
+use crate::chess_board::{ChessBoard, Color};
+use crate::commands::Subcommand;
+use clap::builder::PossibleValuesParser;
+use clap::{arg, ArgMatches, Command};
+use std::io::{stdin, BufRead};
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "fen",
+    command,
+    run,
+};
+
+const OPERATIONS: [&str; 5] = ["validate", "flip", "mirror", "strip", "show"];
+
+fn command() -> Command {
+    Command::new("fen")
+        .about("Validate, flip, mirror, strip or show a position, or every FEN line of stdin without a position")
+        .arg(
+            arg!(<OPERATION> "validate lists the issues, flip changes the side to move, mirror swaps the colors, \
+                strip drops the move counters and show draws the board")
+            .value_parser(PossibleValuesParser::new(OPERATIONS)),
+        )
+        .arg(arg!([FEN] ... "The position, its fields may be passed as separate arguments"))
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let operation = arg_matches.get_one::<String>("OPERATION").unwrap();
+    match arg_matches.get_many::<String>("FEN") {
+        Some(fields) => print_result(operation, &fields.map(String::as_str).collect::<Vec<_>>().join(" ")),
+        None => {
+            for line in stdin().lock().lines().map_while(Result::ok) {
+                if !line.trim().is_empty() {
+                    print_result(operation, line.trim());
+                }
+            }
+        }
+    }
+}
+
+fn print_result(operation: &str, fen: &str) {
+    match apply(operation, fen) {
+        Ok(output) => println!("{}", output),
+        Err(e) => println!("Invalid FEN {}: {}", fen, e),
+    }
+}
+
+/// The output of the operation for one FEN. A transformed position is written as a FEN, with a note if it is no
+/// longer legal.
+fn apply(operation: &str, fen: &str) -> Result<String, FenError> {
+    if operation == "validate" {
+        let issues = fen::issues(fen);
+        return Ok(if issues.is_empty() {
+            format!("{}: valid", fen)
+        } else {
+            let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+            format!("{}: {}", fen, issues.join(", "))
+        });
+    }
+    let board = ChessBoard::from_fen(fen)?;
+    let output = match operation {
+        "flip" => {
+            let mut flipped = board.clone();
+            flipped.set_side_to_move(match board.active_color {
+                Color::White => Color::Black,
+                Color::Black => Color::White,
+            });
+            with_issues(&flipped, flipped.to_fen())
+        }
+        "mirror" => board.mirror().to_fen(),
+        "strip" => board.to_fen().split_whitespace().take(4).collect::<Vec<_>>().join(" "),
+        "show" => with_issues(
+            &board,
+            format!(
+                "{}FEN: {}\n{:?} to move",
+                board.render_to_string(),
+                board.to_fen(),
+                board.active_color
+            ),
+        ),
+        _ => unreachable!("{} is one of the operations", operation),
+    };
+    Ok(output)
+}
+
+fn with_issues(board: &ChessBoard, output: String) -> String {
+    match board.validate() {
+        Ok(()) => output,
+        Err(e) => format!("{}\nNot a legal position: {}", output, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+
+    #[test]
+    fn test_apply() {
+        let fen = "r3k2r/8/8/8/4P3/8/8/R3K2R b KQq e3 3 20";
+        assert_eq!(apply("validate", fen), Ok(format!("{}: valid", fen)));
+        assert_eq!(
+            apply("validate", "4k3/8/8/8/8/8/8/4R1K1 w K - 0 1"),
+            Ok(String::from(
+                "4k3/8/8/8/8/8/8/4R1K1 w K - 0 1: the side not to move is in check, castling right 'K' without king \
+                 and rook on their squares"
+            ))
+        );
+        assert_eq!(
+            apply("flip", INITIAL_POSITION).unwrap(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"
+        );
+        assert_eq!(
+            apply("flip", "4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap(),
+            "4k3/8/8/8/8/8/8/4R1K1 w - - 0 1\nNot a legal position: the side not to move is in check"
+        );
+        assert_eq!(apply("flip", fen).unwrap(), "r3k2r/8/8/8/4P3/8/8/R3K2R w KQq - 0 20");
+        assert_eq!(apply("mirror", fen).unwrap(), "r3k2r/8/8/4p3/8/8/8/R3K2R w Qkq - 3 20");
+        assert_eq!(apply("strip", fen).unwrap(), "r3k2r/8/8/8/4P3/8/8/R3K2R b KQq -");
+        assert!(apply("show", INITIAL_POSITION)
+            .unwrap()
+            .ends_with(&format!("FEN: {}\nWhite to move", INITIAL_POSITION)));
+        assert_eq!(apply("mirror", "8/8 w - -"), Err(FenError::WrongRankCount(2)));
+    }
+}
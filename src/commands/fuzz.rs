@@ -0,0 +1,51 @@
+use crate::chess_board::fuzz::{fuzz, START_POSITIONS};
+use crate::commands::Subcommand;
+use clap::{arg, ArgMatches, Command};
+use std::time::Instant;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "fuzz",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("fuzz")
+        .about("Check the move generation, hashes and FENs of random games against a reference implementation")
+        .arg(
+            arg!(-g --games <GAMES> "Number of random games")
+                .default_value("1000")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(-p --plies <PLIES> "Maximum length of a game")
+                .default_value("300")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(-s --seed <SEED> "Seed of the random moves")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(arg!(-f --fen <FEN> "Start position, by default a set of positions with castling, en passant and promotions"))
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let games = *arg_matches.get_one::<usize>("games").unwrap();
+    let plies = *arg_matches.get_one::<usize>("plies").unwrap();
+    let seed = *arg_matches.get_one::<u64>("seed").unwrap();
+    let start_positions: Vec<&str> = match arg_matches.get_one::<String>("fen") {
+        Some(fen) => vec![fen.as_str()],
+        None => START_POSITIONS.to_vec(),
+    };
+    let start = Instant::now();
+    match fuzz(seed, games, plies, &start_positions) {
+        Ok(positions) => println!(
+            "{} games with {} positions checked in {:.1}s, no mismatch",
+            games,
+            positions,
+            start.elapsed().as_secs_f32()
+        ),
+        Err(mismatch) => println!("Mismatch found\n{}", mismatch),
+    }
+}
@@ -0,0 +1,105 @@
+use crate::commands::arena::{adjudication, adjudication_args};
+use crate::commands::Subcommand;
+use crate::tuner::self_play::{play_game, SelfPlayConfig};
+use clap::builder::PossibleValuesParser;
+use clap::{arg, ArgMatches, Command};
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "gen-data",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("gen-data")
+        .about("Generate training positions for the tuner from fast self-play games")
+        .arg(arg!(-o --output <FILE> "Output file").required(true))
+        .arg(
+            arg!(-f --format <FORMAT> "\"<FEN> [result]\" lines for the tune subcommand or fen,score,result rows")
+                .default_value("text")
+                .value_parser(PossibleValuesParser::new(["text", "csv"])),
+        )
+        .arg(
+            arg!(-g --games <N> "Number of games")
+                .default_value("100")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(-n --nodes <N> "Nodes of the search of every move")
+                .default_value("5000")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            arg!(-r --"sample-rate" <P> "Probability that a quiet position is written")
+                .default_value("0.1")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--plies <N> "Random opening plies")
+                .default_value("8")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(--seed <N> "Seed of the random openings and the sampling")
+                .default_value("1")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .args(adjudication_args())
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let sample_rate = *arg_matches.get_one::<f64>("sample-rate").unwrap();
+    if !(0.0..=1.0).contains(&sample_rate) {
+        println!("The sample rate has to be between 0 and 1");
+        return;
+    }
+    let config = SelfPlayConfig {
+        nodes: *arg_matches.get_one::<u64>("nodes").unwrap(),
+        sample_rate,
+        random_plies: *arg_matches.get_one::<usize>("plies").unwrap(),
+        adjudication: adjudication(arg_matches),
+    };
+    let games = *arg_matches.get_one::<usize>("games").unwrap();
+    let csv = arg_matches.get_one::<String>("format").unwrap() == "csv";
+    let output = arg_matches.get_one::<String>("output").unwrap();
+    let seed = *arg_matches.get_one::<u64>("seed").unwrap();
+    if let Err(e) = generate(&config, games, seed, csv, output) {
+        println!("Could not write {}: {}", output, e);
+    }
+}
+
+fn generate(config: &SelfPlayConfig, games: usize, seed: u64, csv: bool, output: &str) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(output)?);
+    if csv {
+        writeln!(writer, "fen,score,result")?;
+    }
+    let mut rng = Pcg64::seed_from_u64(seed);
+    let mut positions = 0;
+    let start = Instant::now();
+    for game in 1..=games {
+        let played = play_game(config, &mut rng);
+        for sample in &played.samples {
+            let line = if csv { sample.to_csv() } else { sample.to_text() };
+            writeln!(writer, "{}", line)?;
+        }
+        positions += played.samples.len();
+        println!(
+            "Game {}/{}: {:.1} after {} plies, {} positions in total",
+            game, games, played.result, played.plies, positions
+        );
+    }
+    writer.flush()?;
+    println!(
+        "{} positions of {} games written to {} in {:.1}s",
+        positions,
+        games,
+        output,
+        start.elapsed().as_secs_f32()
+    );
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use crate::commands::Subcommand;
+use crate::lichess::client::{LichessClient, DEFAULT_SERVER};
+use crate::lichess::run_bot;
+use clap::{arg, ArgMatches, Command};
+
+const TOKEN_VARIABLE: &str = "LICHESS_BOT_TOKEN";
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "lichess-bot",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("lichess-bot")
+        .about("Play on Lichess through the Bot API")
+        .arg(arg!(-t --token <TOKEN> "API token of the bot account, defaults to $LICHESS_BOT_TOKEN"))
+        .arg(arg!(-s --server <URL> "Lichess server").default_value(DEFAULT_SERVER))
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let token = match arg_matches
+        .get_one::<String>("token")
+        .cloned()
+        .or_else(|| std::env::var(TOKEN_VARIABLE).ok())
+    {
+        Some(token) => token,
+        None => {
+            println!("No API token given, use --token or set {}", TOKEN_VARIABLE);
+            return;
+        }
+    };
+    let server = arg_matches.get_one::<String>("server").unwrap();
+    if let Err(e) = run_bot(LichessClient::new(server, &token)) {
+        println!("Lichess bot stopped: {}", e);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::{self, Config};
+use crate::engines::eval_params::EvalParams;
+use crate::engines::protocol_log;
+use clap::{arg, ArgMatches, Command};
+
+mod analyze;
+#[cfg(not(target_arch = "wasm32"))]
+mod arena;
+mod bench;
+mod fen;
+mod fuzz;
+#[cfg(not(target_arch = "wasm32"))]
+mod gen_data;
+#[cfg(not(target_arch = "wasm32"))]
+mod lichess_bot;
+mod perft;
+mod perft_eval;
+mod play;
+#[cfg(not(target_arch = "wasm32"))]
+mod regress;
+mod stats;
+mod trace;
+mod tune;
+mod verify_backends;
+
+/// A subcommand of the chic binary with its own argument definitions.
+pub struct Subcommand {
+    pub name: &'static str,
+    pub command: fn() -> Command,
+    pub run: fn(&ArgMatches),
+}
+
+/// All subcommands. New tools only need an entry here.
+pub const SUBCOMMANDS: &[Subcommand] = &[
+    analyze::SUBCOMMAND,
+    #[cfg(not(target_arch = "wasm32"))]
+    arena::SUBCOMMAND,
+    bench::SUBCOMMAND,
+    fen::SUBCOMMAND,
+    fuzz::SUBCOMMAND,
+    #[cfg(not(target_arch = "wasm32"))]
+    gen_data::SUBCOMMAND,
+    play::SUBCOMMAND,
+    #[cfg(not(target_arch = "wasm32"))]
+    regress::SUBCOMMAND,
+    Subcommand {
+        name: "uci",
+        command: || {
+            Command::new("uci")
+                .about("Run in CLI mode")
+                .arg(arg!(--"eval-file" <FILE> "JSON file with the evaluation parameters"))
+                .arg(arg!(--log <FILE> "Records the protocol transcript and search events in this file"))
+                .args(config::args())
+        },
+        run: run_uci,
+    },
+    perft::SUBCOMMAND,
+    perft_eval::SUBCOMMAND,
+    stats::SUBCOMMAND,
+    trace::SUBCOMMAND,
+    tune::SUBCOMMAND,
+    verify_backends::SUBCOMMAND,
+    #[cfg(not(target_arch = "wasm32"))]
+    lichess_bot::SUBCOMMAND,
+];
+
+/// Adds all registered subcommands to the given root command.
+pub fn register_subcommands(mut command: Command) -> Command {
+    for subcommand in SUBCOMMANDS {
+        command = command.subcommand((subcommand.command)());
+    }
+    command
+}
+
+fn run_uci(arg_matches: &ArgMatches) {
+    let eval_params = match arg_matches.get_one::<String>("eval-file") {
+        Some(path) => match EvalParams::from_file(path) {
+            Ok(params) => params,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        },
+        None => EvalParams::default(),
+    };
+    let config = Config::from_matches_or_default(arg_matches);
+    if let Some(path) = arg_matches.get_one::<String>("log") {
+        if let Err(e) = protocol_log::open(path) {
+            println!("{}", e);
+            return;
+        }
+    }
+    crate::engines::uci::run_uci_interface(eval_params, &config.engine);
+}
+
+/// Runs the registered subcommand selected on the command line. Returns false if no subcommand was given.
+pub fn run_subcommand(matches: &ArgMatches) -> bool {
+    match matches.subcommand() {
+        Some((name, arg_matches)) => {
+            let subcommand = SUBCOMMANDS
+                .iter()
+                .find(|s| s.name == name)
+                .unwrap_or_else(|| unreachable!("Exhausted list of subcommands"));
+            (subcommand.run)(arg_matches);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subcommand_names_match_commands() {
+        for subcommand in SUBCOMMANDS {
+            assert_eq!((subcommand.command)().get_name(), subcommand.name);
+        }
+        register_subcommands(Command::new("chic")).debug_assert();
+    }
+}
@@ -0,0 +1,138 @@
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::chess_board::perft::{perft_divide, PerftCache, PerftProgress};
+use crate::chess_board::{ChessBoard, Move};
+use crate::commands::Subcommand;
+use clap::{arg, ArgMatches, Command};
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Time between two progress lines of a count.
+const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "perft",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("perft")
+        .about("Run Perft test")
+        .arg(
+            arg!(
+            -f --fen <FEN> "Board position"
+                    )
+            .default_value(INITIAL_POSITION),
+        )
+        .arg(
+            arg!(
+            -x --depth <d> "depth"
+                    )
+            .default_value("3")
+            .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(
+            -m --moves <moves> "List of moves"
+                    )
+            .num_args(1..)
+            .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            arg!(-t --threads <THREADS> "Threads the root moves are split over")
+                .default_value("1")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(--hash <MB> "Size of the cache counting transpositions once, none by default")
+                .value_parser(clap::value_parser!(usize)),
+        )
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let fen = arg_matches.get_one::<String>("fen").unwrap();
+    let depth = arg_matches.get_one::<usize>("depth").unwrap();
+    let moves = arg_matches
+        .get_many::<String>("moves")
+        .unwrap_or_default()
+        .filter(|&v| !v.is_empty())
+        .collect::<Vec<_>>();
+    let threads = *arg_matches.get_one::<usize>("threads").unwrap();
+    let cache = arg_matches
+        .get_one::<usize>("hash")
+        .map(|&size_mb| PerftCache::new(size_mb));
+    perft(fen.clone(), moves, (*depth) as u8, threads, cache.as_ref());
+}
+
+fn perft(fen: String, moves: Vec<&String>, depth: u8, threads: usize, cache: Option<&PerftCache>) {
+    println!("Perft test for {} moves {:?} with depth {}", fen, moves, depth);
+    let mut chess_board = ChessBoard::from_fen(&fen).unwrap();
+    for m in moves {
+        let legal_move = chess_board.generate_legal_moves();
+        if legal_move.contains(&Move::from_algebraic(m)) {
+            chess_board.make_move(Move::from_algebraic(m));
+        } else {
+            panic!("Invalid move: {}", m);
+        }
+    }
+
+    catch_interrupt();
+    let last_report = Mutex::new(Instant::now());
+    let report = |progress: &PerftProgress| {
+        let mut last_report = last_report.lock().unwrap();
+        if last_report.elapsed() >= PROGRESS_REPORT_INTERVAL {
+            *last_report = Instant::now();
+            println!(
+                "{} nodes, {:.0} nodes/s, {} of {} root moves counted",
+                progress.nodes,
+                progress.nodes as f64 / progress.elapsed.as_secs_f64(),
+                progress.root_moves_done,
+                progress.root_moves
+            );
+        }
+        !INTERRUPTED.load(Relaxed)
+    };
+    let start = Instant::now();
+    let root_moves = chess_board.generate_legal_moves().len();
+    let mut result_moves = perft_divide(&chess_board, depth, threads, cache, &report)
+        .into_iter()
+        .map(|(mv, node_count)| (mv.as_algebraic(), node_count))
+        .collect::<Vec<_>>();
+    result_moves.sort();
+    let counted = result_moves.len();
+
+    let mut num_nodes = 0;
+    for (m, c) in result_moves {
+        println!("{}: {}", m, c);
+        num_nodes += c;
+    }
+    if depth > 0 && counted < root_moves {
+        println!("\nInterrupted, {} of {} root moves counted", counted, root_moves);
+    }
+    println!("\nNodes searched: {}", num_nodes);
+    println!("Time: {} ms", start.elapsed().as_millis());
+}
+
+/// Set by Ctrl-C, the count stops and prints the root moves counted so far.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// A second Ctrl-C ends the program as usual.
+#[cfg(unix)]
+fn catch_interrupt() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Relaxed);
+        // SAFETY: signal is async-signal-safe
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+    }
+    // SAFETY: the handler only stores to an atomic and restores the default handler
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn catch_interrupt() {}
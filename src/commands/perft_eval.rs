@@ -0,0 +1,58 @@
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::chess_board::ChessBoard;
+use crate::commands::Subcommand;
+use crate::engines::eval_params::EvalParams;
+use crate::engines::evaluation::TunedEvaluator;
+use crate::engines::perft_eval::perft_eval;
+use clap::{arg, ArgMatches, Command};
+use std::time::Instant;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "perft-eval",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("perft-eval")
+        .about("Check the hash, the evaluation and its symmetry in every position of the game tree")
+        .arg(arg!(-f --fen <FEN> "Board position").default_value(INITIAL_POSITION))
+        .arg(
+            arg!(-x --depth <DEPTH> "Depth of the game tree")
+                .default_value("3")
+                .value_parser(clap::value_parser!(u8)),
+        )
+        .arg(arg!(--"eval-file" <FILE> "JSON file with the evaluation parameters"))
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let fen = arg_matches.get_one::<String>("fen").unwrap();
+    let depth = *arg_matches.get_one::<u8>("depth").unwrap();
+    let params = match arg_matches.get_one::<String>("eval-file") {
+        Some(path) => match EvalParams::from_file(path) {
+            Ok(params) => params,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        },
+        None => EvalParams::default(),
+    };
+    let mut board = match ChessBoard::from_fen(fen) {
+        Ok(board) => board,
+        Err(e) => {
+            println!("Invalid FEN {}: {}", fen, e);
+            return;
+        }
+    };
+    let start = Instant::now();
+    match perft_eval(&mut board, depth, &TunedEvaluator::new(params)) {
+        Ok(positions) => println!(
+            "{} positions to depth {} checked in {:.1}s, no mismatch",
+            positions,
+            depth,
+            start.elapsed().as_secs_f32()
+        ),
+        Err(mismatch) => println!("Mismatch found\n{}", mismatch),
+    }
+}
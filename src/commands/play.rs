@@ -0,0 +1,216 @@
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::chess_board::game::Game;
+use crate::chess_board::pgn::{game_result, move_to_san, san_to_move};
+use crate::chess_board::render::{BoardSize, RenderOptions};
+use crate::chess_board::Color;
+use crate::commands::Subcommand;
+use crate::config::{self, Config};
+use crate::engines::engine_alpha_beta::AlphaBetaEngine;
+use crate::engines::preset::Preset;
+use crate::engines::ChessEngine;
+use clap::{arg, ArgMatches, Command};
+use std::io::{self, stdin, stdout, BufRead, Write};
+use std::time::Duration;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "play",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("play")
+        .about("Play a game")
+        .arg(arg!(--cli "Play in the terminal instead of the user interface"))
+        .arg(arg!(-f --fen <FEN> "Start position of a game in the terminal").default_value(INITIAL_POSITION))
+        .arg(
+            arg!(-c --color <COLOR> "Color of the player in the terminal")
+                .value_parser(["white", "black"])
+                .default_value("white"),
+        )
+        .arg(
+            arg!(-t --movetime <MS> "Thinking time of the engine per move in milliseconds")
+                .default_value("1000")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(arg!(-x --depth <DEPTH> "Maximum search depth of the engine").value_parser(clap::value_parser!(usize)))
+        .arg(
+            arg!(--preset <PRESET> "Search preset of the engine in the terminal, the user interface takes engine.preset")
+                .value_parser(Preset::NAMES)
+                .default_value("Default"),
+        )
+        .arg(arg!(--unicode "Draw the pieces of the board in the terminal as chess glyphs"))
+        .arg(arg!(--colors "Draw the light and dark squares of the board in the terminal in color"))
+        .arg(
+            arg!(--size <SIZE> "Size of the board in the terminal")
+                .value_parser(BoardSize::NAMES)
+                .default_value("standard"),
+        )
+        .args(config::args())
+}
+
+/// The settings of a game in the terminal.
+struct TerminalGame {
+    player: Color,
+    move_time: Duration,
+    max_depth: Option<usize>,
+    preset: Preset,
+    render: RenderOptions,
+}
+
+fn run(arg_matches: &ArgMatches) {
+    if !arg_matches.get_flag("cli") {
+        chic::play_with_config(&Config::from_matches_or_default(arg_matches));
+        return;
+    }
+    let fen = arg_matches.get_one::<String>("fen").unwrap();
+    let game = match Game::new(fen) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("Invalid FEN {}: {}", fen, e);
+            return;
+        }
+    };
+    let config = TerminalGame {
+        player: match arg_matches.get_one::<String>("color").unwrap().as_str() {
+            "black" => Color::Black,
+            _ => Color::White,
+        },
+        move_time: Duration::from_millis(*arg_matches.get_one::<u64>("movetime").unwrap()),
+        max_depth: arg_matches.get_one::<usize>("depth").copied(),
+        preset: Preset::from_name(arg_matches.get_one::<String>("preset").unwrap()).unwrap(),
+        render: RenderOptions {
+            unicode: arg_matches.get_flag("unicode"),
+            colors: arg_matches.get_flag("colors"),
+            size: BoardSize::from_name(arg_matches.get_one::<String>("size").unwrap()).unwrap_or_default(),
+            footer: true,
+        },
+    };
+    let game = match play_in_terminal(game, &config, stdin().lock(), &mut stdout()) {
+        Ok(game) => game,
+        Err(e) => {
+            println!("Game aborted: {}", e);
+            return;
+        }
+    };
+    println!("Result: {}", game.result);
+    match game.to_pgn() {
+        Ok(pgn) => println!("\n{}", pgn),
+        Err(e) => println!("No PGN: {}", e),
+    }
+}
+
+/// Plays the game until it is decided or the player resigns, the player's moves are read from input in SAN or
+/// coordinate notation. The end of the input leaves the game unfinished.
+fn play_in_terminal(
+    mut game: Game,
+    config: &TerminalGame,
+    input: impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<Game> {
+    let mut board = game.final_position().map_err(io::Error::other)?;
+    let mut engine = AlphaBetaEngine::with_board(board.clone());
+    if let Some(depth) = config.max_depth {
+        engine.set_max_depth(depth);
+    }
+    engine.set_preset(config.preset);
+    if config.preset != Preset::Default {
+        engine.set_hash_size(config.preset.hash_mb());
+    }
+    let (white, black) = match config.player {
+        Color::White => ("Player", engine.name()),
+        Color::Black => (engine.name(), "Player"),
+    };
+    game.set_tag("White", white);
+    game.set_tag("Black", black);
+
+    let mut lines = input.lines();
+    loop {
+        writeln!(output, "{}", board.render(&config.render))?;
+        if game_result(&board) != "*" {
+            break;
+        }
+        let mv = if board.active_color == config.player {
+            write!(output, "Your move: ")?;
+            output.flush()?;
+            let Some(line) = lines.next() else {
+                writeln!(output)?;
+                return Ok(game);
+            };
+            match line?.trim() {
+                "" => continue,
+                "resign" | "quit" => {
+                    game.result = String::from(match config.player {
+                        Color::White => "0-1",
+                        Color::Black => "1-0",
+                    });
+                    return Ok(game);
+                }
+                text => match san_to_move(&board, text) {
+                    Ok(mv) => mv,
+                    Err(e) => {
+                        writeln!(output, "{}", e)?;
+                        continue;
+                    }
+                },
+            }
+        } else {
+            let Some((pv, _, _, _)) = engine.find_best_move_iterative(config.move_time, &mut |_| {}) else {
+                break;
+            };
+            writeln!(output, "{} plays {}", engine.name(), move_to_san(&board, pv[0]))?;
+            pv[0]
+        };
+        engine.make_move(&mv.as_algebraic()).map_err(io::Error::other)?;
+        board.make_move(mv);
+        game.push_move(mv);
+    }
+    game.update_result().map_err(io::Error::other)?;
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(fen: &str, player: Color, input: &str) -> (Game, String) {
+        let config = TerminalGame {
+            player,
+            move_time: Duration::from_secs(60),
+            max_depth: Some(2),
+            preset: Preset::Default,
+            render: RenderOptions {
+                size: BoardSize::Compact,
+                footer: true,
+                ..Default::default()
+            },
+        };
+        let mut output = Vec::new();
+        let game = play_in_terminal(Game::new(fen).unwrap(), &config, input.as_bytes(), &mut output).unwrap();
+        (game, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_play_in_terminal() {
+        // Invalid input is reported and asked again
+        let (game, output) = play("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", Color::White, "Rb9\nRa8\n");
+        assert!(output.contains("Your move: "));
+        assert!(output.contains("1 R . . . . . K . 1\n  a b c d e f g h\nWhite to move, castling -, en passant -\n"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.get_tag("White"), Some("Player"));
+        assert!(game.to_pgn().unwrap().contains("1. Ra8# 1-0"));
+
+        // The engine mates, the move is written in SAN
+        let (game, output) = play("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", Color::Black, "");
+        assert!(output.contains("plays Ra8#"));
+        assert_eq!(game.result, "1-0");
+
+        let (game, _) = play(INITIAL_POSITION, Color::White, "e2e4\nresign\n");
+        assert_eq!(game.moves.len(), 2);
+        assert_eq!(game.result, "0-1");
+
+        // The game stays unfinished at the end of the input
+        let (game, _) = play(INITIAL_POSITION, Color::White, "");
+        assert_eq!(game.result, "*");
+    }
+}
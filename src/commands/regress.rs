@@ -0,0 +1,255 @@
+use crate::commands::bench::BENCH_POSITIONS;
+use crate::commands::Subcommand;
+use crate::engines::uci_client::{SearchLimit, UciClient};
+use clap::{arg, ArgMatches, Command};
+use std::time::{Duration, Instant};
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "regress",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("regress")
+        .about(
+            "Compare time to depth, nodes and best moves of this build with a baseline engine on the bench positions",
+        )
+        .arg(
+            arg!(--baseline <COMMAND> "UCI engine to compare with, e.g. the chic-engine of an older build")
+                .required(true),
+        )
+        .arg(arg!(--current <COMMAND> "UCI engine under test, by default this binary in UCI mode"))
+        .arg(arg!(--option <OPTION> "UCI option Name=Value of both engines").num_args(1..))
+        .arg(
+            arg!(-x --depth <DEPTH> "Search depth")
+                .default_value("4")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+        .arg(
+            arg!(--margin <PERCENT> "Changes of the total time to depth within this margin count as noise")
+                .default_value("5")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(--runs <N> "Searches of every position, the fastest counts")
+                .default_value("1")
+                .value_parser(clap::value_parser!(u32).range(1..)),
+        )
+}
+
+/// The search of a position by one engine.
+#[derive(Debug, Clone, PartialEq)]
+struct Search {
+    best_move: String,
+    nodes: u64,
+    time: Duration,
+}
+
+#[derive(Tabled)]
+struct RegressRow {
+    position: usize,
+    baseline_move: String,
+    current_move: String,
+    agree: &'static str,
+    baseline_ms: u128,
+    current_ms: u128,
+    time: String,
+    baseline_nodes: u64,
+    current_nodes: u64,
+}
+
+/// The totals over all positions.
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    positions: usize,
+    agreements: usize,
+    baseline_time: Duration,
+    current_time: Duration,
+    baseline_nodes: u64,
+    current_nodes: u64,
+}
+
+impl Summary {
+    fn new(searches: &[(Search, Search)]) -> Summary {
+        let mut summary = Summary {
+            positions: searches.len(),
+            ..Summary::default()
+        };
+        for (baseline, current) in searches {
+            summary.agreements += usize::from(baseline.best_move == current.best_move);
+            summary.baseline_time += baseline.time;
+            summary.current_time += current.time;
+            summary.baseline_nodes += baseline.nodes;
+            summary.current_nodes += current.nodes;
+        }
+        summary
+    }
+
+    /// Whether the total time to depth changed by more than the margin, in percent.
+    fn verdict(&self, margin: f64) -> &'static str {
+        let ratio = self.current_time.as_secs_f64() / self.baseline_time.as_secs_f64().max(f64::EPSILON);
+        if ratio > 1.0 + margin / 100.0 {
+            "slower than the baseline"
+        } else if ratio < 1.0 - margin / 100.0 {
+            "faster than the baseline"
+        } else {
+            "no change beyond the noise"
+        }
+    }
+}
+
+/// The relative change from before to after, "+5.0%".
+fn change(before: f64, after: f64) -> String {
+    if before == 0.0 {
+        return String::from("-");
+    }
+    format!("{:+.1}%", (after / before - 1.0) * 100.0)
+}
+
+fn nodes_per_second(nodes: u64, time: Duration) -> u64 {
+    (nodes as f64 / time.as_secs_f64().max(f64::EPSILON)) as u64
+}
+
+fn start_engine(command: &str, options: &[String]) -> Result<UciClient, String> {
+    let mut engine = UciClient::start(command)?;
+    engine.set_options(options)?;
+    Ok(engine)
+}
+
+/// Searches the position to the depth, the fastest of the runs counts. The node count is the highest the engine
+/// reported in an info line, engines do not send one for every depth.
+fn search(engine: &mut UciClient, fen: &str, depth: u32, runs: u32) -> Result<Search, String> {
+    let mut fastest: Option<Search> = None;
+    for _ in 0..runs {
+        // A new game clears the hash, no search profits from the one before
+        engine.new_game()?;
+        let start = Instant::now();
+        let mut nodes = 0;
+        let best_move = engine.search(fen, &[], SearchLimit::Depth(depth), |info| {
+            nodes = nodes.max(info.nodes.unwrap_or_default());
+        })?;
+        let search = Search {
+            time: start.elapsed(),
+            nodes,
+            best_move: best_move.mv,
+        };
+        if fastest.as_ref().is_none_or(|fastest| search.time < fastest.time) {
+            fastest = Some(search);
+        }
+    }
+    fastest.ok_or_else(|| String::from("No search run"))
+}
+
+fn run(arg_matches: &ArgMatches) {
+    if let Err(e) = regress(arg_matches) {
+        println!("{}", e);
+    }
+}
+
+fn regress(arg_matches: &ArgMatches) -> Result<(), String> {
+    let depth = *arg_matches.get_one::<u32>("depth").unwrap();
+    let runs = *arg_matches.get_one::<u32>("runs").unwrap();
+    let margin = *arg_matches.get_one::<f64>("margin").unwrap();
+    let options: Vec<String> = arg_matches
+        .get_many::<String>("option")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+    let current_command = match arg_matches.get_one::<String>("current") {
+        Some(command) => command.clone(),
+        None => {
+            let exe = std::env::current_exe().map_err(|e| format!("Could not find this binary: {}", e))?;
+            format!("{} uci", exe.display())
+        }
+    };
+    let mut baseline = start_engine(arg_matches.get_one::<String>("baseline").unwrap(), &options)?;
+    let mut current = start_engine(&current_command, &options)?;
+    println!(
+        "{} against the baseline {} at depth {}",
+        current.name, baseline.name, depth
+    );
+
+    let mut searches = Vec::new();
+    let mut rows = Vec::new();
+    for (index, fen) in BENCH_POSITIONS.iter().enumerate() {
+        let baseline_search = search(&mut baseline, fen, depth, runs)?;
+        let current_search = search(&mut current, fen, depth, runs)?;
+        rows.push(RegressRow {
+            position: index + 1,
+            baseline_move: baseline_search.best_move.clone(),
+            current_move: current_search.best_move.clone(),
+            agree: if baseline_search.best_move == current_search.best_move {
+                "yes"
+            } else {
+                "no"
+            },
+            baseline_ms: baseline_search.time.as_millis(),
+            current_ms: current_search.time.as_millis(),
+            time: change(baseline_search.time.as_secs_f64(), current_search.time.as_secs_f64()),
+            baseline_nodes: baseline_search.nodes,
+            current_nodes: current_search.nodes,
+        });
+        searches.push((baseline_search, current_search));
+    }
+
+    let summary = Summary::new(&searches);
+    println!("{}", Table::new(rows).with(Style::modern()));
+    println!("Moves agreeing  : {}/{}", summary.agreements, summary.positions);
+    println!(
+        "Time to depth   : {} ms -> {} ms ({})",
+        summary.baseline_time.as_millis(),
+        summary.current_time.as_millis(),
+        change(summary.baseline_time.as_secs_f64(), summary.current_time.as_secs_f64())
+    );
+    println!(
+        "Nodes searched  : {} -> {} ({})",
+        summary.baseline_nodes,
+        summary.current_nodes,
+        change(summary.baseline_nodes as f64, summary.current_nodes as f64)
+    );
+    println!(
+        "Nodes/second    : {} -> {}",
+        nodes_per_second(summary.baseline_nodes, summary.baseline_time),
+        nodes_per_second(summary.current_nodes, summary.current_time)
+    );
+    println!("Verdict         : {}", summary.verdict(margin));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(best_move: &str, nodes: u64, millis: u64) -> Search {
+        Search {
+            best_move: best_move.to_string(),
+            nodes,
+            time: Duration::from_millis(millis),
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::new(&[
+            (search("e2e4", 1000, 100), search("e2e4", 900, 80)),
+            (search("d7d5", 2000, 300), search("g8f6", 2100, 240)),
+        ]);
+        assert_eq!(summary.positions, 2);
+        assert_eq!(summary.agreements, 1);
+        assert_eq!(summary.baseline_time, Duration::from_millis(400));
+        assert_eq!(summary.current_nodes, 3000);
+        assert_eq!(summary.verdict(5.0), "faster than the baseline");
+        assert_eq!(summary.verdict(25.0), "no change beyond the noise");
+        assert_eq!(
+            Summary::new(&[(search("e2e4", 10, 100), search("e2e4", 10, 106))]).verdict(5.0),
+            "slower than the baseline"
+        );
+
+        assert_eq!(change(400.0, 320.0), "-20.0%");
+        assert_eq!(change(3000.0, 3000.0), "+0.0%");
+        assert_eq!(change(0.0, 10.0), "-");
+        assert_eq!(nodes_per_second(5000, Duration::from_millis(500)), 10_000);
+    }
+}
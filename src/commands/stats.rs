@@ -0,0 +1,60 @@
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::commands::Subcommand;
+use crate::engines::engine_alpha_beta::AlphaBetaEngine;
+use crate::engines::{ChessEngine, InfoEvent};
+use clap::{arg, ArgMatches, Command};
+use std::time::Duration;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "stats",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("stats")
+        .about("Search a position and print search statistics for tuning move ordering and pruning")
+        .arg(arg!(-f --fen <FEN> "Board position").default_value(INITIAL_POSITION))
+        .arg(
+            arg!(-x --depth <DEPTH> "Search depth")
+                .default_value("5")
+                .value_parser(clap::value_parser!(usize)),
+        )
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let fen = arg_matches.get_one::<String>("fen").unwrap();
+    let depth = *arg_matches.get_one::<usize>("depth").unwrap();
+
+    let mut engine = AlphaBetaEngine::new();
+    if let Err(e) = engine.set_position(fen) {
+        println!("Invalid FEN {}: {}", fen, e);
+        return;
+    }
+    engine.set_max_depth(depth);
+    engine.set_collect_stats(true);
+    let result = engine.find_best_move_iterative(Duration::from_secs(60 * 60), &mut |event| {
+        if let InfoEvent::DepthCompleted {
+            depth,
+            seldepth,
+            score,
+            nodes,
+            elapsed,
+            pv,
+        } = event
+        {
+            println!(
+                "Depth {}/{}: score {} nodes {} time {:?} pv {}",
+                depth, seldepth, score, nodes, elapsed, pv
+            )
+        }
+    });
+    if result.is_none() {
+        println!("No legal move in {}", fen);
+    }
+    if let Some(stats) = engine.search_stats() {
+        for line in stats.summary() {
+            println!("{}", line);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use crate::commands::Subcommand;
+use crate::engines::search_trace::{self, NodeKind, TraceNode, TraceRecord};
+use crate::engines::transposition_table::Bound;
+use clap::{arg, ArgMatches, Command};
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "trace",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("trace")
+        .about("Summarize a search trace written with the UCI options TraceFile and TraceDepth")
+        .arg(arg!(<FILE> "Trace file"))
+        .arg(
+            arg!(-l --line <MOVES> "Show the nodes below this line of moves, the root moves by default")
+                .default_value(""),
+        )
+        .arg(
+            arg!(-i --iteration <DEPTH> "Iteration to show, the last completed one by default")
+                .value_parser(clap::value_parser!(i32)),
+        )
+}
+
+#[derive(Tabled)]
+struct IterationRow {
+    depth: i32,
+    score: i32,
+    nodes: u64,
+    pv: String,
+}
+
+#[derive(Tabled)]
+struct NodeRow {
+    #[tabled(rename = "move")]
+    mv: String,
+    depth: i32,
+    window: String,
+    score: i32,
+    bound: String,
+    best: String,
+    kind: String,
+    moves: usize,
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let path = arg_matches.get_one::<String>("FILE").unwrap();
+    let line = arg_matches.get_one::<String>("line").unwrap();
+    let records = match search_trace::read_trace(path) {
+        Ok(records) => records,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    let mut iterations = Vec::new();
+    let mut nodes = Vec::new();
+    for record in &records {
+        match record {
+            TraceRecord::Start { fen, min_depth } => {
+                println!("Position {}, nodes of depth {} and more", fen, min_depth)
+            }
+            TraceRecord::Node(node) => nodes.push(node),
+            TraceRecord::Iteration {
+                depth,
+                score,
+                nodes,
+                pv,
+            } => iterations.push(IterationRow {
+                depth: *depth,
+                score: *score,
+                nodes: *nodes,
+                pv: pv.clone(),
+            }),
+        }
+    }
+    println!("{}", Table::new(&iterations).with(Style::modern()));
+
+    let count = |kind: NodeKind, bound: Bound| {
+        nodes
+            .iter()
+            .filter(|node| node.kind == kind && node.bound == bound)
+            .count()
+    };
+    println!(
+        "Nodes searched : {} exact, {} fail high, {} fail low",
+        count(NodeKind::Searched, Bound::Exact),
+        count(NodeKind::Searched, Bound::Lower),
+        count(NodeKind::Searched, Bound::Upper)
+    );
+    println!(
+        "Hash cutoffs   : {} exact, {} fail high, {} fail low",
+        count(NodeKind::HashCutoff, Bound::Exact),
+        count(NodeKind::HashCutoff, Bound::Lower),
+        count(NodeKind::HashCutoff, Bound::Upper)
+    );
+    println!("Multicuts      : {}", count(NodeKind::Multicut, Bound::Lower));
+
+    // The last completed iteration, the nodes of an aborted one are incomplete
+    let last_iteration = iterations
+        .last()
+        .map(|row| row.depth)
+        .or_else(|| nodes.iter().map(|node| node.iteration).max());
+    let Some(iteration) = arg_matches.get_one::<i32>("iteration").copied().or(last_iteration) else {
+        return;
+    };
+    let children = search_trace::children(&records, iteration, line);
+    if children.is_empty() {
+        println!("No nodes below \"{}\" in iteration {}", line, iteration);
+        return;
+    }
+    println!(
+        "Iteration {}, below \"{}\", scores for the side to move after the line",
+        iteration, line
+    );
+    let rows: Vec<NodeRow> = children.into_iter().map(node_row).collect();
+    println!("{}", Table::new(rows).with(Style::modern()));
+}
+
+/// The node as seen from its parent: window and score are negated.
+fn node_row(node: &TraceNode) -> NodeRow {
+    let bound = match node.bound {
+        Bound::Exact => "exact",
+        // A fail high of the child is a fail low of the parent
+        Bound::Lower => "upper",
+        Bound::Upper => "lower",
+    };
+    NodeRow {
+        mv: node.line.split_whitespace().last().unwrap_or_default().to_string(),
+        depth: node.depth,
+        window: format!("[{}, {}]", bound_string(-node.beta), bound_string(-node.alpha)),
+        score: -node.score,
+        bound: bound.to_string(),
+        best: node.best_move.clone().unwrap_or_else(|| String::from("-")),
+        kind: format!("{:?}", node.kind),
+        moves: node.moves,
+    }
+}
+
+fn bound_string(score: i32) -> String {
+    match score {
+        i32::MIN..=-1_000_000_000 => String::from("-inf"),
+        1_000_000_000..=i32::MAX => String::from("inf"),
+        _ => score.to_string(),
+    }
+}
@@ -0,0 +1,72 @@
+use crate::commands::Subcommand;
+use crate::engines::eval_params::DEFAULT_EVAL_PARAMS;
+use crate::tuner::{parse_training_line, tune};
+use clap::{arg, ArgMatches, Command};
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "tune",
+    command,
+    run,
+};
+
+fn command() -> Command {
+    Command::new("tune")
+        .about("Tune the evaluation weights on positions with known game results (Texel tuning)")
+        .arg(arg!(-d --data <FILE> "File with one \"<FEN> [result]\" per line").required(true))
+        .arg(
+            arg!(-i --iterations <N> "Gradient descent iterations")
+                .default_value("500")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(-l --"learning-rate" <RATE> "Step size in 1/1000 pawn")
+                .default_value("5")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            arg!(-o --output <FILE> "Output file, JSON if it ends in .json, Rust source otherwise")
+                .default_value("tuned_eval_params.rs"),
+        )
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let data = arg_matches.get_one::<String>("data").unwrap();
+    let content = match std::fs::read_to_string(data) {
+        Ok(content) => content,
+        Err(e) => {
+            println!("Could not read {}: {}", data, e);
+            return;
+        }
+    };
+    let mut positions = Vec::new();
+    for (number, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        match parse_training_line(line) {
+            Ok(position) => positions.push(position),
+            Err(e) => println!("Skipping line {}: {}", number + 1, e),
+        }
+    }
+    if positions.is_empty() {
+        println!("No training positions in {}", data);
+        return;
+    }
+    println!("Tuning on {} positions", positions.len());
+
+    let tuned = tune(
+        &DEFAULT_EVAL_PARAMS,
+        &positions,
+        *arg_matches.get_one::<usize>("iterations").unwrap(),
+        *arg_matches.get_one::<f64>("learning-rate").unwrap(),
+        &mut |iteration, error| println!("Iteration {}: error {:.6}", iteration, error),
+    );
+
+    let output = arg_matches.get_one::<String>("output").unwrap();
+    let content = if output.ends_with(".json") {
+        tuned.to_json()
+    } else {
+        tuned.to_rust_source("TUNED_EVAL_PARAMS")
+    };
+    match std::fs::write(output, content) {
+        Ok(()) => println!("Tuned weights written to {}", output),
+        Err(e) => println!("Could not write {}: {}", output, e),
+    }
+}
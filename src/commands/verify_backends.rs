@@ -0,0 +1,91 @@
+use crate::chess_board::fuzz::START_POSITIONS;
+use crate::chess_board::ChessBoard;
+use crate::commands::Subcommand;
+use crate::engines::board::Board;
+use crate::engines::verify_backends::{verify_games, verify_perft};
+use clap::{arg, ArgMatches, Command};
+use std::time::Instant;
+
+pub const SUBCOMMAND: Subcommand = Subcommand {
+    name: "verify-backends",
+    command,
+    run,
+};
+
+/// The board implementations that can be verified against the mailbox board, the reference.
+const BACKENDS: [&str; 1] = ["mailbox"];
+
+fn command() -> Command {
+    Command::new("verify-backends")
+        .about("Run a board backend in lockstep with the mailbox board through random games and perft trees")
+        .arg(
+            arg!(-b --backend <BACKEND> "Board backend checked against the mailbox board")
+                .default_value(BACKENDS[0])
+                .value_parser(BACKENDS),
+        )
+        .arg(
+            arg!(-g --games <GAMES> "Number of random games")
+                .default_value("200")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(-p --plies <PLIES> "Maximum length of a game")
+                .default_value("300")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            arg!(-s --seed <SEED> "Seed of the random moves")
+                .default_value("0")
+                .value_parser(clap::value_parser!(u64)),
+        )
+        .arg(
+            arg!(-x --depth <DEPTH> "Depth of the perft trees")
+                .default_value("3")
+                .value_parser(clap::value_parser!(u8)),
+        )
+        .arg(arg!(-f --fen <FEN> "Start position, by default a set of positions with castling, en passant and promotions"))
+}
+
+fn run(arg_matches: &ArgMatches) {
+    let backend = arg_matches.get_one::<String>("backend").unwrap();
+    match backend.as_str() {
+        "mailbox" => verify::<ChessBoard>(arg_matches),
+        _ => unreachable!("Backends are checked by clap"),
+    }
+}
+
+fn verify<B: Board>(arg_matches: &ArgMatches) {
+    let games = *arg_matches.get_one::<usize>("games").unwrap();
+    let plies = *arg_matches.get_one::<usize>("plies").unwrap();
+    let seed = *arg_matches.get_one::<u64>("seed").unwrap();
+    let depth = *arg_matches.get_one::<u8>("depth").unwrap();
+    let start_positions: Vec<&str> = match arg_matches.get_one::<String>("fen") {
+        Some(fen) => vec![fen.as_str()],
+        None => START_POSITIONS.to_vec(),
+    };
+    let start = Instant::now();
+    let mut nodes = 0;
+    for fen in &start_positions {
+        match verify_perft::<ChessBoard, B>(fen, depth) {
+            Ok(positions) => nodes += positions,
+            Err(mismatch) => {
+                println!("Mismatch in the perft tree\n{}", mismatch);
+                std::process::exit(1);
+            }
+        }
+    }
+    match verify_games::<ChessBoard, B>(seed, games, plies, &start_positions) {
+        Ok(positions) => println!(
+            "{} perft nodes to depth {} and {} positions of {} games identical in {:.1}s",
+            nodes,
+            depth,
+            positions,
+            games,
+            start.elapsed().as_secs_f32()
+        ),
+        Err(mismatch) => {
+            println!("Mismatch in a random game\n{}", mismatch);
+            std::process::exit(1);
+        }
+    }
+}
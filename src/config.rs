@@ -0,0 +1,262 @@
+//! The user configuration shared by the user interface and the UCI engine, read from a TOML file:
+//!
+//! ```toml
+//! [ui]
+//! piece_set = "Letters"
+//! light_squares = "#ECDAB9"
+//! dark_squares = "#AE8A68"
+//! clock_minutes = 5
+//! clock_increment = 3
+//! external_engine = "stockfish"
+//! external_options = ["Threads=2", "Skill Level=10"]
+//!
+//! [engine]
+//! hash = 64
+//! threads = 2
+//! elo = 1800
+//! eval_file = "params.txt"
+//! preset = "Strong"
+//! ```
+use crate::engines::preset::Preset;
+use crate::engines::transposition_table::DEFAULT_HASH_MB;
+use clap::{arg, Arg, ArgAction, ArgMatches};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Preferences of the user interface. Unknown piece sets fall back to the classic one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    pub piece_set: String,
+    /// Colors of the squares as "#rrggbb".
+    pub light_squares: String,
+    pub dark_squares: String,
+    /// Time control the clock is started with.
+    pub clock_minutes: f32,
+    pub clock_increment: u64,
+    /// Command line of a UCI engine offered to play and analyze instead of chic, with its "Name=Value" options.
+    pub external_engine: Option<String>,
+    pub external_options: Vec<String>,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        UiConfig {
+            piece_set: String::from("Classic"),
+            light_squares: String::from("#ECDAB9"),
+            dark_squares: String::from("#AE8A68"),
+            clock_minutes: 5.0,
+            clock_increment: 3,
+            external_engine: None,
+            external_options: Vec::new(),
+        }
+    }
+}
+
+/// Defaults of the engine, the UCI options of a GUI override them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    /// Size of the hash table in MB.
+    pub hash: usize,
+    pub threads: usize,
+    /// Playing strength in Elo, full strength if not set.
+    pub elo: Option<u32>,
+    /// Evaluation parameters of the engine in the user interface, UCI takes them from the EvalFile option.
+    pub eval_file: Option<String>,
+    /// Search settings "Fast", "Default", "Strong" or "Analysis". The hash size of the preset applies unless the hash
+    /// is set as well.
+    pub preset: Preset,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        EngineConfig {
+            hash: DEFAULT_HASH_MB,
+            threads: 1,
+            elo: None,
+            eval_file: None,
+            preset: Preset::Default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub ui: UiConfig,
+    pub engine: EngineConfig,
+}
+
+impl Config {
+    /// Reads the given file, or the file at [`default_path`] if there is one. Then applies the overrides, each one
+    /// "section.key=value" like "engine.hash=64".
+    pub fn load(path: Option<&str>, overrides: &[String]) -> Result<Self, String> {
+        let text = match path {
+            Some(path) => read_file(Path::new(path))?,
+            None => match default_path().filter(|path| path.exists()) {
+                Some(path) => read_file(&path)?,
+                None => String::new(),
+            },
+        };
+        let mut table: toml::Table = toml::from_str(&text).map_err(|e| format!("Invalid configuration: {}", e))?;
+        for setting in overrides {
+            apply_override(&mut table, setting)?;
+        }
+        let engine = table.get("engine").and_then(toml::Value::as_table);
+        let preset_hash = engine.is_some_and(|engine| engine.contains_key("preset") && !engine.contains_key("hash"));
+        let mut config: Config = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| format!("Invalid configuration: {}", e))?;
+        if preset_hash {
+            config.engine.hash = config.engine.preset.hash_mb();
+        }
+        rgb(&config.ui.light_squares)?;
+        rgb(&config.ui.dark_squares)?;
+        Ok(config)
+    }
+
+    /// Loads the configuration selected with the arguments of [`args`].
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self, String> {
+        let overrides: Vec<String> = matches.get_many::<String>("set").unwrap_or_default().cloned().collect();
+        Self::load(matches.get_one::<String>("config").map(String::as_str), &overrides)
+    }
+
+    /// Like [`Config::from_matches`], but an invalid configuration is reported on stderr and replaced by the
+    /// defaults. Stdout may be a protocol channel that a stray line would break.
+    pub fn from_matches_or_default(matches: &ArgMatches) -> Self {
+        Self::from_matches(matches).unwrap_or_else(report_and_default)
+    }
+
+    /// Like [`Config::load`], with the fallback of [`Config::from_matches_or_default`].
+    pub fn load_or_default(path: Option<&str>, overrides: &[String]) -> Self {
+        Self::load(path, overrides).unwrap_or_else(report_and_default)
+    }
+}
+
+fn report_and_default(error: String) -> Config {
+    eprintln!("{}, using the default configuration", error);
+    Config::default()
+}
+
+/// The command line arguments selecting the configuration.
+pub fn args() -> [Arg; 2] {
+    [
+        arg!(--config <FILE> "Configuration file instead of the one in the user's configuration directory"),
+        arg!(--set <SETTING> "Overrides a setting of the configuration file, like engine.hash=64")
+            .action(ArgAction::Append),
+    ]
+}
+
+/// chic/config.toml in $XDG_CONFIG_HOME, ~/.config or %APPDATA%.
+pub fn default_path() -> Option<PathBuf> {
+    let directory = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(directory.join("chic").join("config.toml"))
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+}
+
+/// Values that are no TOML value, like colors, are taken as strings.
+fn apply_override(table: &mut toml::Table, setting: &str) -> Result<(), String> {
+    let invalid = || format!("Invalid setting {}, expected section.key=value", setting);
+    let (key, value) = setting.split_once('=').ok_or_else(invalid)?;
+    let (section, key) = key.trim().split_once('.').ok_or_else(invalid)?;
+    let value = value.trim();
+    let value = match toml::from_str::<toml::Table>(&format!("value = {}", value)) {
+        Ok(mut parsed) => parsed.remove("value").unwrap(),
+        Err(_) => toml::Value::String(value.to_string()),
+    };
+    let section = table
+        .entry(section)
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or_else(invalid)?;
+    section.insert(key.to_string(), value);
+    Ok(())
+}
+
+/// Parses a color "#rrggbb".
+pub fn rgb(color: &str) -> Result<[u8; 3], String> {
+    let invalid = || format!("Invalid color {}, expected #rrggbb", color);
+    let hex = color
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6 && hex.is_ascii())
+        .ok_or_else(invalid)?;
+    let mut rgb = [0; 3];
+    for (index, channel) in rgb.iter_mut().enumerate() {
+        *channel = u8::from_str_radix(&hex[2 * index..2 * index + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("chic-config-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_load_config() {
+        let path = write_config(
+            "load",
+            "[ui]\npiece_set = \"Letters\"\n\n[engine]\nhash = 64\nelo = 1800\n",
+        );
+        let config = Config::load(Some(&path), &[]).unwrap();
+        assert_eq!(config.ui.piece_set, "Letters");
+        assert_eq!(config.ui.clock_minutes, 5.0);
+        assert_eq!(config.engine.hash, 64);
+        assert_eq!(config.engine.threads, 1);
+        assert_eq!(config.engine.elo, Some(1800));
+
+        let overrides = [
+            String::from("engine.hash=128"),
+            String::from("ui.dark_squares = #8877AA"),
+            String::from("engine.threads=4"),
+        ];
+        let config = Config::load(Some(&path), &overrides).unwrap();
+        assert_eq!(config.engine.hash, 128);
+        assert_eq!(config.engine.threads, 4);
+        assert_eq!(config.ui.dark_squares, "#8877AA");
+        assert_eq!(config.ui.piece_set, "Letters");
+
+        // A preset brings its hash size, unless the hash is set
+        let preset = [String::from("engine.preset=Fast")];
+        let config = Config::load(Some(&path), &preset).unwrap();
+        assert_eq!((config.engine.preset, config.engine.hash), (Preset::Fast, 64));
+        let empty = write_config("preset", "");
+        let config = Config::load(Some(&empty), &preset).unwrap();
+        assert_eq!(config.engine.hash, Preset::Fast.hash_mb());
+        std::fs::remove_file(&empty).unwrap();
+        assert!(Config::load(Some(&path), &[String::from("engine.preset=Fastest")]).is_err());
+
+        assert!(Config::load(Some(&path), &[String::from("engine.hashsize=16")]).is_err());
+        assert!(Config::load(Some(&path), &[String::from("engine.hash=many")]).is_err());
+        assert!(Config::load(Some(&path), &[String::from("hash=16")]).is_err());
+        assert!(Config::load(Some(&path), &[String::from("ui.light_squares=white")]).is_err());
+        assert_eq!(
+            Config::load_or_default(Some(&path), &[String::from("engine.hash=many")]),
+            Config::default()
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(Config::load(Some(&path), &[]).is_err());
+        assert_eq!(Config::load_or_default(Some(&path), &[]), Config::default());
+    }
+
+    #[test]
+    fn test_rgb() {
+        assert_eq!(rgb("#AE8A68"), Ok([0xAE, 0x8A, 0x68]));
+        assert!(rgb("AE8A68").is_err());
+        assert!(rgb("#AE8A6").is_err());
+        assert!(rgb("#AE8A6G").is_err());
+    }
+}
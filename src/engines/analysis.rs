@@ -0,0 +1,194 @@
+use crate::chess_board::{ChessBoard, Move};
+use crate::engines::engine_alpha_beta::{AlphaBetaEngine, IterativeSearch, SearchProgress, WIN};
+use crate::engines::{ChessEngine, InfoEvent, INFINITE_SEARCH_TIME};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// The lines of a completed depth of an analysis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisDepth {
+    pub depth: i32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// Score from the side to move's point of view and principal variation of the best root moves, best first.
+    pub lines: Vec<(i32, Vec<Move>)>,
+}
+
+pub enum AnalysisStep {
+    Running,
+    Depth(AnalysisDepth),
+    /// The maximum depth is reached, there is no legal move or the analysis was aborted.
+    Finished,
+}
+
+/// An infinite search of a single position in steps, reporting the MultiPV lines of every completed depth.
+/// A new position starts a new analysis, so the caller restarts by replacing it.
+pub struct Analysis {
+    engine: AlphaBetaEngine,
+    search: IterativeSearch,
+    /// A depth of an earlier analysis of the position, reported by the first step. Only deeper depths follow it.
+    known: Option<AnalysisDepth>,
+    known_depth: i32,
+}
+
+impl Analysis {
+    pub fn new(board: ChessBoard, multi_pv: usize) -> Self {
+        Self::with_time_limit(board, multi_pv, INFINITE_SEARCH_TIME)
+    }
+
+    /// An analysis that finishes after time_limit, like the search for a move. The first depth always completes.
+    pub fn with_time_limit(board: ChessBoard, multi_pv: usize, time_limit: Duration) -> Self {
+        let mut engine = AlphaBetaEngine::with_board(board);
+        engine.set_multi_pv(multi_pv);
+        let search = engine.start_search(time_limit);
+        Analysis {
+            engine,
+            search,
+            known: None,
+            known_depth: 0,
+        }
+    }
+
+    /// Continues an earlier analysis of the position, like one stored in the analysis database. The known depth is
+    /// reported at once, the search reports the depths beyond it.
+    pub fn resume(board: ChessBoard, multi_pv: usize, known: Option<AnalysisDepth>) -> Self {
+        let mut analysis = Self::new(board, multi_pv);
+        analysis.known_depth = known.as_ref().map_or(0, |known| known.depth);
+        analysis.known = known;
+        analysis
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.engine.set_max_depth(max_depth);
+    }
+
+    pub fn abort_channel(&self) -> Arc<AtomicBool> {
+        self.engine.get_abort_channel()
+    }
+
+    /// The first move of the best line of the last completed depth.
+    pub fn best_move(&self) -> Option<Move> {
+        self.engine
+            .multi_pv_lines()
+            .first()
+            .and_then(|(_, pv)| pv.first().copied())
+    }
+
+    /// Searches for about step_time, returning early when a depth completes so every depth is reported.
+    pub fn step(&mut self, step_time: Duration) -> AnalysisStep {
+        if let Some(known) = self.known.take() {
+            return AnalysisStep::Depth(known);
+        }
+        let step_end = Instant::now().checked_add(step_time);
+        loop {
+            let mut completed = None;
+            // A zero step time searches a single root move
+            let progress = self
+                .engine
+                .continue_search(&mut self.search, Duration::ZERO, &mut |event| {
+                    if let InfoEvent::DepthCompleted {
+                        depth, nodes, elapsed, ..
+                    } = event
+                    {
+                        completed = Some((depth, nodes, elapsed))
+                    }
+                });
+            if let Some((depth, nodes, elapsed)) = completed.filter(|&(depth, _, _)| depth > self.known_depth) {
+                return AnalysisStep::Depth(AnalysisDepth {
+                    depth,
+                    nodes,
+                    elapsed,
+                    lines: self.engine.multi_pv_lines().to_vec(),
+                });
+            }
+            if let SearchProgress::Finished(_) = progress {
+                return AnalysisStep::Finished;
+            }
+            if step_end.is_some_and(|end| Instant::now() >= end) {
+                return AnalysisStep::Running;
+            }
+        }
+    }
+}
+
+/// A score from White's point of view in pawns, or the sign of a mate.
+pub fn format_evaluation(white_score: i32) -> String {
+    if white_score.abs() >= WIN {
+        // Mate scores are LOSS - depth, so only the sign is meaningful here
+        if white_score > 0 {
+            String::from("#+")
+        } else {
+            String::from("#-")
+        }
+    } else {
+        format!("{:+.2}", white_score as f32 / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analysis_reports_multi_pv_lines() {
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut analysis = Analysis::new(board, 3);
+        analysis.set_max_depth(3);
+        let mut depths = Vec::new();
+        loop {
+            match analysis.step(Duration::from_millis(10)) {
+                AnalysisStep::Running => {}
+                AnalysisStep::Depth(depth) => depths.push(depth),
+                AnalysisStep::Finished => break,
+            }
+        }
+        let last = depths.last().unwrap();
+        assert_eq!(last.depth, 3);
+        assert_eq!(last.lines.len(), 3);
+        // Back rank mate first
+        assert_eq!(last.lines[0].1[0], Move::from_algebraic("a1a8"));
+        assert!(last.lines[0].0 > last.lines[1].0);
+        assert!(last.lines.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+        assert_eq!(
+            depths.iter().map(|depth| depth.depth).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_resumed_analysis_reports_deeper_depths() {
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let known = AnalysisDepth {
+            depth: 2,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+            lines: vec![(WIN, vec![Move::from_algebraic("a1a8")])],
+        };
+        let mut analysis = Analysis::resume(board, 1, Some(known.clone()));
+        analysis.set_max_depth(3);
+        let mut depths = Vec::new();
+        loop {
+            match analysis.step(Duration::from_millis(10)) {
+                AnalysisStep::Running => {}
+                AnalysisStep::Depth(depth) => depths.push(depth),
+                AnalysisStep::Finished => break,
+            }
+        }
+        assert_eq!(depths[0], known);
+        assert_eq!(depths.iter().map(|depth| depth.depth).collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_analysis_with_time_limit_finishes() {
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut analysis = Analysis::with_time_limit(board, 1, Duration::from_millis(50));
+        assert_eq!(analysis.best_move(), None);
+        while !matches!(analysis.step(Duration::from_millis(10)), AnalysisStep::Finished) {}
+        assert_eq!(analysis.best_move(), Some(Move::from_algebraic("a1a8")));
+    }
+}
@@ -0,0 +1,221 @@
+//! The analyses of earlier sessions, so a position analyzed before starts from the depth it reached. The database is
+//! a text file with a line per analysis, "<hash> <depth> <score> <moves>": the Zobrist hash of the position in hex,
+//! the depth, the score of the best line from the side to move's point of view and the line in coordinate notation.
+//! A deeper analysis of a position is appended and replaces the earlier lines of the position when the file is read.
+use crate::chess_board::ChessBoard;
+use crate::engines::analysis::AnalysisDepth;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    depth: i32,
+    score: i32,
+    moves: Vec<String>,
+}
+
+/// Analyses by position hash, kept in a file or, without a path, in memory only.
+#[derive(Debug, Default)]
+pub struct AnalysisDb {
+    path: Option<PathBuf>,
+    entries: HashMap<u64, Entry>,
+}
+
+impl AnalysisDb {
+    /// A database that is not saved.
+    pub fn in_memory() -> Self {
+        AnalysisDb::default()
+    }
+
+    /// Reads the database at the path, which is created by the first analysis if it does not exist. Invalid lines are
+    /// skipped. A file with many superseded lines is rewritten with one line per position.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut db = AnalysisDb {
+            path: Some(path.to_path_buf()),
+            entries: HashMap::new(),
+        };
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(db),
+            Err(e) => return Err(e),
+        };
+        let mut lines = 0;
+        for line in BufReader::new(file).lines() {
+            if let Some((hash, entry)) = parse_line(&line?) {
+                lines += 1;
+                if db.entries.get(&hash).is_none_or(|known| known.depth < entry.depth) {
+                    db.entries.insert(hash, entry);
+                }
+            }
+        }
+        if lines > 2 * db.entries.len() {
+            db.compact()?;
+        }
+        Ok(db)
+    }
+
+    /// chic/analysis.db beside the configuration file.
+    pub fn default_path() -> Option<PathBuf> {
+        crate::config::default_path().map(|path| path.with_file_name("analysis.db"))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The stored analysis of the position as a depth with its best line, without nodes and time. A line that is not
+    /// legal in the position belongs to another position with the same hash and is ignored.
+    pub fn get(&self, board: &ChessBoard) -> Option<AnalysisDepth> {
+        let entry = self.entries.get(&board.zobrist_hash())?;
+        let mut line_board = board.clone();
+        let mut pv = Vec::with_capacity(entry.moves.len());
+        for notation in &entry.moves {
+            let mv = line_board.legal_move_from_uci(notation).ok()?;
+            line_board.make_move(mv);
+            pv.push(mv);
+        }
+        Some(AnalysisDepth {
+            depth: entry.depth,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+            lines: vec![(entry.score, pv)],
+        })
+    }
+
+    /// Stores the best line of a depth of the analysis of the position if it is deeper than the stored one. Returns
+    /// whether it was stored.
+    pub fn record(&mut self, board: &ChessBoard, depth: &AnalysisDepth) -> io::Result<bool> {
+        let Some((score, pv)) = depth.lines.first() else {
+            return Ok(false);
+        };
+        let hash = board.zobrist_hash();
+        if self.entries.get(&hash).is_some_and(|known| known.depth >= depth.depth) {
+            return Ok(false);
+        }
+        let entry = Entry {
+            depth: depth.depth,
+            score: *score,
+            moves: pv.iter().map(|mv| mv.as_algebraic()).collect(),
+        };
+        if let Some(path) = &self.path {
+            if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+                fs::create_dir_all(directory)?;
+            }
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", format_line(hash, &entry))?;
+        }
+        self.entries.insert(hash, entry);
+        Ok(true)
+    }
+
+    fn compact(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(&hash, entry)| format_line(hash, entry))
+            .collect();
+        lines.sort();
+        let temporary = path.with_extension("db.tmp");
+        fs::write(&temporary, lines.join("\n") + "\n")?;
+        fs::rename(&temporary, path)
+    }
+}
+
+fn format_line(hash: u64, entry: &Entry) -> String {
+    let mut line = format!("{:016x} {} {}", hash, entry.depth, entry.score);
+    for mv in &entry.moves {
+        line.push(' ');
+        line.push_str(mv);
+    }
+    line
+}
+
+fn parse_line(line: &str) -> Option<(u64, Entry)> {
+    let mut fields = line.split_whitespace();
+    let hash = u64::from_str_radix(fields.next()?, 16).ok()?;
+    let depth = fields.next()?.parse().ok()?;
+    let score = fields.next()?.parse().ok()?;
+    let moves = fields.map(String::from).collect();
+    Some((hash, Entry { depth, score, moves }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::Move;
+
+    fn depth(depth: i32, score: i32, pv: &[&str]) -> AnalysisDepth {
+        AnalysisDepth {
+            depth,
+            nodes: 1000,
+            elapsed: Duration::from_millis(10),
+            lines: vec![(score, pv.iter().map(|mv| Move::from_algebraic(mv)).collect())],
+        }
+    }
+
+    #[test]
+    fn test_analysis_db() {
+        let path = std::env::temp_dir().join(format!("chic-analysis-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let other = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1").unwrap();
+
+        let mut db = AnalysisDb::open(&path).unwrap();
+        assert!(db.is_empty());
+        assert!(db.record(&board, &depth(2, 900, &["a1b1"])).unwrap());
+        assert!(db.record(&board, &depth(3, 30_000, &["a1a8"])).unwrap());
+        // A shallower analysis does not replace a deeper one
+        assert!(!db.record(&board, &depth(1, 0, &["g1g2"])).unwrap());
+        assert!(db.record(&other, &depth(1, -900, &["g8f8", "a1a8"])).unwrap());
+
+        // The deepest line of every position is read back
+        let db = AnalysisDb::open(&path).unwrap();
+        assert_eq!(db.len(), 2);
+        let stored = db.get(&board).unwrap();
+        assert_eq!((stored.depth, stored.nodes), (3, 0));
+        assert_eq!(stored.lines, vec![(30_000, vec![Move::from_algebraic("a1a8")])]);
+        assert_eq!(db.get(&other).unwrap().lines[0].1.len(), 2);
+        assert_eq!(
+            db.get(&ChessBoard::from_fen("8/8/8/8/8/8/8/K6k w - - 0 1").unwrap()),
+            None
+        );
+
+        // Garbage and superseded lines are dropped by the rewrite
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(file, "not an analysis").unwrap();
+        let first_line = fs::read_to_string(&path).unwrap().lines().next().unwrap().to_string();
+        for _ in 0..3 {
+            writeln!(file, "{}", first_line).unwrap();
+        }
+        drop(file);
+        let db = AnalysisDb::open(&path).unwrap();
+        assert_eq!(db.len(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_illegal_stored_line_is_ignored() {
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mut db = AnalysisDb::in_memory();
+        db.entries.insert(
+            board.zobrist_hash(),
+            Entry {
+                depth: 5,
+                score: 0,
+                moves: vec![String::from("e2e4")],
+            },
+        );
+        assert_eq!(db.get(&board), None);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::chess_board::fen::FenError;
+use crate::chess_board::move_list::MoveList;
+use crate::chess_board::render::RenderOptions;
+use crate::chess_board::{ChessBoard, Color, Move};
+use crate::engines::evaluation::Evaluator;
+use crate::engines::move_picker::{self, MovePicker, KILLERS};
+
+/// The position operations AlphaBetaEngine searches with. ChessBoard is the reference implementation, another board
+/// representation only has to implement this trait to be searched, the search code does not change.
+/// The search makes its moves on a copy of the board. Callers that keep a single board take moves back with
+/// unmake_move.
+pub trait Board: Clone + Send + 'static {
+    /// What unmake_move needs to take a move back.
+    type Undo;
+
+    fn from_fen(fen: &str) -> Result<Self, FenError>;
+    fn to_fen(&self) -> String;
+    fn render(&self, options: &RenderOptions) -> String;
+
+    fn active_color(&self) -> Color;
+    fn halfmove_clock(&self) -> u32;
+    /// The hash of the position, equal positions have equal hashes.
+    fn zobrist_hash(&self) -> u64;
+
+    fn generate_legal_moves(&self) -> Vec<Move>;
+    fn generate_legal_capture_moves(&self) -> Vec<Move>;
+    /// Replaces the moves of the list by the legal captures in search order, the search reuses its lists.
+    fn generate_legal_capture_moves_into(&self, moves: &mut MoveList);
+    /// The legal moves in search order: the hash move first, then the captures that do not lose material, the
+    /// killer moves, the quiet moves and the losing captures. Hash move and killers may come from other positions
+    /// and are skipped if they are not legal here. The moves are generated into the list, which the search reuses.
+    fn ordered_moves<'a>(
+        &'a self,
+        hash_move: Option<Move>,
+        killers: [Option<Move>; KILLERS],
+        moves: &'a mut MoveList,
+    ) -> impl Iterator<Item = Move> + 'a;
+    /// Captures and queen promotions, killer moves are only kept for the other moves.
+    fn is_noisy(&self, mv: Move) -> bool;
+    /// An optimistic estimate of the material a capture wins in 1/1000 pawn like the evaluation, the quiescence
+    /// search skips captures that cannot raise alpha even then.
+    fn max_capture_gain(&self, mv: Move) -> i32;
+    /// Checks a move that was not generated from this position.
+    fn is_legal(&self, mv: Move) -> bool;
+    /// Parses a move sent by a GUI, castling may be written as king takes rook or O-O. The error tells why it is
+    /// not legal.
+    fn legal_move_from_uci(&self, notation: &str) -> Result<Move, String>;
+    fn make_move(&mut self, mv: Move);
+    /// Makes the move like make_move and returns the state to take it back.
+    fn make_move_with_undo(&mut self, mv: Move) -> Self::Undo;
+    /// Takes back the move whose undo state is given, the last one made must be taken back first.
+    fn unmake_move(&mut self, undo: Self::Undo);
+
+    fn is_in_check(&self) -> bool;
+    fn is_checkmate(&self) -> bool;
+    /// Stops at the first legal move.
+    fn has_legal_move(&self) -> bool;
+    fn is_draw_by_fifty_move_rule(&self) -> bool;
+    /// Halfmoves without capture or pawn move left until a draw by the fifty-move rule may be claimed.
+    fn halfmoves_until_fifty_move_draw(&self) -> u32;
+    fn is_threefold_repetition(&self) -> bool;
+
+    /// The static evaluation by the evaluator from White's point of view.
+    fn evaluate(&self, evaluator: &dyn Evaluator) -> i32;
+    /// The terms of the evaluation, one line each, for the debug diagnostics of the search.
+    fn evaluation_breakdown(&self, evaluator: &dyn Evaluator) -> Vec<String>;
+}
+
+impl Board for ChessBoard {
+    /// The position before the move, the board has no cheaper way back.
+    type Undo = ChessBoard;
+
+    fn from_fen(fen: &str) -> Result<Self, FenError> {
+        ChessBoard::from_fen(fen)
+    }
+
+    fn to_fen(&self) -> String {
+        ChessBoard::to_fen(self)
+    }
+
+    fn render(&self, options: &RenderOptions) -> String {
+        ChessBoard::render(self, options)
+    }
+
+    fn active_color(&self) -> Color {
+        self.active_color
+    }
+
+    fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    fn zobrist_hash(&self) -> u64 {
+        ChessBoard::zobrist_hash(self)
+    }
+
+    fn generate_legal_moves(&self) -> Vec<Move> {
+        ChessBoard::generate_legal_moves(self)
+    }
+
+    fn generate_legal_capture_moves(&self) -> Vec<Move> {
+        ChessBoard::generate_legal_capture_moves(self)
+    }
+
+    fn generate_legal_capture_moves_into(&self, moves: &mut MoveList) {
+        ChessBoard::generate_legal_capture_moves_into(self, moves)
+    }
+
+    fn ordered_moves<'a>(
+        &'a self,
+        hash_move: Option<Move>,
+        killers: [Option<Move>; KILLERS],
+        moves: &'a mut MoveList,
+    ) -> impl Iterator<Item = Move> + 'a {
+        MovePicker::new(self, hash_move, killers, moves)
+    }
+
+    fn is_noisy(&self, mv: Move) -> bool {
+        move_picker::is_noisy(self, mv)
+    }
+
+    fn max_capture_gain(&self, mv: Move) -> i32 {
+        move_picker::max_capture_gain(self, mv) * 10
+    }
+
+    fn is_legal(&self, mv: Move) -> bool {
+        ChessBoard::is_legal(self, mv)
+    }
+
+    fn legal_move_from_uci(&self, notation: &str) -> Result<Move, String> {
+        ChessBoard::legal_move_from_uci(self, notation)
+    }
+
+    fn make_move(&mut self, mv: Move) {
+        ChessBoard::make_move(self, mv)
+    }
+
+    fn make_move_with_undo(&mut self, mv: Move) -> Self::Undo {
+        let undo = self.clone();
+        ChessBoard::make_move(self, mv);
+        undo
+    }
+
+    fn unmake_move(&mut self, undo: Self::Undo) {
+        *self = undo;
+    }
+
+    fn is_in_check(&self) -> bool {
+        ChessBoard::is_in_check(self)
+    }
+
+    fn is_checkmate(&self) -> bool {
+        ChessBoard::is_checkmate(self)
+    }
+
+    fn has_legal_move(&self) -> bool {
+        ChessBoard::has_legal_move(self)
+    }
+
+    fn is_draw_by_fifty_move_rule(&self) -> bool {
+        ChessBoard::is_draw_by_fifty_move_rule(self)
+    }
+
+    fn halfmoves_until_fifty_move_draw(&self) -> u32 {
+        ChessBoard::halfmoves_until_fifty_move_draw(self)
+    }
+
+    fn is_threefold_repetition(&self) -> bool {
+        ChessBoard::is_threefold_repetition(self)
+    }
+
+    fn evaluate(&self, evaluator: &dyn Evaluator) -> i32 {
+        evaluator.evaluate(self)
+    }
+
+    fn evaluation_breakdown(&self, evaluator: &dyn Evaluator) -> Vec<String> {
+        evaluator.breakdown(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Searches through the trait only, like the engine does.
+    fn count_nodes<B: Board>(board: &B, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        board
+            .ordered_moves(None, [None; KILLERS], &mut MoveList::new())
+            .map(|mv| {
+                let mut child = board.clone();
+                child.make_move(mv);
+                count_nodes(&child, depth - 1)
+            })
+            .sum()
+    }
+
+    /// Like count_nodes on a single board that takes its moves back.
+    fn count_nodes_unmaking<B: Board>(board: &mut B, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for mv in board.generate_legal_moves() {
+            let undo = board.make_move_with_undo(mv);
+            nodes += count_nodes_unmaking(board, depth - 1);
+            board.unmake_move(undo);
+        }
+        nodes
+    }
+
+    #[test]
+    fn test_make_and_unmake_move() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut board = <ChessBoard as Board>::from_fen(fen).unwrap();
+        assert_eq!(count_nodes_unmaking(&mut board, 2), 2039);
+        assert_eq!(Board::to_fen(&board), fen);
+    }
+
+    #[test]
+    fn test_ordered_moves_are_the_legal_moves() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let board = <ChessBoard as Board>::from_fen(fen).unwrap();
+        assert_eq!(count_nodes(&board, 2), 2039);
+
+        // A hash move of another position is skipped, a legal one comes first
+        let sorted = |mut moves: Vec<Move>| {
+            moves.sort_by_key(|mv| mv.as_algebraic());
+            moves
+        };
+        let mut moves = MoveList::new();
+        let ordered = board.ordered_moves(Some(Move::from_algebraic("a7a5")), [None; KILLERS], &mut moves);
+        assert_eq!(sorted(ordered.collect()), sorted(Board::generate_legal_moves(&board)));
+        let hash_move = Move::from_algebraic("e1g1");
+        assert_eq!(
+            board.ordered_moves(Some(hash_move), [None; KILLERS], &mut moves).next(),
+            Some(hash_move)
+        );
+    }
+}
@@ -0,0 +1,393 @@
+//! Comments of a coach on a move in plain language: how much worse it is than the best move by a shallow search and
+//! the tactical and positional motifs it creates.
+use crate::chess_board::pgn::move_to_san;
+use crate::chess_board::{ChessBoard, ChessField, Color, Move, PieceType, Square};
+use crate::engines::engine_alpha_beta::{AlphaBetaEngine, WIN};
+use crate::engines::eval_params::{is_passed_pawn, EvalParams};
+use crate::engines::move_picker::see;
+use crate::engines::ChessEngine;
+use std::time::Duration;
+
+/// Deep enough to see simple tactics, shallow enough to comment on every move without waiting.
+const COACH_DEPTH: usize = 3;
+/// Bounds the searches of positions with many captures, the first iteration always completes.
+const COACH_NODES: u64 = 20_000;
+
+/// How good a move is compared to the best move of the search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Verdict {
+    /// The verdict of a move that scores the loss below the best move, in 1/1000 pawn.
+    fn from_loss(loss: i32) -> Self {
+        match loss {
+            ..=0 => Verdict::Best,
+            1..=499 => Verdict::Good,
+            500..=999 => Verdict::Inaccuracy,
+            1_000..=2_999 => Verdict::Mistake,
+            _ => Verdict::Blunder,
+        }
+    }
+}
+
+/// Something the move brought about that a player should notice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Motif {
+    /// A piece of the side that moved can be won by the opponent.
+    HangingPiece { square: ChessField, kind: PieceType },
+    /// The moved piece attacks two pieces that cannot both be saved.
+    ForkThreat {
+        square: ChessField,
+        targets: Vec<ChessField>,
+    },
+    /// The king of the color has no escape square from a check along its first rank.
+    BackRankWeakness { color: Color },
+    /// A pawn of the side that moved became a passed pawn.
+    PassedPawn { square: ChessField },
+}
+
+/// The comments on a move.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commentary {
+    /// The move in SAN.
+    pub san: String,
+    pub verdict: Verdict,
+    /// How much worse the move is than the best move from the mover's point of view, in 1/1000 pawn.
+    pub loss: i32,
+    /// The best move in SAN if it is another one.
+    pub best_move: Option<String>,
+    pub motifs: Vec<Motif>,
+}
+
+impl Commentary {
+    /// A sentence per comment.
+    pub fn messages(&self) -> Vec<String> {
+        let mut messages = vec![match (self.verdict, &self.best_move) {
+            (Verdict::Best, _) => format!("{} is the best move.", self.san),
+            (Verdict::Good, _) => format!("{} is a good move.", self.san),
+            (verdict, best_move) => {
+                let kind = match verdict {
+                    Verdict::Inaccuracy => "an inaccuracy",
+                    Verdict::Mistake => "a mistake",
+                    _ => "a blunder",
+                };
+                let mut message = format!("{} is {}, it gives up {}.", self.san, kind, format_loss(self.loss));
+                if let Some(best_move) = best_move {
+                    message.push_str(&format!(" Better was {}.", best_move));
+                }
+                message
+            }
+        }];
+        messages.extend(self.motifs.iter().map(|motif| match motif {
+            Motif::HangingPiece { square, kind } => {
+                format!("The {} on {} can be taken.", piece_name(*kind), square.as_algebraic())
+            }
+            Motif::ForkThreat { square, targets } => {
+                let targets: Vec<String> = targets.iter().map(|target| target.as_algebraic()).collect();
+                format!(
+                    "The piece on {} forks {}.",
+                    square.as_algebraic(),
+                    targets.join(" and ")
+                )
+            }
+            Motif::BackRankWeakness { color } => {
+                format!("{:?}'s back rank is weak, the king has no escape square.", color)
+            }
+            Motif::PassedPawn { square } => format!("The pawn on {} is now a passed pawn.", square.as_algebraic()),
+        }));
+        messages
+    }
+}
+
+/// Comments on the move in the position, by shallow searches of the position before and after it. The move must be
+/// legal.
+pub fn comment(board: &ChessBoard, mv: Move, params: &EvalParams) -> Commentary {
+    let mover = board.active_color;
+    let mut after = board.clone();
+    after.make_move(mv);
+
+    let (best_line, best_score) = search(board, params).unwrap_or_default();
+    let score = if after.is_checkmate() {
+        WIN
+    } else {
+        search(&after, params).map_or(0, |(_, score)| -score)
+    };
+    let best_move = best_line.first().copied().filter(|&best| best != mv);
+    let loss = if best_move.is_some() {
+        (best_score - score).max(0)
+    } else {
+        0
+    };
+    Commentary {
+        san: move_to_san(board, mv),
+        verdict: Verdict::from_loss(loss),
+        loss,
+        best_move: best_move.map(|best| move_to_san(board, best)),
+        motifs: motifs(board, mv, &after, mover),
+    }
+}
+
+/// The principal variation and the score from the side to move's point of view, None without legal moves.
+fn search(board: &ChessBoard, params: &EvalParams) -> Option<(Vec<Move>, i32)> {
+    let mut engine = AlphaBetaEngine::with_board(board.clone());
+    engine.set_eval_params(params.clone());
+    engine.set_max_depth(COACH_DEPTH);
+    engine.set_max_nodes(COACH_NODES);
+    engine
+        .find_best_move_iterative(Duration::from_secs(10), &mut |_| {})
+        .map(|(pv, score, _, _)| (pv, score))
+}
+
+/// The motifs the move created, the ones that were there before it are not repeated.
+pub fn motifs(before: &ChessBoard, mv: Move, after: &ChessBoard, mover: Color) -> Vec<Motif> {
+    let mut motifs = Vec::new();
+    let hanging = hanging_pieces(before, mv, after);
+    for &square in &hanging {
+        if let Square::Occupied(piece) = after.squares[square.row()][square.col()] {
+            motifs.push(Motif::HangingPiece {
+                square,
+                kind: piece.kind,
+            });
+        }
+    }
+    if !hanging.contains(&mv.to) {
+        let targets = fork_targets(after, mv.to);
+        if targets.len() >= 2 {
+            motifs.push(Motif::ForkThreat { square: mv.to, targets });
+        }
+    }
+    for color in [Color::White, Color::Black] {
+        if has_weak_back_rank(after, color) && !has_weak_back_rank(before, color) {
+            motifs.push(Motif::BackRankWeakness { color });
+        }
+    }
+    for square in passed_pawns(after, mover) {
+        let moved_passed_pawn = square == mv.to && passed_pawns(before, mover).contains(&mv.from);
+        if !passed_pawns(before, mover).contains(&square) && !moved_passed_pawn {
+            motifs.push(Motif::PassedPawn { square });
+        }
+    }
+    motifs
+}
+
+/// The pieces of the side that moved that the opponent newly wins by a capture. The piece that just captured is not
+/// hanging if the exchange it started does not lose material, the other pieces only if they were safe before.
+fn hanging_pieces(before: &ChessBoard, mv: Move, after: &ChessBoard) -> Vec<ChessField> {
+    let even_exchange = before.squares[mv.to.row()][mv.to.col()] != Square::Empty && see(before, mv) >= 0;
+    let mut opponent_to_move = before.clone();
+    opponent_to_move.set_side_to_move(after.active_color);
+    let hanging_before = winning_captures(&opponent_to_move);
+    winning_captures(after)
+        .into_iter()
+        .filter(|&square| {
+            if square == mv.to {
+                !even_exchange
+            } else {
+                !hanging_before.contains(&square)
+            }
+        })
+        .collect()
+}
+
+/// The squares of the pieces the side to move wins material against by capturing them.
+fn winning_captures(board: &ChessBoard) -> Vec<ChessField> {
+    let mut squares = Vec::new();
+    for capture in board.generate_legal_capture_moves() {
+        let target = capture.to;
+        let piece = matches!(board.squares[target.row()][target.col()], Square::Occupied(_));
+        if piece && !squares.contains(&target) && see(board, capture) > 0 {
+            squares.push(target);
+        }
+    }
+    squares
+}
+
+/// The pieces the piece on the square attacks that are worth more than it or not defended, kings included.
+fn fork_targets(board: &ChessBoard, square: ChessField) -> Vec<ChessField> {
+    let Square::Occupied(attacker) = board.squares[square.row()][square.col()] else {
+        return Vec::new();
+    };
+    let mut targets = Vec::new();
+    for row in 0..8 {
+        for col in 0..8 {
+            let Square::Occupied(piece) = board.squares[row][col] else {
+                continue;
+            };
+            let target = ChessField::new(row, col);
+            if piece.color == attacker.color
+                || piece.kind == PieceType::Pawn
+                || !board.attackers_to(target, attacker.color).contains(&square)
+            {
+                continue;
+            }
+            let defended = !board.attackers_to(target, piece.color).is_empty();
+            if piece.kind == PieceType::King || value(piece.kind) > value(attacker.kind) || !defended {
+                targets.push(target);
+            }
+        }
+    }
+    targets
+}
+
+/// A king on its first rank walled in by its own pieces while the opponent has a rook or queen to check it.
+fn has_weak_back_rank(board: &ChessBoard, color: Color) -> bool {
+    let (back_rank, forward) = match color {
+        Color::White => (0, 1),
+        Color::Black => (7, 6),
+    };
+    let Some(king) = (0..8).find(|&col| {
+        matches!(board.squares[back_rank][col], Square::Occupied(piece) if piece.color == color && piece.kind == PieceType::King)
+    }) else {
+        return false;
+    };
+    let heavy_pieces = |owner: Color| {
+        board.squares.iter().flatten().any(|square| {
+            matches!(square, Square::Occupied(piece)
+                if piece.color == owner && matches!(piece.kind, PieceType::Rook | PieceType::Queen))
+        })
+    };
+    let escape_blocked = (king.saturating_sub(1)..=(king + 1).min(7))
+        .all(|col| matches!(board.squares[forward][col], Square::Occupied(piece) if piece.color == color));
+    // A rook or queen of its own on the back rank covers it
+    let covered = (0..8).any(|col| {
+        matches!(board.squares[back_rank][col], Square::Occupied(piece)
+            if piece.color == color && matches!(piece.kind, PieceType::Rook | PieceType::Queen))
+    });
+    escape_blocked && !covered && heavy_pieces(opponent(color))
+}
+
+fn passed_pawns(board: &ChessBoard, color: Color) -> Vec<ChessField> {
+    let mut squares = Vec::new();
+    for row in 1..7 {
+        for col in 0..8 {
+            if matches!(board.squares[row][col], Square::Occupied(piece) if piece.color == color && piece.kind == PieceType::Pawn)
+                && is_passed_pawn(board, color, row, col)
+            {
+                squares.push(ChessField::new(row, col));
+            }
+        }
+    }
+    squares
+}
+
+/// Material in pawns for comparing pieces.
+fn value(kind: PieceType) -> i32 {
+    match kind {
+        PieceType::Pawn => 1,
+        PieceType::Knight | PieceType::Bishop => 3,
+        PieceType::Rook => 5,
+        PieceType::Queen => 9,
+        PieceType::King => 100,
+    }
+}
+
+fn piece_name(kind: PieceType) -> &'static str {
+    match kind {
+        PieceType::Pawn => "pawn",
+        PieceType::Knight => "knight",
+        PieceType::Bishop => "bishop",
+        PieceType::Rook => "rook",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+    }
+}
+
+fn format_loss(loss: i32) -> String {
+    if loss >= WIN / 2 {
+        String::from("a forced mate")
+    } else {
+        format!("{:.1} pawns", loss as f32 / 1000.0)
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn motifs_of(fen: &str, mv: &str) -> Vec<Motif> {
+        let before = ChessBoard::from_fen(fen).unwrap();
+        let mv = before.legal_move_from_uci(mv).unwrap();
+        let mut after = before.clone();
+        after.make_move(mv);
+        motifs(&before, mv, &after, before.active_color)
+    }
+
+    #[test]
+    fn test_motifs() {
+        // The knight forks king and rook
+        assert_eq!(
+            motifs_of("r3k3/8/8/8/8/8/8/4K1N1 w - - 0 1", "g1f3")
+                .into_iter()
+                .chain(motifs_of("r3k3/8/8/3N4/8/8/8/4K3 w - - 0 1", "d5c7"))
+                .collect::<Vec<_>>(),
+            vec![Motif::ForkThreat {
+                square: ChessField::from_algebraic("c7"),
+                targets: vec![ChessField::from_algebraic("a8"), ChessField::from_algebraic("e8")],
+            }]
+        );
+        // The queen steps next to a pawn that takes it
+        assert_eq!(
+            motifs_of("4k3/8/2p5/8/8/8/8/3QK3 w - - 0 1", "d1d5"),
+            vec![Motif::HangingPiece {
+                square: ChessField::from_algebraic("d5"),
+                kind: PieceType::Queen,
+            }]
+        );
+        // The knight was attacked by the pawn before the king move already
+        assert_eq!(motifs_of("4k3/8/8/8/1p6/2N5/8/4K3 w - - 0 1", "e1d1"), vec![]);
+        // Taking a defended pawn with a pawn is an even exchange, the pawn is not hanging
+        assert_eq!(motifs_of("4k3/8/2p5/3p4/4P3/8/8/4K3 w - - 0 1", "e4d5"), vec![]);
+        // The rook leaves the back rank behind the pawns of its king
+        assert_eq!(
+            motifs_of("3rk3/8/8/8/8/8/5PPP/5RK1 w - - 0 1", "f1e1")
+                .into_iter()
+                .chain(motifs_of("3rk3/8/8/8/8/8/5PPP/2R3K1 w - - 0 1", "c1c4"))
+                .collect::<Vec<_>>(),
+            vec![Motif::BackRankWeakness { color: Color::White }]
+        );
+        assert_eq!(
+            motifs_of("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1", "d5d6")
+                .into_iter()
+                .chain(motifs_of("4k3/2p5/8/8/3P4/8/8/4K3 w - - 0 1", "d4d5"))
+                .collect::<Vec<_>>(),
+            vec![]
+        );
+        assert_eq!(
+            motifs_of("4k3/8/2p5/3P4/8/8/8/4K3 w - - 0 1", "d5c6"),
+            vec![Motif::PassedPawn {
+                square: ChessField::from_algebraic("c6"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_comment() {
+        // Letting the back rank mate go
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let params = EvalParams::default();
+        let best = comment(&board, Move::from_algebraic("d1d8"), &params);
+        assert_eq!(best.verdict, Verdict::Best);
+        assert_eq!(best.messages(), vec!["Rd8# is the best move."]);
+
+        let blunder = comment(&board, Move::from_algebraic("g1f1"), &params);
+        assert_eq!(blunder.verdict, Verdict::Blunder);
+        assert_eq!(blunder.best_move.as_deref(), Some("Rd8#"));
+        assert_eq!(
+            blunder.messages()[0],
+            "Kf1 is a blunder, it gives up a forced mate. Better was Rd8#."
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use crate::chess_board::material::MaterialSignature;
+use crate::chess_board::{ChessBoard, Color, PieceType, Square};
+use crate::engines::eval_params::EvalParams;
+
+/// Bonus of a won basic endgame over its material, so the engine goes for it instead of keeping material it cannot
+/// mate with.
+const KNOWN_WIN: i32 = 10_000;
+/// Bonus per step the weak king is away from the center.
+const EDGE_WEIGHT: i32 = 200;
+/// Bonus per step the weak king is closer to a corner it can be mated in.
+const CORNER_WEIGHT: i32 = 200;
+/// Bonus per step the strong king is closer to the weak one.
+const PROXIMITY_WEIGHT: i32 = 100;
+/// Lead from which a position counts as clearly won, four pawns.
+const CLEAR_WIN: i32 = 4_000;
+/// Penalty of a won position in which the weak side has no legal move without being stalemated yet, a careless
+/// waiting move away from a draw.
+const NO_MOBILITY_PENALTY: i32 = 500;
+
+/// An endgame with its own evaluation. The signature lists the pieces of the strong side, then those of the weak
+/// side, each starting with the king and ordered Q, R, B, N, P.
+struct EndgameRecognizer {
+    signature: &'static str,
+    /// Evaluates the board from the point of view of the strong side, given as second argument.
+    evaluate: fn(&ChessBoard, Color, &EvalParams) -> i32,
+}
+
+const RECOGNIZERS: [EndgameRecognizer; 8] = [
+    EndgameRecognizer {
+        signature: "KQK",
+        evaluate: drive_to_edge,
+    },
+    EndgameRecognizer {
+        signature: "KRK",
+        evaluate: drive_to_edge,
+    },
+    EndgameRecognizer {
+        signature: "KBBK",
+        evaluate: drive_to_corner,
+    },
+    EndgameRecognizer {
+        signature: "KBNK",
+        evaluate: drive_to_bishop_corner,
+    },
+    EndgameRecognizer {
+        signature: "KK",
+        evaluate: draw,
+    },
+    EndgameRecognizer {
+        signature: "KBK",
+        evaluate: draw,
+    },
+    EndgameRecognizer {
+        signature: "KNK",
+        evaluate: draw,
+    },
+    EndgameRecognizer {
+        signature: "KNNK",
+        evaluate: draw,
+    },
+];
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+fn distance(from: (usize, usize), to: (usize, usize)) -> i32 {
+    from.0.abs_diff(to.0).max(from.1.abs_diff(to.1)) as i32
+}
+
+/// The pieces of a color like "KRP", in the order of the recognizer signatures.
+fn signature_letters(material: &MaterialSignature, color: Color) -> String {
+    let mut signature = String::new();
+    for (kind, letter) in [
+        (PieceType::King, 'K'),
+        (PieceType::Queen, 'Q'),
+        (PieceType::Rook, 'R'),
+        (PieceType::Bishop, 'B'),
+        (PieceType::Knight, 'N'),
+        (PieceType::Pawn, 'P'),
+    ] {
+        for _ in 0..material.count(color, kind) {
+            signature.push(letter);
+        }
+    }
+    signature
+}
+
+/// The material of the strong side, the weak side only has its king. The square tables are left out, they would keep
+/// the kings on their back ranks.
+fn material(board: &ChessBoard, strong: Color, eval_params: &EvalParams) -> i32 {
+    let material = board.material_signature();
+    [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+    ]
+    .into_iter()
+    .map(|kind| material.count(strong, kind) as i32 * eval_params.material[kind as usize])
+    .sum()
+}
+
+fn kings(board: &ChessBoard, strong: Color) -> Option<((usize, usize), (usize, usize))> {
+    let strong_king = board.find_king_position(strong)?;
+    let weak_king = board.find_king_position(opponent(strong))?;
+    Some((
+        (strong_king.row(), strong_king.col()),
+        (weak_king.row(), weak_king.col()),
+    ))
+}
+
+fn proximity(strong_king: (usize, usize), weak_king: (usize, usize)) -> i32 {
+    (7 - distance(strong_king, weak_king)) * PROXIMITY_WEIGHT
+}
+
+/// Mates with a queen or a rook happen on any edge.
+fn drive_to_edge(board: &ChessBoard, strong: Color, eval_params: &EvalParams) -> i32 {
+    let evaluation = material(board, strong, eval_params);
+    let Some((strong_king, weak_king)) = kings(board, strong) else {
+        return evaluation;
+    };
+    evaluation + KNOWN_WIN + edge_distance(weak_king) * EDGE_WEIGHT + proximity(strong_king, weak_king)
+}
+
+/// Steps from the center in both directions, 0 on the central squares and 6 in a corner.
+fn edge_distance(king: (usize, usize)) -> i32 {
+    let center_distance = |x: usize| (3 - x as i32).max(x as i32 - 4);
+    center_distance(king.0) + center_distance(king.1)
+}
+
+fn drive_to_corners(board: &ChessBoard, strong: Color, eval_params: &EvalParams, corners: &[(usize, usize)]) -> i32 {
+    let evaluation = material(board, strong, eval_params);
+    let Some((strong_king, weak_king)) = kings(board, strong) else {
+        return evaluation;
+    };
+    let corner_distance = corners.iter().map(|&corner| distance(weak_king, corner)).min().unwrap();
+    evaluation + KNOWN_WIN + (7 - corner_distance) * CORNER_WEIGHT + proximity(strong_king, weak_king)
+}
+
+/// Two bishops mate in any corner.
+fn drive_to_corner(board: &ChessBoard, strong: Color, eval_params: &EvalParams) -> i32 {
+    drive_to_corners(board, strong, eval_params, &[(0, 0), (0, 7), (7, 0), (7, 7)])
+}
+
+/// Bishop and knight only mate in a corner of the bishop's color.
+fn drive_to_bishop_corner(board: &ChessBoard, strong: Color, eval_params: &EvalParams) -> i32 {
+    let bishop = (0..64).map(|i| (i / 8, i % 8)).find(|&(row, col)| {
+        matches!(board.squares[row][col], Square::Occupied(piece) if piece.color == strong && piece.kind == PieceType::Bishop)
+    });
+    match bishop {
+        // a1 and h8 are dark squares
+        Some((row, col)) if (row + col) % 2 == 0 => drive_to_corners(board, strong, eval_params, &[(0, 0), (7, 7)]),
+        Some(_) => drive_to_corners(board, strong, eval_params, &[(0, 7), (7, 0)]),
+        None => material(board, strong, eval_params),
+    }
+}
+
+/// Neither side can mate.
+fn draw(_: &ChessBoard, _: Color, _: &EvalParams) -> i32 {
+    0
+}
+
+/// Nudges the evaluation of a clearly won position, where the weak side has nothing but the king and pawns, towards
+/// progress. A stalemate is a draw, so the quiescence search standing pat on one does not score it as a win, and a
+/// weak side without legal moves is penalized as it is one waiting move away from it. With `drive_king` a bare weak
+/// king is also driven to the edge, recognized endgames do that in their own evaluation.
+pub fn make_progress(board: &ChessBoard, evaluation: i32, drive_king: bool) -> i32 {
+    if evaluation.abs() < CLEAR_WIN {
+        return evaluation;
+    }
+    let (strong, sign) = if evaluation > 0 {
+        (Color::White, 1)
+    } else {
+        (Color::Black, -1)
+    };
+    let weak = opponent(strong);
+    let mut bare_king = true;
+    for square in board.squares.iter().flatten() {
+        if let Square::Occupied(piece) = square {
+            if piece.color == weak && piece.kind != PieceType::King {
+                if piece.kind != PieceType::Pawn {
+                    return evaluation;
+                }
+                bare_king = false;
+            }
+        }
+    }
+    let Some(weak_king) = board.find_king_position(weak) else {
+        return evaluation;
+    };
+    let mut weak_to_move = board.clone();
+    weak_to_move.active_color = weak;
+    weak_to_move.en_passant = None;
+    let mut evaluation = evaluation;
+    if !weak_to_move.has_legal_move() && !weak_to_move.is_in_check() {
+        if board.active_color == weak {
+            return 0;
+        }
+        evaluation -= sign * NO_MOBILITY_PENALTY;
+    }
+    if drive_king && bare_king {
+        evaluation += sign * edge_distance((weak_king.row(), weak_king.col())) * EDGE_WEIGHT;
+    }
+    evaluation
+}
+
+/// Evaluates recognized endgames from White's point of view, returns None for other positions.
+pub fn evaluate(eval_params: &EvalParams, board: &ChessBoard) -> Option<i32> {
+    // Most positions have too much material for a recognizer
+    let material = board.material_signature();
+    if material.pieces() > 4 {
+        return None;
+    }
+    let white = signature_letters(&material, Color::White);
+    let black = signature_letters(&material, Color::Black);
+    for (strong, signature) in [(Color::White, white.clone() + &black), (Color::Black, black + &white)] {
+        if let Some(recognizer) = RECOGNIZERS.iter().find(|r| r.signature == signature) {
+            return Some(match strong {
+                Color::White => (recognizer.evaluate)(board, strong, eval_params),
+                Color::Black => -(recognizer.evaluate)(board, strong, eval_params),
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate_fen(fen: &str) -> Option<i32> {
+        evaluate(&EvalParams::default(), &ChessBoard::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn test_recognized_endgames() {
+        assert_eq!(
+            evaluate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            None
+        );
+        assert_eq!(evaluate_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1"), None);
+        assert_eq!(evaluate_fen("4k3/8/8/8/8/8/8/2N1K3 w - - 0 1"), Some(0));
+        assert_eq!(evaluate_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), Some(0));
+
+        // The weak king is better off in the center, the strong side wants the kings close
+        let center = evaluate_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        let edge = evaluate_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let close = evaluate_fen("4k3/8/4K3/8/8/8/8/R7 w - - 0 1").unwrap();
+        assert!(KNOWN_WIN < center && center < edge && edge < close);
+        assert_eq!(evaluate_fen("r3k3/8/8/8/8/8/8/4K3 w - - 0 1"), Some(-edge));
+
+        // With a dark-squared bishop the mate is on a1 or h8
+        let right_corner = evaluate_fen("7k/8/5K2/8/8/8/8/2B1N3 w - - 0 1").unwrap();
+        let wrong_corner = evaluate_fen("k7/8/2K5/8/8/8/8/2B1N3 w - - 0 1").unwrap();
+        assert!(right_corner > wrong_corner);
+    }
+
+    #[test]
+    fn test_make_progress() {
+        let progress = |fen: &str, drive_king| {
+            let board = ChessBoard::from_fen(fen).unwrap();
+            let evaluation = evaluate(&EvalParams::default(), &board).unwrap_or(9_000);
+            make_progress(&board, evaluation, drive_king) - evaluation
+        };
+        // Black is stalemated, with White to move the king has to be given room
+        let stalemate = ChessBoard::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(make_progress(&stalemate, 20_000, false), 0);
+        assert_eq!(progress("k7/2Q5/1K6/8/8/8/8/8 w - - 0 1", false), -NO_MOBILITY_PENALTY);
+        assert_eq!(
+            progress("k7/2Q5/1K6/8/8/8/8/8 w - - 0 1", true),
+            -NO_MOBILITY_PENALTY + 6 * EDGE_WEIGHT
+        );
+        assert_eq!(progress("k7/8/1K6/8/8/8/8/2Q5 w - - 0 1", false), 0);
+        // Mate is no stalemate, and for Black the penalty counts the other way
+        let mate = ChessBoard::from_fen("kQ6/8/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(make_progress(&mate, 20_000, false), 20_000);
+        assert_eq!(progress("8/8/8/8/8/1k6/2q5/K7 b - - 0 1", false), NO_MOBILITY_PENALTY);
+
+        // A blocked pawn does not move either, a weak king with pawns is not driven
+        let blocked = ChessBoard::from_fen("k7/p1Q5/P1K5/8/8/8/8/7R b - - 0 1").unwrap();
+        assert_eq!(make_progress(&blocked, 15_000, true), 0);
+        assert_eq!(progress("8/8/8/3k4/8/8/P7/Q3K3 w - - 0 1", true), 0);
+        assert_eq!(progress("3k4/8/8/8/8/8/P7/Q3K3 w - - 0 1", true), 3 * EDGE_WEIGHT);
+        // Not clearly won, or the weak side still has a piece
+        let board = ChessBoard::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(make_progress(&board, 3_000, true), 3_000);
+        let board = ChessBoard::from_fen("kn6/8/1K6/8/8/8/8/Q7 b - - 0 1").unwrap();
+        assert_eq!(make_progress(&board, 6_000, true), 6_000);
+    }
+}
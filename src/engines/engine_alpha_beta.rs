@@ -1,47 +1,471 @@
-use crate::chess_board::{ChessBoard, Color, Move, PieceType, Square};
-use crate::engines::{ChessEngine, InfoCallback};
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::chess_board::move_list::MoveList;
+use crate::chess_board::render::RenderOptions;
+use crate::chess_board::{ChessBoard, Color, Move};
+use crate::engines::board::Board;
+use crate::engines::eval_params::EvalParams;
+use crate::engines::evaluation::{Evaluator, TunedEvaluator};
+use crate::engines::preset::Preset;
+use crate::engines::search_stack::{Frame, SearchStack};
+use crate::engines::search_stats::SearchStats;
+use crate::engines::search_trace::{NodeKind, SearchTrace, TraceNode, TraceRecord};
+use crate::engines::skill::Skill;
+use crate::engines::transposition_table::{Bound, TranspositionTable, TtEntry, DEFAULT_HASH_MB};
+use crate::engines::{effective_thread_count, ChessEngine, InfoCallback, InfoEvent, InfoSink};
 use rand::prelude::SliceRandom;
-use std::sync::atomic::AtomicBool;
+use std::cmp::Reverse;
 use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize};
 use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 #[cfg(target_arch = "wasm32")]
-use web_time::{Instant, SystemTime};
+use web_time::Instant;
 
-pub const MAX_PLY: usize = 20;
+/// The deepest ply searched, deeper nodes only get the quiescence search. The search stack grows with the search, so
+/// the limit is only a guard against runaway lines.
+pub const MAX_PLY: usize = 128;
 const MIN_EVALUATION: i32 = i32::MIN + 1; // +1 is important because -MIN is not a i32 number
-const WIN: i32 = 10_000_000;
+pub const WIN: i32 = 10_000_000;
 const LOSS: i32 = -10_000_000;
 const DRAW: i32 = 0;
+const FIRST_ITERATION_TIME: Duration = Duration::from_secs(60 * 60);
+/// Halfmoves over which the evaluation fades, at the fifty-move draw it is scaled to half.
+const FIFTY_MOVE_SCALE: i32 = 200;
+/// Root moves are only reported once the search runs this long, short searches would just flood the GUI.
+const CURRMOVE_DELAY: Duration = Duration::from_secs(1);
+/// Time between two reports of the hash table fill during a search.
+const HASHFULL_INTERVAL: Duration = Duration::from_secs(1);
+/// Default margin per ply of depth by which the other moves have to stay below the hash move for it to be singular,
+/// in 1/1000 pawn like the evaluation.
+pub const DEFAULT_SINGULAR_MARGIN: i32 = 50;
+/// Default of how far a capture may fall short of alpha, even winning its optimistic material gain, before the
+/// quiescence search skips it. Covers the positional swing of a capture, in 1/1000 pawn.
+pub const DEFAULT_DELTA_MARGIN: i32 = 2_000;
+/// Default margin per ply of depth by which the static evaluation has to stay below alpha before the quiet moves of
+/// a node next to the quiescence search are skipped, in 1/1000 pawn.
+pub const DEFAULT_FUTILITY_MARGIN: i32 = 1_500;
+/// The deepest node that prunes futile quiet moves.
+const FUTILITY_MAX_DEPTH: i32 = 2;
+/// Shallower nodes are not worth the reduced search of the singular extension.
+const SINGULAR_MIN_DEPTH: i32 = 6;
+/// How much shallower than the node the hash entry may be to be trusted for a singular extension.
+const SINGULAR_TT_DEPTH: i32 = 3;
 
-pub struct AlphaBetaEngine {
-    board: ChessBoard,
-    principal_variation: [([Move; MAX_PLY], usize); MAX_PLY],
+/// The moves of the line up to the first one that is not legal, so a reported principal variation can always be
+/// played.
+fn legal_line<B: Board>(board: &B, line: &[Move]) -> Vec<Move> {
+    let mut board = board.clone();
+    let mut legal = Vec::new();
+    for &mv in line {
+        if !board.is_legal(mv) {
+            break;
+        }
+        board.make_move(mv);
+        legal.push(mv);
+    }
+    legal
+}
+
+fn pv_string(pv: &[Move]) -> String {
+    pv.iter().map(|mv| mv.as_algebraic()).collect::<Vec<_>>().join(" ")
+}
+
+/// Checks that the evaluation does not favor a color: the mirrored position, with colors and ranks flipped, has to
+/// evaluate to the negated score. Returns the score of the position and of the mirrored one if it does not.
+pub fn evaluation_asymmetry(evaluator: &dyn Evaluator, board: &ChessBoard) -> Option<(i32, i32)> {
+    let score = AlphaBetaEngine::evaluate_board(evaluator, board);
+    let mirrored = AlphaBetaEngine::evaluate_board(evaluator, &board.mirror());
+    (score != -mirrored).then_some((score, mirrored))
+}
+
+/// Whether the engine takes a draw, by a claim or by accepting an offer, given the score of its last search. It only
+/// does when it believes it stands worse than a draw.
+pub fn accepts_draw(score: i32) -> bool {
+    score < DRAW
+}
+
+/// Searches any Board, by default the mailbox ChessBoard.
+pub struct AlphaBetaEngine<B: Board = ChessBoard> {
+    board: B,
+    /// The frames of the nodes on the current line with their principal variations and killer moves. Taken by the
+    /// search of a root move and passed down the recursion, it is kept between the searches for the killers and the
+    /// principal variation of the root.
+    stack: SearchStack,
     max_depth: usize,
     aborted: Arc<AtomicBool>,
     last_pvs: Vec<Move>,
+    threads: usize,
+    /// The engines of the other threads, they search root moves beside this one. Set up by every search.
+    helpers: Vec<AlphaBetaEngine<B>>,
+    /// Shared with the helpers.
+    evaluator: Arc<dyn Evaluator>,
+    stats: Option<SearchStats>,
+    /// The principal variation of the last completed iteration, searched first by the next one.
+    previous_pv: Vec<Move>,
+    /// Move lists for the nodes of the search, a node takes one and gives it back when it is done, so the lists are
+    /// only allocated for the deepest line. The lists of an aborted search are dropped.
+    // A list holds a fixed array of MAX_MOVES moves. Boxed, taking and giving back a list at every node moves a
+    // pointer instead of copying the array.
+    #[allow(clippy::vec_box)]
+    move_lists: Vec<Box<MoveList>>,
+    /// The deepest ply reached by the current iteration, quiescence search included.
+    seldepth: usize,
+    debug: bool,
+    /// Receives the events sent outside of a search call: debug messages and the fill of the hash table.
+    info_sink: Option<InfoSink>,
+    multi_pv: usize,
+    /// The root moves of the last completed iteration with their scores and principal variations, best first. Only
+    /// the best move is kept unless several lines are needed for MultiPV or the skill level.
+    root_lines: Vec<(i32, Vec<Move>)>,
+    max_nodes: u64,
+    /// Nodes the current iteration may search before it stops.
+    node_limit: u64,
+    /// When the current iteration stops.
+    deadline: Instant,
+    skill: Option<Skill>,
+    /// How much the engine avoids draws, subtracted from the draw score of the side to move at the root.
+    contempt: i32,
+    /// Margin per ply of the singular extension, None turns it and the multicut off.
+    singular_margin: Option<i32>,
+    multicut: bool,
+    /// Margin of the delta pruning in the quiescence search, None turns it off.
+    delta_margin: Option<i32>,
+    /// Margin per ply of the futility pruning, None turns it off.
+    futility_margin: Option<i32>,
+    /// Plies of captures the quiescence search follows, None follows them until the position is quiet.
+    quiescence_depth: Option<i32>,
+    /// Depth of the current iteration, extensions stop at twice this ply.
+    root_depth: i32,
+    /// Kept from one search and game to the next, every search starts a new generation of entries. Shared with the
+    /// helpers.
+    transposition_table: Arc<TranspositionTable>,
+    last_hashfull_report: Instant,
+    trace: Option<SearchTrace>,
+}
+
+/// Root moves listed by the debug output of an iteration.
+const DEBUG_ROOT_MOVES: usize = 8;
+
+/// The state of an iterative deepening between two calls of AlphaBetaEngine::continue_search.
+pub struct IterativeSearch {
+    start_time: Instant,
+    time_limit: Duration,
+    depth: i32,
+    iteration: Option<Iteration>,
+    total_node_count: u64,
+    best_move: Option<(Vec<Move>, i32, u64, i32)>,
+}
+
+pub enum SearchProgress {
+    Running,
+    /// The principal variation, score, nodes and depth of the last completed iteration.
+    Finished(Option<(Vec<Move>, i32, u64, i32)>),
+}
+
+/// A single depth of the iterative deepening, searched one root move at a time.
+struct Iteration {
+    depth: i32,
+    /// The root moves in search order.
+    moves: Vec<Move>,
+    next: usize,
+    /// The best move with its score and index in moves.
+    best: Option<(Move, i32, usize)>,
+    /// The score and principal variation of every searched root move, the root is searched with a full window so
+    /// the scores are exact.
+    root_lines: Vec<(i32, Vec<Move>)>,
+    node_count: u64,
+}
+
+enum RootStep {
+    Searched,
+    Complete,
+    Stopped,
+}
+
+/// The root moves of an iteration shared by the threads of a parallel root search.
+struct RootSplit<'a> {
+    moves: &'a [Move],
+    depth: i32,
+    /// The index of the next root move to search.
+    next: AtomicUsize,
+    /// The best score so far, a move has to beat it. Without a shared alpha every move gets a full window.
+    alpha: Option<AtomicI32>,
+}
+
+/// What a thread of the parallel root search found: the index, score and principal variation of the root moves that
+/// beat the alpha at their start.
+struct RootResults {
+    lines: Vec<(usize, i32, Vec<Move>)>,
+    node_count: u64,
+    stopped: bool,
+}
+
+impl Default for AlphaBetaEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AlphaBetaEngine {
     pub fn new() -> Self {
+        AlphaBetaEngine::with_board(ChessBoard::new())
+    }
+}
+
+impl<B: Board> AlphaBetaEngine<B> {
+    pub fn with_board(board: B) -> Self {
+        Self::with_table(board, Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)))
+    }
+
+    fn with_table(board: B, transposition_table: Arc<TranspositionTable>) -> Self {
         AlphaBetaEngine {
-            board: ChessBoard::new(),
-            principal_variation: [([Move::new(99, 99, 99, 99); MAX_PLY], 0); MAX_PLY],
-            max_depth: 20,
+            board,
+            stack: SearchStack::new(),
+            max_depth: MAX_PLY,
             aborted: Arc::new(AtomicBool::new(false)),
             last_pvs: Vec::new(),
+            threads: 1,
+            helpers: Vec::new(),
+            evaluator: Arc::new(TunedEvaluator::default()),
+            stats: None,
+            previous_pv: Vec::new(),
+            move_lists: Vec::new(),
+            seldepth: 0,
+            debug: false,
+            info_sink: None,
+            multi_pv: 1,
+            root_lines: Vec::new(),
+            max_nodes: u64::MAX,
+            node_limit: u64::MAX,
+            deadline: Instant::now(),
+            skill: None,
+            contempt: 0,
+            singular_margin: Some(DEFAULT_SINGULAR_MARGIN),
+            multicut: true,
+            delta_margin: Some(DEFAULT_DELTA_MARGIN),
+            futility_margin: Some(DEFAULT_FUTILITY_MARGIN),
+            quiescence_depth: None,
+            root_depth: 0,
+            transposition_table,
+            last_hashfull_report: Instant::now(),
+            trace: None,
         }
     }
 
-    pub fn with_board(board: ChessBoard) -> Self {
-        let mut engine = AlphaBetaEngine::new();
-        engine.board = board;
-        engine
+    /// Replaces the position by a board the caller set up and checked.
+    pub fn set_board(&mut self, board: B) {
+        self.board = board;
+    }
+
+    /// The position the next search starts from.
+    pub fn board(&self) -> &B {
+        &self.board
+    }
+
+    /// Evaluates with the tuned evaluation of these parameters.
+    pub fn set_eval_params(&mut self, eval_params: EvalParams) {
+        self.evaluator = Arc::new(TunedEvaluator::new(eval_params));
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.evaluator = Arc::from(evaluator);
+    }
+
+    /// Limits the iterative deepening, the search stops after this depth even if there is time left.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth.clamp(1, MAX_PLY);
+    }
+
+    /// Statistics are opt-in because counting costs time in every node.
+    pub fn set_collect_stats(&mut self, enabled: bool) {
+        self.stats = if enabled { Some(SearchStats::new()) } else { None };
+    }
+
+    /// Enables the debug diagnostics of the search: the time management, the root move ordering and the evaluation of
+    /// the root position. They are sent to the info sink as InfoEvent::String, one line per event.
+    pub fn set_debug(&mut self, enabled: bool) {
+        self.debug = enabled;
+    }
+
+    /// Kept from one search to the next, unlike the callback of a search call. Receives InfoEvent::String in debug
+    /// mode and InfoEvent::HashFull about once a second during a search.
+    pub fn set_info_sink(&mut self, sink: Option<InfoSink>) {
+        self.info_sink = sink;
+    }
+
+    fn send_info(&mut self, event: InfoEvent) {
+        if let Some(sink) = &mut self.info_sink {
+            sink(event);
+        }
+    }
+
+    /// The message is only formatted in debug mode.
+    fn debug(&mut self, message: impl FnOnce() -> String) {
+        if self.debug && self.info_sink.is_some() {
+            self.send_info(InfoEvent::String(message()));
+        }
+    }
+
+    /// The number of principal variations kept by multi_pv_lines.
+    pub fn set_multi_pv(&mut self, multi_pv: usize) {
+        self.multi_pv = multi_pv.max(1);
+    }
+
+    /// The best root moves of the last completed iteration with their scores, best first.
+    pub fn multi_pv_lines(&self) -> &[(i32, Vec<Move>)] {
+        &self.root_lines[..self.root_lines.len().min(self.multi_pv)]
+    }
+
+    /// Limits the nodes of a search. Like the time limit, it does not apply to the first iteration.
+    pub fn set_max_nodes(&mut self, max_nodes: u64) {
+        self.max_nodes = max_nodes;
+    }
+
+    /// Plays weaker than the engine can: the skill level bounds depth and nodes and picks a near-best move.
+    pub fn set_skill(&mut self, skill: Option<Skill>) {
+        self.skill = skill;
+    }
+
+    /// A positive contempt makes the engine play on when it believes it is stronger, a negative one makes it seek a
+    /// draw. In 1/1000 pawn like the evaluation.
+    pub fn set_contempt(&mut self, contempt: i32) {
+        self.contempt = contempt;
+    }
+
+    /// Extends the hash move when the reduced search of all other moves stays below its score by margin per ply of
+    /// depth. None turns the singular extension off, and with it the multicut.
+    pub fn set_singular_margin(&mut self, margin: Option<i32>) {
+        self.singular_margin = margin;
+    }
+
+    /// Cuts a node off when the reduced search of the singular extension shows that another move besides the hash
+    /// move fails high.
+    pub fn set_multicut(&mut self, enabled: bool) {
+        self.multicut = enabled;
+    }
+
+    /// Skips captures in the quiescence search that stay below alpha by the margin even winning their material. None
+    /// searches every capture.
+    pub fn set_delta_margin(&mut self, margin: Option<i32>) {
+        self.delta_margin = margin;
+    }
+
+    /// Skips the quiet moves of nodes at depth 1 and 2 whose static evaluation stays below alpha by the margin per
+    /// ply of depth. None searches them.
+    pub fn set_futility_margin(&mut self, margin: Option<i32>) {
+        self.futility_margin = margin;
+    }
+
+    /// Stops the quiescence search after the plies of captures and takes the static evaluation. None follows the
+    /// captures until the position is quiet.
+    pub fn set_quiescence_depth(&mut self, depth: Option<i32>) {
+        self.quiescence_depth = depth;
+    }
+
+    /// Sets the pruning, extensions and quiescence search of the preset. The hash table keeps its size.
+    pub fn set_preset(&mut self, preset: Preset) {
+        let settings = preset.settings();
+        self.set_singular_margin(settings.singular_margin);
+        self.set_multicut(settings.multicut);
+        self.set_delta_margin(settings.delta_margin);
+        self.set_futility_margin(settings.futility_margin);
+        self.set_quiescence_depth(settings.quiescence_depth);
+    }
+
+    /// Replaces the hash table by an empty one of at most size_mb megabytes. Returns the size in bytes.
+    pub fn set_hash_size(&mut self, size_mb: usize) -> usize {
+        self.transposition_table = Arc::new(TranspositionTable::new(size_mb));
+        self.transposition_table.size_bytes()
+    }
+
+    /// Writes the nodes of the following searches to the trace, None stops tracing and closes the file.
+    pub fn set_trace(&mut self, trace: Option<SearchTrace>) {
+        self.trace = trace;
+    }
+
+    fn record_trace(&mut self, record: impl FnOnce() -> TraceRecord) {
+        if let Some(trace) = &mut self.trace {
+            trace.record(&record());
+        }
+    }
+
+    /// Records a node of the search if the trace includes its depth. The result holds the depth, score, bound and
+    /// best move of the node like a hash entry.
+    fn trace_node(&mut self, line: &[Move], alpha: i32, beta: i32, result: TtEntry, kind: NodeKind, moves: usize) {
+        if self.trace.as_ref().is_none_or(|trace| result.depth < trace.min_depth()) {
+            return;
+        }
+        let node = TraceNode {
+            iteration: self.root_depth,
+            line: pv_string(line),
+            depth: result.depth,
+            alpha,
+            beta,
+            score: result.score,
+            bound: result.bound,
+            best_move: result.best_move.map(|mv| mv.as_algebraic()),
+            kind,
+            moves,
+        };
+        self.record_trace(|| TraceRecord::Node(node));
+    }
+
+    pub fn clear_hash(&mut self) {
+        self.transposition_table.clear();
+    }
+
+    /// Forgets the previous game: the hash table, the killer moves, the lines and the repetitions of the game. The
+    /// board is back at the initial position, so the search of a game does not depend on the games before.
+    pub fn new_game(&mut self) {
+        self.transposition_table.clear();
+        self.stack.clear();
+        self.previous_pv.clear();
+        self.last_pvs.clear();
+        self.root_lines.clear();
+        self.helpers.clear();
+        self.board = B::from_fen(INITIAL_POSITION).expect("the initial position is valid");
+    }
+
+    fn report_hashfull(&mut self) {
+        if self.info_sink.is_none() || self.last_hashfull_report.elapsed() < HASHFULL_INTERVAL {
+            return;
+        }
+        self.last_hashfull_report = Instant::now();
+        self.send_info(InfoEvent::HashFull(self.transposition_table.hashfull()));
+    }
+
+    /// The score of a draw from the point of view of the side to move at the ply.
+    fn draw_score(&self, ply: usize) -> i32 {
+        if ply.is_multiple_of(2) {
+            DRAW - self.contempt
+        } else {
+            DRAW + self.contempt
+        }
+    }
+
+    fn depth_limit(&self) -> usize {
+        self.skill
+            .map_or(self.max_depth, |skill| skill.max_depth().min(self.max_depth))
+    }
+
+    fn nodes_limit(&self) -> u64 {
+        self.skill
+            .map_or(self.max_nodes, |skill| skill.max_nodes().min(self.max_nodes))
+    }
+
+    fn keeps_root_lines(&self) -> bool {
+        self.multi_pv > 1 || self.skill.is_some()
+    }
+
+    /// The statistics of the last search, if collecting them is enabled.
+    pub fn search_stats(&self) -> Option<&SearchStats> {
+        self.stats.as_ref()
     }
 }
-impl ChessEngine for AlphaBetaEngine {
+impl<B: Board> ChessEngine for AlphaBetaEngine<B> {
     fn name(&self) -> &str {
         "Chic Alpha Beta Engine"
     }
@@ -49,71 +473,45 @@ impl ChessEngine for AlphaBetaEngine {
         "Cyril Flaig"
     }
     fn set_position(&mut self, position: &str) -> Result<(), String> {
-        self.board = ChessBoard::from_fen(position)?;
+        self.board = B::from_fen(position)?;
         Ok(())
     }
-    fn make_move(&mut self, move_algebraic_notation: &str) -> Result<(), &'static str> {
-        let mv = Move::from_algebraic(move_algebraic_notation);
+    fn make_move(&mut self, move_algebraic_notation: &str) -> Result<(), String> {
+        let mv = self.board.legal_move_from_uci(move_algebraic_notation)?;
         self.board.make_move(mv);
         Ok(())
     }
     fn find_best_move_iterative(
         &mut self,
         time_limit: Duration,
-        info_callback: InfoCallback,
+        info_callback: InfoCallback<'_>,
     ) -> Option<(Vec<Move>, i32, u64, i32)> {
-        let mut best_move = None;
-        let mut total_node_count = 0;
-
-        self.aborted.store(false, Relaxed);
-
-        let start_time = Instant::now();
-        let mut depth = 1;
-
-        while start_time.elapsed() < time_limit {
-            let remaining_time = time_limit - start_time.elapsed();
-
-            // Call the existing find_best_move function for the current depth.
-            if let Some((current_move, current_score, node_count)) =
-                self.find_best_move_with_timeout(depth, false, remaining_time)
-            {
-                best_move = Some((
-                    self.principal_variation[0].0[0..self.principal_variation[0].1].to_vec(),
-                    current_score,
-                    total_node_count + node_count,
-                    depth,
-                ));
-                total_node_count += node_count;
-                let pv = self.principal_variation[0].0[0..self.principal_variation[0].1]
-                    .iter()
-                    .map(|mv| mv.as_algebraic())
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                info_callback(depth, current_score, total_node_count, start_time.elapsed(), pv);
-                self.last_pvs = self.principal_variation[0].0[0..self.principal_variation[0].1].iter().rev().map(|c|c.clone()).collect();
-
-                depth += 1; // Increase the depth for the next iteration
-            } else {
-                break;
+        let mut search = self.start_search(time_limit);
+        loop {
+            if let SearchProgress::Finished(best_move) = self.continue_search(&mut search, time_limit, info_callback) {
+                return best_move;
             }
         }
-
-        best_move
     }
     fn get_active_player(&self) -> Color {
-        self.board.active_color
+        self.board.active_color()
     }
 
     fn get_abort_channel(&self) -> Arc<AtomicBool> {
         self.aborted.clone()
     }
 
-    fn render_board(&self) {
-        println!("{}", self.board.render_to_string());
+    fn render_board(&self, options: &RenderOptions) {
+        println!("{}", self.board.render(options));
+    }
+
+    fn set_threads(&mut self, threads: usize) -> usize {
+        self.threads = effective_thread_count(threads);
+        self.threads
     }
 }
 
-impl AlphaBetaEngine {
+impl<B: Board> AlphaBetaEngine<B> {
     #[allow(dead_code)]
     pub fn find_best_move(&mut self, depth: i32, random: bool) -> Option<(Move, i32, u64)> {
         self.find_best_move_with_timeout(depth, random, Duration::from_secs(60 * 60))
@@ -124,101 +522,595 @@ impl AlphaBetaEngine {
         random: bool,
         remaining_time: Duration,
     ) -> Option<(Move, i32, u64)> {
-        let mut best_move = None;
-        let mut best_score = i32::MIN;
-        let mut node_count = 0;
+        let mut iteration = self.start_iteration(depth, random, remaining_time, u64::MAX);
+        loop {
+            match self.search_next_root_move(&mut iteration) {
+                RootStep::Searched => {}
+                RootStep::Complete => return iteration.best.map(|(mv, score, _)| (mv, score, iteration.node_count)),
+                RootStep::Stopped => return None,
+            }
+        }
+    }
 
-        let deadline = Instant::now() + remaining_time;
+    /// Resets the state of the previous search and starts the iterative deepening, which is run by continue_search.
+    pub fn start_search(&mut self, time_limit: Duration) -> IterativeSearch {
+        self.aborted.store(false, Relaxed);
+        if let Some(stats) = &mut self.stats {
+            *stats = SearchStats::new();
+        }
+        self.stack.clear();
+        self.previous_pv.clear();
+        self.root_lines.clear();
+        self.transposition_table.age();
+        self.last_hashfull_report = Instant::now();
+        self.helpers = (1..self.threads).map(|_| self.helper()).collect();
+        let max_depth = self.depth_limit();
+        self.debug(|| {
+            format!(
+                "Search time limit {} ms, maximum depth {}",
+                time_limit.as_millis(),
+                max_depth
+            )
+        });
+        if let Some(skill) = self.skill {
+            let max_nodes = self.nodes_limit();
+            self.debug(|| format!("Skill level of {} Elo, at most {} nodes", skill.elo(), max_nodes));
+        }
+        if self.debug && self.info_sink.is_some() {
+            for line in self.board.evaluation_breakdown(&*self.evaluator) {
+                self.debug(|| line);
+            }
+            let entries = self.transposition_table.capacity();
+            let hashfull = self.transposition_table.hashfull();
+            self.debug(|| format!("Hash table of {} entries, {} per mille used", entries, hashfull));
+        }
+        if let Some(min_depth) = self.trace.as_ref().map(|trace| trace.min_depth()) {
+            let fen = self.board.to_fen();
+            self.record_trace(|| TraceRecord::Start { fen, min_depth });
+        }
+        IterativeSearch {
+            start_time: Instant::now(),
+            time_limit,
+            depth: 1,
+            iteration: None,
+            total_node_count: 0,
+            best_move: None,
+        }
+    }
+
+    /// Searches root moves until step_time has passed and returns, so a caller without threads can keep its event
+    /// loop running in between. At least one root move is searched per call.
+    pub fn continue_search(
+        &mut self,
+        search: &mut IterativeSearch,
+        step_time: Duration,
+        info_callback: InfoCallback<'_>,
+    ) -> SearchProgress {
+        let step_end = Instant::now().checked_add(step_time);
+        loop {
+            if search.iteration.is_none() {
+                // The first iteration always completes, so there is a move even for very short time limits.
+                let depth = search.depth;
+                if depth > self.depth_limit() as i32 {
+                    self.debug(|| format!("Maximum depth {} reached", depth - 1));
+                    return self.finish_search(search);
+                }
+                let nodes_limit = self.nodes_limit();
+                if depth > 1 && search.total_node_count >= nodes_limit {
+                    self.debug(|| format!("Depth {} not started, node limit {} reached", depth, nodes_limit));
+                    return self.finish_search(search);
+                }
+                if depth > 1 && search.start_time.elapsed() >= search.time_limit {
+                    self.debug(|| {
+                        format!(
+                            "Depth {} not started, {} ms of {} ms used",
+                            depth,
+                            search.start_time.elapsed().as_millis(),
+                            search.time_limit.as_millis()
+                        )
+                    });
+                    return self.finish_search(search);
+                }
+                let (remaining_time, node_limit) = if depth == 1 {
+                    (FIRST_ITERATION_TIME, u64::MAX)
+                } else {
+                    (
+                        search.time_limit - search.start_time.elapsed(),
+                        nodes_limit - search.total_node_count,
+                    )
+                };
+                search.iteration = Some(self.start_iteration(depth, false, remaining_time, node_limit));
+            }
+
+            let iteration = search.iteration.as_mut().unwrap();
+            if search.start_time.elapsed() >= CURRMOVE_DELAY {
+                if let Some(&mv) = iteration.moves.get(iteration.next) {
+                    info_callback(InfoEvent::CurrMove {
+                        depth: iteration.depth,
+                        mv,
+                        number: iteration.next + 1,
+                    });
+                }
+            }
+            let step = if self.helpers.is_empty() {
+                self.search_next_root_move(iteration)
+            } else {
+                self.search_root_moves_parallel(iteration)
+            };
+            match step {
+                RootStep::Searched => {
+                    // The first root move is the best of the previous iteration, a later one replacing it is news
+                    if let Some((_, score, index)) = iteration.best.filter(|&(_, _, index)| index > 0) {
+                        if index + 1 == iteration.next {
+                            info_callback(InfoEvent::NewBestMove {
+                                depth: iteration.depth,
+                                score,
+                                pv: pv_string(&legal_line(&self.board, self.stack.pv(0))),
+                            });
+                        }
+                    }
+                }
+                RootStep::Complete if iteration.best.is_some() => {
+                    let iteration = search.iteration.take().unwrap();
+                    self.complete_iteration(search, &iteration, info_callback);
+                }
+                RootStep::Complete => {
+                    search.iteration = None;
+                    self.debug(|| String::from("No legal move"));
+                    return self.finish_search(search);
+                }
+                RootStep::Stopped => {
+                    let iteration = search.iteration.take().unwrap();
+                    let reason = if self.aborted.load(Relaxed) {
+                        "aborted"
+                    } else if iteration.node_count >= self.node_limit {
+                        "stopped at the node limit"
+                    } else {
+                        "out of time"
+                    };
+                    self.debug(|| {
+                        format!(
+                            "Depth {} {} after {} of {} root moves, the last completed depth is played",
+                            iteration.depth,
+                            reason,
+                            iteration.next - 1,
+                            iteration.moves.len()
+                        )
+                    });
+                    return self.finish_search(search);
+                }
+            }
 
+            if step_end.is_some_and(|end| Instant::now() >= end) {
+                return SearchProgress::Running;
+            }
+        }
+    }
 
+    fn complete_iteration(
+        &mut self,
+        search: &mut IterativeSearch,
+        iteration: &Iteration,
+        info_callback: InfoCallback<'_>,
+    ) {
+        let (best_move, current_score, best_index) = iteration.best.unwrap();
+        let node_count = iteration.node_count;
+        self.debug(|| {
+            let shown: Vec<String> = iteration
+                .moves
+                .iter()
+                .take(DEBUG_ROOT_MOVES)
+                .map(|mv| mv.as_algebraic())
+                .collect();
+            let more = iteration.moves.len().saturating_sub(DEBUG_ROOT_MOVES);
+            format!(
+                "Depth {} root moves in search order: {}{}",
+                iteration.depth,
+                shown.join(" "),
+                if more > 0 {
+                    format!(" and {} more", more)
+                } else {
+                    String::new()
+                }
+            )
+        });
+        let previous_best = self.previous_pv.first().copied();
+        self.debug(|| {
+            let change = match previous_best {
+                Some(previous) if previous != best_move => format!(", changed from {}", previous.as_algebraic()),
+                Some(_) => String::from(", unchanged"),
+                None => String::new(),
+            };
+            format!(
+                "Depth {} best move {} found as root move {} of {}{}",
+                iteration.depth,
+                best_move.as_algebraic(),
+                best_index + 1,
+                iteration.moves.len(),
+                change
+            )
+        });
+        let pv = legal_line(&self.board, self.stack.pv(0));
+        search.total_node_count += node_count;
+        search.best_move = Some((pv.clone(), current_score, search.total_node_count, search.depth));
+        if let Some(stats) = &mut self.stats {
+            stats.record_iteration(node_count);
+        }
+        info_callback(InfoEvent::DepthCompleted {
+            depth: search.depth,
+            seldepth: self.seldepth as i32,
+            score: current_score,
+            nodes: search.total_node_count,
+            elapsed: search.start_time.elapsed(),
+            pv: pv_string(&pv),
+        });
+        let nodes = search.total_node_count;
+        self.record_trace(|| TraceRecord::Iteration {
+            depth: iteration.depth,
+            score: current_score,
+            nodes,
+            pv: pv_string(&pv),
+        });
+        self.root_lines = if self.keeps_root_lines() {
+            let mut root_lines = iteration.root_lines.clone();
+            // The sort is stable, so the best move stays first among equal scores
+            root_lines.sort_by_key(|&(score, _)| Reverse(score));
+            root_lines
+        } else {
+            vec![(current_score, pv.clone())]
+        };
+        self.last_pvs = pv.iter().rev().copied().collect();
+        self.previous_pv = pv;
+
+        search.depth += 1; // Increase the depth for the next iteration
+    }
+
+    /// Ends the search with the best move of the last completed iteration, or a near-best one at a skill level.
+    fn finish_search(&mut self, search: &mut IterativeSearch) -> SearchProgress {
+        let best_move = search.best_move.take();
+        let Some(skill) = self.skill else {
+            return SearchProgress::Finished(best_move);
+        };
+        let (_, score, nodes, depth) = match best_move {
+            Some(best_move) => best_move,
+            None => return SearchProgress::Finished(None),
+        };
+        let index = skill.choose_line(&self.root_lines, &mut rand::thread_rng());
+        let (chosen_score, chosen_pv) = self.root_lines[index].clone();
+        let line_count = self.root_lines.len();
+        self.debug(|| {
+            format!(
+                "Skill level plays root move {} of {} with score {} instead of {}",
+                index + 1,
+                line_count,
+                chosen_score / 10,
+                score / 10
+            )
+        });
+        SearchProgress::Finished(Some((chosen_pv, chosen_score, nodes, depth)))
+    }
+
+    fn start_iteration(&mut self, depth: i32, random: bool, remaining_time: Duration, node_limit: u64) -> Iteration {
+        self.node_limit = node_limit;
+        self.deadline = Instant::now() + remaining_time;
+        self.seldepth = 0;
+        self.root_depth = depth;
         let mut moves = self.board.generate_legal_moves();
         if random {
             moves.shuffle(&mut rand::thread_rng());
         }
+        Iteration {
+            depth,
+            moves,
+            next: 0,
+            best: None,
+            root_lines: Vec::new(),
+            node_count: 0,
+        }
+    }
+
+    fn search_next_root_move(&mut self, iteration: &mut Iteration) -> RootStep {
+        let Some(&mv) = iteration.moves.get(iteration.next) else {
+            return RootStep::Complete;
+        };
+        iteration.next += 1;
+        if Instant::now() > self.deadline || self.aborted.load(Relaxed) {
+            return RootStep::Stopped;
+        }
+        let mut new_board = self.board.clone();
+        new_board.make_move(mv);
+        let mut stack = std::mem::take(&mut self.stack);
+        stack.frame_mut(0).current_move = Some(mv);
+
+        let score = self.negamax(
+            &mut stack,
+            &new_board,
+            iteration.depth,
+            MIN_EVALUATION,
+            -MIN_EVALUATION,
+            1,
+            &mut iteration.node_count,
+        );
+        self.stack = stack;
+        let score = match score {
+            None => return RootStep::Stopped,
+            Some(score) => -score,
+        };
+
+        if self.keeps_root_lines() {
+            let mut line = vec![mv];
+            line.extend_from_slice(self.stack.pv(1));
+            iteration.root_lines.push((score, legal_line(&self.board, &line)));
+        }
+        if iteration.best.is_none_or(|(_, best_score, _)| score > best_score) {
+            iteration.best = Some((mv, score, iteration.next - 1));
+            self.stack.save_pv(mv, 0);
+        }
+        RootStep::Searched
+    }
 
-        let mut alpha = MIN_EVALUATION;
-        for mv in moves {
-            if Instant::now() > deadline || self.aborted.load(Relaxed) {
-                return None;
+    /// An engine for another thread of the search, with the position and settings of this one. It shares the hash
+    /// table, the evaluator and the abort flag.
+    fn helper(&self) -> Self {
+        let mut helper = Self::with_table(self.board.clone(), self.transposition_table.clone());
+        helper.evaluator = self.evaluator.clone();
+        helper.aborted = self.aborted.clone();
+        helper.contempt = self.contempt;
+        helper.singular_margin = self.singular_margin;
+        helper.multicut = self.multicut;
+        helper.delta_margin = self.delta_margin;
+        helper.futility_margin = self.futility_margin;
+        helper.quiescence_depth = self.quiescence_depth;
+        helper
+    }
+
+    /// Searches the remaining root moves of the iteration on all threads, a thread takes the next root move until
+    /// none is left. Unless the root lines are kept, the best score so far is shared as alpha, so the other moves only
+    /// have to be refuted. The node limit is split between the threads.
+    fn search_root_moves_parallel(&mut self, iteration: &mut Iteration) -> RootStep {
+        let threads = self.helpers.len() as u64 + 1;
+        let node_limit = self.node_limit;
+        self.node_limit = node_limit.saturating_sub(iteration.node_count) / threads;
+        let mut helpers = std::mem::take(&mut self.helpers);
+        for helper in &mut helpers {
+            helper.deadline = self.deadline;
+            helper.node_limit = self.node_limit;
+            helper.root_depth = self.root_depth;
+            helper.seldepth = 0;
+            helper.previous_pv.clone_from(&self.previous_pv);
+        }
+        let split = RootSplit {
+            moves: &iteration.moves,
+            depth: iteration.depth,
+            next: AtomicUsize::new(iteration.next),
+            alpha: (!self.keeps_root_lines())
+                .then(|| AtomicI32::new(iteration.best.map_or(MIN_EVALUATION, |(_, score, _)| score))),
+        };
+        let results: Vec<RootResults> = thread::scope(|scope| {
+            let workers: Vec<_> = helpers
+                .iter_mut()
+                .map(|helper| scope.spawn(|| helper.search_split_root_moves(&split)))
+                .collect();
+            let mut results = vec![self.search_split_root_moves(&split)];
+            results.extend(workers.into_iter().map(|worker| worker.join().unwrap()));
+            results
+        });
+        iteration.next = split.next.load(Relaxed).min(iteration.moves.len());
+        self.node_limit = node_limit;
+        self.seldepth = helpers
+            .iter()
+            .map(|helper| helper.seldepth)
+            .fold(self.seldepth, usize::max);
+        self.helpers = helpers;
+
+        let mut lines = Vec::new();
+        let mut stopped = false;
+        for result in results {
+            iteration.node_count += result.node_count;
+            stopped |= result.stopped;
+            lines.extend(result.lines);
+        }
+        if stopped {
+            return RootStep::Stopped;
+        }
+        // In search order, so the first of equal scores is the best move like in the search on one thread
+        lines.sort_by_key(|&(index, _, _)| index);
+        for (index, score, line) in lines {
+            if self.keeps_root_lines() {
+                iteration.root_lines.push((score, legal_line(&self.board, &line)));
+            }
+            if iteration.best.is_none_or(|(_, best_score, _)| score > best_score) {
+                iteration.best = Some((line[0], score, index));
+                self.stack.frame_mut(0).pv = line;
+            }
+        }
+        RootStep::Complete
+    }
+
+    /// The loop of a thread of the parallel root search.
+    fn search_split_root_moves(&mut self, split: &RootSplit) -> RootResults {
+        let mut results = RootResults {
+            lines: Vec::new(),
+            node_count: 0,
+            stopped: false,
+        };
+        loop {
+            let index = split.next.fetch_add(1, Relaxed);
+            let Some(&mv) = split.moves.get(index) else {
+                return results;
+            };
+            if Instant::now() > self.deadline || self.aborted.load(Relaxed) {
+                results.stopped = true;
+                return results;
             }
+            let alpha = split.alpha.as_ref().map_or(MIN_EVALUATION, |alpha| alpha.load(Relaxed));
             let mut new_board = self.board.clone();
             new_board.make_move(mv);
-
-            let score = match self.negamax(
+            let mut stack = std::mem::take(&mut self.stack);
+            stack.frame_mut(0).current_move = Some(mv);
+            let score = self.negamax(
+                &mut stack,
                 &new_board,
-                depth,
+                split.depth,
                 MIN_EVALUATION,
-                -MIN_EVALUATION,
+                -alpha,
                 1,
-                deadline,
-                &mut node_count,
-            ) {
-                None => return None,
-                Some(score) => -score,
+                &mut results.node_count,
+            );
+            self.stack = stack;
+            let Some(score) = score else {
+                results.stopped = true;
+                return results;
             };
-
-            if score > best_score {
-                alpha = score;
-                best_score = score;
-                best_move = Some(mv);
-                self.save_principal_variation(mv, depth as usize, 0);
+            let score = -score;
+            // A move that does not beat alpha only has an upper bound and is not the best
+            if score > alpha {
+                if let Some(alpha) = &split.alpha {
+                    alpha.fetch_max(score, Relaxed);
+                }
+                let mut line = vec![mv];
+                line.extend_from_slice(self.stack.pv(1));
+                results.lines.push((index, score, line));
             }
-            //println!("With depth {} Move: {} Score: {}", depth, mv.as_algebraic(), score);
         }
-
-        best_move.map(|mv| (mv, best_score, node_count))
     }
 
+    /// The excluded move of the frame is skipped, the result is then not stored in the hash table since it does not
+    /// hold for the position.
+    #[allow(clippy::too_many_arguments)]
     fn negamax(
         &mut self,
-        board: &ChessBoard,
+        stack: &mut SearchStack,
+        board: &B,
         depth: i32,
         alpha: i32,
         beta: i32,
         ply: usize,
-        deadline: Instant,
         node_count: &mut u64,
     ) -> Option<i32> {
-        if Instant::now() > deadline || self.aborted.load(Relaxed) {
+        if Instant::now() > self.deadline || self.aborted.load(Relaxed) || *node_count >= self.node_limit {
             return None;
         }
         *node_count += 1;
+        if node_count.is_multiple_of(1024) {
+            self.report_hashfull();
+        }
 
-        self.principal_variation[ply].1 = 0;
+        let excluded = stack.enter(ply).excluded;
 
         if board.is_threefold_repetition() {
-            return Some(0);
+            return Some(self.draw_score(ply));
+        }
+        // Checkmate takes precedence over the fifty-move rule
+        if board.is_draw_by_fifty_move_rule() && (!board.is_in_check() || board.has_legal_move()) {
+            return Some(self.draw_score(ply));
         }
 
         if depth <= 0 || ply > MAX_PLY {
             *node_count -= 1;
-            return AlphaBetaEngine::quiescence_search_prunning(
-                board,
-                node_count,
-                alpha,
-                beta,
-                deadline,
-                &self.aborted,
-            );
+            let main_nodes = *node_count;
+            let quiescence_depth = self.quiescence_depth.unwrap_or(i32::MAX);
+            let score = self.quiescence_search_prunning(board, node_count, alpha, beta, ply, quiescence_depth);
+            if let Some(stats) = &mut self.stats {
+                stats.quiescence_nodes += *node_count - main_nodes;
+            }
+            return score;
+        }
+
+        if let Some(stats) = &mut self.stats {
+            stats.nodes += 1;
+        }
+        self.seldepth = self.seldepth.max(ply);
+
+        let hash = board.zobrist_hash();
+        let tt_entry = self.transposition_table.probe(hash);
+        if let Some(stats) = &mut self.stats {
+            stats.record_tt_probe(tt_entry.is_some());
+        }
+        if let Some(entry) = tt_entry.filter(|entry| entry.depth >= depth && excluded.is_none()) {
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => entry.score >= beta,
+                Bound::Upper => entry.score <= alpha,
+            };
+            if cutoff {
+                if let (Bound::Exact, Some(mv)) = (entry.bound, entry.best_move) {
+                    stack.frame_mut(ply).pv.push(mv);
+                }
+                self.trace_node(&stack.line(ply), alpha, beta, entry, NodeKind::HashCutoff, 0);
+                return Some(entry.score);
+            }
         }
 
+        let original_alpha = alpha;
         let mut alpha = alpha;
         let mut max_score = MIN_EVALUATION;
+        let mut best_move = None;
 
-        let moves = board.generate_legal_moves();
-        if moves.is_empty() {
-            // Handle checkmate or stalemate
-            if board.is_checkmate() {
-                return Some(LOSS - depth);
-            } else if board.is_stalemate() {
-                return Some(DRAW);
+        let mut singular_move = None;
+        if let Some((tt_move, singular_beta)) = self.singular_candidate(tt_entry, depth, ply, excluded) {
+            let reduced_depth = (depth - 1) / 2;
+            stack.frame_mut(ply).excluded = Some(tt_move);
+            let score = self.negamax(
+                stack,
+                board,
+                reduced_depth,
+                singular_beta - 1,
+                singular_beta,
+                ply,
+                node_count,
+            );
+            stack.frame_mut(ply).excluded = None;
+            let score = score?;
+            if score < singular_beta {
+                singular_move = Some(tt_move);
+                if let Some(stats) = &mut self.stats {
+                    stats.singular_extensions += 1;
+                }
+            } else if self.multicut && singular_beta >= beta {
+                // The hash move and another move fail high, one of them will hold
+                if let Some(stats) = &mut self.stats {
+                    stats.multicuts += 1;
+                }
+                let result = TtEntry {
+                    best_move: Some(tt_move),
+                    score: singular_beta,
+                    depth,
+                    bound: Bound::Lower,
+                };
+                self.trace_node(&stack.line(ply), alpha, beta, result, NodeKind::Multicut, 0);
+                return Some(singular_beta);
             }
         }
 
-        for mv in moves {
+        let futility_score = self.futility_score(stack.frame_mut(ply), board, depth, alpha, beta);
+        let hash_move = self.pv_move(stack, ply).or(tt_entry.and_then(|entry| entry.best_move));
+        let mut move_list = self.move_lists.pop().unwrap_or_default();
+        let moves = board.ordered_moves(hash_move, stack.frame_mut(ply).killers, &mut move_list);
+        let mut has_legal_move = false;
+        let mut searched = 0;
+
+        for (index, mv) in moves.enumerate() {
+            if Some(mv) == excluded {
+                continue;
+            }
+            has_legal_move = true;
+            searched += 1;
             let mut new_board = board.clone();
             new_board.make_move(mv);
-            let score = match self.negamax(&new_board, depth - 1, -beta, -alpha, ply + 1, deadline, node_count) {
+            // A quiet move cannot lift a node that far below alpha, the score is raised by the margin as its bound
+            if let Some(futility_score) = futility_score {
+                if searched > 1 && !board.is_noisy(mv) && !new_board.is_in_check() {
+                    max_score = max_score.max(futility_score);
+                    if let Some(stats) = &mut self.stats {
+                        stats.futility_prunes += 1;
+                    }
+                    continue;
+                }
+            }
+            stack.frame_mut(ply).current_move = Some(mv);
+            let child_depth = depth - 1 + i32::from(singular_move == Some(mv));
+            let score = match self.negamax(stack, &new_board, child_depth, -beta, -alpha, ply + 1, node_count) {
                 None => {
                     return None;
                 }
@@ -226,60 +1118,164 @@ impl AlphaBetaEngine {
             };
             if score > max_score {
                 max_score = score;
+                best_move = Some(mv);
                 if score > alpha {
                     alpha = score;
-                    self.save_principal_variation(mv, depth as usize, ply);
+                    stack.save_pv(mv, ply);
                     if alpha >= beta {
                         // Beta cutoff fail soft
+                        if let Some(stats) = &mut self.stats {
+                            stats.record_cutoff(index);
+                        }
+                        if !board.is_noisy(mv) {
+                            stack.store_killer(mv, ply);
+                        }
                         break;
                     }
                 }
             }
         }
+        self.move_lists.push(move_list);
+
+        if !has_legal_move {
+            if excluded.is_some() {
+                // The excluded move is the only one
+                return Some(alpha);
+            }
+            // Handle checkmate or stalemate
+            return if board.is_in_check() {
+                Some(LOSS - depth)
+            } else {
+                Some(self.draw_score(ply))
+            };
+        }
 
+        let bound = if max_score >= beta {
+            Bound::Lower
+        } else if max_score > original_alpha {
+            Bound::Exact
+        } else {
+            // Every move failed low, none of them is known to be best
+            best_move = None;
+            Bound::Upper
+        };
+        if excluded.is_none() {
+            self.transposition_table.store(hash, best_move, max_score, depth, bound);
+            let result = TtEntry {
+                best_move,
+                score: max_score,
+                depth,
+                bound,
+            };
+            self.trace_node(
+                &stack.line(ply),
+                original_alpha,
+                beta,
+                result,
+                NodeKind::Searched,
+                searched,
+            );
+        }
         Some(max_score)
     }
 
-    fn save_principal_variation(&mut self, mv: Move, depth: usize, ply: usize) {
-        self.principal_variation[ply].0[0] = mv;
-        for i in 0..self.principal_variation[ply + 1].1 {
-            self.principal_variation[ply].0[i + 1] = self.principal_variation[ply + 1].0[i];
+    /// The static evaluation raised by the futility margin, if it stays below alpha at a node close enough to the
+    /// quiescence search. Not in check, where every move is an evasion, and not near mate scores. The static
+    /// evaluation is kept in the frame of the node.
+    fn futility_score(&self, frame: &mut Frame, board: &B, depth: i32, alpha: i32, beta: i32) -> Option<i32> {
+        let margin = self.futility_margin?;
+        if depth > FUTILITY_MAX_DEPTH || frame.excluded.is_some() || alpha.abs() >= WIN / 2 || beta.abs() >= WIN / 2 {
+            return None;
         }
-        self.principal_variation[ply].1 = self.principal_variation[ply + 1].1 + 1;
+        if board.is_in_check() {
+            return None;
+        }
+        let static_score = *frame.static_eval.get_or_insert_with(|| {
+            AlphaBetaEngine::evaluate_board(&*self.evaluator, board)
+                * if board.active_color() == Color::White { 1 } else { -1 }
+        });
+        let futility_score = static_score + margin * depth;
+        (futility_score <= alpha).then_some(futility_score)
     }
 
+    /// The hash move and the bound the other moves have to stay below for it to be singular, if the hash entry is deep
+    /// and reliable enough to try.
+    fn singular_candidate(
+        &self,
+        tt_entry: Option<TtEntry>,
+        depth: i32,
+        ply: usize,
+        excluded: Option<Move>,
+    ) -> Option<(Move, i32)> {
+        let margin = self.singular_margin?;
+        let entry = tt_entry?;
+        let tt_move = entry.best_move?;
+        let candidate = excluded.is_none()
+            && depth >= SINGULAR_MIN_DEPTH
+            && (ply as i32) < 2 * self.root_depth
+            && entry.bound != Bound::Upper
+            && entry.depth >= depth - SINGULAR_TT_DEPTH
+            && entry.score.abs() < WIN;
+        candidate.then_some((tt_move, entry.score - margin * depth))
+    }
+
+    /// The move of the previous principal variation, if the current node is on it.
+    fn pv_move(&self, stack: &SearchStack, ply: usize) -> Option<Move> {
+        if ply < self.previous_pv.len() && stack.follows(&self.previous_pv[..ply]) {
+            Some(self.previous_pv[ply])
+        } else {
+            None
+        }
+    }
+
+    /// Captures reset the fifty-move counter and never repeat a position, and stalemates are not detected here, so
+    /// the draws are all scored by negamax before it calls the quiescence search. Without depth left the static
+    /// evaluation stands.
     fn quiescence_search_prunning(
-        board: &ChessBoard,
+        &mut self,
+        board: &B,
         node_count: &mut u64,
         mut alpha: i32,
         beta: i32,
-        deadline: Instant,
-        aborted: &Arc<AtomicBool>,
+        ply: usize,
+        depth: i32,
     ) -> Option<i32> {
-        if Instant::now() > deadline || aborted.load(Relaxed) {
+        if Instant::now() > self.deadline || self.aborted.load(Relaxed) {
             return None;
         }
         *node_count += 1;
+        self.seldepth = self.seldepth.max(ply);
 
-        let stand_pat =
-            AlphaBetaEngine::evaluate_board(board) * if board.active_color == Color::White { 1 } else { -1 };
+        let stand_pat = AlphaBetaEngine::evaluate_board(&*self.evaluator, board)
+            * if board.active_color() == Color::White { 1 } else { -1 };
         let mut max_score = stand_pat;
         alpha = alpha.max(stand_pat);
 
-        if alpha >= beta {
+        if alpha >= beta || depth <= 0 {
             return Some(max_score);
         }
 
-        let moves = board.generate_legal_capture_moves();
+        let mut moves = self.move_lists.pop().unwrap_or_default();
+        board.generate_legal_capture_moves_into(&mut moves);
 
         //println!("Number of Capture Moves: {}", moves.len() );
 
-        for mv in moves {
+        // Only looked up for the delta pruning, a king in check has to escape whatever the material
+        let mut in_check = None;
+        for &(mv, _) in moves.iter() {
+            // Delta pruning, the gain is only estimated if the stand pat is far enough below alpha
+            if let Some(margin) = self.delta_margin {
+                if stand_pat + margin <= alpha
+                    && stand_pat + board.max_capture_gain(mv) + margin <= alpha
+                    && !*in_check.get_or_insert_with(|| board.is_in_check())
+                {
+                    continue;
+                }
+            }
             let mut new_board = board.clone();
             new_board.make_move(mv);
-            let score = match AlphaBetaEngine::quiescence_search_prunning(
-                &new_board, node_count, -beta, -alpha, deadline, aborted,
-            ) {
+            let score = match self.quiescence_search_prunning(&new_board, node_count, -beta, -alpha, ply + 1, depth - 1)
+            {
                 None => return None,
                 Some(score) => -score,
             };
@@ -290,101 +1286,17 @@ impl AlphaBetaEngine {
                 break;
             }
         }
+        self.move_lists.push(moves);
         Some(max_score)
     }
 
-    #[rustfmt::skip]
-    const PAWN_SQUARE_TABLE: [[i32; 8]; 8] = [
-    [  0,   0,   0,   0,   0,   0,   0,   0],
-    [100, 100, 100, 100, 100, 100, 100, 100],
-    [ 25,  50,  50,  50,  50,  50,  50,  25],
-    [  0,   0,   0,   2,   2,   0,   0,   0],
-    [  0,   0,  20,  25,  25,  20,   0,   0],
-    [  0,   0,  15,  10,  10,  15,   0,   0],
-    [  0,   0,   0,-250,-250,   0,   0,   0],
-    [  0,   0,   0,   0,   0,   0,   0,   0],
-];
-
-    #[rustfmt::skip]
-    const KNIGHT_SQUARE_TABLE: [[i32; 8]; 8] = [
-    [-200,-100,-100,-100,-100,-100,-100,-200],
-    [-100,   0,   0,   0,   0,   0,   0,-100],
-    [-100,   0,  50,  50,  50,  50,   0,-100],
-    [-100,   0,  50, 100, 150,  50,   0,-100],
-    [-100,   0,  50, 100, 100,  50,   0,-100],
-    [-100,   0,  50,  50,  50,  50,   0,-100],
-    [-100,   0,   0,   0,   0,   0,   0,-100],
-    [-200,-100,-100,-100,-100,-100,-100,-200],
-];
-
-    #[rustfmt::skip]
-    const BISHOP_SQUARE_TABLE: [[i32; 8]; 8] = [
-    [-200,-100,-100,-100,-100,-100,-100,-200],
-    [-100,   0,   0,   0,   0,   0,   0,-100],
-    [-100,   0,  50,  50,  50,  50,   0,-100],
-    [-100,   0,  50, 100, 150,  50,   0,-100],
-    [-100,   0,  50, 100, 100,  50,   0,-100],
-    [-100,   0,  50,  50,  50,  50,   0,-100],
-    [-100,  25,   0,   0,   0,  25,   0,-100],
-    [-200,-100,-100,-100,-100,-100,-100,-200],
-];
-
-    #[rustfmt::skip]
-    const KING_SQUARE_TABLE: [[i32; 8]; 8] = [
-    [-100, -100, -100, -100, -100, -100, -100, -100],
-    [-100, -100, -100, -100, -100, -100, -100, -100],
-    [-100, -100, -100, -100, -100, -100, -100, -100],
-    [-100, -100, -100, -100, -100, -100, -100, -100],
-    [-100, -100, -100, -100, -100, -100, -100, -100],
-    [-100, -100, -100, -100, -100, -100, -100, -100],
-    [ -50,  -50,  -50,  -50,  -50, -500,  -50,  -50],
-    [ 300,  350,  400,  -50,    0,  -50,  500,  300],
-];
-
-    /// Evaluates the board state and assigns a score based on material balance.
-    fn evaluate_board(board: &ChessBoard) -> i32 {
-        let mut evaluation = 0;
-
-        for row in 0..8 {
-            for col in 0..8 {
-                match board.squares[row][col] {
-                    Square::Occupied(piece) => {
-                        let piece_value = match piece.kind {
-                            PieceType::Pawn => 1_000,
-                            PieceType::Knight => 3_000,
-                            PieceType::Bishop => 3_000,
-                            PieceType::Rook => 5_000,
-                            PieceType::Queen => 9_000,
-                            PieceType::King => WIN, // if one king is on the board, it is won
-                        };
-
-                        //Check position value
-                        let psq_row = match piece.color {
-                            Color::White => 7 - row,
-                            Color::Black => row,
-                        };
-
-                        let possition_value = match piece.kind {
-                            PieceType::King => AlphaBetaEngine::KING_SQUARE_TABLE[psq_row][col],
-                            PieceType::Pawn => AlphaBetaEngine::PAWN_SQUARE_TABLE[psq_row][col],
-                            PieceType::Knight => AlphaBetaEngine::KNIGHT_SQUARE_TABLE[psq_row][col],
-                            PieceType::Bishop => AlphaBetaEngine::BISHOP_SQUARE_TABLE[psq_row][col],
-                            _ => 0,
-                        };
-
-                        let piece_evaluation = piece_value + possition_value;
-                        evaluation += match piece.color {
-                            Color::White => piece_evaluation,
-                            Color::Black => -piece_evaluation,
-                        };
-                    }
-
-                    Square::Empty => {}
-                }
-            }
-        }
-
-        evaluation
+    /// Evaluates the board state and assigns a score based on material balance. Recognized endgames have their own
+    /// evaluation. The score shrinks as the fifty-move counter grows, so a winning side prefers captures and pawn
+    /// moves to shuffling towards the draw.
+    fn evaluate_board(evaluator: &dyn Evaluator, board: &B) -> i32 {
+        let evaluation = board.evaluate(evaluator);
+        let halfmoves = 100 - board.halfmoves_until_fifty_move_draw() as i32;
+        evaluation * (FIFTY_MOVE_SCALE - halfmoves) / FIFTY_MOVE_SCALE
     }
 }
 
@@ -415,7 +1327,9 @@ mod tests {
             );
             println!(
                 "Principal variation: {}",
-                engine.principal_variation[0].0[0..engine.principal_variation[0].1]
+                engine
+                    .stack
+                    .pv(0)
                     .iter()
                     .map(|mv| mv.as_algebraic())
                     .collect::<Vec<_>>()
@@ -437,7 +1351,9 @@ mod tests {
             );
             println!(
                 "Principal variation: {}",
-                engine.principal_variation[0].0[0..engine.principal_variation[0].1]
+                engine
+                    .stack
+                    .pv(0)
                     .iter()
                     .map(|mv| mv.as_algebraic())
                     .collect::<Vec<_>>()
@@ -479,7 +1395,10 @@ mod tests {
             println!("No best move found!");
         }
         let board = ChessBoard::from_fen("rnbqkbnr/p1p2ppp/1p1p4/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 1 4").unwrap();
-        println!("Evaluation: {}", AlphaBetaEngine::evaluate_board(&board));
+        println!(
+            "Evaluation: {}",
+            AlphaBetaEngine::evaluate_board(&*engine.evaluator, &board)
+        );
     }
 
     #[test]
@@ -502,7 +1421,9 @@ mod tests {
                 );
                 println!(
                     "Principal variation: {}",
-                    engine.principal_variation[0].0[0..engine.principal_variation[0].1]
+                    engine
+                        .stack
+                        .pv(0)
                         .iter()
                         .map(|mv| mv.as_algebraic())
                         .collect::<Vec<_>>()
@@ -513,4 +1434,344 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_stepped_search_matches_iterative_search() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position(fen).unwrap();
+        engine.set_max_depth(3);
+        let expected = engine.find_best_move_iterative(Duration::from_secs(60), &mut |_| {});
+
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position(fen).unwrap();
+        engine.set_max_depth(3);
+        let mut search = engine.start_search(Duration::from_secs(60));
+        let mut steps = 0;
+        let mut depths = Vec::new();
+        let result = loop {
+            steps += 1;
+            match engine.continue_search(&mut search, Duration::ZERO, &mut |event| {
+                if let InfoEvent::DepthCompleted { depth, .. } = event {
+                    depths.push(depth)
+                }
+            }) {
+                SearchProgress::Running => {}
+                SearchProgress::Finished(result) => break result,
+            }
+        };
+        // Every step searches a single root move
+        assert!(steps > 3 * 20);
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(result, expected);
+
+        let mut search = engine.start_search(Duration::from_secs(60));
+        engine.get_abort_channel().store(true, Relaxed);
+        assert!(matches!(
+            engine.continue_search(&mut search, Duration::ZERO, &mut |_| {}),
+            SearchProgress::Finished(None)
+        ));
+    }
+
+    #[test]
+    fn test_parallel_root_search() {
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1";
+        let search = |threads: usize, multi_pv: usize| {
+            let mut engine = AlphaBetaEngine::new();
+            engine.set_position(fen).unwrap();
+            engine.set_max_depth(4);
+            engine.set_multi_pv(multi_pv);
+            // Set directly, the machine running the tests may have a single core
+            engine.threads = threads;
+            let (pv, score, nodes, depth) = engine
+                .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+                .unwrap();
+            assert!(nodes > 0);
+            assert_eq!(depth, 4);
+            (pv[0].as_algebraic(), score, engine.multi_pv_lines().to_vec())
+        };
+        let (expected_move, expected_score, _) = search(1, 1);
+        assert_eq!(expected_move, "d1d8");
+        let (best_move, score, _) = search(3, 1);
+        assert_eq!((best_move, score), (expected_move, expected_score));
+
+        // The root lines need exact scores, every root move gets a full window
+        let (best_move, score, lines) = search(3, 3);
+        assert_eq!((best_move, score), (String::from("d1d8"), expected_score));
+        assert_eq!(lines.len(), 3);
+        assert!(lines.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+        assert!(lines[1].0 < expected_score);
+    }
+
+    #[test]
+    fn test_skill_level() {
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position(fen).unwrap();
+        engine.set_skill(Some(Skill::new(crate::engines::skill::MIN_ELO)));
+        let legal_moves = ChessBoard::from_fen(fen).unwrap().generate_legal_moves();
+        for _ in 0..5 {
+            let (pv, _, _, depth) = engine
+                .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+                .unwrap();
+            assert!(legal_moves.contains(&pv[0]));
+            assert_eq!(depth, 1);
+        }
+
+        // Depth 1 always completes, the node limit stops the deeper iterations
+        engine.set_skill(None);
+        engine.set_max_nodes(1);
+        let (_, _, _, depth) = engine
+            .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+            .unwrap();
+        assert_eq!(depth, 1);
+    }
+
+    #[test]
+    fn test_contempt() {
+        // Every move but a rook capture ends the game by the fifty-move rule
+        let fen = "8/8/8/4k3/8/8/8/K6R w - - 99 80";
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position(fen).unwrap();
+        engine.set_max_depth(3);
+        for contempt in [0, 500, -500] {
+            engine.set_contempt(contempt);
+            let (_, score, _, _) = engine
+                .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+                .unwrap();
+            assert_eq!(score, -contempt);
+        }
+    }
+
+    #[test]
+    fn test_principal_variation() {
+        let board = ChessBoard::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3").unwrap();
+        let line: Vec<Move> = ["f1b5", "a7a6", "b5a4", "a6a5", "e1e2"]
+            .iter()
+            .map(|mv| Move::from_algebraic(mv))
+            .collect();
+        assert_eq!(legal_line(&board, &line), line);
+        // Black cannot play twice
+        let illegal = [line[0], line[2]];
+        assert_eq!(legal_line(&board, &illegal), vec![line[0]]);
+
+        let mut engine = AlphaBetaEngine::with_board(board.clone());
+        engine.set_max_depth(4);
+        let (pv, _, _, _) = engine
+            .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+            .unwrap();
+        assert!(pv.len() >= 4);
+        assert_eq!(legal_line(&board, &pv), pv);
+
+        // The stack grows with the ply instead of overflowing
+        engine.deadline = Instant::now() + Duration::from_secs(60);
+        let mut nodes = 0;
+        let ply = MAX_PLY + 5;
+        let mut stack = SearchStack::new();
+        let score = engine.negamax(&mut stack, &board, 1, MIN_EVALUATION, -MIN_EVALUATION, ply, &mut nodes);
+        assert!(score.is_some());
+        assert!(stack.frame(ply + 1).is_some());
+    }
+
+    #[test]
+    fn test_singular_extension_and_multicut() {
+        // Taking the rook is the only good move
+        let fen = "8/5k2/8/3r4/8/2N5/5K2/8 w - - 0 1";
+        let search = |singular_margin: Option<i32>| {
+            let mut engine = AlphaBetaEngine::new();
+            engine.set_position(fen).unwrap();
+            engine.set_max_depth(7);
+            engine.set_collect_stats(true);
+            engine.set_singular_margin(singular_margin);
+            let (pv, _, _, _) = engine
+                .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+                .unwrap();
+            let stats = engine.search_stats().unwrap();
+            (pv[0], stats.singular_extensions, stats.multicuts)
+        };
+        let (best_move, extensions, multicuts) = search(Some(DEFAULT_SINGULAR_MARGIN));
+        assert_eq!(best_move, Move::from_algebraic("c3d5"));
+        assert!(extensions > 0 && multicuts > 0);
+        assert_eq!(search(None), (best_move, 0, 0));
+    }
+
+    #[test]
+    fn test_futility_pruning() {
+        // A rook up, most quiet moves of the defender cannot bring it back to alpha
+        let fen = "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1";
+        let search = |futility_margin: Option<i32>| {
+            let mut engine = AlphaBetaEngine::new();
+            engine.set_position(fen).unwrap();
+            engine.set_max_depth(5);
+            engine.set_collect_stats(true);
+            engine.set_futility_margin(futility_margin);
+            let (pv, _, _, _) = engine
+                .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+                .unwrap();
+            (pv[0], engine.search_stats().unwrap().futility_prunes)
+        };
+        let (best_move, prunes) = search(Some(DEFAULT_FUTILITY_MARGIN));
+        assert_eq!(best_move, Move::from_algebraic("d1d8"));
+        assert!(prunes > 0);
+        assert_eq!(search(None), (best_move, 0));
+    }
+
+    #[test]
+    fn test_fifty_move_scaling() {
+        let evaluator = TunedEvaluator::default();
+        let evaluate = |fen: &str| AlphaBetaEngine::evaluate_board(&evaluator, &ChessBoard::from_fen(fen).unwrap());
+        let fresh = evaluate("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        let stale = evaluate("4k3/8/8/8/8/8/8/R3K3 w - - 80 60");
+        assert!(0 < stale && stale < fresh);
+        assert_eq!(evaluate("4k3/8/8/8/8/8/8/r3K3 w - - 100 70"), -fresh / 2);
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        engine.set_hash_size(1);
+        engine.set_max_depth(3);
+        let (pv, score, _, _) = engine
+            .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+            .unwrap();
+        assert_eq!(pv[0].as_algebraic(), "a1a8");
+        assert!(engine.transposition_table.hashfull() > 0);
+
+        // The filled table gives the same result
+        let (pv_again, score_again, _, _) = engine
+            .find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+            .unwrap();
+        assert_eq!((pv_again[0], score_again), (pv[0], score));
+
+        engine.clear_hash();
+        assert_eq!(engine.transposition_table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_new_game() {
+        let search = |engine: &mut AlphaBetaEngine| {
+            engine.set_max_depth(4);
+            engine.find_best_move_iterative(Duration::from_secs(60), &mut |_| {})
+        };
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        search(&mut engine).unwrap();
+        for mv in ["g1f1", "g8f8", "f1g1", "f8g8", "g1f1"] {
+            engine.make_move(mv).unwrap();
+        }
+
+        // The next game searches like a new engine, nothing of the last game is left
+        engine.new_game();
+        assert_eq!(engine.board().to_fen(), INITIAL_POSITION);
+        assert_eq!(engine.board().history.len(), 1);
+        assert_eq!(engine.transposition_table.hashfull(), 0);
+        let mut new_engine = AlphaBetaEngine::new();
+        new_engine.set_position(INITIAL_POSITION).unwrap();
+        assert_eq!(search(&mut engine), search(&mut new_engine));
+    }
+
+    #[test]
+    fn test_evaluation_symmetry() {
+        use crate::chess_board::fuzz::START_POSITIONS;
+        use rand::SeedableRng;
+        use rand_pcg::Pcg64;
+
+        let evaluator = TunedEvaluator::default();
+        let mut rng = Pcg64::seed_from_u64(3);
+        let endgames = [
+            "8/8/8/4k3/8/8/8/Q3K3 w - - 0 1",
+            "7k/8/5K2/8/8/8/8/2B1N3 w - - 0 1",
+            "8/5k2/8/1P6/8/8/6K1/8 b - - 0 1",
+            "4k3/8/8/8/8/8/P7/R3K3 w - - 60 80",
+        ];
+        for fen in START_POSITIONS.iter().chain(&endgames) {
+            let mut board = ChessBoard::from_fen(fen).unwrap();
+            for _ in 0..100 {
+                let asymmetry = evaluation_asymmetry(&evaluator, &board);
+                assert_eq!(asymmetry, None, "{}", board.to_fen());
+                let Some(&mv) = board.generate_legal_moves().choose(&mut rng) else {
+                    break;
+                };
+                board.make_move(mv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seldepth() {
+        // The quiescence search follows the captures beyond the nominal depth
+        let mut engine = AlphaBetaEngine::new();
+        engine
+            .set_position("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+            .unwrap();
+        engine.set_max_depth(2);
+        let mut seldepths = Vec::new();
+        engine.find_best_move_iterative(Duration::from_secs(60), &mut |event| {
+            if let InfoEvent::DepthCompleted { depth, seldepth, .. } = event {
+                seldepths.push((depth, seldepth))
+            }
+        });
+        assert_eq!(seldepths.len(), 2);
+        assert!(seldepths.iter().all(|&(depth, seldepth)| seldepth >= depth));
+        assert!(seldepths.iter().any(|&(depth, seldepth)| seldepth > depth));
+    }
+
+    #[test]
+    fn test_info_events() {
+        use std::sync::Mutex;
+
+        let mut engine = AlphaBetaEngine::new();
+        engine
+            .set_position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .unwrap();
+        engine.set_max_depth(4);
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let sink_messages = messages.clone();
+        engine.set_info_sink(Some(Box::new(move |event| {
+            if let InfoEvent::String(message) = event {
+                sink_messages.lock().unwrap().push(message);
+            }
+        })));
+        engine.find_best_move_iterative(Duration::from_secs(60), &mut |_| {});
+        assert!(messages.lock().unwrap().is_empty());
+
+        engine.set_debug(true);
+        let mut events = Vec::new();
+        engine.find_best_move_iterative(Duration::from_secs(60), &mut |event| events.push(event));
+        let messages = messages.lock().unwrap();
+        assert!(messages.iter().any(|m| m.starts_with("Depth 4 best move")));
+        let depths: Vec<i32> = events
+            .iter()
+            .filter_map(|event| match event {
+                InfoEvent::DepthCompleted { depth, .. } => Some(*depth),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(depths, vec![1, 2, 3, 4]);
+        for event in &events {
+            if let InfoEvent::NewBestMove { depth, pv, .. } = event {
+                assert!((1..=4).contains(depth));
+                assert!(!pv.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_basic_mates() {
+        // Too deep for the search alone, the endgame evaluation shows the way
+        for fen in ["8/8/8/4k3/8/8/8/Q3K3 w - - 0 1", "8/8/8/3k4/8/8/8/R3K3 w - - 0 1"] {
+            let mut engine = AlphaBetaEngine::new();
+            engine.set_position(fen).unwrap();
+            for _ in 0..100 {
+                // Entries of the previous moves would make the fixed depth search depend on the move ordering
+                engine.clear_hash();
+                let Some((mv, _, _)) = engine.find_best_move(3, false) else {
+                    break;
+                };
+                engine.board.make_move(mv);
+            }
+            assert!(engine.board.is_checkmate(), "{}", engine.board.to_fen());
+        }
+    }
 }
@@ -1,14 +1,25 @@
 use crate::chess_board::Square::Empty;
-use crate::chess_board::{ChessBoard, Color, Move, PieceType, Square};
+use crate::chess_board::{ChessBoard, Color, Move};
+use crate::engines::evaluation::Evaluator;
 use rand::prelude::SliceRandom;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
 #[allow(dead_code)]
-pub fn find_best_move(board: &ChessBoard, depth: i32, random: bool) -> Option<(Move, i32, u64)> {
-    find_best_move_with_timeout(board, depth, random, Duration::from_secs(60 * 60))
+pub fn find_best_move(
+    board: &ChessBoard,
+    evaluator: &dyn Evaluator,
+    depth: i32,
+    random: bool,
+) -> Option<(Move, i32, u64)> {
+    find_best_move_with_timeout(board, evaluator, depth, random, Duration::from_secs(60 * 60))
 }
 pub fn find_best_move_with_timeout(
     board: &ChessBoard,
+    evaluator: &dyn Evaluator,
     depth: i32,
     random: bool,
     remaining_time: Duration,
@@ -28,7 +39,7 @@ pub fn find_best_move_with_timeout(
             return None;
         }
         let mut new_board = board.clone();
-        let last_capture_move = if new_board.squares[mv.to.row][mv.to.col] == Empty {
+        let last_capture_move = if new_board.squares[mv.to.row()][mv.to.col()] == Empty {
             None
         } else {
             Some(mv)
@@ -36,7 +47,7 @@ pub fn find_best_move_with_timeout(
         new_board.make_move(mv);
 
         // Negamax for the opponent's position (invert the returned evaluation)
-        let score = -negamax(&new_board, depth, &mut node_count, last_capture_move);
+        let score = -negamax(&new_board, evaluator, depth, &mut node_count, last_capture_move);
 
         if score > best_score {
             best_score = score;
@@ -49,7 +60,11 @@ pub fn find_best_move_with_timeout(
 }
 
 #[allow(dead_code)]
-pub fn find_best_move_iterative(board: &ChessBoard, time_limit: Duration) -> Option<(Move, i32, u64, i32)> {
+pub fn find_best_move_iterative(
+    board: &ChessBoard,
+    evaluator: &dyn Evaluator,
+    time_limit: Duration,
+) -> Option<(Move, i32, u64, i32)> {
     let mut best_move = None;
     let mut total_node_count = 0;
 
@@ -61,7 +76,7 @@ pub fn find_best_move_iterative(board: &ChessBoard, time_limit: Duration) -> Opt
 
         // Call the existing find_best_move function for the current depth.
         if let Some((current_move, current_score, node_count)) =
-            find_best_move_with_timeout(board, depth, true, remaining_time)
+            find_best_move_with_timeout(board, evaluator, depth, true, remaining_time)
         {
             best_move = Some((current_move, current_score, total_node_count + node_count, depth));
             total_node_count += node_count;
@@ -76,21 +91,26 @@ pub fn find_best_move_iterative(board: &ChessBoard, time_limit: Duration) -> Opt
 }
 
 const MIN_EVALUATION: i32 = i32::MIN + 1; // +1 is important because -MIN is not a i32 number
-const WIN: i32 = 10_000_000;
 const LOSS: i32 = -10_000_000;
 const DRAW: i32 = 0;
 
-fn negamax(board: &ChessBoard, depth: i32, node_count: &mut u64, last_capture_move: Option<Move>) -> i32 {
+fn negamax(
+    board: &ChessBoard,
+    evaluator: &dyn Evaluator,
+    depth: i32,
+    node_count: &mut u64,
+    last_capture_move: Option<Move>,
+) -> i32 {
     *node_count += 1;
     if board.is_threefold_repetition() {
         return 0;
     }
     if depth <= 0 {
         return match last_capture_move {
-            None => evaluate_board(board) * if board.active_color == Color::White { 1 } else { -1 },
+            None => evaluator.evaluate(board) * if board.active_color == Color::White { 1 } else { -1 },
             Some(mv) => {
                 *node_count -= 1;
-                quiescence_search(board, node_count, &mv)
+                quiescence_search(board, evaluator, node_count, &mv)
             }
         };
     }
@@ -99,7 +119,7 @@ fn negamax(board: &ChessBoard, depth: i32, node_count: &mut u64, last_capture_mo
 
     for (mv, _) in board.generate_pseudo_moves() {
         let mut new_board = board.clone();
-        let last_capture_move = if new_board.squares[mv.to.row][mv.to.col] == Empty {
+        let last_capture_move = if new_board.squares[mv.to.row()][mv.to.col()] == Empty {
             None
         } else {
             Some(mv)
@@ -107,31 +127,27 @@ fn negamax(board: &ChessBoard, depth: i32, node_count: &mut u64, last_capture_mo
         new_board.make_move(mv);
         let king_position = new_board.find_king_position(board.active_color);
         if let Some(king_pos) = king_position {
-            if !new_board.is_square_attacked_by_color(king_pos.row, king_pos.col, new_board.active_color) {
+            if !new_board.is_square_attacked_by_color(king_pos.row(), king_pos.col(), new_board.active_color) {
                 // No legal move
                 // Negate the evaluation of the next level (opponent's perspective)
-                let score = -negamax(&new_board, depth - 1, node_count, last_capture_move);
+                let score = -negamax(&new_board, evaluator, depth - 1, node_count, last_capture_move);
                 max_score = max_score.max(score);
             }
         }
     }
     if max_score == MIN_EVALUATION {
-        //No legal moves
-        if let Some(king_pos) = board.find_king_position(board.active_color) {
-            if board.is_square_attacked(king_pos.row, king_pos.col) {
-                LOSS - depth // Closer loss is punished harder
-            } else {
-                DRAW // Stalemate
-            }
+        // No legal moves, the generation above tells checkmate and stalemate apart with the check of the king
+        if board.is_in_check() {
+            LOSS - depth // Closer loss is punished harder
         } else {
-            DRAW //Illegal state when no king is found
+            DRAW // Stalemate, or an illegal state without king
         }
     } else {
         max_score
     }
 }
 
-fn quiescence_search(board: &ChessBoard, node_count: &mut u64, &last_move: &Move) -> i32 {
+fn quiescence_search(board: &ChessBoard, evaluator: &dyn Evaluator, node_count: &mut u64, &last_move: &Move) -> i32 {
     *node_count += 1;
 
     let mut max_score = MIN_EVALUATION;
@@ -142,59 +158,30 @@ fn quiescence_search(board: &ChessBoard, node_count: &mut u64, &last_move: &Move
 
     for mv in moves
         .iter()
-        .filter(|mv| mv.to.row == last_move.to.row && mv.to.col == last_move.to.col)
+        .filter(|mv| mv.to.row() == last_move.to.row() && mv.to.col() == last_move.to.col())
     {
         let mut new_board = board.clone();
         new_board.make_move(*mv);
-        let score = -quiescence_search(&new_board, node_count, &last_move);
+        let score = -quiescence_search(&new_board, evaluator, node_count, &last_move);
         max_score = max_score.max(score);
     }
     if max_score == MIN_EVALUATION {
-        evaluate_board(board) * if board.active_color == Color::White { 1 } else { -1 }
+        evaluator.evaluate(board) * if board.active_color == Color::White { 1 } else { -1 }
     } else {
         max_score
     }
 }
 
-/// Evaluates the board state and assigns a score based on material balance.
-fn evaluate_board(board: &ChessBoard) -> i32 {
-    let mut evaluation = 0;
-
-    for row in 0..8 {
-        for col in 0..8 {
-            match board.squares[row][col] {
-                Square::Occupied(piece) => {
-                    let piece_value = match piece.kind {
-                        PieceType::Pawn => 1_000,
-                        PieceType::Knight => 3_000,
-                        PieceType::Bishop => 3_000,
-                        PieceType::Rook => 5_000,
-                        PieceType::Queen => 9_000,
-                        PieceType::King => WIN, // if one king is on the board, it is won
-                    };
-
-                    evaluation += match piece.color {
-                        Color::White => piece_value,
-                        Color::Black => -piece_value,
-                    };
-                }
-                Square::Empty => {}
-            }
-        }
-    }
-
-    evaluation
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::chess_board::ChessBoard;
+    use crate::engines::evaluation::MaterialEvaluator;
 
     #[test]
     fn test_some_positions() {
         let board = ChessBoard::from_fen("8/4p3/8/3P4/8/8/8/8 b - - 0 1").unwrap();
-        if let Some((best_move, score, nodes)) = find_best_move(&board, 2, false) {
+        if let Some((best_move, score, nodes)) = find_best_move(&board, &MaterialEvaluator, 2, false) {
             println!(
                 "Best move: {} with score: {} evaluated nodes: {}",
                 best_move.as_algebraic(),
@@ -206,7 +193,7 @@ mod tests {
         }
 
         let board = ChessBoard::from_fen("8/7k/5KR1/8/8/8/8/8 w - - 0 1").unwrap();
-        if let Some((best_move, score, nodes)) = find_best_move(&board, 6, false) {
+        if let Some((best_move, score, nodes)) = find_best_move(&board, &MaterialEvaluator, 6, false) {
             println!(
                 "Best move: {} with score: {} evaluated nodes: {}",
                 best_move.as_algebraic(),
@@ -218,7 +205,7 @@ mod tests {
         }
 
         let board = ChessBoard::from_fen("4k1nr/2p3p1/b2pPp1p/8/1nN1P1P1/p1R2N2/PR3P2/5K2 b k - 1 26").unwrap();
-        if let Some((best_move, score, nodes)) = find_best_move(&board, 3, false) {
+        if let Some((best_move, score, nodes)) = find_best_move(&board, &MaterialEvaluator, 3, false) {
             println!(
                 "Best move: {} with score: {} evaluated nodes: {}",
                 best_move.as_algebraic(),
@@ -233,7 +220,7 @@ mod tests {
     #[test]
     fn test_from_a_played_position() {
         let board = ChessBoard::from_fen("4k1nr/2p3p1/b2pPp1p/8/1nN1P1P1/p1R2N2/PR3P2/5K2 b k - 1 26").unwrap();
-        if let Some((best_move, score, nodes)) = find_best_move(&board, 0, false) {
+        if let Some((best_move, score, nodes)) = find_best_move(&board, &MaterialEvaluator, 0, false) {
             println!(
                 "Best move: {} with score: {} evaluated nodes: {}",
                 best_move.as_algebraic(),
@@ -0,0 +1,987 @@
+use crate::chess_board::{ChessBoard, Color, Piece, PieceType, Square};
+use crate::engines::engine_alpha_beta::WIN;
+use serde::{Deserialize, Serialize};
+
+pub const PIECE_TYPES: [PieceType; 6] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+    PieceType::King,
+];
+
+/// Material values of all pieces but the king, followed by the square tables of all pieces, the passed pawn weights,
+/// the opening weights, the threat weights and the middlegame weights.
+pub const PARAMETER_COUNT: usize = MIDDLEGAME_OFFSET + 5;
+
+/// Index of the first passed pawn weight in the tuning vector.
+const PASSED_PAWN_OFFSET: usize = 5 + 6 * 64;
+
+/// Index of the first opening weight in the tuning vector.
+const OPENING_OFFSET: usize = PASSED_PAWN_OFFSET + 8 + 3;
+
+/// Index of the first threat weight in the tuning vector.
+const THREAT_OFFSET: usize = OPENING_OFFSET + 4;
+
+/// Index of the first middlegame weight in the tuning vector.
+const MIDDLEGAME_OFFSET: usize = THREAT_OFFSET + 3;
+
+pub use crate::chess_board::material::OPENING_PHASE;
+
+/// The last move in which a knight beyond its third rank counts as an early excursion.
+const OPENING_MOVES: u32 = 10;
+
+/// Material of knights, bishops, rooks and queens of both sides in pawns, up to which the king proximity of passed
+/// pawns counts: a rook and a minor piece each.
+const ENDGAME_MATERIAL: i32 = 16;
+
+/// Weights of the evaluation, all from White's point of view in 1/1000 pawn.
+/// The square tables are seen from the owner of the piece, row 0 is its promotion rank. Fields missing in a
+/// JSON file keep their default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    pub material: [i32; 6],
+    pub piece_square_tables: [[[i32; 8]; 8]; 6],
+    /// Bonus of a passed pawn by row, seen from its owner like the square tables.
+    pub passed_pawn: [i32; 8],
+    /// Bonus per square the enemy king is farther from the square in front of a passed pawn than the own king, times
+    /// the ranks the pawn advanced. Only counted in endgames.
+    pub passed_pawn_king_distance: i32,
+    /// Bonus of a rook behind a passed pawn of its color.
+    pub rook_behind_passed_pawn: i32,
+    /// Bonus of a passed pawn the enemy king cannot catch by the rule of the square, with only kings and pawns left.
+    pub unstoppable_passed_pawn: i32,
+    /// Bonus of a knight or bishop still on its starting square. The opening weights are per phase unit, they are
+    /// multiplied by the game phase, so they count most with all pieces on the board and fade out as pieces are
+    /// traded.
+    pub undeveloped_minor: i32,
+    /// Bonus of a king on a castled square, the two squares next to a corner of its back rank.
+    pub castled_king: i32,
+    /// Bonus of a queen that left its starting square while at least two minor pieces of its color are undeveloped.
+    pub early_queen: i32,
+    /// Bonus of a knight beyond its third rank in the first moves of the game. The board has no history, but such a
+    /// knight has moved twice.
+    pub knight_excursion: i32,
+    /// Bonus per enemy piece that is attacked and undefended or attacked by a less valuable piece.
+    pub threatened_piece: i32,
+    /// Bonus per square a knight can jump to safely and fork two enemy pieces from.
+    pub knight_fork: i32,
+    /// Bonus per enemy piece pinned or skewered by a sliding piece.
+    pub pin: i32,
+    /// Bonus of a rook on the seventh rank while the enemy king is on its back rank or enemy pawns are on the
+    /// seventh.
+    pub rook_on_seventh: i32,
+    /// Bonus of a queen on the seventh rank, under the same condition as the rook.
+    pub queen_on_seventh: i32,
+    /// Bonus of two rooks of a color that defend each other along a rank or file.
+    pub connected_rooks: i32,
+    /// Bonus of two connected rooks on a file without pawns, on top of the connected rooks.
+    pub doubled_rooks_open_file: i32,
+    /// Bonus of a knight on the fourth to sixth rank that is protected by a pawn and that no enemy pawn can attack.
+    pub knight_outpost: i32,
+}
+
+/// The passed pawn features of a board from White's point of view, the evaluation is their dot product with the
+/// passed pawn weights.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PassedPawnTerms {
+    rows: [i32; 8],
+    king_distance: i32,
+    rook_behind: i32,
+    unstoppable: i32,
+}
+
+/// The opening features of a board from White's point of view and the game phase, the evaluation is the dot product
+/// with the opening weights times the phase.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct OpeningTerms {
+    undeveloped_minor: i32,
+    castled_king: i32,
+    early_queen: i32,
+    knight_excursion: i32,
+    phase: i32,
+}
+
+/// The threat features of a board from White's point of view, see the attack map of the board.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ThreatTerms {
+    threatened_pieces: i32,
+    knight_forks: i32,
+    pins: i32,
+}
+
+/// The placement features of the rooks, queens and knights of a board from White's point of view.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct MiddlegameTerms {
+    rooks_on_seventh: i32,
+    queens_on_seventh: i32,
+    connected_rooks: i32,
+    doubled_rooks_open_file: i32,
+    knight_outposts: i32,
+}
+
+#[rustfmt::skip]
+const PAWN_SQUARE_TABLE: [[i32; 8]; 8] = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [100, 100, 100, 100, 100, 100, 100, 100],
+    [ 25,  50,  50,  50,  50,  50,  50,  25],
+    [  0,   0,   0,   2,   2,   0,   0,   0],
+    [  0,   0,  20,  25,  25,  20,   0,   0],
+    [  0,   0,  15,  10,  10,  15,   0,   0],
+    [  0,   0,   0,-250,-250,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const KNIGHT_SQUARE_TABLE: [[i32; 8]; 8] = [
+    [-200,-100,-100,-100,-100,-100,-100,-200],
+    [-100,   0,   0,   0,   0,   0,   0,-100],
+    [-100,   0,  50,  50,  50,  50,   0,-100],
+    [-100,   0,  50, 100, 150,  50,   0,-100],
+    [-100,   0,  50, 100, 100,  50,   0,-100],
+    [-100,   0,  50,  50,  50,  50,   0,-100],
+    [-100,   0,   0,   0,   0,   0,   0,-100],
+    [-200,-100,-100,-100,-100,-100,-100,-200],
+];
+
+#[rustfmt::skip]
+const BISHOP_SQUARE_TABLE: [[i32; 8]; 8] = [
+    [-200,-100,-100,-100,-100,-100,-100,-200],
+    [-100,   0,   0,   0,   0,   0,   0,-100],
+    [-100,   0,  50,  50,  50,  50,   0,-100],
+    [-100,   0,  50, 100, 150,  50,   0,-100],
+    [-100,   0,  50, 100, 100,  50,   0,-100],
+    [-100,   0,  50,  50,  50,  50,   0,-100],
+    [-100,  25,   0,   0,   0,  25,   0,-100],
+    [-200,-100,-100,-100,-100,-100,-100,-200],
+];
+
+#[rustfmt::skip]
+const KING_SQUARE_TABLE: [[i32; 8]; 8] = [
+    [-100, -100, -100, -100, -100, -100, -100, -100],
+    [-100, -100, -100, -100, -100, -100, -100, -100],
+    [-100, -100, -100, -100, -100, -100, -100, -100],
+    [-100, -100, -100, -100, -100, -100, -100, -100],
+    [-100, -100, -100, -100, -100, -100, -100, -100],
+    [-100, -100, -100, -100, -100, -100, -100, -100],
+    [ -50,  -50,  -50,  -50,  -50, -500,  -50,  -50],
+    [ 300,  350,  400,  -50,    0,  -50,  500,  300],
+];
+
+pub const DEFAULT_EVAL_PARAMS: EvalParams = EvalParams {
+    // If one king is on the board, it is won
+    material: [1_000, 3_000, 3_000, 5_000, 9_000, WIN],
+    piece_square_tables: [
+        PAWN_SQUARE_TABLE,
+        KNIGHT_SQUARE_TABLE,
+        BISHOP_SQUARE_TABLE,
+        [[0; 8]; 8],
+        [[0; 8]; 8],
+        KING_SQUARE_TABLE,
+    ],
+    passed_pawn: [0, 500, 300, 150, 75, 40, 20, 0],
+    passed_pawn_king_distance: 10,
+    rook_behind_passed_pawn: 150,
+    unstoppable_passed_pawn: 5_000,
+    undeveloped_minor: -6,
+    castled_king: 10,
+    early_queen: -8,
+    knight_excursion: -5,
+    threatened_piece: 100,
+    knight_fork: 150,
+    pin: 100,
+    rook_on_seventh: 200,
+    queen_on_seventh: 100,
+    connected_rooks: 100,
+    doubled_rooks_open_file: 150,
+    knight_outpost: 200,
+};
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        DEFAULT_EVAL_PARAMS
+    }
+}
+
+fn piece_index(kind: PieceType) -> usize {
+    // PIECE_TYPES is in declaration order
+    kind as usize
+}
+
+fn square_table_row(color: Color, row: usize) -> usize {
+    match color {
+        Color::White => 7 - row,
+        Color::Black => row,
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+fn distance(from: (usize, usize), to: (usize, usize)) -> i32 {
+    from.0.abs_diff(to.0).max(from.1.abs_diff(to.1)) as i32
+}
+
+/// The game phase of the opening terms, from OPENING_PHASE with all pieces on the board down to 0 with pawns and
+/// kings only.
+pub fn game_phase(board: &ChessBoard) -> i32 {
+    board.phase()
+}
+
+pub fn is_passed_pawn(board: &ChessBoard, color: Color, row: usize, col: usize) -> bool {
+    let ahead = match color {
+        Color::White => row + 1..8,
+        Color::Black => 0..row,
+    };
+    let enemy_pawn = Square::Occupied(Piece {
+        color: opponent(color),
+        kind: PieceType::Pawn,
+    });
+    ahead
+        .into_iter()
+        .all(|r| (col.saturating_sub(1)..=(col + 1).min(7)).all(|c| board.squares[r][c] != enemy_pawn))
+}
+
+/// The first piece behind the pawn on its file.
+fn piece_behind(board: &ChessBoard, color: Color, row: usize, col: usize) -> Option<Piece> {
+    let behind: Vec<usize> = match color {
+        Color::White => (0..row).rev().collect(),
+        Color::Black => (row + 1..8).collect(),
+    };
+    behind.into_iter().find_map(|r| match board.squares[r][col] {
+        Square::Occupied(piece) => Some(piece),
+        Square::Empty => None,
+    })
+}
+
+/// Rule of the square: the pawn promotes before the enemy king reaches its promotion square, nothing blocks it.
+fn is_unstoppable(board: &ChessBoard, color: Color, row: usize, col: usize, enemy_king: (usize, usize)) -> bool {
+    let promotion_row = match color {
+        Color::White => 7,
+        Color::Black => 0,
+    };
+    let path_clear = (row.min(promotion_row)..=row.max(promotion_row))
+        .filter(|&r| r != row)
+        .all(|r| board.squares[r][col] == Square::Empty);
+    // The double step from the initial row saves a move
+    let pawn_moves = square_table_row(color, row).min(5) as i32;
+    let mut king_moves = distance(enemy_king, (promotion_row, col));
+    if board.active_color != color {
+        king_moves -= 1;
+    }
+    path_clear && king_moves > pawn_moves
+}
+
+fn passed_pawn_terms(board: &ChessBoard) -> PassedPawnTerms {
+    let mut terms = PassedPawnTerms::default();
+    let mut pawns = Vec::new();
+    for row in 0..8 {
+        for col in 0..8 {
+            if let Square::Occupied(piece) = board.squares[row][col] {
+                if piece.kind == PieceType::Pawn {
+                    pawns.push((piece.color, row, col));
+                }
+            }
+        }
+    }
+    let material = board.material_signature();
+    let piece_material = [Color::White, Color::Black]
+        .into_iter()
+        .map(|color| {
+            let count = |kind| material.count(color, kind) as i32;
+            3 * (count(PieceType::Knight) + count(PieceType::Bishop))
+                + 5 * count(PieceType::Rook)
+                + 9 * count(PieceType::Queen)
+        })
+        .sum::<i32>();
+    let king = |color| board.find_king_position(color).map(|field| (field.row(), field.col()));
+    for (color, row, col) in pawns {
+        if !is_passed_pawn(board, color, row, col) {
+            continue;
+        }
+        let sign = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        let table_row = square_table_row(color, row);
+        terms.rows[table_row] += sign;
+        if piece_behind(board, color, row, col)
+            == Some(Piece {
+                color,
+                kind: PieceType::Rook,
+            })
+        {
+            terms.rook_behind += sign;
+        }
+        let (Some(own_king), Some(enemy_king)) = (king(color), king(opponent(color))) else {
+            continue;
+        };
+        if piece_material <= ENDGAME_MATERIAL {
+            let stop_square = match color {
+                Color::White => (row + 1, col),
+                Color::Black => (row - 1, col),
+            };
+            let advancement = 6 - table_row as i32;
+            terms.king_distance +=
+                sign * advancement * (distance(enemy_king, stop_square) - distance(own_king, stop_square));
+        }
+        if piece_material == 0 && is_unstoppable(board, color, row, col, enemy_king) {
+            terms.unstoppable += sign;
+        }
+    }
+    terms
+}
+
+fn opening_terms(board: &ChessBoard) -> OpeningTerms {
+    let mut terms = OpeningTerms::default();
+    for row in 0..8 {
+        for col in 0..8 {
+            if let Square::Occupied(piece) = board.squares[row][col] {
+                let sign = match piece.color {
+                    Color::White => 1,
+                    Color::Black => -1,
+                };
+                // The rank seen from the owner of the piece, 0 is its back rank
+                let rank = 7 - square_table_row(piece.color, row);
+                match piece.kind {
+                    PieceType::Knight if rank > 2 && board.fullmove_number <= OPENING_MOVES => {
+                        terms.knight_excursion += sign;
+                    }
+                    PieceType::King if rank == 0 && matches!(col, 1 | 2 | 6 | 7) => terms.castled_king += sign,
+                    _ => {}
+                }
+            }
+        }
+    }
+    terms.phase = board.phase();
+
+    let material = board.material_signature();
+    for color in [Color::White, Color::Black] {
+        let sign = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        let back_row = match color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        let on = |col: usize, kind| board.squares[back_row][col] == Square::Occupied(Piece { color, kind });
+        let undeveloped = [
+            (1, PieceType::Knight),
+            (6, PieceType::Knight),
+            (2, PieceType::Bishop),
+            (5, PieceType::Bishop),
+        ]
+        .into_iter()
+        .filter(|&(col, kind)| on(col, kind))
+        .count() as i32;
+        terms.undeveloped_minor += sign * undeveloped;
+        if undeveloped >= 2 && material.count(color, PieceType::Queen) > 0 && !on(3, PieceType::Queen) {
+            terms.early_queen += sign;
+        }
+    }
+    terms
+}
+
+fn threat_terms(board: &ChessBoard) -> ThreatTerms {
+    let map = board.attack_map();
+    let count = |color| {
+        let enemy = opponent(color);
+        ThreatTerms {
+            threatened_pieces: map.threatened_pieces(enemy).len() as i32,
+            knight_forks: map.knight_forks(color).len() as i32,
+            pins: map.pins(enemy).len() as i32,
+        }
+    };
+    let (white, black) = (count(Color::White), count(Color::Black));
+    ThreatTerms {
+        threatened_pieces: white.threatened_pieces - black.threatened_pieces,
+        knight_forks: white.knight_forks - black.knight_forks,
+        pins: white.pins - black.pins,
+    }
+}
+
+/// Whether nothing stands between the two squares on a rank or file.
+fn sees_along_line(board: &ChessBoard, from: (usize, usize), to: (usize, usize)) -> bool {
+    if from.0 == to.0 {
+        (from.1.min(to.1) + 1..from.1.max(to.1)).all(|col| board.squares[from.0][col] == Square::Empty)
+    } else if from.1 == to.1 {
+        (from.0.min(to.0) + 1..from.0.max(to.0)).all(|row| board.squares[row][from.1] == Square::Empty)
+    } else {
+        false
+    }
+}
+
+/// A knight on the fourth to sixth rank, protected by a pawn and out of reach of the enemy pawns on the files next to
+/// it.
+fn is_outpost(board: &ChessBoard, color: Color, row: usize, col: usize) -> bool {
+    let rank = 7 - square_table_row(color, row);
+    if !(3..=5).contains(&rank) {
+        return false;
+    }
+    let pawn = |color| {
+        Square::Occupied(Piece {
+            color,
+            kind: PieceType::Pawn,
+        })
+    };
+    let (behind, ahead) = match color {
+        Color::White => (row - 1, row + 1..8),
+        Color::Black => (row + 1, 0..row),
+    };
+    let sides = [col.checked_sub(1), (col < 7).then_some(col + 1)].into_iter().flatten();
+    let protected = sides.clone().any(|c| board.squares[behind][c] == pawn(color));
+    protected
+        && ahead
+            .into_iter()
+            .all(|r| sides.clone().all(|c| board.squares[r][c] != pawn(opponent(color))))
+}
+
+fn middlegame_terms(board: &ChessBoard) -> MiddlegameTerms {
+    let mut terms = MiddlegameTerms::default();
+    let is_pawn = |square| {
+        matches!(
+            square,
+            Square::Occupied(Piece {
+                kind: PieceType::Pawn,
+                ..
+            })
+        )
+    };
+    for color in [Color::White, Color::Black] {
+        let sign = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+        let enemy = opponent(color);
+        let (seventh_row, enemy_back_row) = match color {
+            Color::White => (6, 7),
+            Color::Black => (1, 0),
+        };
+        let enemy_pawn = Square::Occupied(Piece {
+            color: enemy,
+            kind: PieceType::Pawn,
+        });
+        // The seventh rank only counts if there is something to attack or a king to cut off
+        let seventh = board
+            .find_king_position(enemy)
+            .is_some_and(|field| field.row() == enemy_back_row)
+            || board.squares[seventh_row].contains(&enemy_pawn);
+        let mut rooks = Vec::new();
+        for row in 0..8 {
+            for col in 0..8 {
+                let Square::Occupied(piece) = board.squares[row][col] else {
+                    continue;
+                };
+                if piece.color != color {
+                    continue;
+                }
+                match piece.kind {
+                    PieceType::Rook => {
+                        rooks.push((row, col));
+                        if seventh && row == seventh_row {
+                            terms.rooks_on_seventh += sign;
+                        }
+                    }
+                    PieceType::Queen if seventh && row == seventh_row => terms.queens_on_seventh += sign,
+                    PieceType::Knight if is_outpost(board, color, row, col) => terms.knight_outposts += sign,
+                    _ => {}
+                }
+            }
+        }
+        for (i, &first) in rooks.iter().enumerate() {
+            for &second in &rooks[i + 1..] {
+                if !sees_along_line(board, first, second) {
+                    continue;
+                }
+                terms.connected_rooks += sign;
+                if first.1 == second.1 && (0..8).all(|row| !is_pawn(board.squares[row][first.1])) {
+                    terms.doubled_rooks_open_file += sign;
+                }
+            }
+        }
+    }
+    terms
+}
+
+impl EvalParams {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid evaluation parameters: {}", e))
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Evaluates the board from White's point of view.
+    pub fn evaluate(&self, board: &ChessBoard) -> i32 {
+        let mut evaluation = 0;
+        for row in 0..8 {
+            for col in 0..8 {
+                if let Square::Occupied(piece) = board.squares[row][col] {
+                    let index = piece_index(piece.kind);
+                    let piece_evaluation =
+                        self.material[index] + self.piece_square_tables[index][square_table_row(piece.color, row)][col];
+                    evaluation += match piece.color {
+                        Color::White => piece_evaluation,
+                        Color::Black => -piece_evaluation,
+                    };
+                }
+            }
+        }
+        evaluation + self.passed_pawns(board) + self.opening(board) + self.threats(board) + self.middlegame(board)
+    }
+
+    /// The passed pawn part of the evaluation from White's point of view.
+    pub fn passed_pawns(&self, board: &ChessBoard) -> i32 {
+        let terms = passed_pawn_terms(board);
+        let rows: i32 = terms
+            .rows
+            .iter()
+            .zip(self.passed_pawn)
+            .map(|(count, bonus)| count * bonus)
+            .sum();
+        rows + terms.king_distance * self.passed_pawn_king_distance
+            + terms.rook_behind * self.rook_behind_passed_pawn
+            + terms.unstoppable * self.unstoppable_passed_pawn
+    }
+
+    /// The opening part of the evaluation from White's point of view, zero once the pieces are traded.
+    pub fn opening(&self, board: &ChessBoard) -> i32 {
+        let terms = opening_terms(board);
+        (terms.undeveloped_minor * self.undeveloped_minor
+            + terms.castled_king * self.castled_king
+            + terms.early_queen * self.early_queen
+            + terms.knight_excursion * self.knight_excursion)
+            * terms.phase
+    }
+
+    /// The threat part of the evaluation from White's point of view. The attack map is not built if the weights are
+    /// zero.
+    pub fn threats(&self, board: &ChessBoard) -> i32 {
+        if self.threatened_piece == 0 && self.knight_fork == 0 && self.pin == 0 {
+            return 0;
+        }
+        let terms = threat_terms(board);
+        terms.threatened_pieces * self.threatened_piece + terms.knight_forks * self.knight_fork + terms.pins * self.pin
+    }
+
+    /// The middlegame part of the evaluation from White's point of view: rooks and queens on the seventh rank,
+    /// connected rooks and knight outposts.
+    pub fn middlegame(&self, board: &ChessBoard) -> i32 {
+        let terms = middlegame_terms(board);
+        terms.rooks_on_seventh * self.rook_on_seventh
+            + terms.queens_on_seventh * self.queen_on_seventh
+            + terms.connected_rooks * self.connected_rooks
+            + terms.doubled_rooks_open_file * self.doubled_rooks_open_file
+            + terms.knight_outposts * self.knight_outpost
+    }
+
+    /// The material and square table sums of every piece type from White's point of view, in PIECE_TYPES order.
+    /// Together with the passed pawns, the opening, the threat and the middlegame terms they add up to the evaluation.
+    pub fn breakdown(&self, board: &ChessBoard) -> [(i32, i32); 6] {
+        let mut breakdown = [(0, 0); 6];
+        for row in 0..8 {
+            for col in 0..8 {
+                if let Square::Occupied(piece) = board.squares[row][col] {
+                    let index = piece_index(piece.kind);
+                    let sign = match piece.color {
+                        Color::White => 1,
+                        Color::Black => -1,
+                    };
+                    breakdown[index].0 += sign * self.material[index];
+                    breakdown[index].1 +=
+                        sign * self.piece_square_tables[index][square_table_row(piece.color, row)][col];
+                }
+            }
+        }
+        breakdown
+    }
+
+    /// The evaluation and its breakdown in centipawns, for the diagnostics of the UCI debug mode.
+    pub fn breakdown_lines(&self, board: &ChessBoard) -> Vec<String> {
+        let mut lines = vec![format!("Evaluation {:+} cp for White", self.evaluate(board) / 10)];
+        for (kind, (material, squares)) in PIECE_TYPES.iter().zip(self.breakdown(board)) {
+            lines.push(format!(
+                "Evaluation {:?}: material {:+} cp, squares {:+} cp",
+                kind,
+                material / 10,
+                squares / 10
+            ));
+        }
+        lines.push(format!(
+            "Evaluation passed pawns: {:+} cp",
+            self.passed_pawns(board) / 10
+        ));
+        lines.push(format!("Evaluation opening: {:+} cp", self.opening(board) / 10));
+        lines.push(format!("Evaluation threats: {:+} cp", self.threats(board) / 10));
+        lines.push(format!("Evaluation middlegame: {:+} cp", self.middlegame(board) / 10));
+        lines
+    }
+
+    /// The tunable weights. The king's material value cancels out and is left out.
+    pub fn to_vector(&self) -> Vec<f64> {
+        let mut vector: Vec<f64> = self.material[..5].iter().map(|&v| v as f64).collect();
+        for table in &self.piece_square_tables {
+            vector.extend(table.iter().flatten().map(|&v| v as f64));
+        }
+        vector.extend(self.passed_pawn.iter().map(|&v| v as f64));
+        vector.extend(
+            [
+                self.passed_pawn_king_distance,
+                self.rook_behind_passed_pawn,
+                self.unstoppable_passed_pawn,
+                self.undeveloped_minor,
+                self.castled_king,
+                self.early_queen,
+                self.knight_excursion,
+                self.threatened_piece,
+                self.knight_fork,
+                self.pin,
+                self.rook_on_seventh,
+                self.queen_on_seventh,
+                self.connected_rooks,
+                self.doubled_rooks_open_file,
+                self.knight_outpost,
+            ]
+            .map(|v| v as f64),
+        );
+        vector
+    }
+
+    pub fn from_vector(vector: &[f64]) -> Self {
+        assert_eq!(vector.len(), PARAMETER_COUNT);
+        let mut params = DEFAULT_EVAL_PARAMS;
+        for (i, value) in vector[..5].iter().enumerate() {
+            params.material[i] = value.round() as i32;
+        }
+        for (i, value) in vector[5..PASSED_PAWN_OFFSET].iter().enumerate() {
+            params.piece_square_tables[i / 64][(i % 64) / 8][i % 8] = value.round() as i32;
+        }
+        let passed_pawn = &vector[PASSED_PAWN_OFFSET..];
+        for (i, value) in passed_pawn[..8].iter().enumerate() {
+            params.passed_pawn[i] = value.round() as i32;
+        }
+        params.passed_pawn_king_distance = passed_pawn[8].round() as i32;
+        params.rook_behind_passed_pawn = passed_pawn[9].round() as i32;
+        params.unstoppable_passed_pawn = passed_pawn[10].round() as i32;
+        let opening = &vector[OPENING_OFFSET..];
+        params.undeveloped_minor = opening[0].round() as i32;
+        params.castled_king = opening[1].round() as i32;
+        params.early_queen = opening[2].round() as i32;
+        params.knight_excursion = opening[3].round() as i32;
+        let threat = &vector[THREAT_OFFSET..];
+        params.threatened_piece = threat[0].round() as i32;
+        params.knight_fork = threat[1].round() as i32;
+        params.pin = threat[2].round() as i32;
+        let middlegame = &vector[MIDDLEGAME_OFFSET..];
+        params.rook_on_seventh = middlegame[0].round() as i32;
+        params.queen_on_seventh = middlegame[1].round() as i32;
+        params.connected_rooks = middlegame[2].round() as i32;
+        params.doubled_rooks_open_file = middlegame[3].round() as i32;
+        params.knight_outpost = middlegame[4].round() as i32;
+        params
+    }
+
+    /// The evaluation is linear in the weights. Returns the (weight index, coefficient) pairs of the board, so the
+    /// evaluation without kings is the dot product with to_vector().
+    pub fn features(board: &ChessBoard) -> Vec<(usize, f64)> {
+        let mut features = Vec::new();
+        for row in 0..8 {
+            for col in 0..8 {
+                if let Square::Occupied(piece) = board.squares[row][col] {
+                    let sign = match piece.color {
+                        Color::White => 1.0,
+                        Color::Black => -1.0,
+                    };
+                    let index = piece_index(piece.kind);
+                    if piece.kind != PieceType::King {
+                        features.push((index, sign));
+                    }
+                    features.push((5 + index * 64 + square_table_row(piece.color, row) * 8 + col, sign));
+                }
+            }
+        }
+        let terms = passed_pawn_terms(board);
+        let counts = terms
+            .rows
+            .iter()
+            .chain(&[terms.king_distance, terms.rook_behind, terms.unstoppable])
+            .copied()
+            .collect::<Vec<i32>>();
+        for (i, count) in counts.into_iter().enumerate() {
+            if count != 0 {
+                features.push((PASSED_PAWN_OFFSET + i, count as f64));
+            }
+        }
+        let terms = opening_terms(board);
+        let counts = [
+            terms.undeveloped_minor,
+            terms.castled_king,
+            terms.early_queen,
+            terms.knight_excursion,
+        ];
+        for (i, count) in counts.into_iter().enumerate() {
+            if count != 0 && terms.phase != 0 {
+                features.push((OPENING_OFFSET + i, (count * terms.phase) as f64));
+            }
+        }
+        let terms = threat_terms(board);
+        for (i, count) in [terms.threatened_pieces, terms.knight_forks, terms.pins]
+            .into_iter()
+            .enumerate()
+        {
+            if count != 0 {
+                features.push((THREAT_OFFSET + i, count as f64));
+            }
+        }
+        let terms = middlegame_terms(board);
+        let counts = [
+            terms.rooks_on_seventh,
+            terms.queens_on_seventh,
+            terms.connected_rooks,
+            terms.doubled_rooks_open_file,
+            terms.knight_outposts,
+        ];
+        for (i, count) in counts.into_iter().enumerate() {
+            if count != 0 {
+                features.push((MIDDLEGAME_OFFSET + i, count as f64));
+            }
+        }
+        features
+    }
+
+    /// Renders the weights as Rust source of an EvalParams constant.
+    pub fn to_rust_source(&self, name: &str) -> String {
+        let mut source = format!("pub const {}: EvalParams = EvalParams {{\n    material: [", name);
+        let material: Vec<String> = self.material[..5].iter().map(|v| v.to_string()).collect();
+        source.push_str(&material.join(", "));
+        source.push_str(", WIN],\n    piece_square_tables: [\n");
+        for (kind, table) in PIECE_TYPES.iter().zip(&self.piece_square_tables) {
+            source.push_str(&format!("        // {:?}\n        [\n", kind));
+            for row in table {
+                let values: Vec<String> = row.iter().map(|v| format!("{:5}", v)).collect();
+                source.push_str(&format!("            [{}],\n", values.join(",")));
+            }
+            source.push_str("        ],\n");
+        }
+        source.push_str("    ],\n");
+        let passed_pawn: Vec<String> = self.passed_pawn.iter().map(|v| v.to_string()).collect();
+        source.push_str(&format!("    passed_pawn: [{}],\n", passed_pawn.join(", ")));
+        source.push_str(&format!(
+            "    passed_pawn_king_distance: {},\n",
+            self.passed_pawn_king_distance
+        ));
+        source.push_str(&format!(
+            "    rook_behind_passed_pawn: {},\n",
+            self.rook_behind_passed_pawn
+        ));
+        source.push_str(&format!(
+            "    unstoppable_passed_pawn: {},\n",
+            self.unstoppable_passed_pawn
+        ));
+        source.push_str(&format!("    undeveloped_minor: {},\n", self.undeveloped_minor));
+        source.push_str(&format!("    castled_king: {},\n", self.castled_king));
+        source.push_str(&format!("    early_queen: {},\n", self.early_queen));
+        source.push_str(&format!("    knight_excursion: {},\n", self.knight_excursion));
+        source.push_str(&format!("    threatened_piece: {},\n", self.threatened_piece));
+        source.push_str(&format!("    knight_fork: {},\n", self.knight_fork));
+        source.push_str(&format!("    pin: {},\n", self.pin));
+        source.push_str(&format!("    rook_on_seventh: {},\n", self.rook_on_seventh));
+        source.push_str(&format!("    queen_on_seventh: {},\n", self.queen_on_seventh));
+        source.push_str(&format!("    connected_rooks: {},\n", self.connected_rooks));
+        source.push_str(&format!(
+            "    doubled_rooks_open_file: {},\n",
+            self.doubled_rooks_open_file
+        ));
+        source.push_str(&format!("    knight_outpost: {},\n", self.knight_outpost));
+        source.push_str("};\n");
+        source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+
+    #[test]
+    fn test_features_match_evaluation() {
+        let vector = DEFAULT_EVAL_PARAMS.to_vector();
+        for fen in [
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "8/5k2/1p6/P1P5/4K3/8/6p1/3R4 b - - 0 50",
+            "r1b1k2r/ppppqppp/2n2n2/2b1N3/2B1P3/8/PPPP1PPP/RNBQ1RK1 w kq - 6 6",
+        ] {
+            let board = ChessBoard::from_fen(fen).unwrap();
+            let linear: f64 = EvalParams::features(&board).iter().map(|&(i, c)| vector[i] * c).sum();
+            assert_eq!(linear as i32, DEFAULT_EVAL_PARAMS.evaluate(&board));
+        }
+        assert_eq!(EvalParams::from_vector(&vector), DEFAULT_EVAL_PARAMS);
+    }
+
+    #[test]
+    fn test_passed_pawns() {
+        let terms = |fen| passed_pawn_terms(&ChessBoard::from_fen(fen).unwrap());
+        // d5 is passed, seen from White it is the fourth row of the square table
+        let passed = terms("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1");
+        assert_eq!(passed.rows, [0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(terms("4k3/8/4p3/3P4/8/8/8/4K3 w - - 0 1").rows, [0; 8]);
+        assert_eq!(terms("4k3/8/8/3p4/8/8/8/4K3 w - - 0 1").rows, [0, 0, 0, 0, -1, 0, 0, 0]);
+        // The black king is closer to d6 than the white one
+        assert!(passed.king_distance < 0);
+        assert!(terms("4k3/8/4K3/3P4/8/8/8/8 w - - 0 1").king_distance > 0);
+
+        assert_eq!(terms("4k3/8/8/3P4/8/8/8/3RK3 w - - 0 1").rook_behind, 1);
+        assert_eq!(terms("4k3/8/8/3P4/8/8/3N4/3RK3 w - - 0 1").rook_behind, 0);
+
+        // The black king on e5 catches the a-pawn only when it is to move
+        assert_eq!(terms("8/8/8/P3k3/8/8/8/K7 w - - 0 1").unstoppable, 1);
+        assert_eq!(terms("8/8/8/P3k3/8/8/8/K7 b - - 0 1").unstoppable, 0);
+        // Only in pawn endings
+        assert_eq!(terms("8/8/8/P3k3/8/8/8/K6N w - - 0 1").unstoppable, 0);
+
+        let board = ChessBoard::from_fen("8/8/8/P3k3/8/8/8/K7 w - - 0 1").unwrap();
+        assert!(DEFAULT_EVAL_PARAMS.passed_pawns(&board) > DEFAULT_EVAL_PARAMS.material[PieceType::Knight as usize]);
+    }
+
+    #[test]
+    fn test_opening_terms() {
+        let terms = |fen| opening_terms(&ChessBoard::from_fen(fen).unwrap());
+        let start = terms("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        assert_eq!(
+            start,
+            OpeningTerms {
+                phase: OPENING_PHASE,
+                ..OpeningTerms::default()
+            }
+        );
+
+        // White castled with both knights out, Black brought the queen out early and the knight to e4
+        let terms = terms("rnb1kb1r/pppp1ppp/8/4p1q1/4n3/2N2N2/PPPP1PPP/R1BQ1RK1 w kq - 0 5");
+        assert_eq!(terms.undeveloped_minor, 1 - 3);
+        assert_eq!(terms.castled_king, 1);
+        assert_eq!(terms.early_queen, -1);
+        assert_eq!(terms.knight_excursion, -1);
+
+        // Late in the game a knight in the enemy half is no excursion, and the terms fade out with the pieces
+        let board = ChessBoard::from_fen("6k1/5ppp/8/3n4/8/8/5PPP/6K1 w - - 0 40").unwrap();
+        assert_eq!(opening_terms(&board).knight_excursion, 0);
+        assert_eq!(opening_terms(&board).phase, 1);
+        assert_eq!(game_phase(&board), 1);
+        assert_eq!(
+            game_phase(&ChessBoard::from_fen(INITIAL_POSITION).unwrap()),
+            OPENING_PHASE
+        );
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 40").unwrap();
+        assert_eq!(DEFAULT_EVAL_PARAMS.opening(&board), 0);
+    }
+
+    #[test]
+    fn test_threat_terms() {
+        let terms = |fen| threat_terms(&ChessBoard::from_fen(fen).unwrap());
+        assert_eq!(
+            terms("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            ThreatTerms::default()
+        );
+        // The knight on d5 forks from c7, the pawn on b5 attacks the knight on c6
+        let terms = terms("r3k3/6pq/2n5/1P1N4/8/8/8/4K3 w - - 0 1");
+        assert_eq!(
+            terms,
+            ThreatTerms {
+                threatened_pieces: 1,
+                knight_forks: 1,
+                pins: 0,
+            }
+        );
+        let board = ChessBoard::from_fen("4k3/3n4/8/1B6/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(threat_terms(&board).pins, 1);
+        assert_eq!(DEFAULT_EVAL_PARAMS.threats(&board), DEFAULT_EVAL_PARAMS.pin);
+    }
+
+    #[test]
+    fn test_middlegame_terms() {
+        let terms = |fen| middlegame_terms(&ChessBoard::from_fen(fen).unwrap());
+        assert_eq!(
+            terms("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            MiddlegameTerms::default()
+        );
+
+        // The rook on the seventh cuts off the king, the black rooks are doubled on the open c-file
+        assert_eq!(
+            terms("2r3k1/R4ppp/2r5/8/8/8/5PPP/6K1 w - - 0 1"),
+            MiddlegameTerms {
+                rooks_on_seventh: 1,
+                connected_rooks: -1,
+                doubled_rooks_open_file: -1,
+                ..MiddlegameTerms::default()
+            }
+        );
+        // Without a king on the back rank or pawns on the seventh the rank is nothing special
+        assert_eq!(terms("8/R7/5kpp/8/8/8/5PPP/6K1 w - - 0 1").rooks_on_seventh, 0);
+        assert_eq!(terms("8/Q5pp/5k2/8/8/8/5PPP/6K1 w - - 0 1").queens_on_seventh, 1);
+        // A piece between the rooks disconnects them, pawns on the file make it no open file
+        assert_eq!(terms("6k1/8/8/8/8/8/8/R2B1RK1 w - - 0 1").connected_rooks, 0);
+        let semi_open = terms("6k1/2p5/8/8/8/8/2R5/2R3K1 w - - 0 1");
+        assert_eq!((semi_open.connected_rooks, semi_open.doubled_rooks_open_file), (1, 0));
+
+        // The knight on e5 is protected by d4 and no black pawn can drive it away
+        assert_eq!(terms("6k1/pp4pp/8/4N3/3P4/8/8/6K1 w - - 0 1").knight_outposts, 1);
+        assert_eq!(terms("6k1/pp3ppp/8/4N3/3P4/8/8/6K1 w - - 0 1").knight_outposts, 0);
+        assert_eq!(terms("6k1/pp4pp/8/4N3/8/8/8/6K1 w - - 0 1").knight_outposts, 0);
+        assert_eq!(terms("6k1/8/8/5p2/4n3/8/8/6K1 w - - 0 1").knight_outposts, -1);
+    }
+
+    #[test]
+    fn test_square_table_orientation() {
+        // The tables are written from White's side, the first row is the eighth rank
+        let mut params = EvalParams::default();
+        params.piece_square_tables[piece_index(PieceType::Knight)] = [[0; 8]; 8];
+        params.piece_square_tables[piece_index(PieceType::Knight)][1][6] = 70;
+        let knight = |fen: &str| params.breakdown(&ChessBoard::from_fen(fen).unwrap())[1].1;
+        assert_eq!(knight("4k3/6N1/8/8/8/8/8/4K3 w - - 0 1"), 70);
+        assert_eq!(knight("4k3/8/8/8/8/8/6n1/4K3 w - - 0 1"), -70);
+        assert_eq!(knight("4k3/8/8/8/8/8/6N1/4K3 w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn test_breakdown_sums_to_evaluation() {
+        let board = ChessBoard::from_fen("r1bqkb1r/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 4").unwrap();
+        let breakdown = DEFAULT_EVAL_PARAMS.breakdown(&board);
+        let sum: i32 = breakdown.iter().map(|(material, squares)| material + squares).sum();
+        assert_eq!(
+            sum + DEFAULT_EVAL_PARAMS.passed_pawns(&board)
+                + DEFAULT_EVAL_PARAMS.opening(&board)
+                + DEFAULT_EVAL_PARAMS.threats(&board)
+                + DEFAULT_EVAL_PARAMS.middlegame(&board),
+            DEFAULT_EVAL_PARAMS.evaluate(&board)
+        );
+        // Black has lost both knights
+        assert_eq!(breakdown[1].0, 2 * DEFAULT_EVAL_PARAMS.material[1]);
+        assert_eq!(breakdown[5].0, 0);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        assert_eq!(
+            EvalParams::from_json(&DEFAULT_EVAL_PARAMS.to_json()).unwrap(),
+            DEFAULT_EVAL_PARAMS
+        );
+
+        let params = EvalParams::from_json(r#"{"material": [1000, 3200, 3300, 5000, 9000, 10000000]}"#).unwrap();
+        assert_eq!(params.material[2], 3300);
+        assert_eq!(params.piece_square_tables, DEFAULT_EVAL_PARAMS.piece_square_tables);
+        assert!(EvalParams::from_json(r#"{"material": [1]}"#).is_err());
+    }
+}
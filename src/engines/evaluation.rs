@@ -0,0 +1,87 @@
+use crate::chess_board::{ChessBoard, Color, Square};
+use crate::engines::endgame;
+use crate::engines::engine_alpha_beta::WIN;
+use crate::engines::eval_params::EvalParams;
+
+/// Material values of pawn, knight, bishop, rook, queen and king in 1/1000 pawn. A side without a king has lost.
+const PIECE_VALUES: [i32; 6] = [1_000, 3_000, 3_000, 5_000, 9_000, WIN];
+
+/// A static evaluation of positions, from White's point of view in 1/1000 pawn. The engines search with any
+/// evaluator, so a simpler or a learned one replaces the tuned evaluation without changes to the search.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, board: &ChessBoard) -> i32;
+
+    /// The terms of the evaluation, one line each, for the debug diagnostics of the search.
+    fn breakdown(&self, board: &ChessBoard) -> Vec<String> {
+        vec![format!("Evaluation {:+} cp for White", self.evaluate(board) / 10)]
+    }
+}
+
+/// Counts the material only.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&self, board: &ChessBoard) -> i32 {
+        let mut evaluation = 0;
+        for square in board.squares.iter().flatten() {
+            if let Square::Occupied(piece) = square {
+                evaluation += match piece.color {
+                    Color::White => PIECE_VALUES[piece.kind as usize],
+                    Color::Black => -PIECE_VALUES[piece.kind as usize],
+                };
+            }
+        }
+        evaluation
+    }
+}
+
+/// The weighted terms of the evaluation parameters, the recognized endgames have their own evaluation. Clearly won
+/// positions are nudged towards progress and away from stalemate.
+#[derive(Debug, Clone, Default)]
+pub struct TunedEvaluator {
+    pub params: EvalParams,
+}
+
+impl TunedEvaluator {
+    pub fn new(params: EvalParams) -> Self {
+        TunedEvaluator { params }
+    }
+}
+
+impl Evaluator for TunedEvaluator {
+    fn evaluate(&self, board: &ChessBoard) -> i32 {
+        match endgame::evaluate(&self.params, board) {
+            Some(evaluation) => endgame::make_progress(board, evaluation, false),
+            None => endgame::make_progress(board, self.params.evaluate(board), true),
+        }
+    }
+
+    fn breakdown(&self, board: &ChessBoard) -> Vec<String> {
+        self.params.breakdown_lines(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluators() {
+        let board = ChessBoard::from_fen("4k3/8/8/3p4/8/8/PPP5/R3K3 w - - 0 1").unwrap();
+        assert_eq!(MaterialEvaluator.evaluate(&board), 7_000);
+        assert_eq!(
+            MaterialEvaluator.breakdown(&board),
+            vec!["Evaluation +700 cp for White"]
+        );
+        let tuned = TunedEvaluator::default();
+        assert_eq!(tuned.evaluate(&board), EvalParams::default().evaluate(&board));
+        // King and rook against king is a recognized endgame
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(
+            tuned.evaluate(&board),
+            endgame::evaluate(&tuned.params, &board).unwrap()
+        );
+        assert_ne!(tuned.evaluate(&board), EvalParams::default().evaluate(&board));
+    }
+}
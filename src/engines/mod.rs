@@ -1,25 +1,130 @@
+use crate::chess_board::render::RenderOptions;
 use crate::chess_board::{Color, Move};
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 
+pub mod analysis;
+pub mod analysis_db;
+pub mod board;
+pub mod commentary;
+pub mod endgame;
 pub mod engine_alpha_beta;
 pub mod engine_minmax;
+pub mod eval_params;
+pub mod evaluation;
+pub mod move_picker;
+pub mod options;
+pub mod perft_eval;
+pub mod ponder;
+pub mod preset;
+pub mod protocol_log;
+pub mod search_stack;
+pub mod search_stats;
+pub mod search_trace;
+pub mod skill;
+pub mod transposition_table;
 pub mod uci;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod uci_client;
+pub mod verify_backends;
 
-type InfoCallback = fn(depth: i32, best_eval: i32, nodes: u64, elapsed: Duration, pv: String);
+pub const MAX_THREADS: usize = 64;
+
+/// Time limit of a search that only ends when it is stopped or reaches the maximum depth.
+pub const INFINITE_SEARCH_TIME: Duration = Duration::from_secs(60 * 60 * 24 * 10);
+
+/// What a search reports while it runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InfoEvent {
+    /// A depth of the iterative deepening completed. The seldepth is the deepest ply reached, the elapsed time and
+    /// the nodes count from the start of the search.
+    DepthCompleted {
+        depth: i32,
+        seldepth: i32,
+        score: i32,
+        nodes: u64,
+        elapsed: Duration,
+        pv: String,
+    },
+    /// The root move searched next, numbered from 1 in search order. Only sent once the search runs for a while.
+    CurrMove { depth: i32, mv: Move, number: usize },
+    /// A root move searched after the first one became the best of the depth, before the depth completed.
+    NewBestMove { depth: i32, score: i32, pv: String },
+    /// A diagnostic message for the user, only sent in debug mode.
+    String(String),
+    /// The fill of the hash table in per mille.
+    HashFull(u32),
+}
+
+/// Receives the events of a single search call.
+type InfoCallback<'a> = &'a mut dyn FnMut(InfoEvent);
+
+/// Receives the events an engine sends outside of a search call, it is kept by the engine and may capture state.
+pub type InfoSink = Box<dyn FnMut(InfoEvent) + Send>;
 
 pub trait ChessEngine {
     fn name(&self) -> &str;
     fn author(&self) -> &str;
     fn set_position(&mut self, position: &str) -> Result<(), String>;
-    fn make_move(&mut self, move_algebraic_notation: &str) -> Result<(), &'static str>;
+    /// Plays a move in coordinate notation. An illegal move is rejected and leaves the position unchanged.
+    fn make_move(&mut self, move_algebraic_notation: &str) -> Result<(), String>;
     fn find_best_move_iterative(
         &mut self,
         time_limit: Duration,
-        info_callback: InfoCallback,
+        info_callback: InfoCallback<'_>,
     ) -> Option<(Vec<Move>, i32, u64, i32)>;
     fn get_active_player(&self) -> Color;
     fn get_abort_channel(&self) -> Arc<AtomicBool>;
-    fn render_board(&self);
+    /// Prints the position the next search starts from.
+    fn render_board(&self, options: &RenderOptions);
+    /// Sets the number of search threads and returns the effective count after clamping.
+    fn set_threads(&mut self, threads: usize) -> usize;
+}
+
+/// Clamps a requested thread count to the available parallelism. There are no threads on wasm.
+pub fn effective_thread_count(requested: usize) -> usize {
+    #[cfg(target_arch = "wasm32")]
+    let available = 1;
+    #[cfg(not(target_arch = "wasm32"))]
+    let available = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    requested.clamp(1, available.min(MAX_THREADS))
+}
+
+/// The least time a move is searched, also with the clock run out: a search of 0 ms has no move to play.
+pub const MIN_MOVE_TIME: Duration = Duration::from_millis(10);
+
+/// Splits the remaining clock time over the moves to go and adds the increment, never using up the whole clock but
+/// searching at least MIN_MOVE_TIME.
+pub fn allocate_move_time(time_left: Duration, increment: Duration, moves_to_go: u32) -> Duration {
+    let time_for_move = time_left / moves_to_go.max(1) + increment;
+    let time_for_move = if time_for_move > time_left {
+        time_left.saturating_sub(Duration::from_millis(5))
+    } else {
+        time_for_move
+    };
+    time_for_move.max(MIN_MOVE_TIME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_thread_count() {
+        assert_eq!(effective_thread_count(0), 1);
+        assert_eq!(effective_thread_count(1), 1);
+        assert!(effective_thread_count(10_000) <= MAX_THREADS);
+        assert!(effective_thread_count(10_000) >= 1);
+    }
+
+    #[test]
+    fn test_allocate_move_time() {
+        let secs = Duration::from_secs;
+        assert_eq!(allocate_move_time(secs(300), Duration::ZERO, 30), secs(10));
+        assert_eq!(allocate_move_time(secs(300), secs(2), 30), secs(12));
+        assert_eq!(allocate_move_time(secs(1), secs(2), 30), Duration::from_millis(995));
+        assert_eq!(allocate_move_time(Duration::ZERO, Duration::ZERO, 0), MIN_MOVE_TIME);
+        assert_eq!(allocate_move_time(Duration::from_millis(3), secs(2), 30), MIN_MOVE_TIME);
+    }
 }
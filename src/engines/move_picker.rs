@@ -0,0 +1,418 @@
+use crate::chess_board::move_list::{partition_stable, sort_by_score, MoveList};
+use crate::chess_board::{ChessBoard, ChessField, Color, Move, Piece, PieceType, Square};
+use crate::engines::eval_params::{game_phase, is_passed_pawn};
+
+/// Killer moves remembered per ply.
+pub const KILLERS: usize = 2;
+
+/// Added to the ordering score of quiet moves that give check, which puts them before the castling moves and the
+/// other quiet moves.
+const CHECK_SCORE: i32 = 100;
+
+/// Game phase up to which pawn pushes are ordered before the other quiet moves, a rook and a minor piece each.
+const ENDGAME_PHASE: i32 = 6;
+
+/// Game phase up to which king moves towards the center come first, a minor piece each.
+const KING_ACTIVITY_PHASE: i32 = 2;
+
+/// Ordering score of a passed pawn push in the endgame, plus PAWN_RANK_SCORE per rank the pawn reaches. It stays
+/// below CHECK_SCORE.
+const PASSED_PAWN_PUSH_SCORE: i32 = 40;
+
+/// Ordering score per rank of the pawn pushes in the endgame, an advanced pawn is pushed first.
+const PAWN_RANK_SCORE: i32 = 5;
+
+/// Ordering score of a king move towards the center when few pieces remain.
+const KING_ACTIVITY_SCORE: i32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    TtMove,
+    GenerateNoisy,
+    GoodNoisy,
+    Killers,
+    GenerateQuiets,
+    Quiets,
+    BadNoisy,
+    Done,
+}
+
+/// Yields the legal moves of a position in stages: the TT move, captures and queen promotions that do not lose
+/// material, the killer moves, the quiet moves and finally the losing captures.
+/// Quiet moves are only scored and sorted and all moves are only checked for legality when they are reached, so a
+/// node with an early beta cutoff does not pay for the rest.
+/// The moves are kept in a list of the caller, the good noisy moves first, then the losing ones and the quiet moves.
+pub struct MovePicker<'a> {
+    board: &'a ChessBoard,
+    stage: Stage,
+    tt_move: Option<Move>,
+    killers: [Option<Move>; KILLERS],
+    killer_index: usize,
+    moves: &'a mut MoveList,
+    /// The next move of the current stage in the list.
+    index: usize,
+    /// Start of the losing noisy moves.
+    bad_noisy: usize,
+    /// Start of the quiet moves.
+    quiets: usize,
+    /// The pieces giving check, found when the moves are generated. Only evasions of them are tested for legality.
+    checkers: Vec<ChessField>,
+}
+
+impl<'a> MovePicker<'a> {
+    /// The moves of the list are replaced.
+    pub fn new(
+        board: &'a ChessBoard,
+        tt_move: Option<Move>,
+        killers: [Option<Move>; KILLERS],
+        moves: &'a mut MoveList,
+    ) -> Self {
+        MovePicker {
+            board,
+            stage: Stage::TtMove,
+            tt_move,
+            killers,
+            killer_index: 0,
+            moves,
+            index: 0,
+            bad_noisy: 0,
+            quiets: 0,
+            checkers: Vec::new(),
+        }
+    }
+
+    /// The next move of the list before end, if any.
+    fn next_before(&mut self, end: usize) -> Option<Move> {
+        if self.index == end {
+            return None;
+        }
+        self.index += 1;
+        Some(self.moves[self.index - 1].0)
+    }
+
+    fn is_tt_move(&self, mv: Move) -> bool {
+        self.tt_move == Some(mv)
+    }
+
+    fn is_killer(&self, mv: Move) -> bool {
+        self.killers.contains(&Some(mv))
+    }
+
+    /// Whether a generated move is legal.
+    fn is_legal(&self, mv: Move) -> bool {
+        self.board.may_evade(mv, &self.checkers) && self.board.is_pseudo_move_legal(mv)
+    }
+}
+
+impl Iterator for MovePicker<'_> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::GenerateNoisy;
+                    match self.tt_move {
+                        Some(mv) if self.board.is_legal(mv) => return Some(mv),
+                        // A move of another position must not suppress a legal move later on
+                        _ => self.tt_move = None,
+                    }
+                }
+                Stage::GenerateNoisy => {
+                    let board = self.board;
+                    self.moves.clear();
+                    self.checkers = board.checkers();
+                    board.generate_candidate_moves_into(&self.checkers, self.moves);
+                    self.quiets = partition_stable(self.moves, |mv| is_noisy(board, mv));
+                    // Good captures by MVV-LVA, which is positive, losing captures after them by how much they lose
+                    let mut good = 0;
+                    for (mv, score) in &mut self.moves[..self.quiets] {
+                        let see = see(board, *mv);
+                        if see >= 0 || mv.promotion.is_some() {
+                            good += 1;
+                        } else {
+                            *score = see;
+                        }
+                    }
+                    sort_by_score(&mut self.moves[..self.quiets]);
+                    self.bad_noisy = good;
+                    self.index = 0;
+                    self.stage = Stage::GoodNoisy;
+                }
+                Stage::GoodNoisy => match self.next_before(self.bad_noisy) {
+                    Some(mv) if !self.is_tt_move(mv) && self.is_legal(mv) => return Some(mv),
+                    Some(_) => {}
+                    None => self.stage = Stage::Killers,
+                },
+                Stage::Killers => {
+                    if self.killer_index == KILLERS {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    }
+                    let killer = self.killers[self.killer_index];
+                    self.killer_index += 1;
+                    match killer {
+                        Some(mv) if !self.is_tt_move(mv) && !is_noisy(self.board, mv) && self.board.is_legal(mv) => {
+                            return Some(mv)
+                        }
+                        _ => self.killers[self.killer_index - 1] = None,
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    let board = self.board;
+                    if let Some(king) = board.find_king_position(opponent(board.active_color)) {
+                        for (mv, score) in &mut self.moves[self.quiets..] {
+                            if board.gives_direct_check(*mv, king) {
+                                *score += CHECK_SCORE;
+                            }
+                        }
+                    }
+                    let phase = game_phase(board);
+                    if phase <= ENDGAME_PHASE {
+                        for (mv, score) in &mut self.moves[self.quiets..] {
+                            *score += endgame_score(board, *mv, phase);
+                        }
+                    }
+                    sort_by_score(&mut self.moves[self.quiets..]);
+                    self.index = self.quiets;
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.next_before(self.moves.len()) {
+                    Some(mv) if !self.is_tt_move(mv) && !self.is_killer(mv) && self.is_legal(mv) => return Some(mv),
+                    Some(_) => {}
+                    None => {
+                        self.index = self.bad_noisy;
+                        self.stage = Stage::BadNoisy;
+                    }
+                },
+                Stage::BadNoisy => match self.next_before(self.quiets) {
+                    Some(mv) if !self.is_tt_move(mv) && self.is_legal(mv) => return Some(mv),
+                    Some(_) => {}
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+/// The ordering bonus of a quiet move in the endgame: pawn pushes by how far they advance, passed pawns first, and
+/// king moves towards the center once few pieces are left. Piece shuffles come after them.
+fn endgame_score(board: &ChessBoard, mv: Move, phase: i32) -> i32 {
+    let Square::Occupied(piece) = board.squares[mv.from.row()][mv.from.col()] else {
+        return 0;
+    };
+    match piece.kind {
+        PieceType::Pawn => {
+            // The rank seen from the owner of the pawn, 7 is the promotion rank
+            let rank = match piece.color {
+                Color::White => mv.to.row(),
+                Color::Black => 7 - mv.to.row(),
+            } as i32;
+            let passed = is_passed_pawn(board, piece.color, mv.from.row(), mv.from.col());
+            rank * PAWN_RANK_SCORE + if passed { PASSED_PAWN_PUSH_SCORE } else { 0 }
+        }
+        PieceType::King if phase <= KING_ACTIVITY_PHASE => {
+            let center_distance = |row: usize, col: usize| (2 * row as i32 - 7).abs().max((2 * col as i32 - 7).abs());
+            if center_distance(mv.to.row(), mv.to.col()) < center_distance(mv.from.row(), mv.from.col()) {
+                KING_ACTIVITY_SCORE
+            } else {
+                0
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Captures and queen promotions, searched before the quiet moves.
+pub fn is_noisy(board: &ChessBoard, mv: Move) -> bool {
+    board.is_capture(mv) || mv.promotion == Some(PieceType::Queen)
+}
+
+fn see_value(kind: PieceType) -> i32 {
+    match kind {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 300,
+        PieceType::Bishop => 300,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 20_000,
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+/// Static exchange evaluation: the material the moving side wins, in centipawns, if both sides keep recapturing on
+/// the target square with their least valuable attacker and stop when recapturing loses.
+pub fn see(board: &ChessBoard, mv: Move) -> i32 {
+    let attacker = match board.squares[mv.from.row()][mv.from.col()] {
+        Square::Occupied(piece) => piece,
+        Square::Empty => return 0,
+    };
+    // The exchange is played out on the squares only
+    let mut board = board.clone();
+    let target = mv.to;
+
+    let captured = match board.squares[target.row()][target.col()] {
+        Square::Occupied(piece) => see_value(piece.kind),
+        Square::Empty if attacker.kind == PieceType::Pawn && target.col() != mv.from.col() => {
+            // En passant
+            board.squares[mv.from.row()][target.col()] = Square::Empty;
+            see_value(PieceType::Pawn)
+        }
+        Square::Empty => 0,
+    };
+    board.squares[mv.from.row()][mv.from.col()] = Square::Empty;
+    board.squares[target.row()][target.col()] = Square::Occupied(attacker);
+
+    let mut gains = vec![captured];
+    let mut on_target = attacker.kind;
+    let mut side = opponent(attacker.color);
+    while let Some((field, kind)) = board.least_valuable_attacker(target, side) {
+        let gain = see_value(on_target) - gains[gains.len() - 1];
+        let previous = gains[gains.len() - 1];
+        gains.push(gain);
+        if (-previous).max(gain) < 0 {
+            // Neither capturing nor standing pat gains anything, the exchange stops here
+            break;
+        }
+        board.squares[field.row()][field.col()] = Square::Empty;
+        board.squares[target.row()][target.col()] = Square::Occupied(Piece { color: side, kind });
+        on_target = kind;
+        side = opponent(side);
+    }
+
+    // Every side may stop capturing when it does not pay off
+    while gains.len() > 1 {
+        let gain = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(gain);
+    }
+    gains[0]
+}
+
+/// An optimistic estimate of the material a capture wins in centipawns for delta pruning, cheaper than [`see`]: the
+/// captured piece and the promotion. A defended piece worth less than the capturing one wins nothing, at best the
+/// recapture is answered and the exchange ends even.
+pub fn max_capture_gain(board: &ChessBoard, mv: Move) -> i32 {
+    let Square::Occupied(attacker) = board.squares[mv.from.row()][mv.from.col()] else {
+        return 0;
+    };
+    let captured = match board.squares[mv.to.row()][mv.to.col()] {
+        Square::Occupied(piece) => see_value(piece.kind),
+        // En passant
+        Square::Empty if attacker.kind == PieceType::Pawn && mv.to.col() != mv.from.col() => see_value(PieceType::Pawn),
+        Square::Empty => 0,
+    };
+    let promotion = mv
+        .promotion
+        .map_or(0, |kind| see_value(kind) - see_value(PieceType::Pawn));
+    if see_value(attacker.kind) > captured && board.defended(mv.to, opponent(attacker.color)) {
+        promotion
+    } else {
+        captured + promotion
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_see() {
+        // Pawn takes a knight defended by a pawn
+        let board = ChessBoard::from_fen("4k3/8/4p3/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(see(&board, Move::from_algebraic("e4d5")), 200);
+        // Queen takes a pawn defended by a pawn
+        let board = ChessBoard::from_fen("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        assert_eq!(see(&board, Move::from_algebraic("d2d5")), -800);
+        // Rook takes an undefended rook with an x-ray queen behind it
+        let board = ChessBoard::from_fen("3rk3/8/8/3r4/8/8/3R4/3QK3 w - - 0 1").unwrap();
+        assert_eq!(see(&board, Move::from_algebraic("d2d5")), 500);
+    }
+
+    #[test]
+    fn test_max_capture_gain() {
+        let board = ChessBoard::from_fen("4k3/8/2p5/3p4/4P3/8/3Q4/4K3 w - - 0 1").unwrap();
+        assert_eq!(max_capture_gain(&board, Move::from_algebraic("e4d5")), 100);
+        // The defended pawn is worth less than the queen
+        assert_eq!(max_capture_gain(&board, Move::from_algebraic("d2d5")), 0);
+        let board = ChessBoard::from_fen("4k3/8/8/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        assert_eq!(max_capture_gain(&board, Move::from_algebraic("d2d5")), 100);
+        let board = ChessBoard::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(max_capture_gain(&board, Move::from_algebraic("a7b8q")), 1300);
+    }
+
+    #[test]
+    fn test_move_picker_yields_all_legal_moves_once() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2P5/8/8/8/8/5k2/K7 w - - 0 1",
+        ];
+        for fen in fens {
+            let board = ChessBoard::from_fen(fen).unwrap();
+            let mut expected = board.generate_legal_moves();
+            // Killers and the TT move of other positions are skipped
+            let killers = [Some(Move::from_algebraic("a1a2")), Some(Move::from_algebraic("h7h5"))];
+            let tt_move = expected[expected.len() - 1];
+            let mut picked: Vec<Move> = MovePicker::new(&board, Some(tt_move), killers, &mut MoveList::new()).collect();
+            assert_eq!(picked[0], tt_move);
+            expected.sort();
+            picked.sort();
+            assert_eq!(picked, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_move_picker_order() {
+        let board = ChessBoard::from_fen("4k3/8/2p5/3p4/4P3/8/3Q4/4K3 w - - 0 1").unwrap();
+        let killer = Move::from_algebraic("d2d3");
+        let picked: Vec<Move> = MovePicker::new(&board, None, [Some(killer), None], &mut MoveList::new()).collect();
+        assert_eq!(picked[0], Move::from_algebraic("e4d5"));
+        assert_eq!(picked[1], killer);
+        // The losing queen capture comes last
+        assert_eq!(picked[picked.len() - 1], Move::from_algebraic("d2d5"));
+    }
+
+    #[test]
+    fn test_move_picker_checks_before_quiet_moves() {
+        let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let picked: Vec<Move> = MovePicker::new(&board, None, [None; KILLERS], &mut MoveList::new()).collect();
+        let sorted = |moves: &[Move]| {
+            let mut moves: Vec<String> = moves.iter().map(|mv| mv.as_algebraic()).collect();
+            moves.sort();
+            moves
+        };
+        // The checks, then the castling moves, then the other quiet moves
+        assert_eq!(sorted(&picked[..2]), ["a1a8", "h1h8"]);
+        assert_eq!(sorted(&picked[2..4]), ["e1c1", "e1g1"]);
+    }
+
+    #[test]
+    fn test_move_picker_endgame_order() {
+        let quiets = |fen| {
+            let board = ChessBoard::from_fen(fen).unwrap();
+            MovePicker::new(&board, None, [None; KILLERS], &mut MoveList::new())
+                .map(|mv| mv.as_algebraic())
+                .collect::<Vec<_>>()
+        };
+        // The passed pawn on b5 first, then the king towards the center and the pawn on g4, the knight moves last
+        let picked = quiets("7k/6p1/8/1P6/6P1/8/8/5NK1 w - - 0 40");
+        assert_eq!(picked[0], "b5b6");
+        let mut king_moves = picked[1..3].to_vec();
+        king_moves.sort();
+        assert_eq!(king_moves, ["g1f2", "g1g2"]);
+        assert_eq!(picked[3], "g4g5");
+        // Without pieces the pawn that advances further comes first
+        assert_eq!(
+            quiets("8/8/4k3/8/8/8/P7/K7 w - - 0 50"),
+            ["a2a4", "a2a3", "a1b2", "a1b1"]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! The options the engine declares, sent to a GUI by the uci command and shown on the settings page of the user
+//! interface.
+use crate::config::EngineConfig;
+use crate::engines::preset::Preset;
+use crate::engines::skill::{Skill, DEFAULT_ELO, MAX_ELO, MIN_ELO};
+use crate::engines::transposition_table::MAX_HASH_MB;
+use crate::engines::MAX_THREADS;
+use std::fmt;
+
+/// The type of an option with its default, as in the option command of UCI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionType {
+    Check { default: bool },
+    Spin { default: i32, min: i32, max: i32 },
+    Combo { default: String, values: Vec<String> },
+    String { default: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineOption {
+    pub name: &'static str,
+    pub option_type: OptionType,
+}
+
+impl EngineOption {
+    pub fn check(name: &'static str, default: bool) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::Check { default },
+        }
+    }
+
+    pub fn spin(name: &'static str, default: i32, min: i32, max: i32) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::Spin { default, min, max },
+        }
+    }
+
+    pub fn combo(name: &'static str, default: &str, values: &[&str]) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::Combo {
+                default: default.to_string(),
+                values: values.iter().map(|value| value.to_string()).collect(),
+            },
+        }
+    }
+
+    pub fn string(name: &'static str, default: &str) -> Self {
+        EngineOption {
+            name,
+            option_type: OptionType::String {
+                default: default.to_string(),
+            },
+        }
+    }
+
+    /// The default as it is set with setoption, "true", "64" or the text.
+    pub fn default_value(&self) -> String {
+        match &self.option_type {
+            OptionType::Check { default } => default.to_string(),
+            OptionType::Spin { default, .. } => default.to_string(),
+            OptionType::Combo { default, .. } | OptionType::String { default } => default.clone(),
+        }
+    }
+
+    /// Checks a value of setoption and normalizes it, checks to "true" or "false" and spins to the range.
+    pub fn validate(&self, value: &str) -> Result<String, String> {
+        let invalid = || format!("Invalid value for {}: {}", self.name, value);
+        match &self.option_type {
+            OptionType::Check { .. } => value
+                .to_lowercase()
+                .parse::<bool>()
+                .map(|checked| checked.to_string())
+                .map_err(|_| invalid()),
+            OptionType::Spin { min, max, .. } => value
+                .trim()
+                .parse::<i32>()
+                .map(|number| number.clamp(*min, *max).to_string())
+                .map_err(|_| invalid()),
+            OptionType::Combo { values, .. } => values
+                .iter()
+                .find(|choice| choice.eq_ignore_ascii_case(value))
+                .cloned()
+                .ok_or_else(invalid),
+            OptionType::String { .. } if value == "<empty>" => Ok(String::new()),
+            OptionType::String { .. } => Ok(value.to_string()),
+        }
+    }
+}
+
+/// The option command of UCI, "option name Hash type spin default 16 min 1 max 65536".
+impl fmt::Display for EngineOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.option_type {
+            OptionType::Check { default } => write!(f, "check default {}", default),
+            OptionType::Spin { default, min, max } => write!(f, "spin default {} min {} max {}", default, min, max),
+            OptionType::Combo { default, values } => {
+                write!(f, "combo default {}", default)?;
+                values.iter().try_for_each(|value| write!(f, " var {}", value))
+            }
+            OptionType::String { default } if default.is_empty() => write!(f, "string default <empty>"),
+            OptionType::String { default } => write!(f, "string default {}", default),
+        }
+    }
+}
+
+/// The options of every front end with the values of the configuration as defaults. The UCI interface adds the
+/// options of its protocol and of the search tuning.
+pub fn engine_options(config: &EngineConfig) -> Vec<EngineOption> {
+    vec![
+        EngineOption::spin("Threads", config.threads as i32, 1, MAX_THREADS as i32),
+        EngineOption::spin("Hash", config.hash.clamp(1, MAX_HASH_MB) as i32, 1, MAX_HASH_MB as i32),
+        EngineOption::string("EvalFile", config.eval_file.as_deref().unwrap_or_default()),
+        EngineOption::check("UCI_LimitStrength", config.elo.is_some()),
+        EngineOption::spin(
+            "UCI_Elo",
+            Skill::new(config.elo.unwrap_or(DEFAULT_ELO)).elo() as i32,
+            MIN_ELO as i32,
+            MAX_ELO as i32,
+        ),
+        EngineOption::combo("Preset", config.preset.name(), &Preset::NAMES),
+    ]
+}
+
+/// Applies a value of one of the [`engine_options`] to the configuration and returns the value as it was applied.
+/// The strength is limited by setting an Elo, so setting UCI_Elo limits it as well. A preset sets the hash size of the
+/// preset, which can be changed after it.
+pub fn set_engine_option(config: &mut EngineConfig, name: &str, value: &str) -> Result<String, String> {
+    let option = engine_options(config)
+        .into_iter()
+        .find(|option| option.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Unknown option: {}", name))?;
+    let value = option.validate(value)?;
+    match option.name {
+        "Threads" => config.threads = value.parse().unwrap(),
+        "Hash" => config.hash = value.parse().unwrap(),
+        "EvalFile" => config.eval_file = Some(value.clone()).filter(|path| !path.is_empty()),
+        "UCI_LimitStrength" if value == "true" => config.elo = config.elo.or(Some(DEFAULT_ELO)),
+        "UCI_LimitStrength" => config.elo = None,
+        "UCI_Elo" => config.elo = Some(value.parse().unwrap()),
+        "Preset" => {
+            config.preset = Preset::from_name(&value).unwrap();
+            config.hash = config.preset.hash_mb();
+        }
+        _ => unreachable!("{} is one of the engine options", option.name),
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_option_command() {
+        assert_eq!(
+            EngineOption::spin("Hash", 16, 1, 1024).to_string(),
+            "option name Hash type spin default 16 min 1 max 1024"
+        );
+        assert_eq!(
+            EngineOption::check("MultiCut", true).to_string(),
+            "option name MultiCut type check default true"
+        );
+        assert_eq!(
+            EngineOption::string("EvalFile", "").to_string(),
+            "option name EvalFile type string default <empty>"
+        );
+        let combo = EngineOption {
+            name: "Style",
+            option_type: OptionType::Combo {
+                default: String::from("Normal"),
+                values: vec![String::from("Solid"), String::from("Normal")],
+            },
+        };
+        assert_eq!(
+            combo.to_string(),
+            "option name Style type combo default Normal var Solid var Normal"
+        );
+        assert_eq!(combo.validate("solid"), Ok(String::from("Solid")));
+        assert!(combo.validate("Wild").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let hash = EngineOption::spin("Hash", 16, 1, 1024);
+        assert_eq!(hash.validate("64"), Ok(String::from("64")));
+        assert_eq!(hash.validate("4096"), Ok(String::from("1024")));
+        assert!(hash.validate("many").is_err());
+        let check = EngineOption::check("MultiCut", true);
+        assert_eq!(check.validate("FALSE"), Ok(String::from("false")));
+        assert!(check.validate("no").is_err());
+        assert_eq!(
+            EngineOption::string("EvalFile", "").validate("<empty>"),
+            Ok(String::new())
+        );
+    }
+
+    #[test]
+    fn test_set_engine_option() {
+        let mut config = EngineConfig::default();
+        set_engine_option(&mut config, "threads", "4").unwrap();
+        assert_eq!(config.threads, 4);
+        assert_eq!(set_engine_option(&mut config, "Hash", "0"), Ok(String::from("1")));
+        assert_eq!(config.hash, 1);
+        set_engine_option(&mut config, "UCI_LimitStrength", "true").unwrap();
+        assert_eq!(config.elo, Some(DEFAULT_ELO));
+        set_engine_option(&mut config, "UCI_Elo", "1500").unwrap();
+        assert_eq!(config.elo, Some(1500));
+        set_engine_option(&mut config, "UCI_LimitStrength", "false").unwrap();
+        assert_eq!(config.elo, None);
+        set_engine_option(&mut config, "EvalFile", "params.txt").unwrap();
+        assert_eq!(config.eval_file.as_deref(), Some("params.txt"));
+        assert_eq!(
+            set_engine_option(&mut config, "Preset", "analysis"),
+            Ok(String::from("Analysis"))
+        );
+        assert_eq!(
+            (config.preset, config.hash),
+            (Preset::Analysis, Preset::Analysis.hash_mb())
+        );
+        assert!(set_engine_option(&mut config, "Preset", "Fastest").is_err());
+        assert!(set_engine_option(&mut config, "Contempt", "10").is_err());
+        assert!(set_engine_option(&mut config, "Threads", "all").is_err());
+        assert_eq!(config.threads, 4);
+    }
+}
@@ -0,0 +1,107 @@
+use crate::chess_board::ChessBoard;
+use crate::engines::board::Board;
+use crate::engines::evaluation::Evaluator;
+
+/// Walks the game tree like perft and checks every position: the hash and the evaluation of the position reached by
+/// make_move equal those of the same position read from its FEN, the mirrored position evaluates to the negated
+/// score and unmake_move restores the position. A new board representation passes it before it is searched.
+/// Returns the number of positions checked or a description of the first mismatch with the moves leading to it.
+pub fn perft_eval<B: Board>(board: &mut B, depth: u8, evaluator: &dyn Evaluator) -> Result<u64, String> {
+    let mut line = Vec::new();
+    walk(board, depth, evaluator, &mut line)
+}
+
+fn walk<B: Board>(board: &mut B, depth: u8, evaluator: &dyn Evaluator, line: &mut Vec<String>) -> Result<u64, String> {
+    check_position(board, evaluator)
+        .map_err(|e| format!("{}\nFEN: {}\nMoves: {}", e, board.to_fen(), line.join(" ")))?;
+    if depth == 0 {
+        return Ok(1);
+    }
+    let mut positions = 1;
+    for mv in board.generate_legal_moves() {
+        let fen = board.to_fen();
+        let hash = board.zobrist_hash();
+        line.push(mv.as_algebraic());
+        let undo = board.make_move_with_undo(mv);
+        positions += walk(board, depth - 1, evaluator, line)?;
+        board.unmake_move(undo);
+        if board.to_fen() != fen || board.zobrist_hash() != hash {
+            return Err(format!(
+                "unmake_move did not restore the position\nFEN: {}, expected {}\nMoves: {}",
+                board.to_fen(),
+                fen,
+                line.join(" ")
+            ));
+        }
+        line.pop();
+    }
+    Ok(positions)
+}
+
+fn check_position<B: Board>(board: &B, evaluator: &dyn Evaluator) -> Result<(), String> {
+    let fen = board.to_fen();
+    let recomputed = B::from_fen(&fen).map_err(|e| format!("The FEN of the position is invalid: {}", e))?;
+    if board.zobrist_hash() != recomputed.zobrist_hash() {
+        return Err(format!(
+            "Hash {:016x} differs from the recomputed hash {:016x}",
+            board.zobrist_hash(),
+            recomputed.zobrist_hash()
+        ));
+    }
+    let evaluation = board.evaluate(evaluator);
+    if evaluation != recomputed.evaluate(evaluator) {
+        return Err(format!(
+            "Evaluation {} differs from the recomputed evaluation {}",
+            evaluation,
+            recomputed.evaluate(evaluator)
+        ));
+    }
+    // The mirror is built by the reference board, the position is the same for every representation
+    let mirrored_fen = ChessBoard::from_fen(&fen)
+        .map_err(|e| format!("The FEN of the position is invalid: {}", e))?
+        .mirror()
+        .to_fen();
+    let mirrored = B::from_fen(&mirrored_fen).map_err(|e| format!("The mirrored FEN is invalid: {}", e))?;
+    if mirrored.evaluate(evaluator) != -evaluation {
+        return Err(format!(
+            "Evaluation {} is not the negated evaluation {} of the mirrored position {}",
+            evaluation,
+            mirrored.evaluate(evaluator),
+            mirrored_fen
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::evaluation::{MaterialEvaluator, TunedEvaluator};
+
+    /// Favors White, the mirrored position does not get the negated score.
+    struct WhiteBias;
+
+    impl Evaluator for WhiteBias {
+        fn evaluate(&self, board: &ChessBoard) -> i32 {
+            MaterialEvaluator.evaluate(board) + 10
+        }
+    }
+
+    #[test]
+    fn test_perft_eval() {
+        let mut board =
+            ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(perft_eval(&mut board, 2, &TunedEvaluator::default()), Ok(1 + 48 + 2039));
+
+        let mut board = ChessBoard::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft_eval(&mut board, 2, &MaterialEvaluator), Ok(1 + 14 + 191));
+
+        let error = perft_eval(&mut board, 1, &WhiteBias).unwrap_err();
+        assert!(error.starts_with("Evaluation"), "{}", error);
+        assert!(
+            error.contains("FEN: 8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+            "{}",
+            error
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use crate::chess_board::Move;
+
+/// Below this number of predictions the hit rate is not trusted and the single best reply is pondered.
+const MIN_PREDICTIONS: u32 = 10;
+/// Hit rate from which pondering on the predicted reply pays off.
+const SINGLE_REPLY_HIT_RATE: f32 = 0.35;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PonderStrategy {
+    /// Ponder on the position after the predicted opponent reply.
+    SingleReply,
+    /// Ponder on the position before the opponent reply, covering all candidate replies.
+    MultipleCandidates,
+}
+
+/// Counts how often the predicted opponent reply (the ponder move) was actually played.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PonderStats {
+    pub predictions: u32,
+    pub hits: u32,
+}
+
+impl PonderStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, hit: bool) {
+        self.predictions += 1;
+        if hit {
+            self.hits += 1;
+        }
+    }
+
+    pub fn record_reply(&mut self, predicted: Move, actual: Move) {
+        self.record(predicted == actual);
+    }
+
+    pub fn hit_rate(&self) -> Option<f32> {
+        if self.predictions == 0 {
+            None
+        } else {
+            Some(self.hits as f32 / self.predictions as f32)
+        }
+    }
+
+    pub fn strategy(&self) -> PonderStrategy {
+        match self.hit_rate() {
+            Some(rate) if self.predictions >= MIN_PREDICTIONS && rate < SINGLE_REPLY_HIT_RATE => {
+                PonderStrategy::MultipleCandidates
+            }
+            _ => PonderStrategy::SingleReply,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        match self.hit_rate() {
+            None => String::from("no predictions yet"),
+            Some(rate) => format!("{}/{} ({:.0}%)", self.hits, self.predictions, rate * 100.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ponder_stats() {
+        let mut stats = PonderStats::new();
+        assert_eq!(stats.hit_rate(), None);
+        assert_eq!(stats.strategy(), PonderStrategy::SingleReply);
+
+        stats.record_reply(Move::from_algebraic("e7e5"), Move::from_algebraic("e7e5"));
+        stats.record_reply(Move::from_algebraic("e7e5"), Move::from_algebraic("c7c5"));
+        assert_eq!(stats.hit_rate(), Some(0.5));
+        assert_eq!(stats.summary(), "1/2 (50%)");
+
+        // Too few samples to switch strategy
+        stats.record(false);
+        assert_eq!(stats.strategy(), PonderStrategy::SingleReply);
+
+        for _ in 0..10 {
+            stats.record(false);
+        }
+        assert_eq!(stats.strategy(), PonderStrategy::MultipleCandidates);
+
+        for _ in 0..10 {
+            stats.record(true);
+        }
+        assert_eq!(stats.strategy(), PonderStrategy::SingleReply);
+    }
+}
@@ -0,0 +1,130 @@
+//! Bundles of search settings that trade strength for speed. A slow device or the browser gets a usable engine with
+//! Fast, an analysis with Analysis prunes nothing that could hide a move. Every setting can still be changed on its
+//! own after the preset.
+use crate::engines::engine_alpha_beta::{DEFAULT_DELTA_MARGIN, DEFAULT_FUTILITY_MARGIN, DEFAULT_SINGULAR_MARGIN};
+use crate::engines::transposition_table::DEFAULT_HASH_MB;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Preset {
+    Fast,
+    #[default]
+    Default,
+    Strong,
+    Analysis,
+}
+
+/// The settings of a preset, margins in 1/1000 pawn as in the engine, None turns the pruning or extension off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SearchSettings {
+    pub singular_margin: Option<i32>,
+    pub multicut: bool,
+    pub delta_margin: Option<i32>,
+    pub futility_margin: Option<i32>,
+    pub quiescence_depth: Option<i32>,
+    pub hash_mb: usize,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::Fast, Preset::Default, Preset::Strong, Preset::Analysis];
+
+    /// The names of the presets, in the order of ALL.
+    pub const NAMES: [&'static str; 4] = ["Fast", "Default", "Strong", "Analysis"];
+
+    pub fn name(self) -> &'static str {
+        Preset::NAMES[self as usize]
+    }
+
+    /// The preset of a name in any case.
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn settings(self) -> SearchSettings {
+        match self {
+            // Small margins prune more, the quiescence search stops after a few captures and no verification
+            // searches are spent on singular moves
+            Preset::Fast => SearchSettings {
+                singular_margin: None,
+                multicut: false,
+                delta_margin: Some(1_000),
+                futility_margin: Some(1_000),
+                quiescence_depth: Some(4),
+                hash_mb: 8,
+            },
+            Preset::Default => SearchSettings {
+                singular_margin: Some(DEFAULT_SINGULAR_MARGIN),
+                multicut: true,
+                delta_margin: Some(DEFAULT_DELTA_MARGIN),
+                futility_margin: Some(DEFAULT_FUTILITY_MARGIN),
+                quiescence_depth: None,
+                hash_mb: DEFAULT_HASH_MB,
+            },
+            // Wider margins miss fewer tactics, a larger table keeps more of a longer game
+            Preset::Strong => SearchSettings {
+                singular_margin: Some(DEFAULT_SINGULAR_MARGIN),
+                multicut: true,
+                delta_margin: Some(3_000),
+                futility_margin: Some(2_000),
+                quiescence_depth: None,
+                hash_mb: 128,
+            },
+            // Nothing is cut off by a margin or the multicut, the singular extension still deepens forced lines
+            Preset::Analysis => SearchSettings {
+                singular_margin: Some(DEFAULT_SINGULAR_MARGIN),
+                multicut: false,
+                delta_margin: None,
+                futility_margin: None,
+                quiescence_depth: None,
+                hash_mb: 512,
+            },
+        }
+    }
+
+    pub fn hash_mb(self) -> usize {
+        self.settings().hash_mb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::engine_alpha_beta::AlphaBetaEngine;
+    use crate::engines::transposition_table::MAX_HASH_MB;
+    use crate::engines::ChessEngine;
+
+    #[test]
+    fn test_preset_names() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_name(&preset.name().to_lowercase()), Some(preset));
+            assert!((1..=MAX_HASH_MB).contains(&preset.hash_mb()));
+        }
+        assert_eq!(Preset::from_name("Fastest"), None);
+        assert_eq!(Preset::default().hash_mb(), DEFAULT_HASH_MB);
+    }
+
+    #[test]
+    fn test_presets_trade_nodes_for_strength() {
+        let nodes = |preset: Preset| {
+            let mut engine = AlphaBetaEngine::new();
+            engine
+                .set_position("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP2BPPP/R2QKB1R w KQ - 0 8")
+                .unwrap();
+            engine.set_preset(preset);
+            engine.find_best_move(3, false).unwrap().2
+        };
+        let fast = nodes(Preset::Fast);
+        let default = nodes(Preset::Default);
+        let analysis = nodes(Preset::Analysis);
+        assert!(
+            fast < default && default < analysis,
+            "{} {} {}",
+            fast,
+            default,
+            analysis
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Instant, SystemTime};
+#[cfg(target_arch = "wasm32")]
+use web_time::{Instant, SystemTime};
+
+/// Transcript of the protocol lines and search events, written when a log file is set so users can attach it to
+/// bug reports about the interaction with a GUI.
+struct ProtocolLog {
+    writer: LineWriter<File>,
+    opened: Instant,
+}
+
+static LOG: Mutex<Option<ProtocolLog>> = Mutex::new(None);
+
+/// Starts logging to the file, which is truncated. A log that is already open is closed.
+pub fn open(path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("Could not create log file {}: {}", path, e))?;
+    let mut log = ProtocolLog {
+        writer: LineWriter::new(file),
+        opened: Instant::now(),
+    };
+    let started = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let _ = writeln!(
+        log.writer,
+        "# chic {} log started at unix time {}.{:03}, times are seconds since then",
+        env!("CARGO_PKG_VERSION"),
+        started.as_secs(),
+        started.subsec_millis()
+    );
+    *LOG.lock().unwrap() = Some(log);
+    Ok(())
+}
+
+pub fn close() {
+    LOG.lock().unwrap().take();
+}
+
+pub fn is_open() -> bool {
+    LOG.lock().unwrap().is_some()
+}
+
+/// A line received from the GUI.
+pub fn incoming(line: &str) {
+    write('<', line);
+}
+
+/// A line sent to the GUI.
+pub fn outgoing(line: &str) {
+    write('>', line);
+}
+
+/// An event of the engine that is not part of the protocol.
+pub fn event(message: &str) {
+    write('#', message);
+}
+
+fn write(direction: char, line: &str) {
+    if let Some(log) = LOG.lock().unwrap().as_mut() {
+        let elapsed = log.opened.elapsed();
+        // Logging must never interrupt the engine, a failed write only loses the line
+        let _ = writeln!(log.writer, "{:10.3} {} {}", elapsed.as_secs_f64(), direction, line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_log() {
+        let path = std::env::temp_dir().join(format!("chic-protocol-log-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        incoming("not logged");
+        open(path).unwrap();
+        assert!(is_open());
+        incoming("isready");
+        outgoing("readyok");
+        event("Search started");
+        close();
+        outgoing("not logged either");
+
+        let transcript = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let lines: Vec<&str> = transcript.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("# chic "));
+        assert!(lines[1].ends_with(" < isready"));
+        assert!(lines[2].ends_with(" > readyok"));
+        assert!(lines[3].ends_with(" # Search started"));
+        assert!(open("/nonexistent/directory/log.txt").is_err());
+    }
+}
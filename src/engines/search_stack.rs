@@ -0,0 +1,135 @@
+use crate::chess_board::Move;
+use crate::engines::move_picker::KILLERS;
+
+/// The state of the node at a ply of the current line of the search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frame {
+    /// The best line found from the node, starting with its best move.
+    pub pv: Vec<Move>,
+    /// Quiet moves that caused a beta cutoff at this ply, kept from one node of the ply to the next.
+    pub killers: [Option<Move>; KILLERS],
+    /// The move searched from the node, the moves of the frames before lead from the root to it.
+    pub current_move: Option<Move>,
+    /// The static evaluation of the node from the side to move, once a pruning needed it.
+    pub static_eval: Option<i32>,
+    /// The move the singular verification search of the node skips.
+    pub excluded: Option<Move>,
+}
+
+/// The frames of the nodes from the root to the current one, index 0 is the root. A search thread owns its stack and
+/// passes it down the recursion, so the stacks of the threads do not share anything. The frames grow with the depth
+/// of the search and are kept by the next nodes at the same ply.
+#[derive(Debug, Clone, Default)]
+pub struct SearchStack {
+    frames: Vec<Frame>,
+}
+
+impl SearchStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the killer moves and lines of the last search.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub fn frame(&self, ply: usize) -> Option<&Frame> {
+        self.frames.get(ply)
+    }
+
+    pub fn frame_mut(&mut self, ply: usize) -> &mut Frame {
+        if self.frames.len() <= ply {
+            self.frames.resize(ply + 1, Frame::default());
+        }
+        &mut self.frames[ply]
+    }
+
+    /// Prepares the frame of a node and the one of its children. The line and static evaluation of an earlier node
+    /// at the ply are dropped, its killers stay, and the excluded move is the one set by a verification search.
+    pub fn enter(&mut self, ply: usize) -> &mut Frame {
+        self.frame_mut(ply + 1);
+        let frame = &mut self.frames[ply];
+        frame.pv.clear();
+        frame.static_eval = None;
+        frame
+    }
+
+    /// The best line found from the node at the ply.
+    pub fn pv(&self, ply: usize) -> &[Move] {
+        self.frame(ply).map_or(&[], |frame| &frame.pv)
+    }
+
+    /// The move followed by the line of the child node becomes the line of the ply.
+    pub fn save_pv(&mut self, mv: Move, ply: usize) {
+        self.frame_mut(ply + 1);
+        let (frames, child_frames) = self.frames.split_at_mut(ply + 1);
+        let line = &mut frames[ply].pv;
+        line.clear();
+        line.push(mv);
+        line.extend_from_slice(&child_frames[0].pv);
+    }
+
+    pub fn store_killer(&mut self, mv: Move, ply: usize) {
+        let killers = &mut self.frame_mut(ply).killers;
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+
+    /// The moves from the root to the node at the ply.
+    pub fn line(&self, ply: usize) -> Vec<Move> {
+        self.frames
+            .iter()
+            .take(ply)
+            .map_while(|frame| frame.current_move)
+            .collect()
+    }
+
+    /// Whether the current line starts with these moves.
+    pub fn follows(&self, line: &[Move]) -> bool {
+        line.len() <= self.frames.len()
+            && line
+                .iter()
+                .zip(&self.frames)
+                .all(|(&mv, frame)| frame.current_move == Some(mv))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_stack() {
+        let moves: Vec<Move> = ["e2e4", "e7e5", "g1f3", "b8c6"]
+            .iter()
+            .map(|mv| Move::from_algebraic(mv))
+            .collect();
+        let mut stack = SearchStack::new();
+        for (ply, &mv) in moves.iter().enumerate() {
+            stack.enter(ply).current_move = Some(mv);
+        }
+        assert_eq!(stack.line(2), moves[..2]);
+        assert!(stack.follows(&moves[..3]));
+        assert!(!stack.follows(&[moves[0], moves[2]]));
+        assert!(stack.follows(&[]));
+
+        // The lines are built from the deepest node up
+        stack.save_pv(moves[3], 3);
+        stack.save_pv(moves[2], 2);
+        assert_eq!(stack.pv(2), &moves[2..]);
+        stack.enter(2);
+        assert_eq!(stack.pv(2), &[]);
+        assert_eq!(stack.pv(3), &moves[3..]);
+        assert_eq!(stack.pv(10), &[]);
+
+        stack.store_killer(moves[2], 2);
+        stack.store_killer(moves[3], 2);
+        stack.store_killer(moves[3], 2);
+        assert_eq!(stack.frame(2).unwrap().killers, [Some(moves[3]), Some(moves[2])]);
+        stack.clear();
+        assert_eq!(stack.frame(2), None);
+    }
+}
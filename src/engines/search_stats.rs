@@ -0,0 +1,166 @@
+/// Beta cutoffs at this move index or later share the last bucket.
+pub const CUTOFF_BUCKETS: usize = 8;
+
+/// Counters of a single search, collected when statistics are enabled on the engine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Nodes of the main search, without the quiescence search.
+    pub nodes: u64,
+    pub quiescence_nodes: u64,
+    /// Beta cutoffs by the index of the move that caused them in the move list.
+    pub cutoffs: [u64; CUTOFF_BUCKETS],
+    pub tt_probes: u64,
+    pub tt_hits: u64,
+    pub re_searches: u64,
+    /// Hash moves extended because every other move stayed clearly below them.
+    pub singular_extensions: u64,
+    /// Nodes cut off because the hash move and another move failed high.
+    pub multicuts: u64,
+    /// Quiet moves skipped because the static evaluation of their node was too far below alpha.
+    pub futility_prunes: u64,
+    /// Nodes of every completed iteration, index 0 is depth 1.
+    pub iteration_nodes: Vec<u64>,
+}
+
+impl SearchStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_cutoff(&mut self, move_index: usize) {
+        self.cutoffs[move_index.min(CUTOFF_BUCKETS - 1)] += 1;
+    }
+
+    pub fn record_tt_probe(&mut self, hit: bool) {
+        self.tt_probes += 1;
+        if hit {
+            self.tt_hits += 1;
+        }
+    }
+
+    pub fn record_re_search(&mut self) {
+        self.re_searches += 1;
+    }
+
+    pub fn record_iteration(&mut self, nodes: u64) {
+        self.iteration_nodes.push(nodes);
+    }
+
+    pub fn total_cutoffs(&self) -> u64 {
+        self.cutoffs.iter().sum()
+    }
+
+    /// Share of the beta cutoffs caused by the first move, the main measure of the move ordering.
+    pub fn first_move_cutoff_rate(&self) -> Option<f64> {
+        ratio(self.cutoffs[0], self.total_cutoffs())
+    }
+
+    pub fn tt_hit_rate(&self) -> Option<f64> {
+        ratio(self.tt_hits, self.tt_probes)
+    }
+
+    pub fn quiescence_share(&self) -> Option<f64> {
+        ratio(self.quiescence_nodes, self.nodes + self.quiescence_nodes)
+    }
+
+    /// Geometric mean of the node growth from one iteration to the next.
+    pub fn effective_branching_factor(&self) -> Option<f64> {
+        match self.iteration_nodes.as_slice() {
+            [first, .., last] if *first > 0 => {
+                let steps = (self.iteration_nodes.len() - 1) as f64;
+                Some((*last as f64 / *first as f64).powf(1.0 / steps))
+            }
+            _ => None,
+        }
+    }
+
+    /// One line per statistic, to be printed as UCI info strings.
+    pub fn summary(&self) -> Vec<String> {
+        let cutoffs: Vec<String> = self
+            .cutoffs
+            .iter()
+            .enumerate()
+            .map(|(index, count)| {
+                let label = if index == CUTOFF_BUCKETS - 1 {
+                    format!("{}+", index + 1)
+                } else {
+                    (index + 1).to_string()
+                };
+                format!("{}: {}", label, count)
+            })
+            .collect();
+        vec![
+            format!("Nodes: {} main, {} quiescence", self.nodes, self.quiescence_nodes),
+            format!("Quiescence share: {}", percentage(self.quiescence_share())),
+            format!("Beta cutoffs by move: {}", cutoffs.join(", ")),
+            format!("First move cutoffs: {}", percentage(self.first_move_cutoff_rate())),
+            format!(
+                "TT hits: {} of {} probes ({})",
+                self.tt_hits,
+                self.tt_probes,
+                percentage(self.tt_hit_rate())
+            ),
+            format!("Re-searches: {}", self.re_searches),
+            format!(
+                "Singular extensions: {}, multicuts: {}",
+                self.singular_extensions, self.multicuts
+            ),
+            format!("Futility prunes: {}", self.futility_prunes),
+            match self.effective_branching_factor() {
+                Some(ebf) => format!("Effective branching factor: {:.2}", ebf),
+                None => String::from("Effective branching factor: n/a"),
+            },
+        ]
+    }
+}
+
+fn ratio(part: u64, total: u64) -> Option<f64> {
+    if total == 0 {
+        None
+    } else {
+        Some(part as f64 / total as f64)
+    }
+}
+
+fn percentage(rate: Option<f64>) -> String {
+    match rate {
+        Some(rate) => format!("{:.1}%", rate * 100.0),
+        None => String::from("n/a"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_stats() {
+        let mut stats = SearchStats::new();
+        assert_eq!(stats.first_move_cutoff_rate(), None);
+        assert_eq!(stats.effective_branching_factor(), None);
+
+        stats.record_cutoff(0);
+        stats.record_cutoff(0);
+        stats.record_cutoff(2);
+        stats.record_cutoff(100);
+        assert_eq!(stats.cutoffs[CUTOFF_BUCKETS - 1], 1);
+        assert_eq!(stats.first_move_cutoff_rate(), Some(0.5));
+
+        stats.nodes = 300;
+        stats.quiescence_nodes = 100;
+        assert_eq!(stats.quiescence_share(), Some(0.25));
+
+        stats.record_tt_probe(true);
+        stats.record_tt_probe(false);
+        assert_eq!(stats.tt_hit_rate(), Some(0.5));
+
+        for nodes in [20, 80, 320] {
+            stats.record_iteration(nodes);
+        }
+        assert!((stats.effective_branching_factor().unwrap() - 4.0).abs() < 1e-9);
+        assert_eq!(
+            stats.summary()[2],
+            "Beta cutoffs by move: 1: 2, 2: 0, 3: 1, 4: 0, 5: 0, 6: 0, 7: 0, 8+: 1"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use crate::engines::transposition_table::Bound;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+
+/// Nodes with fewer plies left are not traced by default.
+pub const DEFAULT_TRACE_DEPTH: i32 = 4;
+
+/// How the result of a traced node came about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    /// The moves were searched.
+    Searched,
+    /// The hash table had a deep enough result.
+    HashCutoff,
+    /// The reduced search of the singular extension found a second move failing high.
+    Multicut,
+}
+
+/// A node of the search tree. Scores are from the point of view of the side to move at the node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceNode {
+    /// Depth of the iteration the node was searched in.
+    pub iteration: i32,
+    /// The moves from the root to the node in coordinate notation.
+    pub line: String,
+    pub depth: i32,
+    pub alpha: i32,
+    pub beta: i32,
+    pub score: i32,
+    pub bound: Bound,
+    pub best_move: Option<String>,
+    pub kind: NodeKind,
+    /// Moves searched before the node returned, the index of the cutoff move plus one on a fail high.
+    pub moves: usize,
+}
+
+/// A line of a trace file, which holds one JSON object per line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TraceRecord {
+    /// The first line of a trace.
+    Start {
+        fen: String,
+        min_depth: i32,
+    },
+    Node(TraceNode),
+    /// A completed iteration with the score from the point of view of the side to move at the root.
+    Iteration {
+        depth: i32,
+        score: i32,
+        nodes: u64,
+        pv: String,
+    },
+}
+
+/// Writes the nodes of a search with at least min_depth plies left to a file, so users can follow why the engine
+/// preferred a line. Shallower nodes are left out, there are far too many of them.
+pub struct SearchTrace {
+    writer: LineWriter<File>,
+    min_depth: i32,
+}
+
+impl SearchTrace {
+    /// Creates the file, truncating an existing one.
+    pub fn create(path: &str, min_depth: i32) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Could not create trace file {}: {}", path, e))?;
+        Ok(SearchTrace {
+            writer: LineWriter::new(file),
+            min_depth,
+        })
+    }
+
+    pub fn min_depth(&self) -> i32 {
+        self.min_depth
+    }
+
+    /// Write errors are ignored, a broken trace must not stop the search.
+    pub fn record(&mut self, record: &TraceRecord) {
+        if let Ok(line) = serde_json::to_string(record) {
+            let _ = writeln!(self.writer, "{}", line);
+        }
+    }
+}
+
+/// The file of the trace of the n-th search: "trace.ndjson" becomes "trace-3.ndjson".
+pub fn numbered_path(path: &str, number: u64) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains(['/', '\\']) => {
+            format!("{}-{}.{}", stem, number, extension)
+        }
+        _ => format!("{}-{}", path, number),
+    }
+}
+
+/// Reads a trace file, the error names the first line that is not a record.
+pub fn read_trace(path: &str) -> Result<Vec<TraceRecord>, String> {
+    let file = File::open(path).map_err(|e| format!("Could not read trace file {}: {}", path, e))?;
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Could not read trace file {}: {}", path, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| format!("Line {} of {}: {}", index + 1, path, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// The nodes one move below line in the given iteration, in search order.
+pub fn children<'a>(records: &'a [TraceRecord], iteration: i32, line: &str) -> Vec<&'a TraceNode> {
+    let parent: Vec<&str> = line.split_whitespace().collect();
+    records
+        .iter()
+        .filter_map(|record| match record {
+            TraceRecord::Node(node) if node.iteration == iteration => Some(node),
+            _ => None,
+        })
+        .filter(|node| {
+            let moves: Vec<&str> = node.line.split_whitespace().collect();
+            moves.len() == parent.len() + 1 && moves.starts_with(&parent)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::engine_alpha_beta::AlphaBetaEngine;
+    use crate::engines::ChessEngine;
+    use std::time::Duration;
+
+    #[test]
+    fn test_numbered_path() {
+        assert_eq!(numbered_path("trace.ndjson", 3), "trace-3.ndjson");
+        assert_eq!(numbered_path("logs/trace", 1), "logs/trace-1");
+        assert_eq!(numbered_path("logs.d/trace", 1), "logs.d/trace-1");
+        assert_eq!(numbered_path(".trace", 2), ".trace-2");
+    }
+
+    #[test]
+    fn test_trace_of_a_search() {
+        let path = std::env::temp_dir().join(format!("chic-trace-{}.ndjson", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        engine.set_max_depth(3);
+        engine.set_trace(Some(SearchTrace::create(path, 1).unwrap()));
+        engine.find_best_move_iterative(Duration::from_secs(60), &mut |_| {});
+        engine.set_trace(None);
+        let records = read_trace(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            records[0],
+            TraceRecord::Start {
+                fen: String::from("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"),
+                min_depth: 1
+            }
+        );
+        let iterations: Vec<i32> = records
+            .iter()
+            .filter_map(|record| match record {
+                TraceRecord::Iteration { depth, .. } => Some(*depth),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(iterations, vec![1, 2, 3]);
+        assert!(matches!(records.last(), Some(TraceRecord::Iteration { pv, .. }) if pv.starts_with("a1a8")));
+        // The root moves but the mate, which ends the line before a node is recorded
+        let root_moves = children(&records, 3, "");
+        assert_eq!(root_moves.len(), 16);
+        assert!(root_moves.iter().all(|node| node.depth == 3));
+        let replies = children(&records, 3, "a1a2");
+        assert!(
+            !replies.is_empty()
+                && replies
+                    .iter()
+                    .all(|node| node.depth == 2 && node.line.starts_with("a1a2 "))
+        );
+    }
+}
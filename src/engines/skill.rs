@@ -0,0 +1,115 @@
+use crate::chess_board::Move;
+use rand::Rng;
+
+pub const MIN_ELO: u32 = 600;
+pub const MAX_ELO: u32 = 2200;
+pub const DEFAULT_ELO: u32 = 1500;
+
+/// Root moves a limited engine chooses from.
+const CANDIDATE_LINES: usize = 5;
+
+/// The Elo values are rough estimates against human players, not calibrated ratings.
+/// Scores are in 1/1000 pawn like the evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Skill {
+    elo: u32,
+}
+
+impl Skill {
+    pub fn new(elo: u32) -> Self {
+        Skill {
+            elo: elo.clamp(MIN_ELO, MAX_ELO),
+        }
+    }
+
+    pub fn elo(&self) -> u32 {
+        self.elo
+    }
+
+    /// 0.0 at MIN_ELO and 1.0 at MAX_ELO.
+    fn strength(&self) -> f64 {
+        (self.elo - MIN_ELO) as f64 / (MAX_ELO - MIN_ELO) as f64
+    }
+
+    /// From depth 1 at the lowest level to depth 6.
+    pub fn max_depth(&self) -> usize {
+        1 + (self.strength() * 5.0).round() as usize
+    }
+
+    /// From 500 nodes at the lowest level to 200000, growing exponentially with the Elo.
+    pub fn max_nodes(&self) -> u64 {
+        (500.0 * 400f64.powf(self.strength())) as u64
+    }
+
+    /// How far the choice spreads over worse moves, from two pawns at the lowest level to 0.03 pawns.
+    pub fn temperature(&self) -> f64 {
+        30.0 + (1.0 - self.strength()).powi(2) * 1970.0
+    }
+
+    /// Picks one of the best lines, sorted best first, with a probability falling exponentially with its score
+    /// loss against the best line. Returns the index of the line.
+    pub fn choose_line(&self, lines: &[(i32, Vec<Move>)], rng: &mut impl Rng) -> usize {
+        let Some(&(best_score, _)) = lines.first() else {
+            return 0;
+        };
+        let temperature = self.temperature();
+        let weights: Vec<f64> = lines
+            .iter()
+            .take(CANDIDATE_LINES)
+            .map(|&(score, _)| (-(best_score as f64 - score as f64) / temperature).exp())
+            .collect();
+        let mut choice = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (index, weight) in weights.iter().enumerate() {
+            if choice < *weight {
+                return index;
+            }
+            choice -= weight;
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64;
+
+    #[test]
+    fn test_skill_limits() {
+        let weakest = Skill::new(0);
+        let strongest = Skill::new(u32::MAX);
+        assert_eq!(weakest.elo(), MIN_ELO);
+        assert_eq!(weakest.max_depth(), 1);
+        assert_eq!(strongest.max_depth(), 6);
+        assert_eq!(weakest.max_nodes(), 500);
+        assert_eq!(strongest.max_nodes(), 200_000);
+        assert!(weakest.temperature() > Skill::new(DEFAULT_ELO).temperature());
+        assert!(Skill::new(DEFAULT_ELO).temperature() > strongest.temperature());
+    }
+
+    #[test]
+    fn test_choose_line() {
+        let mut rng = Pcg64::seed_from_u64(7);
+        let lines: Vec<(i32, Vec<Move>)> = [0, -100, -300, -5_000, -10_000_000]
+            .iter()
+            .map(|&score| (score, Vec::new()))
+            .collect();
+        let count = |skill: Skill, rng: &mut Pcg64| {
+            let mut counts = [0; 5];
+            for _ in 0..1000 {
+                counts[skill.choose_line(&lines, rng)] += 1;
+            }
+            counts
+        };
+
+        let strongest = count(Skill::new(MAX_ELO), &mut rng);
+        assert!(strongest[0] > 950, "{:?}", strongest);
+        let weakest = count(Skill::new(MIN_ELO), &mut rng);
+        assert!(weakest[1] > 100 && weakest[2] > 100, "{:?}", weakest);
+        // Even the weakest level rarely hangs a rook and never walks into a mate
+        assert!(weakest[3] < 100, "{:?}", weakest);
+        assert_eq!(weakest[4], 0, "{:?}", weakest);
+        assert_eq!(Skill::new(MIN_ELO).choose_line(&[], &mut rng), 0);
+    }
+}
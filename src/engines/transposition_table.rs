@@ -0,0 +1,344 @@
+use crate::chess_board::{ChessField, Move, PieceType};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicU64, AtomicU8};
+
+/// Size of the table of a new engine and the default of the UCI option Hash.
+pub const DEFAULT_HASH_MB: usize = 16;
+/// Largest table the UCI option Hash allows.
+pub const MAX_HASH_MB: usize = 1024;
+/// Entries sharing an index. The first ones keep the deepest entries of the current generation, the last one takes
+/// the entries that are too shallow for them, so deep results survive long analyses.
+const BUCKET_SIZE: usize = 4;
+/// The slot of a bucket that is always replaced.
+const ALWAYS_REPLACE: usize = BUCKET_SIZE - 1;
+/// Generations are counted in the six bits of a slot beside the bound and wrap around.
+const GENERATIONS: u8 = 64;
+/// Entries looked at by hashfull, the UCI standard reports the fill in per mille.
+const HASHFULL_SAMPLE: usize = 1000;
+
+/// What the score of an entry says about the true score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bound {
+    Exact,
+    /// The search failed high, the true score is at least the score.
+    Lower,
+    /// The search failed low, the true score is at most the score.
+    Upper,
+}
+
+/// A search result of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+}
+
+/// An entry with its key as it is stored, the move is packed in 16 bits, the bound and the generation share a byte.
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    key: u64,
+    score: i32,
+    best_move: u16,
+    depth: u8,
+    /// The bound in the low two bits, 0 in an empty slot, and the generation of the search that stored the entry.
+    bound_and_generation: u8,
+}
+
+impl Slot {
+    fn bound(&self) -> Option<Bound> {
+        match self.bound_and_generation & 3 {
+            1 => Some(Bound::Exact),
+            2 => Some(Bound::Lower),
+            3 => Some(Bound::Upper),
+            _ => None,
+        }
+    }
+
+    fn generation(&self) -> u8 {
+        self.bound_and_generation >> 2
+    }
+
+    fn holds(&self, hash: u64) -> bool {
+        self.bound().is_some() && self.key == hash
+    }
+
+    /// Everything but the key in 64 bits.
+    fn pack(&self) -> u64 {
+        self.score as u32 as u64
+            | (self.best_move as u64) << 32
+            | (self.depth as u64) << 48
+            | (self.bound_and_generation as u64) << 56
+    }
+
+    fn unpack(key: u64, data: u64) -> Self {
+        Slot {
+            key,
+            score: data as u32 as i32,
+            best_move: (data >> 32) as u16,
+            depth: (data >> 48) as u8,
+            bound_and_generation: (data >> 56) as u8,
+        }
+    }
+}
+
+/// A slot shared by the search threads without a lock, 16 bytes. Like in the perft cache the key is stored xor the
+/// packed entry, so a slot torn by two threads writing at once does not match the key and reads as another position.
+#[derive(Default)]
+struct AtomicSlot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl AtomicSlot {
+    fn load(&self) -> Slot {
+        let data = self.data.load(Relaxed);
+        Slot::unpack(self.check.load(Relaxed) ^ data, data)
+    }
+
+    fn save(&self, slot: Slot) {
+        let data = slot.pack();
+        self.check.store(slot.key ^ data, Relaxed);
+        self.data.store(data, Relaxed);
+    }
+}
+
+fn pack_bound(bound: Bound, generation: u8) -> u8 {
+    let bound = match bound {
+        Bound::Exact => 1,
+        Bound::Lower => 2,
+        Bound::Upper => 3,
+    };
+    bound | generation << 2
+}
+
+const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+/// From and to square in 6 bits each, the promotion in the top 3 bits. 0 is no move, a1a1 is never a move.
+fn pack_move(mv: Option<Move>) -> u16 {
+    let Some(mv) = mv else {
+        return 0;
+    };
+    let square = |field: ChessField| field.index() as u16;
+    let promotion = mv
+        .promotion
+        .and_then(|kind| PROMOTIONS.iter().position(|&p| p == kind))
+        .map_or(0, |index| index as u16 + 1);
+    square(mv.from) | square(mv.to) << 6 | promotion << 12
+}
+
+fn unpack_move(packed: u16) -> Option<Move> {
+    if packed == 0 {
+        return None;
+    }
+    let square = |bits: u16| ChessField::from_index(bits as usize & 63).unwrap();
+    Some(Move {
+        from: square(packed),
+        to: square(packed >> 6),
+        promotion: match packed >> 12 {
+            0 => None,
+            index => PROMOTIONS.get(index as usize - 1).copied(),
+        },
+    })
+}
+
+/// Search results by the Zobrist hash of the position, the table has a power of two buckets.
+/// Every search starts a new generation. Entries of older ones stay usable but are replaced first, so the table does
+/// not have to be cleared between searches or games. The threads of a search share the table.
+pub struct TranspositionTable {
+    buckets: Vec<[AtomicSlot; BUCKET_SIZE]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// A table of at most size_mb megabytes, rounded down to a power of two buckets.
+    pub fn new(size_mb: usize) -> Self {
+        let buckets = (size_mb * 1024 * 1024 / size_of::<[AtomicSlot; BUCKET_SIZE]>()).max(1);
+        TranspositionTable {
+            buckets: (0..1usize << buckets.ilog2()).map(|_| Default::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    /// The memory used by the entries in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.buckets.len() * size_of::<[AtomicSlot; BUCKET_SIZE]>()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    pub fn clear(&self) {
+        for slot in self.buckets.iter().flatten() {
+            slot.save(Slot::default());
+        }
+    }
+
+    /// Starts a new generation, the entries stored so far become old.
+    pub fn age(&self) {
+        let generation = self.generation.load(Relaxed);
+        self.generation.store((generation + 1) % GENERATIONS, Relaxed);
+    }
+
+    fn bucket_index(&self, hash: u64) -> usize {
+        hash as usize & (self.buckets.len() - 1)
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TtEntry> {
+        let bucket = &self.buckets[self.bucket_index(hash)];
+        let slot = bucket.iter().map(AtomicSlot::load).find(|slot| slot.holds(hash))?;
+        Some(TtEntry {
+            best_move: unpack_move(slot.best_move),
+            score: slot.score,
+            depth: slot.depth as i32,
+            bound: slot.bound()?,
+        })
+    }
+
+    /// Replaces the entry of the position or takes an empty slot. Otherwise the entry goes to the depth-preferred
+    /// slot holding an older generation or the shallowest entry, if it is at least as deep, and to the always-replace
+    /// slot if not. Without a best move the one of a previous entry of the position is kept for the move ordering.
+    pub fn store(&self, hash: u64, best_move: Option<Move>, score: i32, depth: i32, bound: Bound) {
+        let generation = self.generation.load(Relaxed);
+        let bucket = self.buckets[self.bucket_index(hash)].each_ref().map(AtomicSlot::load);
+        let depth = depth.clamp(0, u8::MAX as i32) as u8;
+        let index = match bucket.iter().position(|slot| slot.holds(hash)) {
+            Some(same) => same,
+            None => match bucket.iter().position(|slot| slot.bound().is_none()) {
+                Some(empty) => empty,
+                None => {
+                    let (weakest, slot) = bucket[..ALWAYS_REPLACE]
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, slot)| (slot.generation() == generation, slot.depth))
+                        .unwrap();
+                    if slot.generation() != generation || depth >= slot.depth {
+                        weakest
+                    } else {
+                        ALWAYS_REPLACE
+                    }
+                }
+            },
+        };
+        let slot = &bucket[index];
+        let best_move = match best_move {
+            None if slot.holds(hash) => slot.best_move,
+            _ => pack_move(best_move),
+        };
+        self.buckets[self.bucket_index(hash)][index].save(Slot {
+            key: hash,
+            score,
+            best_move,
+            depth,
+            bound_and_generation: pack_bound(bound, generation),
+        });
+    }
+
+    /// The share of entries stored by the current generation in per mille, estimated from the first entries.
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.capacity().min(HASHFULL_SAMPLE);
+        let generation = self.generation.load(Relaxed);
+        let used = self
+            .buckets
+            .iter()
+            .flatten()
+            .take(sample)
+            .map(AtomicSlot::load)
+            .filter(|slot| slot.bound().is_some() && slot.generation() == generation)
+            .count();
+        (used * 1000 / sample) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sizing() {
+        assert_eq!(size_of::<AtomicSlot>(), 16);
+        let table = TranspositionTable::new(1);
+        assert_eq!(table.size_bytes(), 1024 * 1024);
+        assert_eq!(table.capacity(), 1024 * 1024 / 16);
+        // Sizes are rounded down to a power of two buckets
+        assert_eq!(TranspositionTable::new(3).size_bytes(), 2 * 1024 * 1024);
+        assert_eq!(TranspositionTable::new(0).capacity(), BUCKET_SIZE);
+    }
+
+    #[test]
+    fn test_pack_move() {
+        for mv in ["a1b1", "h8a1", "e7e8q", "b2a1n", "g7h8r", "c2c1b"] {
+            let mv = Move::from_algebraic(mv);
+            assert_eq!(unpack_move(pack_move(Some(mv))), Some(mv));
+        }
+        assert_eq!(unpack_move(pack_move(None)), None);
+    }
+
+    #[test]
+    fn test_probe_and_store() {
+        let table = TranspositionTable::new(1);
+        let mv = Move::from_algebraic("e2e4");
+        assert_eq!(table.probe(42), None);
+        table.store(42, Some(mv), 150, 3, Bound::Exact);
+        let entry = TtEntry {
+            best_move: Some(mv),
+            score: 150,
+            depth: 3,
+            bound: Bound::Exact,
+        };
+        assert_eq!(table.probe(42), Some(entry));
+        // A fail low keeps the move of the position
+        table.store(42, None, -20, 4, Bound::Upper);
+        assert_eq!(table.probe(42).unwrap().best_move, Some(mv));
+        assert_eq!(table.probe(42).unwrap().bound, Bound::Upper);
+        assert_eq!(table.probe(42).unwrap().score, -20);
+
+        // A full bucket loses its shallowest entry
+        let buckets = table.buckets.len() as u64;
+        for (i, depth) in [5, 1, 6].into_iter().enumerate() {
+            table.store(42 + (i as u64 + 1) * buckets, None, 0, depth, Bound::Lower);
+        }
+        table.store(42 + 4 * buckets, None, 0, 2, Bound::Lower);
+        assert!(table.probe(42 + 2 * buckets).is_none());
+        assert!(table.probe(42).is_some());
+        assert!(table.hashfull() > 0);
+
+        table.clear();
+        assert_eq!(table.probe(42), None);
+        assert_eq!(table.hashfull(), 0);
+    }
+
+    #[test]
+    fn test_replacement() {
+        let table = TranspositionTable::new(1);
+        let buckets = table.buckets.len() as u64;
+        let hash = |i: u64| 7 + i * buckets;
+        for (i, depth) in [8, 9, 10, 1].into_iter().enumerate() {
+            table.store(hash(i as u64), None, 0, depth, Bound::Exact);
+        }
+        // Shallow entries only replace each other in the always-replace slot
+        table.store(hash(4), None, 0, 2, Bound::Exact);
+        table.store(hash(5), None, 0, 3, Bound::Exact);
+        assert!(table.probe(hash(3)).is_none() && table.probe(hash(4)).is_none());
+        assert!((0..3).all(|i| table.probe(hash(i)).is_some()));
+        assert!(table.probe(hash(5)).is_some());
+        // A deeper entry replaces the shallowest depth-preferred one
+        table.store(hash(6), None, 0, 9, Bound::Exact);
+        assert!(table.probe(hash(0)).is_none());
+        assert!(table.probe(hash(5)).is_some());
+
+        // Old entries stay usable but give way to the entries of the new generation
+        table.age();
+        assert_eq!(table.hashfull(), 0);
+        assert_eq!(table.probe(hash(1)).unwrap().depth, 9);
+        table.store(hash(7), None, 0, 1, Bound::Exact);
+        table.store(hash(8), None, 0, 1, Bound::Exact);
+        assert!(table.probe(hash(7)).is_some() && table.probe(hash(8)).is_some());
+        assert_eq!((1..3).filter(|&i| table.probe(hash(i)).is_some()).count(), 1);
+        assert!(table.probe(hash(6)).is_none());
+    }
+}
@@ -1,157 +1,791 @@
-use crate::chess_board::fen::INITIAL_POSITION;
-use crate::chess_board::Color;
-use crate::engines::engine_alpha_beta::AlphaBetaEngine;
-use crate::engines::ChessEngine;
+use crate::chess_board::fen::{FenMode, INITIAL_POSITION};
+use crate::chess_board::render::{BoardSize, RenderOptions};
+use crate::chess_board::{ChessBoard, Color, Move};
+use crate::config::EngineConfig;
+use crate::engines::engine_alpha_beta::{
+    AlphaBetaEngine, SearchProgress, DEFAULT_DELTA_MARGIN, DEFAULT_FUTILITY_MARGIN, DEFAULT_SINGULAR_MARGIN, MAX_PLY,
+};
+use crate::engines::eval_params::{EvalParams, PIECE_TYPES};
+use crate::engines::options::{engine_options, EngineOption};
+use crate::engines::ponder::{PonderStats, PonderStrategy};
+use crate::engines::preset::Preset;
+use crate::engines::protocol_log;
+use crate::engines::search_stats::SearchStats;
+use crate::engines::search_trace::{self, SearchTrace, DEFAULT_TRACE_DEPTH};
+use crate::engines::skill::{Skill, DEFAULT_ELO};
+use crate::engines::transposition_table::{DEFAULT_HASH_MB, MAX_HASH_MB};
+use crate::engines::{allocate_move_time, ChessEngine, InfoEvent, INFINITE_SEARCH_TIME, MIN_MOVE_TIME};
+use std::any::Any;
 use std::io::BufRead;
 use std::io::Write;
 use std::io::{stdin, stdout};
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::atomic::Ordering::Relaxed;
+use std::sync::mpsc::{self, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{result, thread};
 
-const MAX_DURATION: Duration = Duration::from_secs(60 * 60 * 24 * 10);
+/// Prints a line for the GUI and records it in the protocol log.
+macro_rules! send {
+    ($($arg:tt)*) => {{
+        let line = format!($($arg)*);
+        println!("{}", line);
+        protocol_log::outgoing(&line);
+    }};
+}
+
+/// Range of the Contempt option in centipawns.
+const MAX_CONTEMPT: i32 = 100;
+
+/// Largest SingularMargin in centipawns per ply.
+const MAX_SINGULAR_MARGIN: i32 = 100;
+
+/// Largest DeltaMargin and FutilityMargin in centipawns.
+const MAX_PRUNING_MARGIN: i32 = 1000;
+
+/// The engine's margin of a SingularMargin, DeltaMargin or FutilityMargin in centipawns, 0 turns the extension or
+/// pruning off.
+fn margin(centipawns: i32) -> Option<i32> {
+    (centipawns > 0).then_some(centipawns * 10)
+}
+
+/// Default and largest Move Overhead in milliseconds, the time the GUI and the connection take for every move.
+const DEFAULT_MOVE_OVERHEAD: i32 = 10;
+const MAX_MOVE_OVERHEAD: i32 = 5000;
 
-pub(crate) fn run_uci_interface() {
-    let mut engine = Arc::new(Mutex::new(AlphaBetaEngine::new()));
-    let mut abort = engine.lock().unwrap().get_abort_channel();
+/// Largest TraceDepth in plies.
+const MAX_TRACE_DEPTH: i32 = 100;
+
+/// How long a search may run over its time before the watchdog sends the fallback move, and how long quit waits for
+/// a search to stop.
+const WATCHDOG_MARGIN: Duration = Duration::from_millis(500);
+
+/// Search time for the position of the GUI when a ponder search on the position before the predicted reply is
+/// stopped.
+const PONDER_STOP_SEARCH_TIME: Duration = Duration::from_millis(50);
+
+/// Names of the UCI options for the material values of all pieces but the king, in PIECE_TYPES order.
+const MATERIAL_OPTIONS: [&str; 5] = ["PawnValue", "KnightValue", "BishopValue", "RookValue", "QueenValue"];
+
+/// The lifecycle of the search as seen by the GUI. Every go gets exactly one bestmove, sent by the main loop when the
+/// search is finished and the GUI may receive it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SearchState {
+    Idle,
+    Searching,
+    /// A go ponder search, its best move is only sent after stop, a ponderhit replaces it by a real search.
+    Pondering,
+    /// The search was stopped, its best move is sent when the search thread has ended.
+    Stopping,
+}
+
+/// The input of the main loop, commands of the GUI and searches that ended.
+enum Event {
+    Command(String),
+    /// The search with the id ended, by itself or because it was aborted.
+    SearchFinished(u64),
+    /// The search with the id should have ended by now, sent by the watchdog.
+    SearchOverdue(u64),
+    EndOfInput,
+}
+
+/// A search on its own thread, the thread returns the principal variation of the last completed iteration.
+struct RunningSearch {
+    id: u64,
+    handle: JoinHandle<Vec<Move>>,
+    /// The first legal move of the position, sent if the search fails or does not stop.
+    fallback: Option<Move>,
+}
+
+pub fn run_uci_interface(mut eval_params: EvalParams, defaults: &EngineConfig) {
+    let engine = Arc::new(Mutex::new(AlphaBetaEngine::new()));
+    engine.lock().unwrap().set_eval_params(eval_params.clone());
+    engine.lock().unwrap().set_info_sink(Some(Box::new(uci_info_callback)));
+    let abort = engine.lock().unwrap().get_abort_channel();
 
     let name = engine.lock().unwrap().name().to_string();
     let author = engine.lock().unwrap().author().to_string();
     let mut search_time_after_pondering = Duration::from_millis(5000);
+    let mut move_overhead = Duration::from_millis(DEFAULT_MOVE_OVERHEAD as u64);
+    // The options start with the defaults of the configuration file
+    let mut preset = defaults.preset;
+    engine.lock().unwrap().set_preset(preset);
+    let mut threads = engine.lock().unwrap().set_threads(defaults.threads);
+    let mut hash_mb = defaults.hash.clamp(1, MAX_HASH_MB);
+    if hash_mb != DEFAULT_HASH_MB {
+        engine.lock().unwrap().set_hash_size(hash_mb);
+    }
+    let mut fen_mode = FenMode::Strict;
+    let mut debug = false;
+    let mut limit_strength = defaults.elo.is_some();
+    let mut elo = Skill::new(defaults.elo.unwrap_or(DEFAULT_ELO)).elo();
+    engine.lock().unwrap().set_skill(skill(limit_strength, elo));
+    let mut trace_file: Option<String> = None;
+    let mut trace_depth = DEFAULT_TRACE_DEPTH;
+    let mut stats = PonderStats::new();
+    let mut prediction: Option<(String, String)> = None;
+    let mut position_outcome: Option<bool> = None;
+    let mut last_position: Option<(String, Vec<String>)> = None;
+    // The ponder search runs on the position before the predicted reply, its best move is for the wrong side
+    let mut pondering_before_reply = false;
+    let mut state = SearchState::Idle;
+    let mut search: Option<RunningSearch> = None;
+    let mut search_id = 0;
 
-    let mut handle: Option<JoinHandle<()>> = None;
+    // Commands are read on their own thread, so the main loop also learns about searches that end by themselves
+    let (events, receiver) = mpsc::channel();
+    let input = events.clone();
+    thread::spawn(move || {
+        for line in stdin().lock().lines().map_while(result::Result::ok) {
+            if input.send(Event::Command(line)).is_err() {
+                return;
+            }
+        }
+        input.send(Event::EndOfInput).ok();
+    });
 
-    for line in stdin().lock().lines() {
-        let line = match line {
-            Ok(l) => l.trim().to_string(),
-            Err(_) => continue,
+    for event in receiver {
+        let line = match event {
+            Event::Command(line) => line.trim().to_string(),
+            Event::SearchFinished(id) => {
+                // A ponder search that ends by itself keeps its best move until stop or ponderhit
+                let current = search.as_ref().is_some_and(|search| search.id == id);
+                if current && matches!(state, SearchState::Searching | SearchState::Stopping) {
+                    let best_move = wait_for_search(&mut search);
+                    prediction = send_best_move(&engine, &best_move);
+                    state = SearchState::Idle;
+                }
+                continue;
+            }
+            Event::SearchOverdue(id) => {
+                let current = search.as_ref().is_some_and(|search| search.id == id);
+                let finished = search.as_ref().is_some_and(|search| search.handle.is_finished());
+                if current && !finished && matches!(state, SearchState::Searching | SearchState::Stopping) {
+                    // The search thread keeps the engine until it ends, the GUI gets the fallback move right away
+                    abort.store(true, Relaxed);
+                    protocol_log::event("Search overdue, sending the fallback move");
+                    send!("info string error Search did not stop in time");
+                    let fallback = search.take().and_then(|search| search.fallback);
+                    prediction = send_best_line(fallback.as_slice());
+                    state = SearchState::Idle;
+                }
+                continue;
+            }
+            Event::EndOfInput => "quit".to_string(),
         };
 
         if line.is_empty() {
             continue;
         }
+        protocol_log::incoming(&line);
 
         // Parse UCI commands:
         let tokens: Vec<&str> = line.split_whitespace().collect();
         match tokens[0] {
             "uci" => {
-                println!("id name {}", name);
-                println!("id author {}", author);
-                println!("uciok");
+                send!("id name {}", name);
+                send!("id author {}", author);
+                let config = EngineConfig {
+                    threads,
+                    hash: hash_mb,
+                    elo: limit_strength.then_some(elo),
+                    eval_file: None,
+                    preset,
+                };
+                for option in engine_options(&config).into_iter().chain(uci_options(&eval_params)) {
+                    send!("{}", option);
+                }
+                send!("uciok");
                 stdout().flush().unwrap();
             }
             "isready" => {
-                println!("readyok");
+                send!("readyok");
                 stdout().flush().unwrap();
             }
             "ucinewgame" => {
-                engine = Arc::new(Mutex::new(AlphaBetaEngine::new()));
-                abort = engine.lock().unwrap().get_abort_channel();
+                // A game does not depend on the one before, the position command follows
+                engine.lock().unwrap().new_game();
+                prediction = None;
+                position_outcome = None;
+                last_position = None;
             }
+            "debug" => match tokens.get(1) {
+                // The search thread holds the engine, the flag is handed to it by the next go
+                Some(&"on") | Some(&"off") => debug = tokens[1] == "on",
+                _ => send!("info string Invalid debug command: {}", line),
+            },
+            "setoption" => match parse_setoption(&tokens[1..]) {
+                Some((option_name, value)) => match option_name.to_lowercase().as_str() {
+                    "threads" => match value.parse::<usize>() {
+                        Ok(requested) => {
+                            threads = engine.lock().unwrap().set_threads(requested);
+                            if threads != requested {
+                                send!(
+                                    "info string Threads {} not available, using {} thread(s)",
+                                    requested,
+                                    threads
+                                );
+                            } else {
+                                send!("info string Using {} thread(s)", threads);
+                            }
+                            stdout().flush().unwrap();
+                        }
+                        Err(_) => send!("info string Invalid value for Threads: {}", value),
+                    },
+                    "hash" => match value.parse::<usize>() {
+                        Ok(requested) => {
+                            hash_mb = requested.clamp(1, MAX_HASH_MB);
+                            let bytes = engine.lock().unwrap().set_hash_size(hash_mb);
+                            send!("info string Hash table of {} MB", bytes / (1024 * 1024));
+                        }
+                        Err(_) => send!("info string Invalid value for Hash: {}", value),
+                    },
+                    "preset" => match Preset::from_name(&value) {
+                        Some(requested) => {
+                            // The tuning options are set by the preset, the hash table gets its size
+                            preset = requested;
+                            hash_mb = preset.hash_mb();
+                            let mut engine = engine.lock().unwrap();
+                            engine.set_preset(preset);
+                            let bytes = engine.set_hash_size(hash_mb);
+                            send!(
+                                "info string Preset {} with a hash table of {} MB",
+                                preset.name(),
+                                bytes / (1024 * 1024)
+                            );
+                        }
+                        None => send!("info string Invalid value for Preset: {}", value),
+                    },
+                    "searchstats" => match value.to_lowercase().parse::<bool>() {
+                        Ok(enabled) => {
+                            engine.lock().unwrap().set_collect_stats(enabled);
+                        }
+                        Err(_) => send!("info string Invalid value for SearchStats: {}", value),
+                    },
+                    "lenientfen" => match value.to_lowercase().parse::<bool>() {
+                        Ok(lenient) => fen_mode = if lenient { FenMode::Lenient } else { FenMode::Strict },
+                        Err(_) => send!("info string Invalid value for LenientFen: {}", value),
+                    },
+                    "uci_limitstrength" => match value.to_lowercase().parse::<bool>() {
+                        Ok(enabled) => {
+                            limit_strength = enabled;
+                            engine.lock().unwrap().set_skill(skill(limit_strength, elo));
+                        }
+                        Err(_) => send!("info string Invalid value for UCI_LimitStrength: {}", value),
+                    },
+                    "uci_elo" => match value.parse::<u32>() {
+                        Ok(requested) => {
+                            elo = Skill::new(requested).elo();
+                            engine.lock().unwrap().set_skill(skill(limit_strength, elo));
+                            if elo != requested {
+                                send!("info string UCI_Elo {} out of range, using {}", requested, elo);
+                            }
+                        }
+                        Err(_) => send!("info string Invalid value for UCI_Elo: {}", value),
+                    },
+                    "contempt" => match value.parse::<i32>() {
+                        Ok(requested) => {
+                            let contempt = requested.clamp(-MAX_CONTEMPT, MAX_CONTEMPT);
+                            // Centipawns, the engine scores in 1/1000 pawn
+                            engine.lock().unwrap().set_contempt(contempt * 10);
+                            if contempt != requested {
+                                send!("info string Contempt {} out of range, using {}", requested, contempt);
+                            }
+                        }
+                        Err(_) => send!("info string Invalid value for Contempt: {}", value),
+                    },
+                    "singularmargin" => match value.parse::<i32>() {
+                        Ok(requested) => {
+                            let singular_margin_cp = requested.clamp(0, MAX_SINGULAR_MARGIN);
+                            engine.lock().unwrap().set_singular_margin(margin(singular_margin_cp));
+                            if singular_margin_cp != requested {
+                                send!(
+                                    "info string SingularMargin {} out of range, using {}",
+                                    requested,
+                                    singular_margin_cp
+                                );
+                            }
+                        }
+                        Err(_) => send!("info string Invalid value for SingularMargin: {}", value),
+                    },
+                    "multicut" => match value.to_lowercase().parse::<bool>() {
+                        Ok(enabled) => {
+                            engine.lock().unwrap().set_multicut(enabled);
+                        }
+                        Err(_) => send!("info string Invalid value for MultiCut: {}", value),
+                    },
+                    "deltamargin" | "futilitymargin" => match value.parse::<i32>() {
+                        Ok(requested) => {
+                            let margin_cp = requested.clamp(0, MAX_PRUNING_MARGIN);
+                            let mut engine = engine.lock().unwrap();
+                            if option_name.eq_ignore_ascii_case("DeltaMargin") {
+                                engine.set_delta_margin(margin(margin_cp));
+                            } else {
+                                engine.set_futility_margin(margin(margin_cp));
+                            }
+                            if margin_cp != requested {
+                                send!(
+                                    "info string {} {} out of range, using {}",
+                                    option_name,
+                                    requested,
+                                    margin_cp
+                                );
+                            }
+                        }
+                        Err(_) => send!("info string Invalid value for {}: {}", option_name, value),
+                    },
+                    "move overhead" => match value.parse::<i32>() {
+                        Ok(requested) => {
+                            let overhead_ms = requested.clamp(0, MAX_MOVE_OVERHEAD);
+                            move_overhead = Duration::from_millis(overhead_ms as u64);
+                            if overhead_ms != requested {
+                                send!(
+                                    "info string Move Overhead {} out of range, using {}",
+                                    requested,
+                                    overhead_ms
+                                );
+                            }
+                        }
+                        Err(_) => send!("info string Invalid value for Move Overhead: {}", value),
+                    },
+                    "tracefile" if value.is_empty() || value == "<empty>" => trace_file = None,
+                    "tracefile" => {
+                        send!("info string Tracing each search to a numbered file of {}", value);
+                        trace_file = Some(value);
+                    }
+                    "tracedepth" => match value.parse::<i32>() {
+                        Ok(requested) => {
+                            trace_depth = requested.clamp(1, MAX_TRACE_DEPTH);
+                            if trace_depth != requested {
+                                send!(
+                                    "info string TraceDepth {} out of range, using {}",
+                                    requested,
+                                    trace_depth
+                                );
+                            }
+                        }
+                        Err(_) => send!("info string Invalid value for TraceDepth: {}", value),
+                    },
+                    "logfile" if value.is_empty() || value == "<empty>" => protocol_log::close(),
+                    "logfile" => match protocol_log::open(&value) {
+                        Ok(()) => send!("info string Logging to {}", value),
+                        Err(e) => send!("info string {}", e),
+                    },
+                    "evalfile" => match EvalParams::from_file(&value) {
+                        Ok(params) => {
+                            eval_params = params;
+                            engine.lock().unwrap().set_eval_params(eval_params.clone());
+                            send!("info string Evaluation parameters loaded from {}", value);
+                        }
+                        Err(e) => send!("info string {}", e),
+                    },
+                    name => match MATERIAL_OPTIONS.iter().position(|option| option.to_lowercase() == name) {
+                        Some(index) => match value.parse::<i32>() {
+                            Ok(material) => {
+                                eval_params.material[index] = material;
+                                engine.lock().unwrap().set_eval_params(eval_params.clone());
+                                send!("info string {:?} value set to {}", PIECE_TYPES[index], material);
+                            }
+                            Err(_) => send!("info string Invalid value for {}: {}", option_name, value),
+                        },
+                        None => send!("info string Unknown option: {}", option_name),
+                    },
+                },
+                None => send!("info string Error parsing setoption command: {}", line),
+            },
             "position" => match parse_position(tokens) {
-                Ok((start_fen, moves)) => {
-                    let mut engine = engine.lock().unwrap();
-                    engine.set_position(start_fen.as_str()).unwrap();
-                    for mv in moves {
-                        engine.make_move(mv.as_str()).unwrap();
+                Ok((start_fen, moves)) => match set_engine_position(&engine, &start_fen, &moves, fen_mode) {
+                    Ok(()) => {
+                        position_outcome = prediction
+                            .take()
+                            .and_then(|(best, ponder)| prediction_outcome(&best, &ponder, &moves));
+                        last_position = Some((start_fen, moves));
                     }
-                }
+                    Err(e) => send!("info string Invalid position: {}", e),
+                },
                 Err(e) => {
-                    println!("Error parsing position command: {}", e);
+                    send!("info string Error parsing position command: {}", e);
                 }
             },
             "go" => {
-                let mut search_time = parse_go_command(&tokens[1..], engine.lock().unwrap().get_active_player());
+                // A go during a search is a protocol error, the previous go still gets its best move. The engine is
+                // only free once that search ended.
+                if state != SearchState::Idle {
+                    abort.store(true, Relaxed);
+                    let mut best_move = wait_for_search(&mut search);
+                    if state == SearchState::Pondering && pondering_before_reply {
+                        search_id += 1;
+                        best_move = search_gui_position(&engine, &last_position, fen_mode, &events, search_id);
+                    }
+                    prediction = send_best_move(&engine, &best_move);
+                }
+                let active_player = engine.lock().unwrap().get_active_player();
+                let (mut search_time, mut time_reason) = parse_go_command(&tokens[1..], active_player, move_overhead);
                 let (depth, nodes) = parse_depth_and_nodes(&tokens[1..]);
-                if tokens.len() > 1 {
-                    match tokens[1] {
-                        "infinite" => {
-                            search_time = MAX_DURATION;
-                        }
-                        "ponder" => {
-                            search_time_after_pondering =
-                                parse_go_command(&tokens[1..], engine.lock().unwrap().get_active_player());
-                            search_time = MAX_DURATION;
-                        }
-                        _ => {
-                            search_time = parse_go_command(&tokens[1..], engine.lock().unwrap().get_active_player());
-                        }
-                    };
+                // The limits hold for this go only, the skill level may limit the search further
+                {
+                    let mut engine = engine.lock().unwrap();
+                    engine.set_max_depth(depth);
+                    engine.set_max_nodes(nodes);
+                    engine.set_debug(debug);
+                }
+                match tokens.get(1) {
+                    Some(&"infinite") => {
+                        search_time = INFINITE_SEARCH_TIME;
+                        time_reason = String::from("infinite search until stop");
+                    }
+                    Some(&"ponder") => {
+                        search_time_after_pondering = search_time;
+                        search_time = INFINITE_SEARCH_TIME;
+                        time_reason = format!("pondering until ponderhit or stop, then {}", time_reason);
+                    }
+                    _ => {}
+                }
+                if debug {
+                    send!("info string Time management: {}", time_reason);
                 }
 
-                abort.store(true, Relaxed);
-                if let Some(h) = handle.take() {
-                    h.join().unwrap();
+                // While pondering the position already contains the predicted reply, so the real
+                // reply is only known on ponderhit or stop.
+                let pondering = tokens.get(1) == Some(&"ponder");
+                if let Some(hit) = position_outcome.take() {
+                    if !pondering {
+                        stats.record(hit);
+                    }
                 }
-                let engine_clone = Arc::clone(&engine);
-                handle = Some(thread::spawn(move || {
-                    let mut engine = engine_clone.lock().unwrap();
-                    let (best_move, _, _, _) = engine.find_best_move_iterative(search_time, uci_info_callback).unwrap();
-                    if best_move.len() > 1 {
-                        println!(
-                            "bestmove {} ponder {}",
-                            best_move[0].as_algebraic(),
-                            best_move[1].as_algebraic()
-                        );
-                    } else {
-                        println!("bestmove {}", best_move[0].as_algebraic());
+
+                pondering_before_reply = false;
+                if pondering && stats.strategy() == PonderStrategy::MultipleCandidates {
+                    // The predicted reply is unreliable, so search the position before it instead.
+                    if let Some((start_fen, moves)) = &last_position {
+                        pondering_before_reply = !moves.is_empty();
+                        let before_reply = &moves[..moves.len().saturating_sub(1)];
+                        if let Err(e) = set_engine_position(&engine, start_fen, before_reply, fen_mode) {
+                            send!("info string Invalid position: {}", e);
+                        }
                     }
-                }));
+                }
+                protocol_log::event(&format!("Search started, {}", time_reason));
+                search_id += 1;
+                set_trace(&engine, trace_file.as_deref(), trace_depth, search_id);
+                search = Some(spawn_search(&engine, search_time, &events, search_id));
+                state = if pondering {
+                    SearchState::Pondering
+                } else {
+                    SearchState::Searching
+                };
+            }
+            "ponderhit" if state != SearchState::Pondering => {
+                send!("info string Ponderhit without a ponder search");
             }
             "ponderhit" => {
+                // The ponder search is replaced, its best move is never sent
                 abort.store(true, Relaxed);
-                if let Some(h) = handle.take() {
-                    h.join().unwrap();
-                }
-                let engine_clone = Arc::clone(&engine);
-                handle = Some(thread::spawn(move || {
-                    let mut engine = engine_clone.lock().unwrap();
-                    let (best_move, _, _, _) = engine
-                        .find_best_move_iterative(search_time_after_pondering, uci_info_callback)
-                        .unwrap();
-                    if best_move.len() > 1 {
-                        println!(
-                            "bestmove {} ponder {}",
-                            best_move[0].as_algebraic(),
-                            best_move[1].as_algebraic()
-                        );
-                    } else {
-                        println!("bestmove {}", best_move[0].as_algebraic());
+                wait_for_search(&mut search);
+                stats.record(true);
+                engine.lock().unwrap().set_debug(debug);
+                if let Some((start_fen, moves)) = &last_position {
+                    if let Err(e) = set_engine_position(&engine, start_fen, moves, fen_mode) {
+                        send!("info string Invalid position: {}", e);
                     }
-                }));
+                }
+                if debug {
+                    send!(
+                        "info string Time management: ponderhit, searching {} ms",
+                        search_time_after_pondering.as_millis()
+                    );
+                }
+                protocol_log::event(&format!(
+                    "Search started after ponderhit, {} ms",
+                    search_time_after_pondering.as_millis()
+                ));
+                search_id += 1;
+                set_trace(&engine, trace_file.as_deref(), trace_depth, search_id);
+                search = Some(spawn_search(&engine, search_time_after_pondering, &events, search_id));
+                state = SearchState::Searching;
             }
-            "stop" => {
-                abort.store(true, Relaxed);
+            "stop" => match state {
+                SearchState::Searching => {
+                    protocol_log::event("Search stopped by the GUI");
+                    abort.store(true, Relaxed);
+                    start_watchdog(&events, search_id, WATCHDOG_MARGIN);
+                    state = SearchState::Stopping;
+                }
+                SearchState::Pondering => {
+                    protocol_log::event("Ponder search stopped by the GUI");
+                    abort.store(true, Relaxed);
+                    stats.record(false);
+                    let mut best_move = wait_for_search(&mut search);
+                    if pondering_before_reply {
+                        search_id += 1;
+                        best_move = search_gui_position(&engine, &last_position, fen_mode, &events, search_id);
+                    }
+                    send_best_move(&engine, &best_move);
+                    // The result of the ponder search is no prediction for the next position.
+                    prediction = None;
+                    state = SearchState::Idle;
+                }
+                SearchState::Idle | SearchState::Stopping => {}
+            },
+            "stats" => {
+                send!("info string Ponder prediction accuracy: {}", stats.summary());
+                send!("info string Ponder strategy: {:?}", stats.strategy());
+                match engine.try_lock() {
+                    Ok(engine) => match engine.search_stats() {
+                        Some(search_stats) => print_search_stats(search_stats),
+                        None => send!("info string Search statistics are disabled, see option SearchStats"),
+                    },
+                    Err(_) => send!("info string Search statistics are available after the search"),
+                }
+                stdout().flush().unwrap();
             }
             "quit" => {
+                abort.store(true, Relaxed);
+                // A search that does not stop ends with the process
+                if search_stops_within(&search, WATCHDOG_MARGIN) {
+                    wait_for_search(&mut search);
+                }
                 return;
             }
-            "d" => {
-                let engine = engine.lock().unwrap();
-                engine.render_board();
-            }
+            "d" => match parse_render_options(&tokens[1..]) {
+                Ok(options) => engine.lock().unwrap().render_board(&options),
+                Err(e) => send!("info string {}", e),
+            },
 
             _ => {
-                println!("Unknown command: {}", line);
+                send!("info string Unknown command: {}", line);
             }
         }
     }
 }
 
-fn parse_depth_and_nodes(tokens: &[&str]) -> (i32, i64) {
+/// The engine keeps its position if the start position or a move is invalid.
+fn set_engine_position(
+    engine: &Mutex<AlphaBetaEngine>,
+    start_fen: &str,
+    moves: &[String],
+    fen_mode: FenMode,
+) -> Result<(), String> {
+    let board = position_board(start_fen, moves, fen_mode)?;
+    engine.lock().unwrap().set_board(board);
+    Ok(())
+}
+
+/// Searches the position of the GUI for PONDER_STOP_SEARCH_TIME and returns the best line. Replaces the result of a
+/// ponder search on the position before the predicted reply, which is no move of the GUI position.
+fn search_gui_position(
+    engine: &Arc<Mutex<AlphaBetaEngine>>,
+    position: &Option<(String, Vec<String>)>,
+    fen_mode: FenMode,
+    events: &Sender<Event>,
+    id: u64,
+) -> Vec<Move> {
+    if let Some((start_fen, moves)) = position {
+        if let Err(e) = set_engine_position(engine, start_fen, moves, fen_mode) {
+            send!("info string Invalid position: {}", e);
+        }
+    }
+    protocol_log::event("Searching the position of the GUI after the ponder search before the reply");
+    let mut search = Some(spawn_search(engine, PONDER_STOP_SEARCH_TIME, events, id));
+    wait_for_search(&mut search)
+}
+
+/// Traces the search with the id to its own file, so the trace of every go is kept. Without a trace file the
+/// search is not traced.
+fn set_trace(engine: &Mutex<AlphaBetaEngine>, trace_file: Option<&str>, min_depth: i32, id: u64) {
+    let trace = trace_file.and_then(|path| {
+        let path = search_trace::numbered_path(path, id);
+        SearchTrace::create(&path, min_depth)
+            .inspect_err(|e| send!("info string {}", e))
+            .ok()
+    });
+    engine.lock().unwrap().set_trace(trace);
+}
+
+/// Starts a search that sends SearchFinished with its id when it ends. The best move is sent by the main loop. A
+/// search with a time limit is watched, SearchOverdue is sent if it runs over.
+fn spawn_search(
+    engine: &Arc<Mutex<AlphaBetaEngine>>,
+    search_time: Duration,
+    events: &Sender<Event>,
+    id: u64,
+) -> RunningSearch {
+    let fallback = engine.lock().unwrap().board().generate_legal_moves().first().copied();
+    // The search is started here, so a stop right after the go is not undone by the reset of the abort flag
+    let mut iterative_search = engine.lock().unwrap().start_search(search_time);
+    let engine_clone = Arc::clone(engine);
+    let search_events = events.clone();
+    let handle = thread::spawn(move || {
+        let mut engine = engine_clone.lock().unwrap();
+        let best_move = contain_panic(|| {
+            let result = loop {
+                let progress = engine.continue_search(&mut iterative_search, search_time, &mut uci_info_callback);
+                if let SearchProgress::Finished(result) = progress {
+                    break result;
+                }
+            };
+            match result {
+                Some((best_move, score, nodes, depth)) => {
+                    protocol_log::event(&format!(
+                        "Search finished at depth {} with score {} after {} nodes",
+                        depth, score, nodes
+                    ));
+                    best_move
+                }
+                None => {
+                    protocol_log::event("Search finished without a completed iteration");
+                    Vec::new()
+                }
+            }
+        });
+        search_events.send(Event::SearchFinished(id)).ok();
+        best_move
+    });
+    if search_time < INFINITE_SEARCH_TIME {
+        start_watchdog(events, id, search_time + WATCHDOG_MARGIN);
+    }
+    RunningSearch { id, handle, fallback }
+}
+
+/// Runs a search and reports a panic to the GUI instead of ending the search thread with it. A failed search has no
+/// line.
+fn contain_panic(search: impl FnOnce() -> Vec<Move>) -> Vec<Move> {
+    panic::catch_unwind(AssertUnwindSafe(search)).unwrap_or_else(|payload| {
+        let message = panic_message(payload.as_ref());
+        protocol_log::event(&format!("Search failed: {}", message));
+        send!("info string error Search failed: {}", message);
+        Vec::new()
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (None, Some(message)) => message,
+        (None, None) => "unknown panic",
+    }
+}
+
+/// Sends SearchOverdue with the id after the delay, the main loop ignores it if the search has ended.
+fn start_watchdog(events: &Sender<Event>, id: u64, delay: Duration) {
+    let events = events.clone();
+    thread::spawn(move || {
+        thread::sleep(delay);
+        events.send(Event::SearchOverdue(id)).ok();
+    });
+}
+
+/// Whether the search thread ends within the timeout, true without a search.
+fn search_stops_within(search: &Option<RunningSearch>, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while search.as_ref().is_some_and(|search| !search.handle.is_finished()) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+/// Waits for the search thread to end and returns its best line, which is empty without a search. A search that
+/// failed or did not complete an iteration returns the fallback move.
+fn wait_for_search(search: &mut Option<RunningSearch>) -> Vec<Move> {
+    search
+        .take()
+        .map(|search| match search.handle.join() {
+            Ok(line) if !line.is_empty() => line,
+            _ => search.fallback.into_iter().collect(),
+        })
+        .unwrap_or_default()
+}
+
+/// Sends the best move of a finished search and returns the prediction of the reply, if the line has one.
+fn send_best_move(engine: &Mutex<AlphaBetaEngine>, best_move: &[Move]) -> Option<(String, String)> {
+    if let Some(search_stats) = engine.lock().unwrap().search_stats() {
+        print_search_stats(search_stats);
+    }
+    send_best_line(best_move)
+}
+
+/// Sends the first move of the line as best move and the second one as ponder move.
+fn send_best_line(best_move: &[Move]) -> Option<(String, String)> {
+    let prediction = match best_move {
+        // No legal move
+        [] => {
+            send!("bestmove 0000");
+            None
+        }
+        [best] => {
+            send!("bestmove {}", best.as_algebraic());
+            None
+        }
+        [best, ponder, ..] => {
+            let (best, ponder) = (best.as_algebraic(), ponder.as_algebraic());
+            send!("bestmove {} ponder {}", best, ponder);
+            Some((best, ponder))
+        }
+    };
+    stdout().flush().unwrap();
+    prediction
+}
+
+/// The options of the protocol and of the search tuning, sent after the [`engine_options`].
+fn uci_options(eval_params: &EvalParams) -> Vec<EngineOption> {
+    let mut options = vec![
+        EngineOption::check("SearchStats", false),
+        EngineOption::check("LenientFen", false),
+        EngineOption::string("LogFile", ""),
+        EngineOption::spin("Contempt", 0, -MAX_CONTEMPT, MAX_CONTEMPT),
+        EngineOption::spin("SingularMargin", DEFAULT_SINGULAR_MARGIN / 10, 0, MAX_SINGULAR_MARGIN),
+        EngineOption::check("MultiCut", true),
+        EngineOption::spin("DeltaMargin", DEFAULT_DELTA_MARGIN / 10, 0, MAX_PRUNING_MARGIN),
+        EngineOption::spin("FutilityMargin", DEFAULT_FUTILITY_MARGIN / 10, 0, MAX_PRUNING_MARGIN),
+        EngineOption::string("TraceFile", ""),
+        EngineOption::spin("TraceDepth", DEFAULT_TRACE_DEPTH, 1, MAX_TRACE_DEPTH),
+        EngineOption::spin("Move Overhead", DEFAULT_MOVE_OVERHEAD, 0, MAX_MOVE_OVERHEAD),
+    ];
+    for (option, value) in MATERIAL_OPTIONS.iter().zip(eval_params.material) {
+        options.push(EngineOption::spin(option, value, 0, 100000));
+    }
+    options
+}
+
+/// The skill level is only used with UCI_LimitStrength, UCI_Elo alone does not weaken the engine.
+fn skill(limit_strength: bool, elo: u32) -> Option<Skill> {
+    limit_strength.then(|| Skill::new(elo))
+}
+
+fn print_search_stats(search_stats: &SearchStats) {
+    for line in search_stats.summary() {
+        send!("info string {}", line);
+    }
+}
+
+/// Checks whether the moves of a position command continue with our best move followed by the predicted
+/// reply. Returns None if the position does not continue from our last best move.
+fn prediction_outcome(best: &str, ponder: &str, moves: &[String]) -> Option<bool> {
+    match moves {
+        [.., our_move, reply] if our_move == best => Some(reply == ponder),
+        _ => None,
+    }
+}
+
+/// Missing or invalid values keep the defaults, a malformed go still searches.
+fn parse_depth_and_nodes(tokens: &[&str]) -> (usize, u64) {
     let mut i = 0;
-    let mut depth = 99;
-    let mut nodes = i64::MAX;
+    let mut depth = MAX_PLY;
+    let mut nodes = u64::MAX;
     while i < tokens.len() {
+        let value = tokens.get(i + 1).copied().unwrap_or_default();
         match tokens[i] {
             "depth" => {
-                depth = tokens[i + 1].parse().unwrap();
+                depth = value.parse().unwrap_or(depth);
                 i += 2;
             }
             "nodes" => {
-                nodes = tokens[i + 1].parse().unwrap();
+                nodes = value.parse().unwrap_or(nodes);
                 i += 2;
             }
             _ => {
@@ -162,7 +796,75 @@ fn parse_depth_and_nodes(tokens: &[&str]) -> (i32, i64) {
     (depth, nodes)
 }
 
-fn uci_info_callback(depth: i32, score: i32, nodes: u64, elapsed: Duration, pv: String) {
+/// The options of the d command: "unicode", "colors" and a board size, the footer is always shown.
+fn parse_render_options(tokens: &[&str]) -> result::Result<RenderOptions, String> {
+    let mut options = RenderOptions {
+        footer: true,
+        ..Default::default()
+    };
+    for &token in tokens {
+        match token {
+            "unicode" => options.unicode = true,
+            "colors" => options.colors = true,
+            _ => {
+                options.size = BoardSize::from_name(token).ok_or_else(|| format!("Invalid d option: {}", token))?;
+            }
+        }
+    }
+    Ok(options)
+}
+
+/// Parses "name <id> [value <x>]" of a setoption command. Names and values may contain spaces.
+fn parse_setoption(tokens: &[&str]) -> Option<(String, String)> {
+    if tokens.first() != Some(&"name") {
+        return None;
+    }
+    let value_index = tokens.iter().position(|&t| t == "value").unwrap_or(tokens.len());
+    let name = tokens[1..value_index].join(" ");
+    if name.is_empty() {
+        return None;
+    }
+    let value = tokens.get(value_index + 1..).map(|v| v.join(" ")).unwrap_or_default();
+    Some((name, value))
+}
+
+/// Sends the events of the engine to the GUI, debug messages of the engine as info strings.
+fn uci_info_callback(event: InfoEvent) {
+    let (depth, seldepth, score, nodes, elapsed, pv) = match event {
+        InfoEvent::DepthCompleted {
+            depth,
+            seldepth,
+            score,
+            nodes,
+            elapsed,
+            pv,
+        } => (depth, seldepth, score, nodes, elapsed, pv),
+        InfoEvent::CurrMove { depth, mv, number } => {
+            send!(
+                "info depth {} currmove {} currmovenumber {}",
+                depth,
+                mv.as_algebraic(),
+                number
+            );
+            stdout().flush().unwrap();
+            return;
+        }
+        InfoEvent::NewBestMove { depth, score, pv } => {
+            send!("info depth {} score cp {} pv {}", depth, score / 10, pv);
+            stdout().flush().unwrap();
+            return;
+        }
+        InfoEvent::String(message) => {
+            send!("info string {}", message);
+            stdout().flush().unwrap();
+            return;
+        }
+        InfoEvent::HashFull(hashfull) => {
+            send!("info hashfull {}", hashfull);
+            stdout().flush().unwrap();
+            return;
+        }
+    };
     let time_ms = elapsed.as_millis();
     let nps = if elapsed.as_secs_f64() > 0.0 {
         (nodes as f64 / elapsed.as_secs_f64()) as u64
@@ -170,9 +872,10 @@ fn uci_info_callback(depth: i32, score: i32, nodes: u64, elapsed: Duration, pv:
         0
     };
 
-    println!(
-        "info depth {} score cp {} time {} nodes {} nps {} pv {}",
+    send!(
+        "info depth {} seldepth {} score cp {} time {} nodes {} nps {} pv {}",
         depth,
+        seldepth,
         score / 10,
         time_ms,
         nodes,
@@ -182,43 +885,55 @@ fn uci_info_callback(depth: i32, score: i32, nodes: u64, elapsed: Duration, pv:
     stdout().flush().unwrap();
 }
 
-fn parse_position(tokens: Vec<&str>) -> result::Result<(String, Vec<String>), &'static str> {
-    if tokens.len() < 2 {
-        return Err("Invalid position command");
-    }
-
-    let mut idx = 1;
-
-    let position = match tokens[idx] {
-        "startpos" => INITIAL_POSITION.to_string(),
-        "fen" => {
-            let mut v: Vec<String> = Vec::new();
-            for token in tokens.iter().take(idx + 7).skip(idx + 1) {
-                v.push((*token).to_string());
+/// Parses "position startpos" or "position fen <FEN>", optionally followed by "moves" and any number of moves, into
+/// the start position and the moves. The FEN ends at the moves keyword, it may come without the move counters.
+fn parse_position(tokens: Vec<&str>) -> result::Result<(String, Vec<String>), String> {
+    let moves_index = tokens
+        .iter()
+        .position(|&token| token == "moves")
+        .unwrap_or(tokens.len());
+    let position = match tokens.get(1) {
+        Some(&"startpos") if moves_index > 2 => return Err(format!("Unexpected {} after startpos", tokens[2])),
+        Some(&"startpos") => INITIAL_POSITION.to_string(),
+        Some(&"fen") => {
+            let fields = &tokens[2..moves_index];
+            if !(4..=6).contains(&fields.len()) {
+                return Err(format!("A FEN has 4 to 6 fields, found {}", fields.len()));
             }
-            idx += 6;
-            v.join(" ")
+            fields.join(" ")
         }
-        _ => return Err("Invalid position command"),
+        Some(token) => return Err(format!("Expected startpos or fen, found {}", token)),
+        None => return Err(String::from("Expected startpos or fen")),
     };
+    let moves = tokens
+        .get(moves_index + 1..)
+        .unwrap_or_default()
+        .iter()
+        .map(|mv| mv.to_string())
+        .collect();
+    Ok((position, moves))
+}
 
-    let mut moves: Vec<String> = Vec::new();
-    idx += 1;
-    if tokens.len() == idx + 1 {
-        return Err("Invalid position command. No Moves specified.");
-    } else if tokens.len() > idx + 1 {
-        if tokens[idx] != "moves" {
-            return Err("Invalid position command. Keyword 'moves' expected.");
-        }
-        idx += 1;
-        for move_str in tokens[idx..].iter() {
-            moves.push(move_str.to_string());
-        }
+/// The board of a position command, checking the start position and that all moves are legal.
+fn position_board(start_fen: &str, moves: &[String], fen_mode: FenMode) -> Result<ChessBoard, String> {
+    let mut board = ChessBoard::from_fen_with_mode(start_fen, fen_mode)?;
+    for mv in moves {
+        let parsed = board
+            .legal_move_from_uci(mv)
+            .map_err(|e| format!("{} in {}", e, board.to_fen()))?;
+        board.make_move(parsed);
     }
-    Ok((position, moves))
+    Ok(board)
 }
 
-fn parse_go_command(tokens: &[&str], active_color: Color) -> Duration {
+/// A time of the go command in milliseconds. A GUI that is late sends a negative time left, which counts as 0.
+fn parse_millis(token: &str) -> Option<u64> {
+    token.parse::<i64>().ok().map(|millis| millis.max(0) as u64)
+}
+
+/// The time for the move and how it was chosen, for the debug mode. The move overhead is taken off the clock and
+/// off movetime, but the search gets at least MIN_MOVE_TIME.
+fn parse_go_command(tokens: &[&str], active_color: Color, move_overhead: Duration) -> (Duration, String) {
     let fallback = Duration::from_secs(5);
 
     let mut wtime: Option<u64> = None;
@@ -226,60 +941,332 @@ fn parse_go_command(tokens: &[&str], active_color: Color) -> Duration {
     let mut movestogo: Option<u64> = None;
     let mut winc: Option<u64> = None;
     let mut binc: Option<u64> = None;
+    let mut movetime: Option<u64> = None;
 
     // Parse the sub-commands following "go"
     // Example: ["wtime", "266667", "btime", "244787", "movestogo", "33"]
     let mut i = 0;
     while i < tokens.len() {
         match tokens[i] {
-            "wtime" => {
-                if i + 1 < tokens.len() {
-                    wtime = tokens[i + 1].parse().ok();
-                    i += 1;
-                }
+            "wtime" if i + 1 < tokens.len() => {
+                wtime = parse_millis(tokens[i + 1]);
+                i += 1;
             }
-            "btime" => {
-                if i + 1 < tokens.len() {
-                    btime = tokens[i + 1].parse().ok();
-                    i += 1;
-                }
+            "btime" if i + 1 < tokens.len() => {
+                btime = parse_millis(tokens[i + 1]);
+                i += 1;
             }
-            "movestogo" => {
-                if i + 1 < tokens.len() {
-                    movestogo = tokens[i + 1].parse().ok();
-                    i += 1;
-                }
+            "movestogo" if i + 1 < tokens.len() => {
+                movestogo = tokens[i + 1].parse().ok();
+                i += 1;
             }
-            "winc" => {
-                if i + 1 < tokens.len() {
-                    winc = tokens[i + 1].parse().ok();
-                    i += 1;
-                }
+            "winc" if i + 1 < tokens.len() => {
+                winc = parse_millis(tokens[i + 1]);
+                i += 1;
             }
-            "binc" => {
-                if i + 1 < tokens.len() {
-                    binc = tokens[i + 1].parse().ok();
-                    i += 1;
-                }
+            "binc" if i + 1 < tokens.len() => {
+                binc = parse_millis(tokens[i + 1]);
+                i += 1;
+            }
+            "movetime" if i + 1 < tokens.len() => {
+                movetime = parse_millis(tokens[i + 1]);
+                i += 1;
             }
             _ => {}
         }
         i += 1;
     }
 
-    let (time_left_millis, increment_milis) = match active_color {
-        Color::White => (wtime.unwrap_or(0), winc.unwrap_or(0)),
-        Color::Black => (btime.unwrap_or(0), binc.unwrap_or(0)),
+    if let Some(movetime) = movetime {
+        let time_for_this_move = Duration::from_millis(movetime)
+            .saturating_sub(move_overhead)
+            .max(MIN_MOVE_TIME);
+        let reason = format!(
+            "{} ms of movetime {} ms with {} ms move overhead",
+            time_for_this_move.as_millis(),
+            movetime,
+            move_overhead.as_millis()
+        );
+        return (time_for_this_move, reason);
+    }
+
+    let (time_left, increment) = match active_color {
+        Color::White => (wtime, winc),
+        Color::Black => (btime, binc),
+    };
+    // Only a missing clock means there is none, a clock at 0 still gets the minimum time
+    let Some(time_left_millis) = time_left else {
+        return (
+            fallback,
+            format!("no clock, using the default of {} ms", fallback.as_millis()),
+        );
     };
+    let increment_millis = increment.unwrap_or(0);
+
+    let moves_to_go = movestogo.unwrap_or(30) as u32;
+    let time_for_this_move = allocate_move_time(
+        Duration::from_millis(time_left_millis).saturating_sub(move_overhead),
+        Duration::from_millis(increment_millis),
+        moves_to_go,
+    );
+    let reason = format!(
+        "{} ms of {} ms left with {} ms increment, {} ms move overhead and {} moves to go",
+        time_for_this_move.as_millis(),
+        time_left_millis,
+        increment_millis,
+        move_overhead.as_millis(),
+        moves_to_go
+    );
+    (time_for_this_move, reason)
+}
 
-    let moves_to_go = movestogo.unwrap_or(30).max(1); // avoid divide by zero
-    let time_for_this_move_ms = time_left_millis / (moves_to_go) + increment_milis;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if time_for_this_move_ms > time_left_millis {
-        Duration::from_millis(time_left_millis - 5)
-    } else if time_for_this_move_ms > 0 {
-        Duration::from_millis(time_for_this_move_ms)
-    } else {
-        fallback
+    #[test]
+    fn test_parse_setoption() {
+        assert_eq!(
+            parse_setoption(&["name", "Threads", "value", "4"]),
+            Some((String::from("Threads"), String::from("4")))
+        );
+        assert_eq!(
+            parse_setoption(&["name", "Move", "Overhead", "value", "10"]),
+            Some((String::from("Move Overhead"), String::from("10")))
+        );
+        assert_eq!(
+            parse_setoption(&["name", "Clear", "Hash"]),
+            Some((String::from("Clear Hash"), String::new()))
+        );
+        assert_eq!(parse_setoption(&["value", "4"]), None);
+        assert_eq!(parse_setoption(&[]), None);
+    }
+
+    #[test]
+    fn test_parse_render_options() {
+        let options = parse_render_options(&[]).unwrap();
+        assert_eq!(
+            options,
+            RenderOptions {
+                footer: true,
+                ..Default::default()
+            }
+        );
+        let options = parse_render_options(&["unicode", "colors", "large"]).unwrap();
+        assert!(options.unicode && options.colors && options.footer);
+        assert_eq!(options.size, BoardSize::Large);
+        assert!(parse_render_options(&["huge"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_position() {
+        let tokens = "position startpos moves e2e4 e7e5".split_whitespace().collect();
+        assert_eq!(
+            parse_position(tokens),
+            Ok((
+                INITIAL_POSITION.to_string(),
+                vec![String::from("e2e4"), String::from("e7e5")]
+            ))
+        );
+        let tokens = "position fen 4k3/8/8/8/8/8/8/4K3 w - - moves e1e2"
+            .split_whitespace()
+            .collect();
+        assert_eq!(
+            parse_position(tokens),
+            Ok((String::from("4k3/8/8/8/8/8/8/4K3 w - -"), vec![String::from("e1e2")]))
+        );
+        let parse = |command: &str| parse_position(command.split_whitespace().collect());
+        assert_eq!(
+            parse("position fen 4k3/8/8/8/8/8/8/4K3 b - - 3 40"),
+            Ok((String::from("4k3/8/8/8/8/8/8/4K3 b - - 3 40"), Vec::new()))
+        );
+        // Without moves, with or without the keyword
+        assert_eq!(
+            parse("position startpos"),
+            Ok((INITIAL_POSITION.to_string(), Vec::new()))
+        );
+        assert_eq!(
+            parse("position startpos moves"),
+            Ok((INITIAL_POSITION.to_string(), Vec::new()))
+        );
+
+        assert_eq!(parse("position"), Err(String::from("Expected startpos or fen")));
+        assert_eq!(
+            parse("position moves e2e4"),
+            Err(String::from("Expected startpos or fen, found moves"))
+        );
+        assert_eq!(
+            parse("position startpos e2e4"),
+            Err(String::from("Unexpected e2e4 after startpos"))
+        );
+        assert_eq!(
+            parse("position fen moves e2e4"),
+            Err(String::from("A FEN has 4 to 6 fields, found 0"))
+        );
+        assert_eq!(
+            parse("position fen 4k3/8/8/8/8/8/8/4K3 w - - 0 1 extra moves e1e2"),
+            Err(String::from("A FEN has 4 to 6 fields, found 7"))
+        );
+    }
+
+    #[test]
+    fn test_position_board() {
+        let moves = vec![String::from("e2e4"), String::from("e7e5")];
+        let board = position_board(INITIAL_POSITION, &moves, FenMode::Strict).unwrap();
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+        );
+        let moves = vec![String::from("e2e5")];
+        assert!(position_board(INITIAL_POSITION, &moves, FenMode::Strict).is_err());
+        assert!(position_board("8/8/8/8/8/8/8/4K3 w - - 0 1", &[], FenMode::Strict).is_err());
+        assert!(position_board("8/8/8/8/8/8/8/4K3 w - -", &[], FenMode::Lenient).is_ok());
+        // Garbage is reported, not a panic
+        for garbage in ["e2", "e2e9", "z1a1", "e7e8x", "é2e4"] {
+            let moves = vec![String::from(garbage)];
+            assert!(position_board(INITIAL_POSITION, &moves, FenMode::Strict).is_err());
+        }
+    }
+
+    #[test]
+    fn test_set_engine_position() {
+        let engine = Mutex::new(AlphaBetaEngine::new());
+        let moves = vec![String::from("e2e4")];
+        assert_eq!(
+            set_engine_position(&engine, INITIAL_POSITION, &moves, FenMode::Strict),
+            Ok(())
+        );
+        assert_eq!(engine.lock().unwrap().get_active_player(), Color::Black);
+        // A corrupted move list leaves the position unchanged
+        let moves = vec![String::from("e2e4"), String::from("e2e4")];
+        let error = set_engine_position(&engine, INITIAL_POSITION, &moves, FenMode::Strict).unwrap_err();
+        assert!(
+            error.starts_with("Illegal move e2e4: There is no piece on that square"),
+            "{}",
+            error
+        );
+        assert_eq!(engine.lock().unwrap().get_active_player(), Color::Black);
+    }
+
+    #[test]
+    fn test_parse_go_command() {
+        let no_overhead = Duration::ZERO;
+        assert_eq!(
+            parse_go_command(&["movetime", "250"], Color::White, no_overhead).0,
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            parse_go_command(
+                &["wtime", "60000", "btime", "30000", "movestogo", "30"],
+                Color::Black,
+                no_overhead
+            )
+            .0,
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            parse_go_command(&[], Color::White, no_overhead).0,
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            parse_go_command(&["wtime", "60000", "btime", "30000"], Color::White, no_overhead),
+            (
+                Duration::from_secs(2),
+                String::from("2000 ms of 60000 ms left with 0 ms increment, 0 ms move overhead and 30 moves to go")
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_go_command_low_time() {
+        let overhead = Duration::from_millis(DEFAULT_MOVE_OVERHEAD as u64);
+        // The overhead comes off the clock and off movetime
+        assert_eq!(
+            parse_go_command(&["wtime", "30010", "btime", "1000"], Color::White, overhead).0,
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            parse_go_command(&["movetime", "250"], Color::White, overhead).0,
+            Duration::from_millis(240)
+        );
+        // A clock at or below 0 is no missing clock, it gets the minimum instead of the default time
+        for wtime in ["0", "-150", "5"] {
+            assert_eq!(
+                parse_go_command(&["wtime", wtime, "btime", "60000"], Color::White, overhead).0,
+                MIN_MOVE_TIME
+            );
+        }
+        assert_eq!(
+            parse_go_command(&["movetime", "0"], Color::White, Duration::ZERO).0,
+            MIN_MOVE_TIME
+        );
+        // An increment larger than the clock does not exceed the clock
+        assert_eq!(
+            parse_go_command(&["wtime", "100", "winc", "2000"], Color::White, overhead).0,
+            Duration::from_millis(85)
+        );
+        // The clock of the other side is no clock for the side to move
+        assert_eq!(
+            parse_go_command(&["btime", "100"], Color::White, overhead).0,
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            parse_go_command(&["wtime", "500", "btime"], Color::Black, overhead).0,
+            Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn test_parse_depth_and_nodes() {
+        assert_eq!(parse_depth_and_nodes(&["depth", "7", "nodes", "5000"]), (7, 5000));
+        assert_eq!(parse_depth_and_nodes(&["wtime", "1000"]), (MAX_PLY, u64::MAX));
+        assert_eq!(parse_depth_and_nodes(&["depth"]), (MAX_PLY, u64::MAX));
+        assert_eq!(
+            parse_depth_and_nodes(&["depth", "deep", "nodes", "-"]),
+            (MAX_PLY, u64::MAX)
+        );
+        assert_eq!(parse_depth_and_nodes(&["depth", "-3"]), (MAX_PLY, u64::MAX));
+    }
+
+    #[test]
+    fn test_search_finished() {
+        let engine = Arc::new(Mutex::new(AlphaBetaEngine::new()));
+        engine.lock().unwrap().set_max_depth(2);
+        set_engine_position(&engine, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &[], FenMode::Strict).unwrap();
+        let (events, receiver) = mpsc::channel();
+        let mut search = Some(spawn_search(&engine, Duration::from_secs(60), &events, 7));
+        assert!(matches!(receiver.recv().unwrap(), Event::SearchFinished(7)));
+        let best_move = wait_for_search(&mut search);
+        assert_eq!(best_move[0].as_algebraic(), "a1a8");
+        assert!(search.is_none());
+        assert!(wait_for_search(&mut search).is_empty());
+    }
+
+    #[test]
+    fn test_failed_search_sends_fallback_move() {
+        let line = contain_panic(|| panic!("search exploded"));
+        assert!(line.is_empty());
+
+        let fallback = Move::from_algebraic("e2e4");
+        let mut search = Some(RunningSearch {
+            id: 1,
+            handle: thread::spawn(Vec::new),
+            fallback: Some(fallback),
+        });
+        assert!(search_stops_within(&search, Duration::from_secs(10)));
+        assert_eq!(wait_for_search(&mut search), vec![fallback]);
+
+        let (events, receiver) = mpsc::channel();
+        start_watchdog(&events, 3, Duration::from_millis(10));
+        assert!(matches!(receiver.recv().unwrap(), Event::SearchOverdue(3)));
+    }
+
+    #[test]
+    fn test_prediction_outcome() {
+        let moves: Vec<String> = ["e2e4", "e7e5", "g1f3", "b8c6"].iter().map(|m| m.to_string()).collect();
+        assert_eq!(prediction_outcome("g1f3", "b8c6", &moves), Some(true));
+        assert_eq!(prediction_outcome("g1f3", "g8f6", &moves), Some(false));
+        assert_eq!(prediction_outcome("d2d4", "d7d5", &moves), None);
+        assert_eq!(prediction_outcome("g1f3", "b8c6", &moves[..1]), None);
     }
 }
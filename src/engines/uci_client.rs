@@ -0,0 +1,516 @@
+//! The client side of UCI: an external engine like Stockfish runs as a child process and is driven over its standard
+//! input and output. The handshake collects its name and the options it declares, searches send the position as the
+//! start position with the moves played and report the info lines of the engine while it thinks.
+use crate::engines::options::OptionType;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Centipawns of a reported mate, beyond any material score. A mate in n moves scores MATE_SCORE - n.
+pub const MATE_SCORE: i32 = 100_000;
+
+/// How long the engine may take to answer uci and isready.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a search may run beyond its move time before the engine counts as hanging.
+const SEARCH_GRACE: Duration = Duration::from_secs(10);
+
+/// The score of an info line from the point of view of the side to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UciScore {
+    Centipawns(i32),
+    /// Moves to the mate, negative if the side to move is mated
+    Mate(i32),
+}
+
+impl UciScore {
+    /// Centipawns, a mate counts as MATE_SCORE less its distance in moves.
+    pub fn centipawns(self) -> i32 {
+        match self {
+            UciScore::Centipawns(value) => value,
+            UciScore::Mate(moves) if moves > 0 => MATE_SCORE - moves,
+            UciScore::Mate(moves) => -MATE_SCORE - moves,
+        }
+    }
+}
+
+/// The fields of an info line that a GUI shows, the others are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UciInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    /// The line of a multi-PV search, from 1
+    pub multipv: Option<u32>,
+    pub score: Option<UciScore>,
+    /// The score is only a bound of the true one
+    pub bound: bool,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time: Option<Duration>,
+    /// The principal variation in coordinate notation
+    pub pv: Vec<String>,
+}
+
+/// The reply to go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UciBestMove {
+    pub mv: String,
+    pub ponder: Option<String>,
+    /// The last info line with a score, the evaluation of the move
+    pub info: Option<UciInfo>,
+}
+
+/// An option the engine declared, None as type for a button.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UciOption {
+    pub name: String,
+    pub option_type: Option<OptionType>,
+}
+
+/// How long the engine searches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchLimit {
+    MoveTime(Duration),
+    /// Until the search completed the depth, however long that takes
+    Depth(u32),
+    /// The clocks of White and Black with their increments
+    Clock {
+        white: Duration,
+        black: Duration,
+        white_increment: Duration,
+        black_increment: Duration,
+    },
+    /// Until the search is stopped
+    Infinite,
+}
+
+impl SearchLimit {
+    fn go_command(&self) -> String {
+        match self {
+            SearchLimit::MoveTime(time) => format!("go movetime {}", time.as_millis()),
+            SearchLimit::Depth(depth) => format!("go depth {}", depth),
+            SearchLimit::Clock {
+                white,
+                black,
+                white_increment,
+                black_increment,
+            } => format!(
+                "go wtime {} btime {} winc {} binc {}",
+                white.as_millis(),
+                black.as_millis(),
+                white_increment.as_millis(),
+                black_increment.as_millis()
+            ),
+            SearchLimit::Infinite => String::from("go infinite"),
+        }
+    }
+
+    /// The longest a search may take, None for a search that runs until it is done or stopped.
+    fn deadline(&self) -> Option<Duration> {
+        match self {
+            SearchLimit::MoveTime(time) => Some(*time + SEARCH_GRACE),
+            SearchLimit::Clock { white, black, .. } => Some((*white).max(*black) + SEARCH_GRACE),
+            SearchLimit::Depth(_) | SearchLimit::Infinite => None,
+        }
+    }
+}
+
+/// Writes commands to the engine, shared so that another thread can stop a search.
+#[derive(Clone)]
+pub struct UciInput {
+    name: String,
+    stdin: Arc<Mutex<ChildStdin>>,
+}
+
+impl UciInput {
+    pub fn send(&self, command: &str) -> Result<(), String> {
+        let mut stdin = self.stdin.lock().unwrap();
+        writeln!(stdin, "{}", command)
+            .and_then(|_| stdin.flush())
+            .map_err(|e| format!("{} closed its input: {}", self.name, e))
+    }
+
+    /// Ends a running search, the engine then sends its best move.
+    pub fn stop(&self) {
+        // An engine that is gone has nothing left to stop
+        let _ = self.send("stop");
+    }
+}
+
+/// An external engine process spoken to over UCI.
+pub struct UciClient {
+    pub name: String,
+    pub author: Option<String>,
+    pub options: Vec<UciOption>,
+    process: Child,
+    input: UciInput,
+    /// The lines of the engine, read by a thread so that reading can time out
+    lines: Receiver<String>,
+}
+
+impl UciClient {
+    /// Starts the engine command, e.g. "stockfish" or "target/release/chic-engine --threads 2", and shakes hands.
+    pub fn start(command: &str) -> Result<Self, String> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or("Empty engine command")?;
+        let mut process = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Could not start {}: {}", command, e))?;
+        let stdin = process.stdin.take().unwrap();
+        let stdout = BufReader::new(process.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in stdout.lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                if sender.send(line.trim().to_string()).is_err() {
+                    return;
+                }
+            }
+        });
+        let mut client = UciClient {
+            name: command.to_string(),
+            author: None,
+            options: Vec::new(),
+            process,
+            input: UciInput {
+                name: command.to_string(),
+                stdin: Arc::new(Mutex::new(stdin)),
+            },
+            lines,
+        };
+
+        client.send("uci")?;
+        for line in client.read_until("uciok", Some(HANDSHAKE_TIMEOUT))? {
+            if let Some(name) = line.strip_prefix("id name ") {
+                client.name = name.to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                client.author = Some(author.to_string());
+            } else if let Some(option) = parse_option(&line) {
+                client.options.push(option);
+            }
+        }
+        client.input.name = client.name.clone();
+        Ok(client)
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), String> {
+        self.input.send(command)
+    }
+
+    /// The input of the engine, to stop a search from another thread.
+    pub fn input(&self) -> UciInput {
+        self.input.clone()
+    }
+
+    /// The next line of the engine, waiting at most until the deadline if there is one.
+    fn read_line(&mut self, deadline: Option<Instant>) -> Result<String, String> {
+        let line = match deadline {
+            Some(deadline) => self
+                .lines
+                .recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => self.lines.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        line.map_err(|e| match e {
+            RecvTimeoutError::Timeout => format!("{} does not answer", self.name),
+            RecvTimeoutError::Disconnected => format!("{} terminated", self.name),
+        })
+    }
+
+    /// Reads lines until one starts with the given token and returns all of them.
+    fn read_until(&mut self, token: &str, timeout: Option<Duration>) -> Result<Vec<String>, String> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line(deadline)?;
+            let done = line.split_whitespace().next() == Some(token);
+            lines.push(line);
+            if done {
+                return Ok(lines);
+            }
+        }
+    }
+
+    /// Waits until the engine processed the commands sent so far.
+    pub fn wait_ready(&mut self) -> Result<(), String> {
+        self.send("isready")?;
+        self.read_until("readyok", Some(HANDSHAKE_TIMEOUT)).map(|_| ())
+    }
+
+    /// Sets an option the engine declared, the name in any case. Buttons are pressed, their value is ignored.
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let option = self
+            .options
+            .iter()
+            .find(|option| option.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("{} has no option {}", self.name, name))?;
+        let command = match option.option_type {
+            None => format!("setoption name {}", option.name),
+            Some(_) => format!("setoption name {} value {}", option.name, value),
+        };
+        self.send(&command)
+    }
+
+    /// Sets the options given as "Name=Value".
+    pub fn set_options(&mut self, options: &[String]) -> Result<(), String> {
+        for option in options {
+            let (name, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid option {}, expected Name=Value", option))?;
+            self.set_option(name.trim(), value.trim())?;
+        }
+        self.wait_ready()
+    }
+
+    pub fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.wait_ready()
+    }
+
+    /// Searches the position after the moves in coordinate notation and returns the best move. The info lines are
+    /// handed to on_info as they arrive. An infinite search returns once it is stopped through the input.
+    pub fn search(
+        &mut self,
+        start_fen: &str,
+        moves: &[String],
+        limit: SearchLimit,
+        mut on_info: impl FnMut(&UciInfo),
+    ) -> Result<UciBestMove, String> {
+        let mut position = format!("position fen {}", start_fen);
+        if !moves.is_empty() {
+            position.push_str(" moves ");
+            position.push_str(&moves.join(" "));
+        }
+        self.send(&position)?;
+        self.send(&limit.go_command())?;
+        let deadline = limit.deadline().map(|deadline| Instant::now() + deadline);
+        let mut last_info = None;
+        loop {
+            let line = self.read_line(deadline)?;
+            if let Some(info) = parse_info(&line) {
+                on_info(&info);
+                if info.score.is_some() && info.multipv.unwrap_or(1) == 1 {
+                    last_info = Some(info);
+                }
+            } else if line.split_whitespace().next() == Some("bestmove") {
+                let (mv, ponder) = parse_best_move(&line).ok_or_else(|| format!("{} sent no best move", self.name))?;
+                return Ok(UciBestMove {
+                    mv,
+                    ponder,
+                    info: last_info,
+                });
+            }
+        }
+    }
+}
+
+impl Drop for UciClient {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // An engine that ignores quit is not waited for
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while let Ok(None) = self.process.try_wait() {
+            if Instant::now() > deadline {
+                let _ = self.process.kill();
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.process.wait();
+    }
+}
+
+/// Parses an info line, None for other lines and for info strings.
+pub fn parse_info(line: &str) -> Option<UciInfo> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.first() != Some(&"info") || tokens.get(1) == Some(&"string") {
+        return None;
+    }
+    let mut info = UciInfo::default();
+    let mut index = 1;
+    while index < tokens.len() {
+        let value = tokens.get(index + 1).copied().unwrap_or_default();
+        index += 2;
+        match tokens[index - 2] {
+            "depth" => info.depth = value.parse().ok(),
+            "seldepth" => info.seldepth = value.parse().ok(),
+            "multipv" => info.multipv = value.parse().ok(),
+            "nodes" => info.nodes = value.parse().ok(),
+            "nps" => info.nps = value.parse().ok(),
+            "time" => info.time = value.parse().ok().map(Duration::from_millis),
+            "score" => {
+                let number = tokens.get(index).and_then(|number| number.parse().ok());
+                index += 1;
+                info.score = match value {
+                    "cp" => number.map(UciScore::Centipawns),
+                    "mate" => number.map(UciScore::Mate),
+                    _ => None,
+                };
+                if matches!(tokens.get(index), Some(&"lowerbound") | Some(&"upperbound")) {
+                    info.bound = true;
+                    index += 1;
+                }
+            }
+            "pv" => {
+                info.pv = tokens[index - 1..].iter().map(|mv| mv.to_string()).collect();
+                break;
+            }
+            // The other fields have a single value
+            _ => {}
+        }
+    }
+    Some(info)
+}
+
+/// The move and the ponder move of a bestmove line.
+pub fn parse_best_move(line: &str) -> Option<(String, Option<String>)> {
+    let mut tokens = line.split_whitespace();
+    if tokens.next() != Some("bestmove") {
+        return None;
+    }
+    let mv = tokens.next()?.to_string();
+    let ponder = match (tokens.next(), tokens.next()) {
+        (Some("ponder"), Some(ponder)) => Some(ponder.to_string()),
+        _ => None,
+    };
+    Some((mv, ponder))
+}
+
+/// Parses an option line like "option name Skill Level type spin default 20 min 0 max 20". The name may have spaces.
+pub fn parse_option(line: &str) -> Option<UciOption> {
+    let rest = line.strip_prefix("option name ")?;
+    let (name, rest) = rest.split_once(" type ")?;
+    let mut tokens = rest.split_whitespace();
+    let kind = tokens.next()?;
+    // The values of the keywords, "var" repeats
+    let mut default = None;
+    let (mut min, mut max) = (None, None);
+    let mut values = Vec::new();
+    let mut key = "";
+    let mut words: Vec<&str> = Vec::new();
+    let mut flush = |key: &str, words: &mut Vec<&str>| {
+        let value = words.join(" ");
+        words.clear();
+        match key {
+            "default" => default = Some(value),
+            "min" => min = value.parse().ok(),
+            "max" => max = value.parse().ok(),
+            "var" => values.push(value),
+            _ => {}
+        }
+    };
+    for token in tokens {
+        if matches!(token, "default" | "min" | "max" | "var") {
+            flush(key, &mut words);
+            key = token;
+        } else {
+            words.push(token);
+        }
+    }
+    flush(key, &mut words);
+    let default = default.unwrap_or_default();
+    let option_type = match kind {
+        "check" => Some(OptionType::Check {
+            default: default == "true",
+        }),
+        "spin" => Some(OptionType::Spin {
+            default: default.parse().ok()?,
+            min: min?,
+            max: max?,
+        }),
+        "combo" => Some(OptionType::Combo { default, values }),
+        "string" if default == "<empty>" => Some(OptionType::String { default: String::new() }),
+        "string" => Some(OptionType::String { default }),
+        "button" => None,
+        _ => return None,
+    };
+    Some(UciOption {
+        name: name.to_string(),
+        option_type,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_info() {
+        let info =
+            parse_info("info depth 7 seldepth 12 multipv 1 score cp -35 nodes 4000 nps 9000 time 120 pv e2e4 e7e5")
+                .unwrap();
+        assert_eq!(info.depth, Some(7));
+        assert_eq!(info.seldepth, Some(12));
+        assert_eq!(info.score, Some(UciScore::Centipawns(-35)));
+        assert_eq!(info.nodes, Some(4000));
+        assert_eq!(info.time, Some(Duration::from_millis(120)));
+        assert_eq!(info.pv, vec!["e2e4", "e7e5"]);
+        assert!(!info.bound);
+
+        let info = parse_info("info depth 9 score mate -2 upperbound hashfull 10").unwrap();
+        assert_eq!(info.score, Some(UciScore::Mate(-2)));
+        assert_eq!(info.score.unwrap().centipawns(), -(MATE_SCORE - 2));
+        assert!(info.bound);
+        assert_eq!(UciScore::Mate(3).centipawns(), MATE_SCORE - 3);
+
+        assert_eq!(
+            parse_info("info depth 3 currmove e2e4 currmovenumber 1").unwrap().score,
+            None
+        );
+        assert_eq!(parse_info("info string score cp 10"), None);
+        assert_eq!(parse_info("bestmove e2e4"), None);
+    }
+
+    #[test]
+    fn test_parse_best_move() {
+        assert_eq!(
+            parse_best_move("bestmove e2e4 ponder e7e5"),
+            Some((String::from("e2e4"), Some(String::from("e7e5"))))
+        );
+        assert_eq!(parse_best_move("bestmove a7a8q"), Some((String::from("a7a8q"), None)));
+        assert_eq!(parse_best_move("info depth 1"), None);
+    }
+
+    #[test]
+    fn test_parse_option() {
+        assert_eq!(
+            parse_option("option name Skill Level type spin default 20 min 0 max 20"),
+            Some(UciOption {
+                name: String::from("Skill Level"),
+                option_type: Some(OptionType::Spin {
+                    default: 20,
+                    min: 0,
+                    max: 20
+                }),
+            })
+        );
+        assert_eq!(
+            parse_option("option name Style type combo default Normal var Solid var Very Risky").unwrap(),
+            UciOption {
+                name: String::from("Style"),
+                option_type: Some(OptionType::Combo {
+                    default: String::from("Normal"),
+                    values: vec![String::from("Solid"), String::from("Very Risky")],
+                }),
+            }
+        );
+        assert_eq!(
+            parse_option("option name EvalFile type string default <empty>")
+                .unwrap()
+                .option_type,
+            Some(OptionType::String { default: String::new() })
+        );
+        assert_eq!(
+            parse_option("option name Clear Hash type button").unwrap().option_type,
+            None
+        );
+        assert_eq!(parse_option("id name chic"), None);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::chess_board::fuzz::Mismatch;
+use crate::chess_board::Move;
+use crate::engines::board::Board;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+
+/// Compares two board implementations in the same position: FEN, hash, side to move, the legal moves and captures,
+/// check, mate and the draw rules. The moves are compared as sets, the order is up to the implementation.
+pub fn compare<A: Board, B: Board>(a: &A, b: &B) -> Result<(), String> {
+    let differ = |what: &str, a: String, b: String| {
+        if a == b {
+            Ok(())
+        } else {
+            Err(format!("{} differ\n  first:  {}\n  second: {}", what, a, b))
+        }
+    };
+    differ("FENs", a.to_fen(), b.to_fen())?;
+    differ(
+        "Hashes",
+        format!("{:016x}", a.zobrist_hash()),
+        format!("{:016x}", b.zobrist_hash()),
+    )?;
+    differ(
+        "Sides to move",
+        format!("{:?}", a.active_color()),
+        format!("{:?}", b.active_color()),
+    )?;
+    differ(
+        "Legal moves",
+        sorted_moves(a.generate_legal_moves()),
+        sorted_moves(b.generate_legal_moves()),
+    )?;
+    differ(
+        "Legal captures",
+        sorted_moves(a.generate_legal_capture_moves()),
+        sorted_moves(b.generate_legal_capture_moves()),
+    )?;
+    let flags = |in_check: bool, checkmate: bool, has_move: bool, fifty: bool, threefold: bool| {
+        format!(
+            "check {} mate {} legal move {} fifty-move rule {} threefold {}",
+            in_check, checkmate, has_move, fifty, threefold
+        )
+    };
+    differ(
+        "Flags",
+        flags(
+            a.is_in_check(),
+            a.is_checkmate(),
+            a.has_legal_move(),
+            a.is_draw_by_fifty_move_rule(),
+            a.is_threefold_repetition(),
+        ),
+        flags(
+            b.is_in_check(),
+            b.is_checkmate(),
+            b.has_legal_move(),
+            b.is_draw_by_fifty_move_rule(),
+            b.is_threefold_repetition(),
+        ),
+    )
+}
+
+fn sorted_moves(moves: Vec<Move>) -> String {
+    let mut moves: Vec<String> = moves.iter().map(|mv| mv.as_algebraic()).collect();
+    moves.sort();
+    moves.join(" ")
+}
+
+fn mismatch(start_fen: &str, moves: &[Move], message: String) -> Mismatch {
+    Mismatch {
+        start_fen: start_fen.to_string(),
+        moves: moves.to_vec(),
+        message,
+    }
+}
+
+fn load<A: Board, B: Board>(start_fen: &str) -> Result<(A, B), Mismatch> {
+    let a = A::from_fen(start_fen).map_err(|e| mismatch(start_fen, &[], format!("First board: {}", e)))?;
+    let b = B::from_fen(start_fen).map_err(|e| mismatch(start_fen, &[], format!("Second board: {}", e)))?;
+    Ok((a, b))
+}
+
+/// Plays random games from the start positions on both boards and compares every position reached. Returns the
+/// number of positions compared.
+pub fn verify_games<A: Board, B: Board>(
+    seed: u64,
+    games: usize,
+    max_plies: usize,
+    start_positions: &[&str],
+) -> Result<usize, Mismatch> {
+    let mut rng = Pcg64::seed_from_u64(seed);
+    let mut positions = 0;
+    for game in 0..games {
+        let start_fen = start_positions[game % start_positions.len()];
+        let (mut a, mut b) = load::<A, B>(start_fen)?;
+        let mut moves = Vec::new();
+        loop {
+            positions += 1;
+            compare(&a, &b).map_err(|message| mismatch(start_fen, &moves, message))?;
+            if moves.len() >= max_plies {
+                break;
+            }
+            let Some(&mv) = a.generate_legal_moves().choose(&mut rng) else {
+                break;
+            };
+            a.make_move(mv);
+            b.make_move(mv);
+            moves.push(mv);
+        }
+    }
+    Ok(positions)
+}
+
+/// Walks the game tree of the position to the depth on both boards and compares every node. The first board makes
+/// its moves on copies, the second one makes and takes them back, so its unmake_move is checked on the way.
+/// Returns the number of positions compared.
+pub fn verify_perft<A: Board, B: Board>(start_fen: &str, depth: u8) -> Result<u64, Mismatch> {
+    let (a, mut b) = load::<A, B>(start_fen)?;
+    let mut line = Vec::new();
+    walk(start_fen, &a, &mut b, depth, &mut line)
+}
+
+fn walk<A: Board, B: Board>(
+    start_fen: &str,
+    a: &A,
+    b: &mut B,
+    depth: u8,
+    line: &mut Vec<Move>,
+) -> Result<u64, Mismatch> {
+    compare(a, b).map_err(|message| mismatch(start_fen, line, message))?;
+    if depth == 0 {
+        return Ok(1);
+    }
+    let mut positions = 1;
+    for mv in a.generate_legal_moves() {
+        let mut child = a.clone();
+        child.make_move(mv);
+        line.push(mv);
+        let undo = b.make_move_with_undo(mv);
+        positions += walk(start_fen, &child, b, depth - 1, line)?;
+        b.unmake_move(undo);
+        line.pop();
+        compare(a, b).map_err(|message| {
+            let message = format!("After unmake_move of {}: {}", mv.as_algebraic(), message);
+            mismatch(start_fen, line, message)
+        })?;
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fuzz::START_POSITIONS;
+    use crate::chess_board::ChessBoard;
+
+    #[test]
+    fn test_verify_backends() {
+        let positions = verify_games::<ChessBoard, ChessBoard>(3, 10, 100, &START_POSITIONS);
+        assert!(positions.is_ok(), "{}", positions.unwrap_err());
+        // The tree of the second start position to depth 2, the root with 48 moves and 2039 replies
+        assert_eq!(
+            verify_perft::<ChessBoard, ChessBoard>(START_POSITIONS[1], 2),
+            Ok(1 + 48 + 2039)
+        );
+
+        let board = ChessBoard::from_fen(START_POSITIONS[0]).unwrap();
+        let mut moved = board.clone();
+        moved.make_move(Move::from_algebraic("e2e4"));
+        assert!(compare(&board, &moved).unwrap_err().starts_with("FENs differ"));
+        // Same placement and side to move, only the castling rights differ
+        let rights = ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").unwrap();
+        assert!(compare(&board, &rights).is_err());
+        assert!(verify_perft::<ChessBoard, ChessBoard>("no fen", 1).is_err());
+    }
+}
@@ -0,0 +1,42 @@
+//! Move generation, search engines and the user interface of the chic chess engine.
+//!
+//! The board and search API is available without the `ui` feature:
+//!
+//! ```
+//! use chic::{AlphaBetaEngine, ChessBoard, ChessEngine};
+//!
+//! let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+//! assert_eq!(board.generate_legal_moves().len(), 17);
+//!
+//! let mut engine = AlphaBetaEngine::with_board(board);
+//! engine.set_max_depth(2);
+//! let (pv, _, _, _) = engine.find_best_move_iterative(std::time::Duration::from_secs(10), &mut |_| {}).unwrap();
+//! assert_eq!(pv[0].as_algebraic(), "a1a8");
+//! ```
+pub mod chess_board;
+pub mod config;
+pub mod engines;
+#[cfg(feature = "ui")]
+pub mod ui;
+
+pub use chess_board::{ChessBoard, ChessField, Color, Move, Piece, PieceType, Square};
+pub use engines::board::Board;
+pub use engines::engine_alpha_beta::AlphaBetaEngine;
+pub use engines::ChessEngine;
+
+#[cfg(feature = "ui")]
+slint::include_modules!();
+
+/// Opens the user interface on the initial position with the default configuration. It is the entry point of the
+/// wasm module.
+#[cfg(feature = "ui")]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen::prelude::wasm_bindgen(start))]
+pub fn play_with_ui() {
+    play_with_config(&config::Config::default());
+}
+
+/// Opens the user interface on the initial position.
+#[cfg(feature = "ui")]
+pub fn play_with_config(config: &config::Config) {
+    ui::setup_ui(chess_board::fen::INITIAL_POSITION, config);
+}
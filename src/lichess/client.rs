@@ -0,0 +1,87 @@
+use serde::de::DeserializeOwned;
+use std::io::{BufRead, BufReader, Read};
+use std::marker::PhantomData;
+
+pub const DEFAULT_SERVER: &str = "https://lichess.org";
+
+/// Blocking client for the Lichess Bot API. Streams are read line by line on the calling thread.
+pub struct LichessClient {
+    agent: ureq::Agent,
+    server: String,
+    token: String,
+}
+
+impl LichessClient {
+    pub fn new(server: &str, token: &str) -> Self {
+        LichessClient {
+            agent: ureq::AgentBuilder::new().build(),
+            server: server.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        self.agent
+            .request(method, &format!("{}{}", self.server, path))
+            .set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    pub fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let response = self
+            .request("GET", path)
+            .call()
+            .map_err(|e| format!("GET {} failed: {}", path, e))?;
+        serde_json::from_reader(response.into_reader()).map_err(|e| format!("Invalid response of {}: {}", path, e))
+    }
+
+    pub fn post(&self, path: &str, form: &[(&str, &str)]) -> Result<(), String> {
+        self.request("POST", path)
+            .send_form(form)
+            .map(|_| ())
+            .map_err(|e| format!("POST {} failed: {}", path, e))
+    }
+
+    /// Opens a newline delimited JSON stream, e.g. the event stream or a game stream.
+    pub fn stream<T: DeserializeOwned>(&self, path: &str) -> Result<NdjsonStream<T>, String> {
+        let response = self
+            .request("GET", path)
+            .call()
+            .map_err(|e| format!("GET {} failed: {}", path, e))?;
+        Ok(NdjsonStream::new(response.into_reader()))
+    }
+}
+
+/// Iterator over the objects of an ndjson stream. Empty keep-alive lines are skipped.
+pub struct NdjsonStream<T> {
+    reader: BufReader<Box<dyn Read + Send + Sync>>,
+    item: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> NdjsonStream<T> {
+    pub fn new(reader: Box<dyn Read + Send + Sync>) -> Self {
+        NdjsonStream {
+            reader: BufReader::new(reader),
+            item: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for NdjsonStream<T> {
+    type Item = Result<T, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => {
+                    let event = serde_json::from_str(line.trim());
+                    return Some(event.map_err(|e| format!("Invalid event {}: {}", line.trim(), e)));
+                }
+                Err(e) => return Some(Err(format!("Stream closed: {}", e))),
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::chess_board::Color;
+use crate::engines::engine_alpha_beta::AlphaBetaEngine;
+use crate::engines::{allocate_move_time, ChessEngine};
+use client::LichessClient;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+pub mod client;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Event {
+    Challenge {
+        challenge: Challenge,
+    },
+    GameStart {
+        game: GameStart,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Challenge {
+    id: String,
+    variant: Variant,
+}
+
+#[derive(Debug, Deserialize)]
+struct Variant {
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GameStart {
+    game_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum GameEvent {
+    #[serde(rename_all = "camelCase")]
+    GameFull {
+        white: Player,
+        initial_fen: String,
+        state: GameState,
+    },
+    GameState(GameState),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Player {
+    // Missing for the Lichess AI
+    id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GameState {
+    moves: String,
+    wtime: u64,
+    btime: u64,
+    winc: u64,
+    binc: u64,
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    id: String,
+}
+
+/// Accepts standard chess challenges and plays every started game in its own thread.
+pub fn run_bot(client: LichessClient) -> Result<(), String> {
+    let client = Arc::new(client);
+    let bot_id = client.get_json::<Account>("/api/account")?.id;
+    println!("Connected as {}", bot_id);
+
+    for event in client.stream::<Event>("/api/stream/event")? {
+        match event? {
+            Event::Challenge { challenge } if challenge.variant.key == "standard" => {
+                println!("Accepting challenge {}", challenge.id);
+                client.post(&format!("/api/challenge/{}/accept", challenge.id), &[])?;
+            }
+            Event::Challenge { challenge } => {
+                println!("Declining {} challenge {}", challenge.variant.key, challenge.id);
+                client.post(
+                    &format!("/api/challenge/{}/decline", challenge.id),
+                    &[("reason", "standard")],
+                )?;
+            }
+            Event::GameStart { game } => {
+                let client = Arc::clone(&client);
+                let bot_id = bot_id.clone();
+                thread::spawn(move || {
+                    if let Err(e) = play_game(&client, &game.game_id, &bot_id) {
+                        println!("Game {} aborted: {}", game.game_id, e);
+                    }
+                });
+            }
+            Event::Other => {}
+        }
+    }
+    Ok(())
+}
+
+fn play_game(client: &LichessClient, game_id: &str, bot_id: &str) -> Result<(), String> {
+    let mut start_fen = INITIAL_POSITION.to_string();
+    let mut bot_color = Color::Black;
+
+    for event in client.stream::<GameEvent>(&format!("/api/bot/game/stream/{}", game_id))? {
+        let state = match event? {
+            GameEvent::GameFull {
+                white,
+                initial_fen,
+                state,
+            } => {
+                if white.id.as_deref() == Some(bot_id) {
+                    bot_color = Color::White;
+                }
+                if initial_fen != "startpos" {
+                    start_fen = initial_fen;
+                }
+                state
+            }
+            GameEvent::GameState(state) => state,
+            GameEvent::Other => continue,
+        };
+        if state.status != "started" {
+            println!("Game {} finished: {}", game_id, state.status);
+            break;
+        }
+
+        let mut engine = AlphaBetaEngine::new();
+        engine.set_position(&start_fen)?;
+        for mv in state.moves.split_whitespace() {
+            engine.make_move(mv)?;
+        }
+        if engine.get_active_player() != bot_color {
+            continue;
+        }
+
+        let mut info_callback = |_| {};
+        if let Some((best_move, _, _, _)) =
+            engine.find_best_move_iterative(move_time(&state, bot_color), &mut info_callback)
+        {
+            client.post(
+                &format!("/api/bot/game/{}/move/{}", game_id, best_move[0].as_algebraic()),
+                &[],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn move_time(state: &GameState, color: Color) -> Duration {
+    let (time_left, increment) = match color {
+        Color::White => (state.wtime, state.winc),
+        Color::Black => (state.btime, state.binc),
+    };
+    allocate_move_time(Duration::from_millis(time_left), Duration::from_millis(increment), 30)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::NdjsonStream;
+
+    #[test]
+    fn test_parse_event_stream() {
+        let stream = concat!(
+            r#"{"type":"challenge","challenge":{"id":"abc","variant":{"key":"standard"},"speed":"blitz"}}"#,
+            "\n\n",
+            r#"{"type":"gameStart","game":{"gameId":"xyz","color":"white"}}"#,
+            "\n",
+            r#"{"type":"gameFinish","game":{"gameId":"xyz"}}"#,
+            "\n"
+        );
+        let events: Vec<Event> = NdjsonStream::new(Box::new(stream.as_bytes()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[0], Event::Challenge { challenge } if challenge.id == "abc"));
+        assert!(matches!(&events[1], Event::GameStart { game } if game.game_id == "xyz"));
+        assert!(matches!(events[2], Event::Other));
+    }
+
+    #[test]
+    fn test_parse_game_stream() {
+        let stream = concat!(
+            r#"{"type":"gameFull","id":"xyz","white":{"id":"chic","name":"chic"},"black":{"aiLevel":1},"#,
+            r#""initialFen":"startpos","state":{"type":"gameState","moves":"e2e4","wtime":60000,"btime":60000,"#,
+            r#""winc":2000,"binc":2000,"status":"started"}}"#,
+            "\n",
+            r#"{"type":"gameState","moves":"e2e4 e7e5","wtime":57000,"btime":60000,"winc":2000,"binc":2000,"status":"started"}"#,
+            "\n",
+            r#"{"type":"chatLine","username":"lichess","text":"hi","room":"player"}"#,
+            "\n"
+        );
+        let events: Vec<GameEvent> = NdjsonStream::new(Box::new(stream.as_bytes()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        match &events[0] {
+            GameEvent::GameFull { white, initial_fen, .. } => {
+                assert_eq!(white.id.as_deref(), Some("chic"));
+                assert_eq!(initial_fen, "startpos");
+            }
+            _ => panic!("gameFull expected"),
+        }
+        match &events[1] {
+            GameEvent::GameState(state) => {
+                assert_eq!(state.moves, "e2e4 e7e5");
+                assert_eq!(move_time(state, Color::White), Duration::from_millis(57000 / 30 + 2000));
+            }
+            _ => panic!("gameState expected"),
+        }
+        assert!(matches!(events[2], GameEvent::Other));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::chess_board::ChessBoard;
+use crate::engines::eval_params::{EvalParams, PARAMETER_COUNT};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod self_play;
+
+/// A training position with its sparse evaluation features and the game result from White's view.
+pub struct TrainingPosition {
+    features: Vec<(usize, f64)>,
+    result: f64,
+}
+
+/// Parses a line "<FEN> [result]" where the result is 1-0, 0-1, 1/2-1/2, 1.0, 0.5 or 0.0, optionally
+/// wrapped in brackets or quotes as in EPD files. FENs without move counters are accepted.
+pub fn parse_training_line(line: &str) -> Result<TrainingPosition, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (result_token, fen_tokens) = tokens.split_last().ok_or("Empty line")?;
+    let result = match result_token.trim_matches(|c| "[]\";".contains(c)) {
+        "1-0" | "1.0" | "1" => 1.0,
+        "0-1" | "0.0" | "0" => 0.0,
+        "1/2-1/2" | "0.5" => 0.5,
+        other => return Err(format!("Invalid result {} in {}", other, line)),
+    };
+    let fen_tokens: Vec<&str> = fen_tokens
+        .iter()
+        .copied()
+        .filter(|t| *t != "c9" && *t != "c9;")
+        .collect();
+    let fen = match fen_tokens.len() {
+        4 => format!("{} 0 1", fen_tokens.join(" ")),
+        6 => fen_tokens.join(" "),
+        _ => return Err(format!("Invalid FEN in {}", line)),
+    };
+    let board = ChessBoard::from_fen(&fen)?;
+    Ok(TrainingPosition {
+        features: EvalParams::features(&board),
+        result,
+    })
+}
+
+fn evaluate(weights: &[f64], position: &TrainingPosition) -> f64 {
+    position.features.iter().map(|&(i, c)| weights[i] * c).sum()
+}
+
+/// Expected score of White for an evaluation in 1/1000 pawn.
+fn sigmoid(k: f64, evaluation: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * evaluation / 4000.0))
+}
+
+/// Mean squared error between the predicted and the actual results.
+pub fn mean_squared_error(weights: &[f64], positions: &[TrainingPosition], k: f64) -> f64 {
+    let sum: f64 = positions
+        .iter()
+        .map(|p| (p.result - sigmoid(k, evaluate(weights, p))).powi(2))
+        .sum();
+    sum / positions.len() as f64
+}
+
+/// Finds the scaling constant K of the sigmoid that fits the current weights best.
+pub fn find_k(weights: &[f64], positions: &[TrainingPosition]) -> f64 {
+    let (mut low, mut high) = (0.0, 10.0);
+    for _ in 0..50 {
+        let third = (high - low) / 3.0;
+        if mean_squared_error(weights, positions, low + third) < mean_squared_error(weights, positions, high - third) {
+            high -= third;
+        } else {
+            low += third;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Minimizes the prediction error with Adam. The evaluation is linear in the weights, so the gradient of every
+/// weight is the sum over the positions containing its feature.
+pub fn tune(
+    initial: &EvalParams,
+    positions: &[TrainingPosition],
+    iterations: usize,
+    learning_rate: f64,
+    report: &mut dyn FnMut(usize, f64),
+) -> EvalParams {
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+
+    let mut weights = initial.to_vector();
+    let k = find_k(&weights, positions);
+    let mut momentum = vec![0.0; PARAMETER_COUNT];
+    let mut velocity = vec![0.0; PARAMETER_COUNT];
+
+    for iteration in 1..=iterations {
+        let mut gradient = vec![0.0; PARAMETER_COUNT];
+        for position in positions {
+            let prediction = sigmoid(k, evaluate(&weights, position));
+            // d/de of (result - sigmoid(e))^2
+            let error_gradient =
+                -2.0 * (position.result - prediction) * prediction * (1.0 - prediction) * k * std::f64::consts::LN_10
+                    / 4000.0;
+            for &(i, c) in &position.features {
+                gradient[i] += error_gradient * c / positions.len() as f64;
+            }
+        }
+        for i in 0..PARAMETER_COUNT {
+            momentum[i] = BETA1 * momentum[i] + (1.0 - BETA1) * gradient[i];
+            velocity[i] = BETA2 * velocity[i] + (1.0 - BETA2) * gradient[i] * gradient[i];
+            let corrected_momentum = momentum[i] / (1.0 - BETA1.powi(iteration as i32));
+            let corrected_velocity = velocity[i] / (1.0 - BETA2.powi(iteration as i32));
+            weights[i] -= learning_rate * corrected_momentum / (corrected_velocity.sqrt() + EPSILON);
+        }
+        if iteration % 10 == 0 || iteration == iterations {
+            report(iteration, mean_squared_error(&weights, positions, k));
+        }
+    }
+    EvalParams::from_vector(&weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engines::eval_params::DEFAULT_EVAL_PARAMS;
+
+    #[test]
+    fn test_parse_training_line() {
+        let position = parse_training_line("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 [0.5]").unwrap();
+        assert_eq!(position.result, 0.5);
+        let position = parse_training_line("4k3/8/8/8/8/8/8/4KQ2 w - - c9 \"1-0\";").unwrap();
+        assert_eq!(position.result, 1.0);
+        assert!(parse_training_line("4k3/8/8/8/8/8/8/4KQ2 w - - 0 1 [2.0]").is_err());
+        assert!(parse_training_line("").is_err());
+    }
+
+    #[test]
+    fn test_tuning_reduces_error() {
+        // White wins with an extra queen, the default weights overrate a pawn the defending king stops
+        let positions: Vec<TrainingPosition> = [
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1 [1.0]",
+            "3qk3/8/8/8/8/8/8/4K3 w - - 0 1 [0.0]",
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1 [0.5]",
+            "4k3/4P3/4K3/8/8/8/8/8 b - - 0 1 [0.5]",
+            "8/8/8/8/8/4k3/4p3/4K3 w - - 0 1 [0.5]",
+        ]
+        .iter()
+        .map(|line| parse_training_line(line).unwrap())
+        .collect();
+        let before = mean_squared_error(&DEFAULT_EVAL_PARAMS.to_vector(), &positions, 1.0);
+        let tuned = tune(&DEFAULT_EVAL_PARAMS, &positions, 200, 10.0, &mut |_, _| {});
+        let after = mean_squared_error(&tuned.to_vector(), &positions, 1.0);
+        assert!(after < before);
+    }
+}
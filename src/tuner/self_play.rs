@@ -0,0 +1,148 @@
+use crate::arena::adjudication::{Adjudication, Adjudicator};
+use crate::chess_board::fen::INITIAL_POSITION;
+use crate::chess_board::pgn::game_result;
+use crate::chess_board::{ChessBoard, Color};
+use crate::engines::engine_alpha_beta::{AlphaBetaEngine, WIN};
+use crate::engines::move_picker::is_noisy;
+use crate::engines::{ChessEngine, INFINITE_SEARCH_TIME};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand_pcg::Pcg64;
+
+pub struct SelfPlayConfig {
+    /// Nodes of the search of every move.
+    pub nodes: u64,
+    /// Probability that a quiet position is sampled.
+    pub sample_rate: f64,
+    /// Random plies from the start position before the engine plays.
+    pub random_plies: usize,
+    pub adjudication: Adjudication,
+}
+
+/// A sampled position with the score of the search in centipawns and the game result, both from White's view.
+pub struct Sample {
+    pub fen: String,
+    pub score: i32,
+    pub result: f64,
+}
+
+impl Sample {
+    /// "<FEN> [result]" as read by the tuner.
+    pub fn to_text(&self) -> String {
+        format!("{} [{:.1}]", self.fen, self.result)
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!("{},{},{:.1}", self.fen, self.score, self.result)
+    }
+}
+
+pub struct SelfPlayGame {
+    pub samples: Vec<Sample>,
+    /// Result from White's point of view, 1.0 for a white win.
+    pub result: f64,
+    pub plies: usize,
+}
+
+/// Plays a game of the engine against itself from a random opening. Positions in check, with a capture or
+/// promotion as best move and with mate scores are not sampled, the evaluation cannot judge them.
+pub fn play_game(config: &SelfPlayConfig, rng: &mut Pcg64) -> SelfPlayGame {
+    let mut board = random_opening(rng, config.random_plies);
+    let mut plies = config.random_plies;
+    let mut engine = AlphaBetaEngine::new();
+    engine.set_max_nodes(config.nodes);
+    let mut adjudicator = Adjudicator::new(config.adjudication);
+    let mut samples = Vec::new();
+    let result = loop {
+        match game_result(&board) {
+            "1-0" => break 1.0,
+            "0-1" => break 0.0,
+            "1/2-1/2" => break 0.5,
+            _ => {}
+        }
+        if plies >= config.adjudication.max_plies {
+            break 0.5;
+        }
+        engine.set_board(board.clone());
+        let Some((pv, score, _, _)) = engine.find_best_move_iterative(INFINITE_SEARCH_TIME, &mut |_| {}) else {
+            break 0.5;
+        };
+        let white_score = match board.active_color {
+            Color::White => score,
+            Color::Black => -score,
+        };
+        if !board.is_in_check() && !is_noisy(&board, pv[0]) && score.abs() < WIN / 2 && rng.gen_bool(config.sample_rate)
+        {
+            samples.push(Sample {
+                fen: board.to_fen(),
+                score: white_score / 10,
+                result: 0.0,
+            });
+        }
+        let adjudicated = adjudicator.record(board.active_color, board.fullmove_number, Some(score / 10));
+        board.make_move(pv[0]);
+        plies += 1;
+        // A game that just ended is scored by the rules
+        if let Some(result) = adjudicated.filter(|_| game_result(&board) == "*") {
+            break result;
+        }
+    };
+    for sample in &mut samples {
+        sample.result = result;
+    }
+    SelfPlayGame { samples, result, plies }
+}
+
+/// Random legal moves, an opening that ends the game is replaced by a new one.
+fn random_opening(rng: &mut Pcg64, plies: usize) -> ChessBoard {
+    loop {
+        let mut board = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+        for _ in 0..plies {
+            match board.generate_legal_moves().choose(rng) {
+                Some(&mv) => board.make_move(mv),
+                None => break,
+            }
+        }
+        if game_result(&board) == "*" {
+            return board;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuner::parse_training_line;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_play_game() {
+        let config = SelfPlayConfig {
+            nodes: 500,
+            sample_rate: 1.0,
+            random_plies: 4,
+            adjudication: Adjudication {
+                resign_score: None,
+                resign_moves: 3,
+                draw_score: None,
+                draw_moves: 8,
+                draw_move_number: 40,
+                max_plies: 20,
+            },
+        };
+        let mut rng = Pcg64::seed_from_u64(1);
+        let game = play_game(&config, &mut rng);
+        assert!(game.plies <= 20);
+        assert!(!game.samples.is_empty());
+        for sample in &game.samples {
+            assert_eq!(sample.result, game.result);
+            assert!(parse_training_line(&sample.to_text()).is_ok());
+            assert!(!ChessBoard::from_fen(&sample.fen).unwrap().is_in_check());
+            assert_eq!(sample.to_csv().split(',').count(), 3);
+        }
+        // A game cut off by the ply limit is drawn
+        if game.plies == 20 {
+            assert_eq!(game.result, 0.5);
+        }
+    }
+}
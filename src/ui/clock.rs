@@ -0,0 +1,226 @@
+use crate::chess_board::Color;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Longest base time in minutes, a day.
+const MAX_BASE_MINUTES: f32 = 24.0 * 60.0;
+/// Longest increment in seconds, an hour.
+const MAX_INCREMENT_SECONDS: u64 = 60 * 60;
+
+/// A chess clock with the same base time and increment for both sides.
+pub struct ChessClock {
+    remaining: [Duration; 2],
+    increment: Duration,
+    running: Option<(Color, Instant)>,
+}
+
+/// The times of a clock in milliseconds, so a session can be saved and resumed. The running clock is the one of the
+/// side to move, the time between saving and restoring is not counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockSnapshot {
+    pub white_ms: u64,
+    pub black_ms: u64,
+    pub increment_ms: u64,
+    pub running: bool,
+}
+
+fn side(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+impl ChessClock {
+    pub fn new(base_time: Duration, increment: Duration) -> Self {
+        ChessClock {
+            remaining: [base_time; 2],
+            increment,
+            running: None,
+        }
+    }
+
+    pub fn increment(&self) -> Duration {
+        self.increment
+    }
+
+    pub fn start(&mut self, color: Color, now: Instant) {
+        self.stop(now);
+        self.running = Some((color, now));
+    }
+
+    /// Stops the clock of the side that just moved, adds its increment and starts the opponent's clock.
+    pub fn press(&mut self, color: Color, now: Instant) {
+        self.stop(now);
+        self.remaining[side(color)] += self.increment;
+        self.running = Some((opponent(color), now));
+    }
+
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((color, started)) = self.running.take() {
+            let elapsed = now.saturating_duration_since(started);
+            self.remaining[side(color)] = self.remaining[side(color)].saturating_sub(elapsed);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    pub fn remaining(&self, color: Color, now: Instant) -> Duration {
+        match self.running {
+            Some((running, started)) if running == color => {
+                self.remaining[side(color)].saturating_sub(now.saturating_duration_since(started))
+            }
+            _ => self.remaining[side(color)],
+        }
+    }
+
+    pub fn snapshot(&self, now: Instant) -> ClockSnapshot {
+        let millis = |color| self.remaining(color, now).as_millis() as u64;
+        ClockSnapshot {
+            white_ms: millis(Color::White),
+            black_ms: millis(Color::Black),
+            increment_ms: self.increment.as_millis() as u64,
+            running: self.is_running(),
+        }
+    }
+
+    /// The clock of a snapshot, started for the side to move if it was running.
+    pub fn from_snapshot(snapshot: &ClockSnapshot, to_move: Color, now: Instant) -> Self {
+        let mut clock = ChessClock {
+            remaining: [
+                Duration::from_millis(snapshot.white_ms),
+                Duration::from_millis(snapshot.black_ms),
+            ],
+            increment: Duration::from_millis(snapshot.increment_ms),
+            running: None,
+        };
+        if snapshot.running {
+            clock.start(to_move, now);
+        }
+        clock
+    }
+
+    /// Returns the side whose time ran out.
+    pub fn flagged(&self, now: Instant) -> Option<Color> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .find(|&color| self.remaining(color, now).is_zero())
+    }
+}
+
+/// Parses the base time in minutes and the increment in seconds of the clock dialog. Times that are not finite,
+/// out of range or, for the base time, not positive are rejected.
+pub fn parse_time_control(base_minutes: &str, increment_seconds: &str) -> Result<(Duration, Duration), String> {
+    let base_time = base_minutes
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|&minutes| minutes > 0.0 && minutes <= MAX_BASE_MINUTES)
+        .and_then(|minutes| Duration::try_from_secs_f32(minutes * 60.0).ok())
+        .ok_or("Invalid base time")?;
+    let increment = increment_seconds
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|&seconds| seconds <= MAX_INCREMENT_SECONDS)
+        .map(Duration::from_secs)
+        .ok_or("Invalid increment")?;
+    Ok((base_time, increment))
+}
+
+/// Formats the time as "m:ss", with tenths of a second below ten seconds.
+pub fn format_clock(time: Duration) -> String {
+    if time < Duration::from_secs(10) {
+        format!("0:{:02}.{}", time.as_secs(), time.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", time.as_secs() / 60, time.as_secs() % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chess_clock() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let mut clock = ChessClock::new(secs(60), secs(2));
+        clock.start(Color::White, start);
+        assert_eq!(clock.remaining(Color::White, start + secs(10)), secs(50));
+        assert_eq!(clock.remaining(Color::Black, start + secs(10)), secs(60));
+
+        clock.press(Color::White, start + secs(10));
+        assert_eq!(clock.remaining(Color::White, start + secs(20)), secs(52));
+        assert_eq!(clock.remaining(Color::Black, start + secs(20)), secs(50));
+        assert_eq!(clock.flagged(start + secs(20)), None);
+        assert_eq!(clock.flagged(start + secs(80)), Some(Color::Black));
+
+        clock.stop(start + secs(30));
+        assert!(!clock.is_running());
+        assert_eq!(clock.remaining(Color::Black, start + secs(100)), secs(40));
+    }
+
+    #[test]
+    fn test_clock_snapshot() {
+        let start = Instant::now();
+        let secs = Duration::from_secs;
+        let mut clock = ChessClock::new(secs(60), secs(2));
+        clock.start(Color::White, start);
+        clock.press(Color::White, start + secs(10));
+        let snapshot = clock.snapshot(start + secs(15));
+        assert_eq!(
+            snapshot,
+            ClockSnapshot {
+                white_ms: 52_000,
+                black_ms: 55_000,
+                increment_ms: 2_000,
+                running: true
+            }
+        );
+
+        // The time between saving and restoring does not count
+        let later = start + secs(1000);
+        let restored = ChessClock::from_snapshot(&snapshot, Color::Black, later);
+        assert_eq!(restored.remaining(Color::Black, later + secs(5)), secs(50));
+        assert_eq!(restored.remaining(Color::White, later + secs(5)), secs(52));
+        assert_eq!(restored.increment(), secs(2));
+        clock.stop(start + secs(15));
+        assert!(!ChessClock::from_snapshot(&clock.snapshot(later), Color::Black, later).is_running());
+    }
+
+    #[test]
+    fn test_parse_time_control() {
+        let secs = Duration::from_secs;
+        assert_eq!(parse_time_control(" 5 ", "3"), Ok((secs(300), secs(3))));
+        assert_eq!(parse_time_control("0.5", "0"), Ok((secs(30), secs(0))));
+        assert_eq!(parse_time_control("inf", "3"), Err(String::from("Invalid base time")));
+        assert_eq!(parse_time_control("5", "1e9"), Err(String::from("Invalid increment")));
+        for base in ["0", "-1", "inf", "NaN", "1e40", "1441", "five"] {
+            assert!(parse_time_control(base, "0").is_err(), "{}", base);
+        }
+        for increment in ["-1", "3601", "18446744073709551615", "1.5"] {
+            assert!(parse_time_control("5", increment).is_err(), "{}", increment);
+        }
+    }
+
+    #[test]
+    fn test_format_clock() {
+        assert_eq!(format_clock(Duration::from_secs(300)), "5:00");
+        assert_eq!(format_clock(Duration::from_secs(75)), "1:15");
+        assert_eq!(format_clock(Duration::from_millis(9_450)), "0:09.4");
+    }
+}
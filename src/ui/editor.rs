@@ -0,0 +1,172 @@
+use super::{abort_engine_search, create_piece, highlight_move, refresh_board, set_game, State};
+use crate::chess_board::castling::Castling;
+use crate::chess_board::fen::{FenError, INITIAL_POSITION};
+use crate::chess_board::game::Game;
+use crate::chess_board::{Color, Piece, PieceType};
+use crate::ChessBoard;
+use crate::ChessField;
+use slint::{ModelRc, SharedString, VecModel};
+use std::rc::Rc;
+
+/// The pieces of the palette, white ones in the first row.
+const PALETTE: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+
+fn palette_piece(index: i32) -> Option<Piece> {
+    let index = usize::try_from(index).ok()?;
+    let color = if index < PALETTE.len() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    PALETTE.get(index % PALETTE.len()).map(|&kind| Piece { color, kind })
+}
+
+fn show_edit_error(state: &Rc<State>, error: FenError) {
+    state
+        .main_ui
+        .set_status_message(SharedString::from(format!("Invalid position: {}", error)));
+}
+
+/// Shows the pieces, side to move and castling rights of the edited position.
+pub(super) fn update_editor(state: &Rc<State>) {
+    let piece_set = state.piece_set.get();
+    let pieces: Vec<_> = [Color::White, Color::Black]
+        .iter()
+        .flat_map(|&color| {
+            PALETTE
+                .iter()
+                .map(move |&kind| create_piece(piece_set.image(color, kind)))
+        })
+        .collect();
+    state.main_ui.set_editor_pieces(ModelRc::new(VecModel::from(pieces)));
+    let chess_board = state.chess_board.borrow();
+    state.main_ui.set_editor_side(match chess_board.active_color {
+        Color::White => 0,
+        Color::Black => 1,
+    });
+    state
+        .main_ui
+        .set_editor_white_kingside(chess_board.castling_rights.has(Castling::WhiteKingside));
+    state
+        .main_ui
+        .set_editor_white_queenside(chess_board.castling_rights.has(Castling::WhiteQueenside));
+    state
+        .main_ui
+        .set_editor_black_kingside(chess_board.castling_rights.has(Castling::BlackKingside));
+    state
+        .main_ui
+        .set_editor_black_queenside(chess_board.castling_rights.has(Castling::BlackQueenside));
+}
+
+fn position_edited(state: &Rc<State>) {
+    refresh_board(state);
+    update_editor(state);
+}
+
+/// Stops the game and lets the user edit the current position.
+pub(super) fn start_editing(state: &Rc<State>) {
+    abort_engine_search(state);
+    state.engine_thinking.set(false);
+    super::watch::finish(state);
+    *state.clock.borrow_mut() = None;
+    *state.selected_field.borrow_mut() = None;
+    *state.premove.borrow_mut() = None;
+    state.main_ui.set_white_clock_text(SharedString::new());
+    state.main_ui.set_black_clock_text(SharedString::new());
+    state.main_ui.set_promotion_dialog_visible(false);
+    // The moves played do not lead to the edited position
+    let fen = state.chess_board.borrow().to_fen();
+    if let Ok(game) = Game::new(&fen) {
+        *state.game.borrow_mut() = game;
+    }
+    *state.evaluations.borrow_mut() = vec![None];
+    state.viewed_ply.set(None);
+    state.main_ui.set_editor_selected(-1);
+    state.main_ui.set_draw_claimable(false);
+    highlight_move(state, None);
+    position_edited(state);
+}
+
+/// Selects the piece clicks put on the board, -1 removes pieces.
+pub(super) fn select_piece(state: &Rc<State>, index: i32) {
+    let index = if palette_piece(index).is_some() { index } else { -1 };
+    state.main_ui.set_editor_selected(index);
+}
+
+/// Puts the selected piece on the field or removes the piece standing there.
+pub(super) fn edit_field(state: &Rc<State>, field: ChessField) {
+    let result = match palette_piece(state.main_ui.get_editor_selected()) {
+        Some(piece) => state.chess_board.borrow_mut().place_piece(field, piece),
+        None => {
+            state.chess_board.borrow_mut().remove_piece(field);
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => state.main_ui.set_status_message(SharedString::new()),
+        Err(e) => show_edit_error(state, e),
+    }
+    position_edited(state);
+}
+
+/// Moves a piece dragged on the board, pieces dragged off the board are removed.
+pub(super) fn move_piece(state: &Rc<State>, source: ChessField, target: Option<ChessField>) {
+    let mut chess_board = state.chess_board.borrow_mut();
+    let result = match (chess_board.remove_piece(source), target) {
+        (Some(piece), Some(target)) => chess_board.place_piece(target, piece).inspect_err(|_| {
+            chess_board.place_piece(source, piece).ok();
+        }),
+        _ => Ok(()),
+    };
+    drop(chess_board);
+    if let Err(e) = result {
+        show_edit_error(state, e);
+    }
+    position_edited(state);
+}
+
+pub(super) fn set_side_to_move(state: &Rc<State>, index: i32) {
+    let color = if index == 0 { Color::White } else { Color::Black };
+    state.chess_board.borrow_mut().set_side_to_move(color);
+    position_edited(state);
+}
+
+/// Toggles a castling right, index in FEN order K, Q, k, q.
+pub(super) fn set_castling(state: &Rc<State>, index: i32, allowed: bool) {
+    let color = if index < 2 { Color::White } else { Color::Black };
+    let result = state
+        .chess_board
+        .borrow_mut()
+        .set_castling(color, index % 2 == 0, allowed);
+    if let Err(e) = result {
+        show_edit_error(state, e);
+    }
+    position_edited(state);
+}
+
+pub(super) fn clear_board(state: &Rc<State>) {
+    state.chess_board.borrow_mut().clear();
+    position_edited(state);
+}
+
+pub(super) fn set_initial_position(state: &Rc<State>) {
+    *state.chess_board.borrow_mut() = ChessBoard::from_fen(INITIAL_POSITION).unwrap();
+    position_edited(state);
+}
+
+/// Starts a game from the edited position if it can be played.
+pub(super) fn finish_editing(state: &Rc<State>) -> Result<(), String> {
+    let fen = {
+        let chess_board = state.chess_board.borrow();
+        chess_board.validate().map_err(|e| format!("Invalid position: {}", e))?;
+        chess_board.to_fen()
+    };
+    set_game(state, Game::new(&fen)?)
+}
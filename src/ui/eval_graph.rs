@@ -0,0 +1,161 @@
+//! The evaluation of every position of the game as a filled area over the moves, White's advantage above the middle
+//! line. The engine fills it in while it plays or analyzes, loaded games bring the [%eval] comments of their moves.
+//! Clicking the graph shows the position after that move until the board is clicked.
+use super::{refresh_board, State};
+use crate::chess_board::game::Game;
+use crate::chess_board::pgn::move_to_san;
+use slint::SharedString;
+use std::rc::Rc;
+
+/// Evaluations beyond this many pawns are drawn at the edge, as on the evaluation bar.
+pub const GRAPH_RANGE: f32 = 5.0;
+
+/// Pawns of the evaluation bar from an engine score in 1/1000 pawn from White's point of view.
+pub fn pawns(white_score: i32) -> f32 {
+    (white_score as f32 / 1000.0).clamp(-10.0, 10.0)
+}
+
+/// The path of the area between the middle line and the evaluations, in a view box of one unit per ply and
+/// 2 * GRAPH_RANGE high with White's advantage at the top. Positions without an evaluation keep the one before.
+pub fn area_path(evaluations: &[Option<f32>]) -> String {
+    if evaluations.len() < 2 {
+        return String::new();
+    }
+    let mut commands = format!("M 0 {}", GRAPH_RANGE);
+    let mut evaluation = 0.0;
+    for (ply, value) in evaluations.iter().enumerate() {
+        evaluation = value.unwrap_or(evaluation);
+        let y = GRAPH_RANGE - evaluation.clamp(-GRAPH_RANGE, GRAPH_RANGE);
+        commands.push_str(&format!(" L {} {}", ply, y));
+    }
+    commands.push_str(&format!(" L {} {} Z", evaluations.len() - 1, GRAPH_RANGE));
+    commands
+}
+
+/// The ply nearest to a click at the fraction of the width of the graph.
+pub fn ply_at(fraction: f32, plies: usize) -> usize {
+    ((fraction.clamp(0.0, 1.0) * plies as f32).round() as usize).min(plies)
+}
+
+/// The evaluation of a [%eval] command in a comment, in pawns from White's point of view. A mate is the edge of
+/// the evaluation bar.
+pub fn parse_eval_comment(comment: &str) -> Option<f32> {
+    let start = comment.find("[%eval ")? + "[%eval ".len();
+    let value = comment[start..].split(']').next()?.trim();
+    match value.strip_prefix('#') {
+        Some(mate) if mate.starts_with('-') => Some(-10.0),
+        Some(_) => Some(10.0),
+        None => value.parse::<f32>().ok().map(|pawns| pawns.clamp(-10.0, 10.0)),
+    }
+}
+
+/// The evaluations of a loaded game, after every move of its main line that has one.
+pub fn from_game(game: &Game) -> Vec<Option<f32>> {
+    let mut evaluations = vec![None];
+    evaluations.extend(
+        game.moves
+            .iter()
+            .map(|game_move| game_move.comment.as_deref().and_then(parse_eval_comment)),
+    );
+    evaluations
+}
+
+/// Stores the evaluation of the position after the ply, in pawns from White's point of view.
+pub fn record(state: &State, ply: usize, evaluation: f32) {
+    let mut evaluations = state.evaluations.borrow_mut();
+    if evaluations.len() <= ply {
+        evaluations.resize(ply + 1, None);
+    }
+    evaluations[ply] = Some(evaluation);
+}
+
+/// Draws the graph with a marker at the shown position.
+pub fn show(state: &State) {
+    let plies = state.game.borrow().moves.len();
+    let mut evaluations = state.evaluations.borrow().clone();
+    evaluations.resize(plies + 1, None);
+    let viewed = state.viewed_ply.get().unwrap_or(plies);
+    state
+        .main_ui
+        .set_eval_graph_commands(SharedString::from(area_path(&evaluations)));
+    state.main_ui.set_eval_graph_plies(plies.max(1) as f32);
+    state
+        .main_ui
+        .set_eval_graph_marker(if plies == 0 { 1.0 } else { viewed as f32 / plies as f32 });
+}
+
+/// Shows the position after the move at the click, the end of the game returns to the game.
+pub fn clicked(state: &Rc<State>, fraction: f32) {
+    if state.main_ui.get_edit_mode() {
+        return;
+    }
+    let plies = state.game.borrow().moves.len();
+    let ply = ply_at(fraction, plies);
+    if ply == plies {
+        stop_viewing(state);
+        return;
+    }
+    state.viewed_ply.set(Some(ply));
+    *state.selected_field.borrow_mut() = None;
+    refresh_board(state);
+    let message = match ply {
+        0 => String::from("The start position, click the board to return to the game"),
+        _ => {
+            let game = state.game.borrow();
+            let san = game
+                .position_after(ply - 1)
+                .map(|board| move_to_san(&board, game.moves[ply - 1].mv))
+                .unwrap_or_default();
+            let dots = if ply % 2 == 1 { "." } else { "..." };
+            format!(
+                "After {}{} {}, click the board to return to the game",
+                ply.div_ceil(2),
+                dots,
+                san
+            )
+        }
+    };
+    state.main_ui.set_status_message(SharedString::from(message));
+}
+
+/// Returns from an earlier position to the game, false if the game is shown.
+pub fn stop_viewing(state: &Rc<State>) -> bool {
+    if state.viewed_ply.take().is_none() {
+        return false;
+    }
+    refresh_board(state);
+    state.main_ui.set_status_message(SharedString::new());
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_area_path() {
+        assert_eq!(area_path(&[Some(0.3)]), "");
+        assert_eq!(
+            area_path(&[None, Some(1.0), None, Some(-7.5)]),
+            "M 0 5 L 0 5 L 1 4 L 2 4 L 3 10 L 3 5 Z"
+        );
+        assert_eq!(ply_at(0.0, 6), 0);
+        assert_eq!(ply_at(0.45, 6), 3);
+        assert_eq!(ply_at(1.2, 6), 6);
+        assert_eq!(pawns(-1500), -1.5);
+    }
+
+    #[test]
+    fn test_parse_eval_comment() {
+        assert_eq!(parse_eval_comment("[%eval 0.35] [%clk 0:00:00.040]"), Some(0.35));
+        assert_eq!(parse_eval_comment("[%eval -1.20]"), Some(-1.2));
+        assert_eq!(parse_eval_comment("[%eval #3] [%clk 0:00:00.000]"), Some(10.0));
+        assert_eq!(parse_eval_comment("[%eval #-2]"), Some(-10.0));
+        assert_eq!(parse_eval_comment("[%clk 0:00:02.000]"), None);
+        assert_eq!(parse_eval_comment("Only a comment"), None);
+
+        let pgn = "1. e4 {[%eval 0.30]} e5 2. Nf3 {[%eval 0.25] [%clk 0:00:01.000]} *";
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(from_game(&game), vec![None, Some(0.3), None, Some(0.25)]);
+    }
+}
@@ -0,0 +1,267 @@
+//! A third-party UCI engine like Stockfish in the user interface. While it is loaded it plays against the user and
+//! analyzes instead of the built-in engine, and it can be chosen for a side of a watched game. The engine runs as a
+//! process, so this needs the desktop version.
+#[cfg(not(target_arch = "wasm32"))]
+use super::{engine_info, engine_move_time};
+use super::{watch, State};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engines::engine_alpha_beta::WIN;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::engines::uci_client::{SearchLimit, UciClient, UciInfo, UciInput, UciScore};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{ChessBoard, Move};
+#[cfg(not(target_arch = "wasm32"))]
+use slint::ComponentHandle;
+use slint::SharedString;
+use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+/// The loaded engine. The client is locked by the thread of a search, the input stays free to stop it.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct External {
+    pub name: String,
+    client: Arc<Mutex<UciClient>>,
+    input: UciInput,
+}
+
+/// There are no processes in the browser.
+#[cfg(target_arch = "wasm32")]
+pub enum External {}
+
+/// The score of an info line in the units of the built-in engine, 1/1000 pawn, a mate as a win.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn internal_score(score: UciScore) -> i32 {
+    match score {
+        UciScore::Centipawns(value) => value.saturating_mul(10).clamp(-WIN + 1, WIN - 1),
+        UciScore::Mate(moves) if moves > 0 => WIN,
+        UciScore::Mate(_) => -WIN,
+    }
+}
+
+/// The name of the loaded engine.
+pub fn name(state: &State) -> Option<String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return state.external.borrow().as_ref().map(|external| external.name.clone());
+    #[cfg(target_arch = "wasm32")]
+    return state.external.borrow().as_ref().map(|external| match *external {});
+}
+
+pub fn is_loaded(state: &State) -> bool {
+    state.external.borrow().is_some()
+}
+
+/// Starts the engine of the command line and sets the "Name=Value" options of the configuration.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(state: &Rc<State>, command: &str, options: &[String]) -> Result<String, String> {
+    unload(state);
+    let mut client = UciClient::start(command)?;
+    client.set_options(options)?;
+    client.new_game()?;
+    let name = client.name.clone();
+    *state.external.borrow_mut() = Some(External {
+        name: name.clone(),
+        input: client.input(),
+        client: Arc::new(Mutex::new(client)),
+    });
+    loaded(state);
+    Ok(format!("{} plays and analyzes instead of chic", name))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load(_state: &Rc<State>, _command: &str, _options: &[String]) -> Result<String, String> {
+    Err(String::from("External engines need the desktop version"))
+}
+
+/// Quits the engine, chic plays and analyzes again.
+pub fn unload(state: &Rc<State>) {
+    if !is_loaded(state) {
+        return;
+    }
+    if watch::plays_external(state) {
+        watch::stop(state);
+    }
+    super::abort_engine_search(state);
+    state.engine_thinking.set(false);
+    state.external.borrow_mut().take();
+    loaded(state);
+}
+
+/// Shows the loaded engine and offers it for watched games.
+fn loaded(state: &Rc<State>) {
+    let name = name(state);
+    state
+        .main_ui
+        .set_external_name(SharedString::from(name.clone().unwrap_or_default()));
+    super::update_watch_engines(state, name.as_deref());
+}
+
+/// Whether the loaded engine plays the side to move: in a watched game if it was chosen for the side, otherwise
+/// whenever it is loaded.
+pub fn plays_move(state: &State) -> bool {
+    match watch::is_watching(state) {
+        true => watch::plays_external(state),
+        false => is_loaded(state),
+    }
+}
+
+/// Stops a search of the engine, it answers with its best move, which is dropped.
+pub fn stop(state: &State) {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(external) = state.external.borrow().as_ref() {
+        external.input.stop();
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = state;
+}
+
+/// The start position of the game with its moves if they lead to the board, so that the engine knows the positions
+/// for the repetitions, otherwise the position of the board alone.
+#[cfg(not(target_arch = "wasm32"))]
+fn position(state: &State) -> (String, Vec<String>) {
+    let board = state.chess_board.borrow();
+    let game = state.game.borrow();
+    match game.final_position() {
+        Ok(position) if position.to_fen() == board.to_fen() => (
+            game.start_fen.clone(),
+            game.main_line().iter().map(|mv| mv.as_algebraic()).collect(),
+        ),
+        _ => (board.to_fen(), Vec::new()),
+    }
+}
+
+/// Runs a search of the engine on a thread. The search is abandoned when the abort flag of the state is set, the
+/// info lines go to on_info and the best move to on_best_move, both with the board of the search.
+#[cfg(not(target_arch = "wasm32"))]
+fn search(
+    state: &Rc<State>,
+    limit: SearchLimit,
+    on_info: impl Fn(&ChessBoard, &UciInfo) + Send + 'static,
+    on_best_move: impl FnOnce(String, String, i32) + Send + 'static,
+) {
+    let Some((client, input)) = state
+        .external
+        .borrow()
+        .as_ref()
+        .map(|external| (external.client.clone(), external.input.clone()))
+    else {
+        return;
+    };
+    let chess_board = state.chess_board.borrow().clone();
+    let (start_fen, moves) = position(state);
+    let aborted = Arc::new(AtomicBool::new(false));
+    *state.engine_abort.borrow_mut() = Some(aborted.clone());
+    let ui_weak = state.main_ui.as_weak();
+    std::thread::spawn(move || {
+        // An aborted search still holds the engine until it sent its best move
+        let mut client = client.lock().unwrap();
+        if aborted.load(Relaxed) {
+            return;
+        }
+        let mut stopped = false;
+        let result = client.search(&start_fen, &moves, limit, |info| {
+            if aborted.load(Relaxed) {
+                // The stop of the user may have come before the go
+                if !stopped {
+                    input.stop();
+                    stopped = true;
+                }
+                return;
+            }
+            on_info(&chess_board, info);
+        });
+        match result {
+            Ok(best_move) if !aborted.load(Relaxed) => {
+                let score = best_move.info.and_then(|info| info.score).map_or(0, internal_score);
+                on_best_move(best_move.mv, best_move.ponder.unwrap_or_default(), score);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                let message = format!("{}, unload the engine to play on with chic", e);
+                let _ = slint::invoke_from_event_loop(move || {
+                    ui_weak.unwrap().set_status_message(SharedString::from(message))
+                });
+            }
+        }
+    });
+}
+
+/// Shows the info lines of the engine like those of the built-in engine.
+#[cfg(not(target_arch = "wasm32"))]
+fn show_info(ui_weak: slint::Weak<crate::MainWindow>, generation: i32) -> impl Fn(&ChessBoard, &UciInfo) + Send {
+    move |chess_board, info| {
+        let (Some(depth), Some(score)) = (info.depth, info.score) else {
+            return;
+        };
+        if info.bound || info.multipv.unwrap_or(1) != 1 {
+            return;
+        }
+        let score = internal_score(score);
+        let nodes = info.nodes.unwrap_or_default();
+        let (evaluation, analysis) = engine_info(chess_board, depth as i32, score, nodes, &info.pv.join(" "));
+        let handle = ui_weak.clone();
+        let _ = slint::invoke_from_event_loop(move || {
+            handle
+                .unwrap()
+                .invoke_engine_info(generation, evaluation, SharedString::from(analysis))
+        });
+    }
+}
+
+/// Lets the engine search its move for the time the built-in engine would take.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn make_move(state: &Rc<State>) {
+    let ui_weak = state.main_ui.as_weak();
+    let generation = state.game_generation.get();
+    let move_time = engine_move_time(state, Duration::from_secs(7));
+    let on_info = show_info(ui_weak.clone(), generation);
+    search(
+        state,
+        SearchLimit::MoveTime(move_time),
+        on_info,
+        move |mv, ponder, score| {
+            let _ = slint::invoke_from_event_loop(move || {
+                ui_weak
+                    .unwrap()
+                    .invoke_make_move(SharedString::from(mv), SharedString::from(ponder), generation, score)
+            });
+        },
+    );
+}
+
+/// Analyzes the position until it changes, the best line is kept for the annotations like that of the built-in
+/// analysis.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn start_analysis(state: &Rc<State>) {
+    let best_line = Arc::new(Mutex::new(None));
+    *state.analysis_best_line.borrow_mut() = best_line.clone();
+    let show = show_info(state.main_ui.as_weak(), state.game_generation.get());
+    let on_info = move |chess_board: &ChessBoard, info: &UciInfo| {
+        if let (Some(score), false) = (info.score, info.pv.is_empty()) {
+            if !info.bound && info.multipv.unwrap_or(1) == 1 {
+                let pv = info.pv.iter().map(|mv| Move::from_algebraic(mv)).collect();
+                *best_line.lock().unwrap() = Some((internal_score(score), pv));
+            }
+        }
+        show(chess_board, info);
+    };
+    search(state, SearchLimit::Infinite, on_info, |_, _, _| {});
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internal_score() {
+        assert_eq!(internal_score(UciScore::Centipawns(-35)), -350);
+        assert_eq!(internal_score(UciScore::Mate(3)), WIN);
+        assert_eq!(internal_score(UciScore::Mate(-1)), -WIN);
+        // A huge score is no mate
+        assert_eq!(internal_score(UciScore::Centipawns(2_000_000)), WIN - 1);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::chess_board::game::Game;
+
+/// What a pasted text is, told apart by its form alone.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ImportText<'a> {
+    Fen(&'a str),
+    Pgn(&'a str),
+    /// The id of a Lichess game, its PGN has to be fetched.
+    LichessGame(&'a str),
+}
+
+/// Tells a FEN, a PGN and a game URL apart. Links other than Lichess games are rejected, their PGN cannot be fetched
+/// without an account.
+pub fn detect(text: &str) -> Result<ImportText<'_>, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(String::from("The clipboard is empty"));
+    }
+    if let Some(url) = text.strip_prefix("https://").or_else(|| text.strip_prefix("http://")) {
+        return lichess_game_id(url).map(ImportText::LichessGame).ok_or_else(|| {
+            format!(
+                "Only Lichess game links can be imported, paste the PGN instead: {}",
+                text
+            )
+        });
+    }
+    // A PGN starts with its tags or the first move number, a FEN with the eighth rank
+    if text.starts_with('[') || text.starts_with("1.") {
+        Ok(ImportText::Pgn(text))
+    } else {
+        Ok(ImportText::Fen(text))
+    }
+}
+
+/// The game id of a link like lichess.org/abcdefgh, lichess.org/abcdefgh/black or the 12 characters link of a
+/// player.
+fn lichess_game_id(url: &str) -> Option<&str> {
+    let path = url.strip_prefix("www.").unwrap_or(url).strip_prefix("lichess.org/")?;
+    let segment = path.split(['/', '?', '#']).next()?;
+    let id = match segment.len() {
+        8 => segment,
+        12 => &segment[..8],
+        _ => return None,
+    };
+    id.chars().all(|c| c.is_ascii_alphanumeric()).then_some(id)
+}
+
+/// Reads a FEN or a PGN, the error names what the text was taken for.
+pub fn parse(text: &ImportText) -> Result<Game, String> {
+    match text {
+        ImportText::Fen(fen) => Game::new(fen).map_err(|e| format!("Neither a FEN nor a PGN: {}", e)),
+        ImportText::Pgn(pgn) => Game::from_pgn(pgn).map_err(|e| format!("Invalid PGN: {}", e)),
+        ImportText::LichessGame(id) => Err(format!("The Lichess game {} has to be fetched first", id)),
+    }
+}
+
+/// Downloads the PGN of a Lichess game.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn fetch_lichess_game(id: &str) -> Result<String, String> {
+    let url = format!("https://lichess.org/game/export/{}", id);
+    ureq::get(&url)
+        .set("Accept", "application/x-chess-pgn")
+        .timeout(std::time::Duration::from_secs(10))
+        .call()
+        .map_err(|e| format!("Could not fetch {}: {}", url, e))?
+        .into_string()
+        .map_err(|e| format!("Could not read {}: {}", url, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            detect(&format!(" {}\n", INITIAL_POSITION)),
+            Ok(ImportText::Fen(INITIAL_POSITION))
+        );
+        assert_eq!(
+            detect("[Event \"Casual\"]\n\n1. e4 e5 *"),
+            Ok(ImportText::Pgn("[Event \"Casual\"]\n\n1. e4 e5 *"))
+        );
+        assert_eq!(detect("1. d4 d5 2. c4"), Ok(ImportText::Pgn("1. d4 d5 2. c4")));
+        assert_eq!(
+            detect("https://lichess.org/q7ZvsdUF"),
+            Ok(ImportText::LichessGame("q7ZvsdUF"))
+        );
+        assert_eq!(
+            detect("https://lichess.org/q7ZvsdUFr4Xz/black#12"),
+            Ok(ImportText::LichessGame("q7ZvsdUF"))
+        );
+        assert!(detect("https://lichess.org/study/abc").is_err());
+        assert!(detect("https://www.chess.com/game/live/123456").is_err());
+        assert!(detect("  ").is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let game = parse(&ImportText::Pgn("1. e4 e5 2. Nf3 *")).unwrap();
+        assert_eq!(game.main_line().len(), 3);
+        assert_eq!(
+            parse(&ImportText::Fen(INITIAL_POSITION)).unwrap().start_fen,
+            INITIAL_POSITION
+        );
+        assert!(parse(&ImportText::Fen("hello"))
+            .unwrap_err()
+            .starts_with("Neither a FEN nor a PGN"));
+        assert!(parse(&ImportText::Pgn("1. e5 *"))
+            .unwrap_err()
+            .starts_with("Invalid PGN"));
+    }
+}
@@ -1,73 +1,154 @@
+use crate::chess_board::fen::FenMode;
+use crate::chess_board::game::{Game, GameMove};
+use crate::chess_board::pgn::move_to_san;
+use crate::chess_board::setup::HANDICAPS;
+use crate::chess_board::MoveLegality;
 use crate::chess_board::PieceType;
 use crate::chess_board::Square;
 use crate::chess_board::Square::Occupied;
 use crate::chess_board::{Color, Piece};
-use crate::engines::engine_alpha_beta::AlphaBetaEngine;
-use crate::engines::ChessEngine;
+use crate::config::{rgb, Config, EngineConfig};
+use crate::engines::analysis::{format_evaluation, Analysis, AnalysisDepth, AnalysisStep};
+use crate::engines::analysis_db::AnalysisDb;
+use crate::engines::commentary;
+use crate::engines::engine_alpha_beta::{accepts_draw, AlphaBetaEngine};
+#[cfg(target_arch = "wasm32")]
+use crate::engines::engine_alpha_beta::{IterativeSearch, SearchProgress};
+use crate::engines::eval_params::EvalParams;
+use crate::engines::options::{engine_options, set_engine_option, OptionType};
+use crate::engines::ponder::PonderStats;
+use crate::engines::skill::Skill;
+use crate::engines::transposition_table::{DEFAULT_HASH_MB, MAX_HASH_MB};
+use crate::engines::{allocate_move_time, ChessEngine, InfoEvent};
 use crate::ChessBoard;
 use crate::ChessField;
 use crate::MainWindow;
 use crate::Move;
 use crate::UiField;
-use lazy_static::lazy_static;
+use crate::UiOption;
 use slint::Image;
 use slint::Model;
 use slint::ModelRc;
 use slint::VecModel;
 use slint::{ComponentHandle, SharedString};
+use std::cell::Cell;
 use std::cell::RefCell;
-use std::collections::HashMap;
-use std::path::Path;
 use std::rc::Rc;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::atomic::{AtomicBool, AtomicI32};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
-// Use a single map for image paths instead of multiple constants
-lazy_static! {
-    static ref PIECE_IMAGES: HashMap<(Color, PieceType), &'static str> = {
-        let mut map = HashMap::new();
-        map.insert((Color::White, PieceType::Pawn), "ui/icons/Piece_White_Pawn.svg");
-        map.insert((Color::White, PieceType::Knight), "ui/icons/Piece_White_Knight.svg");
-        map.insert((Color::White, PieceType::Bishop), "ui/icons/Piece_White_Bishop.svg");
-        map.insert((Color::White, PieceType::Rook), "ui/icons/Piece_White_Rock.svg");
-        map.insert((Color::White, PieceType::Queen), "ui/icons/Piece_White_Queen.svg");
-        map.insert((Color::White, PieceType::King), "ui/icons/Piece_White_King.svg");
-        map.insert((Color::Black, PieceType::Pawn), "ui/icons/Piece_Black_Pawn.svg");
-        map.insert((Color::Black, PieceType::Knight), "ui/icons/Piece_Black_Knight.svg");
-        map.insert((Color::Black, PieceType::Bishop), "ui/icons/Piece_Black_Bishop.svg");
-        map.insert((Color::Black, PieceType::Rook), "ui/icons/Piece_Black_Rock.svg");
-        map.insert((Color::Black, PieceType::Queen), "ui/icons/Piece_Black_Queen.svg");
-        map.insert((Color::Black, PieceType::King), "ui/icons/Piece_Black_King.svg");
-        map
-    };
-}
+mod clock;
+mod editor;
+mod eval_graph;
+mod external;
+mod import;
+mod online;
+mod piece_set;
+mod session;
+mod watch;
+
+use clock::{format_clock, parse_time_control, ChessClock};
+use piece_set::PieceSet;
+
+/// Principal variations shown in the analysis mode.
+const ANALYSIS_LINES: usize = 3;
+
+/// How long the reason for a rejected move is shown.
+const TRANSIENT_MESSAGE_TIME: Duration = Duration::from_secs(3);
+
+/// How long the engine searches for a hint.
+const HINT_TIME: Duration = Duration::from_secs(1);
+
+/// Score from the side to move's point of view and principal variation, shared with the analysis thread.
+type BestLine = Arc<Mutex<Option<(i32, Vec<Move>)>>>;
 
 pub struct State {
     chess_board: RefCell<ChessBoard>,
     main_ui: MainWindow,
     selected_field: RefCell<Option<ChessField>>,
     active_move: RefCell<Option<Move>>,
+    // The moves played with the variations and comments of the analysis mode
+    game: RefCell<Game>,
+    // Bumped whenever the game is replaced, so replies of stale engine searches can be dropped
+    game_generation: Cell<i32>,
+    // Abort flag of the running engine search
+    engine_abort: RefCell<Option<Arc<AtomicBool>>>,
+    // Reply the engine expects from the user, compared against the actual move to track prediction accuracy
+    predicted_reply: RefCell<Option<Move>>,
+    ponder_stats: RefCell<PonderStats>,
+    // The engine searches its reply, or in an online game the opponent is to move
+    engine_thinking: Cell<bool>,
+    // Move queued by the user while the engine is thinking, played as soon as the engine replied
+    premove: RefCell<Option<Move>>,
+    piece_set: Cell<PieceSet>,
+    // Marks the pieces the opponent wins capturing them
+    show_threats: Cell<bool>,
+    // Hash, threads, strength and evaluation file of the engine playing against the user, changed on the settings page
+    engine_config: RefCell<EngineConfig>,
+    // The parameters of the evaluation file of the configuration
+    eval_params: RefCell<EvalParams>,
+    // None in untimed games
+    clock: RefCell<Option<ChessClock>>,
+    game_over: Cell<bool>,
+    // The engine analyzes every position instead of replying, the user moves both sides
+    analysis_mode: Cell<bool>,
+    // Score and principal variation of the last completed depth of the analysis of the current position
+    analysis_best_line: RefCell<BestLine>,
+    // Analyses of earlier sessions, an analysis resumes from the stored depth and stores the deeper ones
+    analysis_db: Arc<Mutex<AnalysisDb>>,
+    // Score of the engine's last search from its point of view, answers the draw offers of the user
+    engine_score: Cell<Option<i32>>,
+    // Bumped whenever the position changes, so hints for earlier positions can be dropped
+    hint_generation: Cell<i32>,
+    // Abort flag of the running hint search
+    hint_abort: RefCell<Option<Arc<AtomicBool>>>,
+    // Bumped with every move, so the comments of the coach on earlier moves can be dropped
+    coach_generation: Arc<AtomicI32>,
+    // The connection and the game of the user against another chic
+    online: RefCell<Option<online::Link>>,
+    // Bumped whenever a connection is opened or left, so the messages of an old connection can be dropped
+    online_generation: Cell<i32>,
+    // The engines of both sides while the user watches them play, the engine to move is thinking
+    watch: RefCell<Option<watch::Watch>>,
+    // A third-party UCI engine, which plays and analyzes instead of the built-in one while it is loaded
+    external: RefCell<Option<external::External>>,
+    // Evaluation in pawns from White's point of view by ply, 0 the start position of the game
+    evaluations: RefCell<Vec<Option<f32>>>,
+    // An earlier position of the game shown from the evaluation graph, the game goes on on the chess board
+    viewed_ply: Cell<Option<usize>>,
 }
 
 // Simplify the mapping process by extracting common logic
-fn square_to_ui_field(square: &Square) -> UiField {
+fn square_to_ui_field(square: &Square, piece_set: PieceSet) -> UiField {
     if let Square::Occupied(piece) = square {
-        if let Some(&piece_svg) = PIECE_IMAGES.get(&(piece.color, piece.kind)) {
-            return create_piece(piece_svg);
-        }
+        return create_piece(piece_set.image(piece.color, piece.kind));
     }
     UiField {
         image: Image::default(),
         highlighted_for_move: false,
+        premove: false,
+        last_move: false,
+        in_check: false,
+        hint: false,
+        threatened: false,
     }
 }
 
-fn create_piece(piece_svg: &str) -> UiField {
-    //use relative path instead absolute path
-    //let path_buf = Path::new(env!("CARGO_MANIFEST_DIR")).join(piece_svg);
-    let path_buf = Path::new(piece_svg);
-
+fn create_piece(image: Image) -> UiField {
     UiField {
-        image: Image::load_from_path(path_buf).unwrap(),
+        image,
         highlighted_for_move: false,
+        premove: false,
+        last_move: false,
+        in_check: false,
+        hint: false,
+        threatened: false,
     }
 }
 
@@ -75,16 +156,54 @@ fn index_to_row_col(index: usize) -> (usize, usize) {
     (index / 8, index % 8)
 }
 
-pub fn map_chessboard_to_ui(chess_board: &ChessBoard) -> ModelRc<UiField> {
+/// Maps the board to the UI fields, marking the squares of the last move, the king in check and, if asked for, the
+/// threatened pieces of both sides.
+pub fn map_chessboard_to_ui(
+    chess_board: &ChessBoard,
+    last_move: Option<Move>,
+    piece_set: PieceSet,
+    show_threats: bool,
+) -> ModelRc<UiField> {
+    // The king in check and the pieces giving check
+    let mut check_fields = Vec::new();
+    if let Some(king) = chess_board.find_king_position(chess_board.active_color) {
+        let opponent = match chess_board.active_color {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+        check_fields = chess_board.attackers_to(king, opponent);
+        if !check_fields.is_empty() {
+            check_fields.push(king);
+        }
+    }
+    let check_fields = &check_fields;
+    let mut threatened = Vec::new();
+    if show_threats {
+        let attack_map = chess_board.attack_map();
+        threatened.extend(attack_map.threatened_pieces(Color::White));
+        threatened.extend(attack_map.threatened_pieces(Color::Black));
+    }
+    let threatened = &threatened;
     let pieces: Vec<UiField> = chess_board
         .squares
         .iter()
-        .flat_map(|row| row.iter().map(square_to_ui_field))
+        .enumerate()
+        .flat_map(|(row, squares)| {
+            squares.iter().enumerate().map(move |(col, square)| {
+                let field = ChessField::new(row, col);
+                let mut ui_field = square_to_ui_field(square, piece_set);
+                ui_field.last_move = last_move.is_some_and(|mv| mv.from == field || mv.to == field);
+                ui_field.in_check = check_fields.contains(&field);
+                ui_field.threatened = threatened.contains(&field);
+                ui_field
+            })
+        })
         .collect();
     ModelRc::new(VecModel::from(pieces))
 }
 
-pub fn highlight_move(state: &Rc<State>, position: ChessField) {
+/// Marks the targets of the legal moves from the position, None only clears the marks.
+pub fn highlight_move(state: &Rc<State>, position: Option<ChessField>) {
     let pieces = state.main_ui.get_chess_fields();
     for index in 0..64 {
         if let Some(mut p) = pieces.row_data(index) {
@@ -93,14 +212,14 @@ pub fn highlight_move(state: &Rc<State>, position: ChessField) {
         }
     }
 
-    if position.row >= 8 || position.col >= 8 {
+    let Some(position) = position else {
         return;
-    }
+    };
 
     let moves = state.chess_board.borrow().generate_legal_moves();
     for m in moves {
-        if m.from.row == position.row && m.from.col == position.col {
-            let index = m.to.row * 8 + m.to.col;
+        if m.from == position {
+            let index = m.to.index();
             if let Some(mut p) = pieces.row_data(index) {
                 p.highlighted_for_move = true;
                 pieces.set_row_data(index, p);
@@ -109,59 +228,701 @@ pub fn highlight_move(state: &Rc<State>, position: ChessField) {
     }
 }
 
-pub fn setup_ui(fen: &str) {
+fn refresh_board(state: &Rc<State>) {
+    let game = state.game.borrow();
+    let viewed = state
+        .viewed_ply
+        .get()
+        .and_then(|ply| Some((game.position_after(ply).ok()?, ply)));
+    let (chess_board, last_move) = match viewed {
+        Some((board, ply)) => (board, ply.checked_sub(1).map(|ply| game.moves[ply].mv)),
+        None => (state.chess_board.borrow().clone(), game.moves.last().map(|m| m.mv)),
+    };
+    state.main_ui.set_chess_fields(map_chessboard_to_ui(
+        &chess_board,
+        last_move,
+        state.piece_set.get(),
+        state.show_threats.get(),
+    ));
+    state.main_ui.set_fen_text(SharedString::from(chess_board.to_fen()));
+    let movetext = game.movetext().unwrap_or_default();
+    // The result is only shown in saved games
+    let move_list = movetext
+        .strip_suffix(game.result.as_str())
+        .unwrap_or(&movetext)
+        .trim_end();
+    state.main_ui.set_move_list_text(SharedString::from(move_list));
+    drop(game);
+    eval_graph::show(state);
+}
+
+/// Enables claiming a draw when the user is to move and may claim one.
+fn update_draw_claim(state: &Rc<State>) {
+    let claimable =
+        !state.game_over.get() && !state.engine_thinking.get() && state.chess_board.borrow().claimable_draw().is_some();
+    state.main_ui.set_draw_claimable(claimable);
+}
+
+/// Ends the game, stopping the clock and the engine.
+fn end_game(state: &Rc<State>, message: &str) {
+    if let Some(clock) = state.clock.borrow_mut().as_mut() {
+        clock.stop(Instant::now());
+    }
+    state.game_over.set(true);
+    state.engine_thinking.set(false);
+    watch::finish(state);
+    *state.premove.borrow_mut() = None;
+    abort_engine_search(state);
+    update_draw_claim(state);
+    state.main_ui.set_status_message(SharedString::from(message));
+    session::save(state);
+}
+
+/// Claims the draw for the user, playing the move announced with the claim if the draw only occurs after it.
+fn claim_draw(state: &Rc<State>) {
+    if state.game_over.get() || state.engine_thinking.get() {
+        return;
+    }
+    let Some(claim) = state.chess_board.borrow().claimable_draw() else {
+        return;
+    };
+    let message = match claim.with_move {
+        Some(mv) => {
+            let san = move_to_san(&state.chess_board.borrow(), mv);
+            apply_move(state, mv);
+            format!("Draw claimed by {} with {}", claim.rule.description(), san)
+        }
+        None => format!("Draw claimed by {}", claim.rule.description()),
+    };
+    end_game(state, &message);
+}
+
+/// The engine accepts a draw offer if its last search scored the position below a draw.
+fn offer_draw(state: &Rc<State>) {
+    if state.game_over.get() || watch::is_watching(state) {
+        return;
+    }
+    if online::is_playing(state) {
+        online::offer_draw(state);
+        return;
+    }
+    if state.engine_score.get().is_some_and(accepts_draw) {
+        end_game(state, "The engine accepts the draw offer");
+    } else {
+        state
+            .main_ui
+            .set_status_message(SharedString::from("The engine declines the draw offer"));
+    }
+}
+
+/// Plays the engine's move, unless the engine rather claims a draw because it stands worse.
+fn play_engine_move(state: &Rc<State>, mv: Move, score: i32) {
+    state.engine_score.set(Some(score));
+    let white_score = match state.chess_board.borrow().active_color {
+        Color::White => score,
+        Color::Black => -score,
+    };
+    let ply = state.game.borrow().moves.len();
+    eval_graph::record(state, ply + 1, eval_graph::pawns(white_score));
+    let claim = state
+        .chess_board
+        .borrow()
+        .claimable_draw()
+        .filter(|_| accepts_draw(score));
+    let Some(claim) = claim else {
+        apply_move(state, mv);
+        return;
+    };
+    let message = match claim.with_move {
+        Some(mv) => {
+            let san = move_to_san(&state.chess_board.borrow(), mv);
+            apply_move(state, mv);
+            format!("The engine claims a draw by {} with {}", claim.rule.description(), san)
+        }
+        None => format!("The engine claims a draw by {}", claim.rule.description()),
+    };
+    end_game(state, &message);
+}
+
+fn apply_move(state: &Rc<State>, mv: Move) {
+    cancel_hint(state);
+    state.viewed_ply.set(None);
+    run_coach(state, state.chess_board.borrow().clone(), mv);
+    let mover = state.chess_board.borrow().active_color;
+    state.chess_board.borrow_mut().make_move(mv);
+    state.game.borrow_mut().push_move(mv);
+    if let Some(clock) = state.clock.borrow_mut().as_mut().filter(|c| c.is_running()) {
+        if state.chess_board.borrow().generate_legal_moves().is_empty() {
+            clock.stop(Instant::now());
+        } else {
+            clock.press(mover, Instant::now());
+        }
+    }
+    refresh_board(state);
+    let automatic_draw = state.chess_board.borrow().automatic_draw();
+    match automatic_draw {
+        Some(rule) => end_game(state, &format!("Draw by {}", rule.description())),
+        None => update_draw_claim(state),
+    }
+    session::save(state);
+}
+
+/// Starts a timed game from the current position with the side to move on the clock.
+fn start_clock(state: &Rc<State>, base_minutes: &str, increment_seconds: &str) -> Result<(), String> {
+    let (base_time, increment) = parse_time_control(base_minutes, increment_seconds)?;
+    let mut clock = ChessClock::new(base_time, increment);
+    clock.start(state.chess_board.borrow().active_color, Instant::now());
+    *state.clock.borrow_mut() = Some(clock);
+    state.game_over.set(false);
+    update_clock(state);
+    session::save(state);
+    Ok(())
+}
+
+/// Refreshes the clock display and ends the game when a flag falls.
+fn update_clock(state: &Rc<State>) {
+    let now = Instant::now();
+    let flagged = match state.clock.borrow().as_ref() {
+        Some(clock) => {
+            state
+                .main_ui
+                .set_white_clock_text(SharedString::from(format_clock(clock.remaining(Color::White, now))));
+            state
+                .main_ui
+                .set_black_clock_text(SharedString::from(format_clock(clock.remaining(Color::Black, now))));
+            clock.flagged(now).filter(|_| clock.is_running())
+        }
+        None => return,
+    };
+    if let Some(color) = flagged {
+        let loser = match color {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        end_game(state, &format!("{} lost on time", loser));
+    }
+}
+
+/// Stops an engine that is still thinking and drops its reply.
+fn abort_engine_search(state: &Rc<State>) {
+    state.game_generation.set(state.game_generation.get() + 1);
+    if let Some(aborted) = state.engine_abort.borrow_mut().take() {
+        aborted.store(true, Relaxed);
+    }
+    external::stop(state);
+    cancel_hint(state);
+}
+
+/// Searches the position of the user briefly and marks the suggested move on the board without playing it.
+fn request_hint(state: &Rc<State>) {
+    if state.game_over.get()
+        || state.engine_thinking.get()
+        || state.main_ui.get_edit_mode()
+        || online::is_playing(state)
+        || watch::is_watching(state)
+    {
+        return;
+    }
+    cancel_hint(state);
+    let analysis = Analysis::with_time_limit(state.chess_board.borrow().clone(), 1, HINT_TIME);
+    *state.hint_abort.borrow_mut() = Some(analysis.abort_channel());
+    state
+        .main_ui
+        .set_status_message(SharedString::from("Searching for a hint"));
+    run_hint(state, analysis);
+}
+
+/// Stops a running hint search and drops its move.
+fn cancel_hint(state: &Rc<State>) {
+    state.hint_generation.set(state.hint_generation.get() + 1);
+    if let Some(aborted) = state.hint_abort.borrow_mut().take() {
+        aborted.store(true, Relaxed);
+    }
+}
+
+/// Marks the squares of the hint, the marks disappear with the next move.
+fn show_hint(state: &Rc<State>, mv: Move) {
+    let pieces = state.main_ui.get_chess_fields();
+    for index in 0..64 {
+        if let Some(mut p) = pieces.row_data(index) {
+            let (row, col) = index_to_row_col(index);
+            let field = ChessField::new(row, col);
+            p.hint = field == mv.from || field == mv.to;
+            pieces.set_row_data(index, p);
+        }
+    }
+    let san = move_to_san(&state.chess_board.borrow(), mv);
+    state
+        .main_ui
+        .set_status_message(SharedString::from(format!("Hint: {}", san)));
+}
+
+/// Shows what the coach says about the move in the position once the searches behind it are done.
+#[cfg(not(target_arch = "wasm32"))]
+fn run_coach(state: &Rc<State>, chess_board: ChessBoard, mv: Move) {
+    let ui_weak = state.main_ui.as_weak();
+    let params = state.eval_params.borrow().clone();
+    let coach_generation = state.coach_generation.clone();
+    let generation = coach_generation.fetch_add(1, Relaxed) + 1;
+    std::thread::spawn(move || {
+        let text = commentary::comment(&chess_board, mv, &params).messages().join(" ");
+        let _ = slint::invoke_from_event_loop(move || {
+            if coach_generation.load(Relaxed) == generation {
+                ui_weak.unwrap().set_coach_text(SharedString::from(text));
+            }
+        });
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn run_coach(state: &Rc<State>, chess_board: ChessBoard, mv: Move) {
+    let state_weak = Rc::downgrade(state);
+    let generation = state.coach_generation.fetch_add(1, Relaxed) + 1;
+    slint::Timer::single_shot(Duration::ZERO, move || {
+        let Some(state) = state_weak.upgrade() else {
+            return;
+        };
+        if state.coach_generation.load(Relaxed) == generation {
+            let commentary = commentary::comment(&chess_board, mv, &state.eval_params.borrow());
+            state
+                .main_ui
+                .set_coach_text(SharedString::from(commentary.messages().join(" ")));
+        }
+    });
+}
+
+/// Time the engine may think: a share of its remaining clock in timed games, otherwise the given default.
+fn engine_move_time(state: &Rc<State>, default: Duration) -> Duration {
+    let color = state.chess_board.borrow().active_color;
+    match state.clock.borrow().as_ref() {
+        Some(clock) => allocate_move_time(clock.remaining(color, Instant::now()), clock.increment(), 30),
+        None => default,
+    }
+}
+
+fn index_to_field(index: i32) -> ChessField {
+    usize::try_from(index)
+        .ok()
+        .and_then(ChessField::from_index)
+        .expect("The board has 64 fields")
+}
+
+fn select_field(state: &Rc<State>, field: ChessField) {
+    *state.selected_field.borrow_mut() = Some(field);
+    if state.engine_thinking.get() {
+        highlight_move(state, None);
+        show_premove(state);
+    } else {
+        highlight_move(state, Some(field));
+    }
+}
+
+/// Marks the queued premove, or the field selected for one, on the board.
+fn show_premove(state: &Rc<State>) {
+    let pieces = state.main_ui.get_chess_fields();
+    let marked: Vec<ChessField> = match *state.premove.borrow() {
+        Some(mv) => vec![mv.from, mv.to],
+        None if state.engine_thinking.get() => state.selected_field.borrow().iter().copied().collect(),
+        None => Vec::new(),
+    };
+    for index in 0..64 {
+        if let Some(mut p) = pieces.row_data(index) {
+            let (row, col) = index_to_row_col(index);
+            p.premove = marked.contains(&ChessField::new(row, col));
+            pieces.set_row_data(index, p);
+        }
+    }
+}
+
+/// Queues a move of the user's pieces while the engine is thinking. It is only checked for legality when it
+/// is played.
+fn queue_premove(state: &Rc<State>, source: ChessField, target: ChessField) -> bool {
+    let chess_board = state.chess_board.borrow().clone();
+    match chess_board.squares[source.row()][source.col()] {
+        Occupied(piece) if piece.color != chess_board.active_color && source != target => {
+            *state.premove.borrow_mut() = Some(Move::new(source.row(), source.col(), target.row(), target.col()));
+            *state.selected_field.borrow_mut() = None;
+            show_premove(state);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Plays the queued premove after the engine replied, promoting to a queen. Illegal premoves are dropped.
+fn play_premove(state: &Rc<State>) {
+    let Some(premove) = state.premove.borrow_mut().take() else {
+        return;
+    };
+    let legal_move = state
+        .chess_board
+        .borrow()
+        .generate_legal_moves()
+        .into_iter()
+        .filter(|m| m.from == premove.from && m.to == premove.to)
+        .max_by_key(|m| m.promotion == Some(PieceType::Queen));
+    match legal_move {
+        Some(mv) => play_user_move(state, mv),
+        None => {
+            show_premove(state);
+            state.main_ui.set_status_message(SharedString::from(format!(
+                "Premove {} is not legal",
+                premove.as_algebraic()
+            )));
+        }
+    }
+}
+
+fn cancel_premove(state: &Rc<State>) {
+    *state.premove.borrow_mut() = None;
+    *state.selected_field.borrow_mut() = None;
+    show_premove(state);
+}
+
+/// Moves the piece on source to target if that is legal, asking for the piece first on promotions.
+/// Returns false if the move is illegal.
+fn try_user_move(state: &Rc<State>, source: ChessField, target: ChessField) -> bool {
+    if state.game_over.get() || watch::is_watching(state) || state.viewed_ply.get().is_some() {
+        return false;
+    }
+    if state.engine_thinking.get() {
+        return queue_premove(state, source, target);
+    }
+    let chess_board = state.chess_board.borrow().clone();
+    if !chess_board
+        .generate_legal_moves()
+        .iter()
+        .any(|m| m.from == source && m.to == target)
+    {
+        explain_illegal_move(state, &chess_board, source, target);
+        return false;
+    }
+    *state.selected_field.borrow_mut() = None;
+    let mv = Move::new(source.row(), source.col(), target.row(), target.col());
+
+    if let Occupied(piece) = chess_board.squares[source.row()][source.col()] {
+        if is_promotion(target, piece) {
+            set_piece_color_of_the_promotion_dialog(&state.main_ui, piece.color, state.piece_set.get());
+            state.main_ui.set_promotion_dialog_visible(true);
+            state.active_move.borrow_mut().replace(mv);
+            return true;
+        }
+    }
+
+    play_user_move(state, mv);
+    true
+}
+
+/// Adds the line the analysis preferred as a variation to the move just played, commented with its evaluation.
+fn annotate_deviation(state: &Rc<State>, score: i32, pv: Vec<Move>) {
+    let mut game = state.game.borrow_mut();
+    let ply = game.moves.len() - 1;
+    let Ok(board) = game.position_after(ply) else {
+        return;
+    };
+    let white_score = if board.active_color == Color::White {
+        score
+    } else {
+        -score
+    };
+    let mut line: Vec<GameMove> = pv.into_iter().map(GameMove::new).collect();
+    line[0].comment = Some(format_evaluation(white_score));
+    game.add_variation(ply, line);
+    drop(game);
+    refresh_board(state);
+}
+
+/// Tells the user why a move was rejected. Clicks on empty or own squares only change the selection and are not
+/// explained.
+fn explain_illegal_move(state: &Rc<State>, chess_board: &ChessBoard, source: ChessField, target: ChessField) {
+    let mut mv = Move::new(source.row(), source.col(), target.row(), target.col());
+    if let Occupied(piece) = chess_board.squares[source.row()][source.col()] {
+        if is_promotion(target, piece) {
+            mv = mv.with_promotion(PieceType::Queen);
+        }
+    }
+    match chess_board.classify_move(mv) {
+        MoveLegality::Legal | MoveLegality::NoPiece | MoveLegality::CapturesOwnPiece => {}
+        legality => show_transient_message(state, legality.description()),
+    }
+}
+
+/// Shows a status message that disappears after a while unless it was replaced meanwhile.
+fn show_transient_message(state: &Rc<State>, message: &'static str) {
+    state.main_ui.set_status_message(SharedString::from(message));
+    let state_weak = Rc::downgrade(state);
+    slint::Timer::single_shot(TRANSIENT_MESSAGE_TIME, move || {
+        if let Some(state) = state_weak.upgrade() {
+            if state.main_ui.get_status_message() == message {
+                state.main_ui.set_status_message(SharedString::new());
+            }
+        }
+    });
+}
+
+/// Plays a move of the user, counts whether the engine predicted it and lets the engine reply.
+fn play_user_move(state: &Rc<State>, mv: Move) {
+    if let Some(predicted) = state.predicted_reply.borrow_mut().take() {
+        let mut stats = state.ponder_stats.borrow_mut();
+        stats.record_reply(predicted, mv);
+        state
+            .main_ui
+            .set_prediction_text(SharedString::from(format!("Reply prediction: {}", stats.summary())));
+    }
+    let best_line = state.analysis_best_line.borrow().lock().unwrap().take();
+    apply_move(state, mv);
+    if online::is_playing(state) {
+        online::send_move(state, mv);
+        state.engine_thinking.set(!state.game_over.get());
+        update_draw_claim(state);
+        return;
+    }
+    if state.analysis_mode.get() {
+        if let Some((score, pv)) = best_line.filter(|(_, pv)| pv.first() != Some(&mv)) {
+            annotate_deviation(state, score, pv);
+        }
+    }
+    if state.game_over.get() {
+        return;
+    }
+    if state.analysis_mode.get() {
+        start_analysis(state);
+        return;
+    }
+    state.engine_thinking.set(true);
+    update_draw_claim(state);
+    session::save(state);
+    make_engine_move(state);
+}
+
+/// Replaces the current game, the board shows the end of its main line.
+fn set_game(state: &Rc<State>, game: Game) -> Result<(), String> {
+    ChessBoard::from_fen_with_mode(&game.start_fen, FenMode::Strict)?;
+    if online::is_playing(state) {
+        online::leave(state);
+    }
+    watch::finish(state);
+    *state.chess_board.borrow_mut() = game.final_position()?;
+    *state.evaluations.borrow_mut() = eval_graph::from_game(&game);
+    state.viewed_ply.set(None);
+    *state.game.borrow_mut() = game;
+    abort_engine_search(state);
+    *state.selected_field.borrow_mut() = None;
+    *state.active_move.borrow_mut() = None;
+    *state.predicted_reply.borrow_mut() = None;
+    *state.premove.borrow_mut() = None;
+    state.engine_thinking.set(false);
+    *state.clock.borrow_mut() = None;
+    state.game_over.set(false);
+    state.engine_score.set(None);
+    state.main_ui.set_edit_mode(false);
+    state.main_ui.set_white_clock_text(SharedString::new());
+    state.main_ui.set_black_clock_text(SharedString::new());
+    state.main_ui.set_promotion_dialog_visible(false);
+    state.main_ui.set_evaluation(0.0);
+    state.main_ui.set_analysis_text(SharedString::new());
+    state.coach_generation.fetch_add(1, Relaxed);
+    state.main_ui.set_coach_text(SharedString::new());
+    refresh_board(state);
+    update_draw_claim(state);
+    if state.analysis_mode.get() {
+        start_analysis(state);
+    }
+    session::save(state);
+    Ok(())
+}
+
+fn save_pgn(state: &Rc<State>, path: &str) -> Result<(), String> {
+    let mut game = state.game.borrow().clone();
+    game.update_result()?;
+    std::fs::write(path, game.to_pgn()?).map_err(|e| format!("Could not write {}: {}", path, e))
+}
+
+fn load_pgn(state: &Rc<State>, path: &str) -> Result<(), String> {
+    let pgn = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    set_game(state, Game::from_pgn(&pgn)?)
+}
+
+/// Loads a pasted FEN or PGN, a Lichess game link is fetched first and imported as PGN when it arrived.
+fn import_game(state: &Rc<State>, text: &str) -> Result<String, String> {
+    let text = import::detect(text)?;
+    if let import::ImportText::LichessGame(id) = text {
+        fetch_lichess_game(state, id)?;
+        return Ok(format!("Fetching the Lichess game {}", id));
+    }
+    set_game(state, import::parse(&text)?)?;
+    Ok(String::from(match text {
+        import::ImportText::Fen(_) => "Position imported",
+        _ => "Game imported",
+    }))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn fetch_lichess_game(state: &Rc<State>, id: &str) -> Result<(), String> {
+    let ui_weak = state.main_ui.as_weak();
+    let id = id.to_string();
+    std::thread::spawn(move || {
+        let result = import::fetch_lichess_game(&id);
+        let _ = slint::invoke_from_event_loop(move || {
+            let ui = ui_weak.unwrap();
+            match result {
+                Ok(pgn) => ui.invoke_import_game(SharedString::from(pgn)),
+                Err(e) => ui.set_status_message(SharedString::from(e)),
+            }
+        });
+    });
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn fetch_lichess_game(_state: &Rc<State>, id: &str) -> Result<(), String> {
+    Err(format!(
+        "Games cannot be fetched in the browser, paste the PGN of the Lichess game {}",
+        id
+    ))
+}
+
+/// Shows the engine options with the values of the configuration on the settings page.
+fn update_engine_options(state: &State) {
+    let options: Vec<UiOption> = engine_options(&state.engine_config.borrow())
+        .into_iter()
+        .map(|option| {
+            let value = SharedString::from(option.default_value());
+            let (kind, minimum, maximum, values) = match option.option_type {
+                OptionType::Check { .. } => (0, 0, 0, Vec::new()),
+                OptionType::Spin { min, max, .. } => (1, min, max, Vec::new()),
+                OptionType::Combo { values, .. } => (2, 0, 0, values.into_iter().map(SharedString::from).collect()),
+                OptionType::String { .. } => (3, 0, 0, Vec::new()),
+            };
+            UiOption {
+                name: SharedString::from(option.name),
+                kind,
+                value,
+                minimum,
+                maximum,
+                values: ModelRc::new(VecModel::from(values)),
+            }
+        })
+        .collect();
+    state.main_ui.set_engine_options(ModelRc::new(VecModel::from(options)));
+}
+
+/// Changes an option of the engine, the next search uses it. An evaluation file is loaded right away, so an invalid
+/// one is reported and the previous parameters stay.
+fn change_engine_option(state: &State, name: &str, value: &str) -> Result<String, String> {
+    let mut config = state.engine_config.borrow().clone();
+    let value = set_engine_option(&mut config, name, value)?;
+    if config.eval_file != state.engine_config.borrow().eval_file {
+        *state.eval_params.borrow_mut() = match &config.eval_file {
+            Some(path) => EvalParams::from_file(path)?,
+            None => EvalParams::default(),
+        };
+    }
+    *state.engine_config.borrow_mut() = config;
+    Ok(format!("{} set to {}", name, value))
+}
+
+pub fn setup_ui(fen: &str, config: &Config) {
+    let piece_set = PieceSet::ALL
+        .into_iter()
+        .find(|piece_set| piece_set.name().eq_ignore_ascii_case(&config.ui.piece_set));
     let state = Rc::new(State {
         chess_board: RefCell::new(ChessBoard::from_fen(fen).expect("Invalid FEN string")),
         main_ui: MainWindow::new().unwrap(),
         selected_field: RefCell::new(None),
         active_move: RefCell::new(None),
+        game: RefCell::new(Game::new(fen).expect("Invalid FEN string")),
+        game_generation: Cell::new(0),
+        engine_abort: RefCell::new(None),
+        predicted_reply: RefCell::new(None),
+        ponder_stats: RefCell::new(PonderStats::new()),
+        engine_thinking: Cell::new(false),
+        premove: RefCell::new(None),
+        piece_set: Cell::new(piece_set.unwrap_or(PieceSet::Classic)),
+        show_threats: Cell::new(false),
+        engine_config: RefCell::new(config.engine.clone()),
+        eval_params: RefCell::new(EvalParams::default()),
+        clock: RefCell::new(None),
+        game_over: Cell::new(false),
+        analysis_mode: Cell::new(false),
+        analysis_best_line: RefCell::new(Arc::new(Mutex::new(None))),
+        analysis_db: Arc::new(Mutex::new(open_analysis_db())),
+        engine_score: Cell::new(None),
+        hint_generation: Cell::new(0),
+        hint_abort: RefCell::new(None),
+        coach_generation: Arc::new(AtomicI32::new(0)),
+        online: RefCell::new(None),
+        online_generation: Cell::new(0),
+        watch: RefCell::new(None),
+        external: RefCell::new(None),
+        evaluations: RefCell::new(vec![None]),
+        viewed_ply: Cell::new(None),
     });
     let state_weak = Rc::downgrade(&state);
 
     state.main_ui.on_clicked(move |index| {
         if let Some(state) = state_weak.upgrade() {
-            let (row, col) = index_to_row_col(index.try_into().unwrap());
-            let clicked_field = ChessField::new(row, col);
-            let mut selected_field = state.selected_field.borrow_mut();
+            let clicked_field = index_to_field(index);
+            if state.main_ui.get_edit_mode() {
+                editor::edit_field(&state, clicked_field);
+                return;
+            }
+            if eval_graph::stop_viewing(&state) {
+                return;
+            }
+            let selected_field = *state.selected_field.borrow();
 
-            match *selected_field {
-                None => {
-                    *selected_field = Some(clicked_field);
-                    highlight_move(&state, clicked_field);
-                }
+            match selected_field {
+                None => select_field(&state, clicked_field),
                 Some(source) => {
-                    let chess_board = state.chess_board.borrow().clone();
-                    let moves = chess_board
-                        .generate_legal_moves()
-                        .into_iter()
-                        .filter(|&m| m.from.row == source.row && m.from.col == source.col)
-                        .collect::<Vec<_>>();
-                    if moves.iter().any(|m| m.to == clicked_field) {
-                        let mv = Move::new(source.row, source.col, clicked_field.row, clicked_field.col);
-
-                        if let Occupied(piece) = chess_board.squares[source.row][source.col] {
-                            if is_promotion(clicked_field, piece) {
-                                set_piece_color_of_the_promotion_dialog(&state.main_ui, piece.color);
-                                state.main_ui.set_promotion_dialog_visible(true);
-                                state.active_move.borrow_mut().replace(mv);
-                                return;
-                            }
-                        }
-
-                        state.chess_board.borrow_mut().make_move(mv);
-                        state
-                            .main_ui
-                            .set_chess_fields(map_chessboard_to_ui(&state.chess_board.borrow()));
-                        make_engine_move(&state);
-                    } else {
-                        *selected_field = Some(clicked_field);
-                        highlight_move(&state, clicked_field);
+                    if !try_user_move(&state, source, clicked_field) {
+                        select_field(&state, clicked_field);
                     }
                 }
             }
         }
     });
 
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_drag_started(move |index| {
+        if let Some(state) = state_weak.upgrade() {
+            if state.main_ui.get_edit_mode() || state.viewed_ply.get().is_some() {
+                return;
+            }
+            select_field(&state, index_to_field(index));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_piece_dropped(move |from_index, to_index| {
+        if let Some(state) = state_weak.upgrade() {
+            let source = index_to_field(from_index);
+            if state.main_ui.get_edit_mode() {
+                if to_index != from_index {
+                    editor::move_piece(&state, source, (to_index >= 0).then(|| index_to_field(to_index)));
+                }
+                return;
+            }
+            if to_index == from_index {
+                // Dropped on its own field, keep the piece selected like a click
+                return;
+            }
+            if to_index < 0 || !try_user_move(&state, source, index_to_field(to_index)) {
+                // Illegal drop, the piece snaps back to its field
+                *state.selected_field.borrow_mut() = None;
+                highlight_move(&state, None);
+                show_premove(&state);
+            }
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_cancel_premove(move || {
+        if let Some(state) = state_weak.upgrade() {
+            cancel_premove(&state);
+        }
+    });
+
     let state_weak = Rc::downgrade(&state);
     state.main_ui.on_promotion_selected(move |choice_index| {
         if let Some(state) = state_weak.upgrade() {
@@ -174,61 +935,687 @@ pub fn setup_ui(fen: &str) {
             state.main_ui.set_promotion_dialog_visible(false);
             if let Some(mv) = *state.active_move.borrow_mut() {
                 let mv = mv.with_promotion(promoted_piece);
-                state.chess_board.borrow_mut().make_move(mv);
-                state
-                    .main_ui
-                    .set_chess_fields(map_chessboard_to_ui(&state.chess_board.borrow()));
-                make_engine_move(&state);
+                play_user_move(&state, mv);
             }
         }
     });
 
     let state_weak = Rc::downgrade(&state);
-    state.main_ui.on_make_move(move |mv_algebraic: SharedString| {
+    state.main_ui.on_make_move(
+        move |mv_algebraic: SharedString, ponder_algebraic: SharedString, generation: i32, score: i32| {
+            if let Some(state) = state_weak.upgrade() {
+                // The game may have been replaced while the engine was thinking
+                if generation != state.game_generation.get() {
+                    return;
+                }
+                state.engine_thinking.set(false);
+                play_engine_move(&state, Move::from_algebraic(mv_algebraic.as_str()), score);
+                if state.game_over.get() {
+                    return;
+                }
+                if watch::is_watching(&state) {
+                    watch::next_move(&state);
+                    return;
+                }
+                *state.predicted_reply.borrow_mut() = Some(ponder_algebraic.as_str())
+                    .filter(|m| !m.is_empty())
+                    .map(Move::from_algebraic);
+                play_premove(&state);
+            }
+        },
+    );
+
+    let state_weak = Rc::downgrade(&state);
+    state
+        .main_ui
+        .on_engine_info(move |generation: i32, evaluation: f32, analysis: SharedString| {
+            if let Some(state) = state_weak.upgrade() {
+                if generation != state.game_generation.get() {
+                    return;
+                }
+                state.main_ui.set_evaluation(evaluation);
+                state.main_ui.set_analysis_text(analysis);
+                let ply = state.game.borrow().moves.len();
+                eval_graph::record(&state, ply, evaluation);
+                eval_graph::show(&state);
+            }
+        });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_eval_graph_clicked(move |fraction: f32| {
         if let Some(state) = state_weak.upgrade() {
-            state
-                .chess_board
-                .borrow_mut()
-                .make_move(Move::from_algebraic(mv_algebraic.as_str()));
+            eval_graph::clicked(&state, fraction);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_analysis_mode_toggled(move |enabled: bool| {
+        if let Some(state) = state_weak.upgrade() {
+            if enabled {
+                watch::stop(&state);
+            }
+            state.analysis_mode.set(enabled);
+            session::save(&state);
+            if state.main_ui.get_edit_mode() {
+                // The analysis starts with the edited position
+                return;
+            }
+            if enabled {
+                start_analysis(&state);
+            } else {
+                abort_engine_search(&state);
+                state.engine_thinking.set(false);
+            }
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_claim_draw(move || {
+        if let Some(state) = state_weak.upgrade() {
+            claim_draw(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_offer_draw(move || {
+        if let Some(state) = state_weak.upgrade() {
+            offer_draw(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_hint(move || {
+        if let Some(state) = state_weak.upgrade() {
+            request_hint(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state
+        .main_ui
+        .on_show_hint(move |generation: i32, mv_algebraic: SharedString| {
+            if let Some(state) = state_weak.upgrade() {
+                // The position may have changed while the engine was searching
+                if generation != state.hint_generation.get() {
+                    return;
+                }
+                state.hint_abort.borrow_mut().take();
+                show_hint(&state, Move::from_algebraic(mv_algebraic.as_str()));
+            }
+        });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_edit_mode_toggled(move |enabled: bool| {
+        if let Some(state) = state_weak.upgrade() {
+            if enabled {
+                editor::start_editing(&state);
+            } else if let Err(e) = editor::finish_editing(&state) {
+                // Invalid positions cannot be played, the editor stays open
+                state.main_ui.set_edit_mode(true);
+                state.main_ui.set_status_message(SharedString::from(e));
+            }
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_editor_piece_selected(move |index| {
+        if let Some(state) = state_weak.upgrade() {
+            editor::select_piece(&state, index);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_editor_side_selected(move |index| {
+        if let Some(state) = state_weak.upgrade() {
+            editor::set_side_to_move(&state, index);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_editor_castling_toggled(move |index, allowed| {
+        if let Some(state) = state_weak.upgrade() {
+            editor::set_castling(&state, index, allowed);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_editor_clear(move || {
+        if let Some(state) = state_weak.upgrade() {
+            editor::clear_board(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_editor_initial_position(move || {
+        if let Some(state) = state_weak.upgrade() {
+            editor::set_initial_position(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_editor_done(move || {
+        if let Some(state) = state_weak.upgrade() {
+            match editor::finish_editing(&state) {
+                Ok(()) => state.main_ui.set_status_message(SharedString::from("Position set")),
+                Err(e) => state.main_ui.set_status_message(SharedString::from(e)),
+            }
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_reset_fen(move || {
+        if let Some(state) = state_weak.upgrade() {
+            refresh_board(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_load_fen(move |fen: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = match Game::new(fen.trim()).and_then(|game| set_game(&state, game)) {
+                Ok(()) => String::from("Position set"),
+                Err(e) => e,
+            };
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_save_pgn(move |path: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = match save_pgn(&state, path.as_str()) {
+                Ok(()) => format!("Game saved to {}", path),
+                Err(e) => e,
+            };
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_load_pgn(move |path: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = match load_pgn(&state, path.as_str()) {
+                Ok(()) => format!("Game loaded from {}", path),
+                Err(e) => e,
+            };
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state
+        .main_ui
+        .on_engine_option_changed(move |name: SharedString, value: SharedString| {
+            if let Some(state) = state_weak.upgrade() {
+                let message = change_engine_option(&state, &name, &value).unwrap_or_else(|e| e);
+                state.main_ui.set_status_message(SharedString::from(message));
+                // Clamped values and a strength limit turned on by an Elo are shown as they are used
+                update_engine_options(&state);
+                session::save(&state);
+            }
+        });
+    if let Some(path) = &config.engine.eval_file {
+        match EvalParams::from_file(path) {
+            Ok(params) => *state.eval_params.borrow_mut() = params,
+            Err(e) => {
+                state.engine_config.borrow_mut().eval_file = None;
+                state.main_ui.set_status_message(SharedString::from(e));
+            }
+        }
+    }
+    update_engine_options(&state);
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_import_game(move |text: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = import_game(&state, text.as_str()).unwrap_or_else(|e| e);
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_new_game(move |index| {
+        if let Some(state) = state_weak.upgrade() {
+            let handicap = HANDICAPS[index as usize];
+            let message = match Game::new(&handicap.start_position().to_fen()).and_then(|game| set_game(&state, game)) {
+                Ok(()) => format!("New game, {}", handicap.name().to_lowercase()),
+                Err(e) => e,
+            };
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+    let handicap_names: Vec<SharedString> = HANDICAPS.iter().map(|h| SharedString::from(h.name())).collect();
+    state
+        .main_ui
+        .set_handicaps(ModelRc::new(VecModel::from(handicap_names)));
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_piece_set_selected(move |index| {
+        if let Some(state) = state_weak.upgrade() {
+            state.piece_set.set(PieceSet::ALL[index as usize]);
+            session::save(&state);
+            refresh_board(&state);
+            editor::update_editor(&state);
+            show_premove(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_show_threats_toggled(move |enabled| {
+        if let Some(state) = state_weak.upgrade() {
+            state.show_threats.set(enabled);
+            session::save(&state);
+            refresh_board(&state);
+            show_premove(&state);
+        }
+    });
+
+    let piece_set_names: Vec<SharedString> = PieceSet::ALL.iter().map(|p| SharedString::from(p.name())).collect();
+    state
+        .main_ui
+        .set_piece_sets(ModelRc::new(VecModel::from(piece_set_names)));
+    let piece_set_index = PieceSet::ALL
+        .iter()
+        .position(|piece_set| *piece_set == state.piece_set.get());
+    state.main_ui.set_piece_set_index(piece_set_index.unwrap_or(0) as i32);
+    if piece_set.is_none() {
+        let message = format!("Unknown piece set {}", config.ui.piece_set);
+        state.main_ui.set_status_message(SharedString::from(message));
+    }
+    // The configuration was validated when it was loaded
+    if let (Ok(light), Ok(dark)) = (rgb(&config.ui.light_squares), rgb(&config.ui.dark_squares)) {
+        state
+            .main_ui
+            .set_light_square_color(slint::Color::from_rgb_u8(light[0], light[1], light[2]));
+        state
+            .main_ui
+            .set_dark_square_color(slint::Color::from_rgb_u8(dark[0], dark[1], dark[2]));
+    }
+    state
+        .main_ui
+        .set_clock_minutes(SharedString::from(config.ui.clock_minutes.to_string()));
+    state
+        .main_ui
+        .set_clock_increment(SharedString::from(config.ui.clock_increment.to_string()));
+    let state_weak = Rc::downgrade(&state);
+    state
+        .main_ui
+        .on_start_clock(move |base_minutes: SharedString, increment_seconds: SharedString| {
+            if let Some(state) = state_weak.upgrade() {
+                let message = match start_clock(&state, &base_minutes, &increment_seconds) {
+                    Ok(()) => String::from("Clock started"),
+                    Err(e) => e,
+                };
+                state.main_ui.set_status_message(SharedString::from(message));
+            }
+        });
+
+    let state_weak = Rc::downgrade(&state);
+    let clock_timer = slint::Timer::default();
+    clock_timer.start(slint::TimerMode::Repeated, Duration::from_millis(100), move || {
+        if let Some(state) = state_weak.upgrade() {
+            update_clock(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_host_online(move |address: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = online::host(&state, &address).unwrap_or_else(|e| e);
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_join_online(move |address: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = online::join(&state, &address).unwrap_or_else(|e| e);
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_leave_online(move || {
+        if let Some(state) = state_weak.upgrade() {
+            online::leave(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_resign(move || {
+        if let Some(state) = state_weak.upgrade() {
+            online::resign(&state);
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_online_opened(move |generation| {
+        if let Some(state) = state_weak.upgrade() {
+            if generation == state.online_generation.get() {
+                online::opened(&state);
+            }
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_online_message(move |generation, json: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            if generation == state.online_generation.get() {
+                online::received(&state, &json);
+            }
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_online_closed(move |generation, reason: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            if generation == state.online_generation.get() {
+                online::closed(&state, &reason);
+            }
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_start_watching(move |white, black| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = watch::start(&state, white as usize, black as usize).unwrap_or_else(|e| e);
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_stop_watching(move || {
+        if let Some(state) = state_weak.upgrade() {
+            watch::stop(&state);
+        }
+    });
+    update_watch_engines(&state, None);
+
+    let state_weak = Rc::downgrade(&state);
+    let external_options = config.ui.external_options.clone();
+    state.main_ui.on_load_external_engine(move |command: SharedString| {
+        if let Some(state) = state_weak.upgrade() {
+            let message = external::load(&state, &command, &external_options).unwrap_or_else(|e| e);
+            state.main_ui.set_status_message(SharedString::from(message));
+        }
+    });
+
+    let state_weak = Rc::downgrade(&state);
+    state.main_ui.on_unload_external_engine(move || {
+        if let Some(state) = state_weak.upgrade() {
+            external::unload(&state);
             state
                 .main_ui
-                .set_chess_fields(map_chessboard_to_ui(&state.chess_board.borrow()));
+                .set_status_message(SharedString::from("chic plays and analyzes again"));
         }
     });
+    state.main_ui.set_external_command(SharedString::from(
+        config.ui.external_engine.clone().unwrap_or_default(),
+    ));
 
-    let fields = map_chessboard_to_ui(&state.chess_board.borrow());
-    state.main_ui.set_chess_fields(fields);
+    state.main_ui.set_pgn_files_enabled(cfg!(not(target_arch = "wasm32")));
+    state.main_ui.set_online_enabled(cfg!(not(target_arch = "wasm32")));
+    state.main_ui.set_external_enabled(cfg!(not(target_arch = "wasm32")));
+    refresh_board(&state);
+    session::restore(&state);
     state.main_ui.run().unwrap();
 }
 
-fn set_piece_color_of_the_promotion_dialog(main_window: &MainWindow, color: Color) {
+/// Offers the built-in presets and the loaded external engine for watched games.
+fn update_watch_engines(state: &State, external: Option<&str>) {
+    let watch_engines: Vec<SharedString> = watch::WatchEngine::choices(external)
+        .iter()
+        .map(|engine| SharedString::from(engine.name()))
+        .collect();
+    // A choice of an unloaded engine falls back to the last preset
+    let last = watch_engines.len() as i32 - 1;
+    state.main_ui.set_watch_white(state.main_ui.get_watch_white().min(last));
+    state.main_ui.set_watch_black(state.main_ui.get_watch_black().min(last));
+    state
+        .main_ui
+        .set_watch_engines(ModelRc::new(VecModel::from(watch_engines)));
+}
+
+fn set_piece_color_of_the_promotion_dialog(main_window: &MainWindow, color: Color, piece_set: PieceSet) {
     let promotion_choices = vec![
-        create_piece(PIECE_IMAGES.get(&(color, PieceType::Queen)).unwrap()),
-        create_piece(PIECE_IMAGES.get(&(color, PieceType::Rook)).unwrap()),
-        create_piece(PIECE_IMAGES.get(&(color, PieceType::Bishop)).unwrap()),
-        create_piece(PIECE_IMAGES.get(&(color, PieceType::Knight)).unwrap()),
+        create_piece(piece_set.image(color, PieceType::Queen)),
+        create_piece(piece_set.image(color, PieceType::Rook)),
+        create_piece(piece_set.image(color, PieceType::Bishop)),
+        create_piece(piece_set.image(color, PieceType::Knight)),
     ];
     main_window.set_promotion_choices(ModelRc::new(VecModel::from(promotion_choices)));
 }
 
 fn is_promotion(clicked_field: ChessField, piece: Piece) -> bool {
-    piece.kind == PieceType::Pawn && (clicked_field.row == 0 || clicked_field.row == 7)
+    piece.kind == PieceType::Pawn && (clicked_field.row() == 0 || clicked_field.row() == 7)
+}
+
+/// Formats a score in centipawns from White's point of view, e.g. "+1.25" or "#-3".
+/// Renders the principal variation in SAN starting from the searched position.
+fn format_principal_variation(chess_board: &ChessBoard, pv: &str) -> String {
+    let mut board = chess_board.clone();
+    let mut san_moves = Vec::new();
+    for mv in pv.split_whitespace().map(Move::from_algebraic) {
+        if !board.is_legal(mv) {
+            break;
+        }
+        san_moves.push(move_to_san(&board, mv));
+        board.make_move(mv);
+    }
+    san_moves.join(" ")
+}
+
+/// The evaluation bar value and the analysis text of an engine info line.
+fn engine_info(chess_board: &ChessBoard, depth: i32, score: i32, nodes: u64, pv: &str) -> (f32, String) {
+    let white_score = if chess_board.active_color == Color::White {
+        score
+    } else {
+        -score
+    };
+    let analysis = format!(
+        "Depth {}  Eval {}  Nodes {}\n{}",
+        depth,
+        format_evaluation(white_score),
+        nodes,
+        format_principal_variation(chess_board, pv)
+    );
+    let evaluation = eval_graph::pawns(white_score);
+    (evaluation, analysis)
+}
+
+/// The evaluation bar value and the analysis text of a completed depth of the analysis mode.
+fn analysis_info(chess_board: &ChessBoard, depth: &AnalysisDepth) -> (f32, String) {
+    let white_score = |score: i32| {
+        if chess_board.active_color == Color::White {
+            score
+        } else {
+            -score
+        }
+    };
+    let mut analysis = format!("Depth {}  Nodes {}", depth.depth, depth.nodes);
+    for (index, (score, pv)) in depth.lines.iter().enumerate() {
+        let pv: Vec<String> = pv.iter().map(|mv| mv.as_algebraic()).collect();
+        analysis.push_str(&format!(
+            "\n{}. {} {}",
+            index + 1,
+            format_evaluation(white_score(*score)),
+            format_principal_variation(chess_board, &pv.join(" "))
+        ));
+    }
+    let best_score = depth.lines.first().map_or(0, |&(score, _)| white_score(score));
+    let evaluation = eval_graph::pawns(best_score);
+    (evaluation, analysis)
+}
+
+/// The analysis database in the configuration directory. Without a file system, or if the file cannot be read, the
+/// analyses are only kept for the session.
+fn open_analysis_db() -> AnalysisDb {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = AnalysisDb::default_path() {
+        match AnalysisDb::open(&path) {
+            Ok(db) => return db,
+            Err(e) => eprintln!("Could not read the analysis database {}: {}", path.display(), e),
+        }
+    }
+    AnalysisDb::in_memory()
+}
+
+/// Replaces a running search by an analysis of the current position, which runs until the position changes.
+fn start_analysis(state: &Rc<State>) {
+    abort_engine_search(state);
+    state.engine_thinking.set(false);
+    #[cfg(not(target_arch = "wasm32"))]
+    if external::is_loaded(state) {
+        external::start_analysis(state);
+        return;
+    }
+    let chess_board = state.chess_board.borrow().clone();
+    let known = state.analysis_db.lock().unwrap().get(&chess_board);
+    let analysis = Analysis::resume(chess_board.clone(), ANALYSIS_LINES, known);
+    *state.engine_abort.borrow_mut() = Some(analysis.abort_channel());
+    let best_line = Arc::new(Mutex::new(None));
+    *state.analysis_best_line.borrow_mut() = best_line.clone();
+    run_analysis(state, analysis, chess_board, best_line);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run_analysis(state: &Rc<State>, mut analysis: Analysis, chess_board: ChessBoard, best_line: BestLine) {
+    let ui_weak = state.main_ui.as_weak();
+    let generation = state.game_generation.get();
+    let analysis_db = state.analysis_db.clone();
+    std::thread::spawn(move || loop {
+        match analysis.step(Duration::from_millis(100)) {
+            AnalysisStep::Running => {}
+            AnalysisStep::Depth(depth) => {
+                *best_line.lock().unwrap() = depth.lines.first().cloned();
+                // The analysis is shown even if it cannot be stored
+                analysis_db.lock().unwrap().record(&chess_board, &depth).ok();
+                let (evaluation, text) = analysis_info(&chess_board, &depth);
+                let handle = ui_weak.clone();
+                let _ = slint::invoke_from_event_loop(move || {
+                    handle
+                        .unwrap()
+                        .invoke_engine_info(generation, evaluation, SharedString::from(text))
+                });
+            }
+            AnalysisStep::Finished => return,
+        }
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn run_analysis(state: &Rc<State>, mut analysis: Analysis, chess_board: ChessBoard, best_line: BestLine) {
+    let state_weak = Rc::downgrade(state);
+    let generation = state.game_generation.get();
+    slint::Timer::single_shot(Duration::ZERO, move || {
+        let Some(state) = state_weak.upgrade() else {
+            return;
+        };
+        match analysis.step(SEARCH_STEP_TIME) {
+            AnalysisStep::Running => run_analysis(&state, analysis, chess_board, best_line),
+            AnalysisStep::Depth(depth) => {
+                *best_line.lock().unwrap() = depth.lines.first().cloned();
+                state.analysis_db.lock().unwrap().record(&chess_board, &depth).ok();
+                let (evaluation, text) = analysis_info(&chess_board, &depth);
+                state
+                    .main_ui
+                    .invoke_engine_info(generation, evaluation, SharedString::from(text));
+                run_analysis(&state, analysis, chess_board, best_line);
+            }
+            AnalysisStep::Finished => {}
+        }
+    });
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn run_hint(state: &Rc<State>, mut analysis: Analysis) {
+    let ui_weak = state.main_ui.as_weak();
+    let generation = state.hint_generation.get();
+    std::thread::spawn(move || {
+        while !matches!(analysis.step(Duration::from_millis(100)), AnalysisStep::Finished) {}
+        if let Some(mv) = analysis.best_move() {
+            let _ = slint::invoke_from_event_loop(move || {
+                ui_weak
+                    .unwrap()
+                    .invoke_show_hint(generation, SharedString::from(mv.as_algebraic()))
+            });
+        }
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+fn run_hint(state: &Rc<State>, mut analysis: Analysis) {
+    let state_weak = Rc::downgrade(state);
+    let generation = state.hint_generation.get();
+    slint::Timer::single_shot(Duration::ZERO, move || {
+        let Some(state) = state_weak.upgrade() else {
+            return;
+        };
+        match analysis.step(SEARCH_STEP_TIME) {
+            AnalysisStep::Finished => {
+                if let Some(mv) = analysis.best_move() {
+                    state
+                        .main_ui
+                        .invoke_show_hint(generation, SharedString::from(mv.as_algebraic()));
+                }
+            }
+            _ => run_hint(&state, analysis),
+        }
+    });
+}
+
+/// The engine playing against the user, set up as in the configuration, or the engine to move in a watched game.
+fn opponent_engine(state: &State, chess_board: ChessBoard) -> AlphaBetaEngine {
+    let config = watch::engine_config(state).unwrap_or_else(|| state.engine_config.borrow().clone());
+    let mut engine = AlphaBetaEngine::with_board(chess_board);
+    engine.set_eval_params(state.eval_params.borrow().clone());
+    engine.set_threads(config.threads);
+    engine.set_preset(config.preset);
+    if config.hash != DEFAULT_HASH_MB {
+        engine.set_hash_size(config.hash.clamp(1, MAX_HASH_MB));
+    }
+    engine.set_skill(config.elo.map(Skill::new));
+    engine
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 fn make_engine_move(state: &Rc<State>) {
+    if external::plays_move(state) {
+        external::make_move(state);
+        return;
+    }
     let state_weak = Rc::downgrade(state);
     let chess_board = state.chess_board.borrow().clone();
     let ui_weak = state_weak.upgrade().unwrap().main_ui.as_weak();
+    let generation = state.game_generation.get();
+    let move_time = engine_move_time(state, Duration::from_secs(7));
+    let mut engine = opponent_engine(state, chess_board.clone());
+    *state.engine_abort.borrow_mut() = Some(engine.get_abort_channel());
 
     std::thread::spawn(move || {
-        let mut engine = AlphaBetaEngine::with_board(chess_board);
-        if let Some((best_move, score, node_count, depth)) = engine.find_best_move_iterative(
-            std::time::Duration::from_secs(7),
-            |_depth, _eval, _nodes, _elapsed, _pv| {
-                // No-op
-            },
-        ) {
+        let info_ui = ui_weak.clone();
+        let mut info_callback = |event: InfoEvent| {
+            let InfoEvent::DepthCompleted {
+                depth,
+                score,
+                nodes,
+                pv,
+                ..
+            } = event
+            else {
+                return;
+            };
+            let (evaluation, analysis) = engine_info(&chess_board, depth, score, nodes, &pv);
+            let handle = info_ui.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                handle
+                    .unwrap()
+                    .invoke_engine_info(generation, evaluation, SharedString::from(analysis))
+            });
+        };
+        if let Some((best_move, score, node_count, depth)) =
+            engine.find_best_move_iterative(move_time, &mut info_callback)
+        {
             println!(
                 "Best move: {} with score: {} nodes: {} depth: {}",
                 best_move[0].as_algebraic(),
@@ -238,34 +1625,87 @@ fn make_engine_move(state: &Rc<State>) {
             );
             let handle = ui_weak.clone();
             let mv = best_move[0].as_algebraic();
+            let ponder = best_move.get(1).map(|m| m.as_algebraic()).unwrap_or_default();
             // now forward the data to the main thread using invoke_from_event_loop
-            let _ = slint::invoke_from_event_loop(move || handle.unwrap().invoke_make_move(SharedString::from(mv)));
+            let _ = slint::invoke_from_event_loop(move || {
+                handle
+                    .unwrap()
+                    .invoke_make_move(SharedString::from(mv), SharedString::from(ponder), generation, score)
+            });
         } else {
             println!("No best move found!");
         }
     });
 }
 
+/// How long the search runs before it hands control back to the browser's event loop.
+#[cfg(target_arch = "wasm32")]
+const SEARCH_STEP_TIME: Duration = Duration::from_millis(15);
+
+/// There are no threads on wasm, so the search runs in short steps on the UI thread, each one scheduled by a timer.
 #[cfg(target_arch = "wasm32")]
 fn make_engine_move(state: &Rc<State>) {
-    let state_weak = Rc::downgrade(state);
     let chess_board = state.chess_board.borrow().clone();
-    let ui_weak = state_weak.upgrade().unwrap().main_ui.as_weak();
-    if let Some((best_move, score, node_count, depth)) =
-        find_best_move_iterative(&chess_board, std::time::Duration::from_secs(3))
-    {
-        println!(
-            "Best move: {} with score: {} nodes: {} depth: {}",
-            best_move.as_algebraic(),
-            score,
-            node_count,
-            depth,
-        );
-        let handle = ui_weak.clone();
-        let mv = best_move.as_algebraic();
-        // now forward the data to the main thread using invoke_from_event_loop
-        let _ = slint::invoke_from_event_loop(move || handle.unwrap().invoke_make_move(SharedString::from(mv)));
-    } else {
-        println!("No best move found!");
-    }
+    let generation = state.game_generation.get();
+    let move_time = engine_move_time(state, Duration::from_secs(3));
+    let mut engine = opponent_engine(state, chess_board.clone());
+    let search = engine.start_search(move_time);
+    *state.engine_abort.borrow_mut() = Some(engine.get_abort_channel());
+    schedule_search_step(Rc::downgrade(state), engine, search, chess_board, generation);
+}
+
+#[cfg(target_arch = "wasm32")]
+fn schedule_search_step(
+    state_weak: std::rc::Weak<State>,
+    mut engine: AlphaBetaEngine,
+    mut search: IterativeSearch,
+    chess_board: ChessBoard,
+    generation: i32,
+) {
+    slint::Timer::single_shot(Duration::ZERO, move || {
+        let Some(state) = state_weak.upgrade() else {
+            return;
+        };
+        if engine.get_abort_channel().load(Relaxed) {
+            return;
+        }
+        let main_ui = state.main_ui.as_weak();
+        let mut info_callback = |event: InfoEvent| {
+            let InfoEvent::DepthCompleted {
+                depth,
+                score,
+                nodes,
+                pv,
+                ..
+            } = event
+            else {
+                return;
+            };
+            let (evaluation, analysis) = engine_info(&chess_board, depth, score, nodes, &pv);
+            main_ui
+                .unwrap()
+                .invoke_engine_info(generation, evaluation, SharedString::from(analysis));
+        };
+        match engine.continue_search(&mut search, SEARCH_STEP_TIME, &mut info_callback) {
+            SearchProgress::Running => schedule_search_step(state_weak, engine, search, chess_board, generation),
+            SearchProgress::Finished(Some((best_move, score, node_count, depth))) => {
+                println!(
+                    "Best move: {} with score: {} nodes: {} depth: {}",
+                    best_move[0].as_algebraic(),
+                    score,
+                    node_count,
+                    depth,
+                );
+                let mv = best_move[0].as_algebraic();
+                let ponder = best_move.get(1).map(|m| m.as_algebraic()).unwrap_or_default();
+                main_ui.unwrap().invoke_make_move(
+                    SharedString::from(mv),
+                    SharedString::from(ponder),
+                    generation,
+                    score,
+                );
+            }
+            SearchProgress::Finished(None) => println!("No best move found!"),
+        }
+    });
 }
@@ -0,0 +1,452 @@
+//! Online play over a direct TCP connection between two chic instances: one hosts on an address, the other joins
+//! it. The messages are JSON, one per line. The host starts the game with the position, the color of the guest and
+//! the clock, then both send their moves with the clock after them, draw offers and resignations. Every side checks
+//! the moves of the opponent against its own board.
+use super::clock::{parse_time_control, ChessClock, ClockSnapshot};
+use super::{apply_move, end_game, play_premove, set_game, update_clock, update_draw_claim, State};
+use crate::chess_board::game::Game;
+use crate::chess_board::{Color, Move};
+use serde::{Deserialize, Serialize};
+use slint::{ComponentHandle, SharedString};
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Messages of another protocol version are not understood, the game does not start.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Message {
+    /// Sent by the host once the guest is connected. The host plays the side to move of the position.
+    #[serde(rename_all = "camelCase")]
+    Start {
+        version: u32,
+        fen: String,
+        guest_color: Color,
+        clock: Option<ClockSnapshot>,
+    },
+    /// A move with the clock after it, the clock of the side to move running.
+    Move {
+        mv: Move,
+        clock: Option<ClockSnapshot>,
+    },
+    OfferDraw,
+    /// Accepts the draw offered by the opponent, which lapses with the next move.
+    AcceptDraw,
+    Resign,
+}
+
+/// The online game of the user while connected.
+pub struct Link {
+    /// Messages to the opponent, written by the thread of the connection
+    sender: Sender<Message>,
+    host: bool,
+    /// The color of the user once the game started
+    color: Option<Color>,
+    /// The side whose draw offer is open
+    draw_offer: Option<Color>,
+}
+
+fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Message) -> io::Result<()> {
+    let line = serde_json::to_string(message).map_err(io::Error::other)?;
+    writeln!(writer, "{}", line)?;
+    writer.flush()
+}
+
+/// Reads the next message, None at the end of the stream. A line that is no message is an error.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Message>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads the messages of the stream on this thread and writes the messages of the channel on another one. Returns
+/// when the opponent closes the connection, or when the channel is dropped and the writing thread shuts it down.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_connection(
+    stream: std::net::TcpStream,
+    outgoing: std::sync::mpsc::Receiver<Message>,
+    mut incoming: impl FnMut(Message),
+) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut writer = stream.try_clone()?;
+    std::thread::spawn(move || {
+        for message in outgoing {
+            if write_message(&mut writer, &message).is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown(std::net::Shutdown::Both);
+    });
+    let mut reader = io::BufReader::new(stream);
+    while let Some(message) = read_message(&mut reader)? {
+        incoming(message);
+    }
+    Ok(())
+}
+
+pub fn is_playing(state: &State) -> bool {
+    state.online.borrow().as_ref().is_some_and(|link| link.color.is_some())
+}
+
+fn send(state: &State, message: Message) {
+    if let Some(link) = state.online.borrow().as_ref() {
+        // A closed connection is reported by the reading thread
+        let _ = link.sender.send(message);
+    }
+}
+
+/// Waits for a guest on the address, the game starts with the current position and the time control of the clock
+/// fields once it connects.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn host(state: &Rc<State>, address: &str) -> Result<String, String> {
+    parse_time_control(&state.main_ui.get_clock_minutes(), &state.main_ui.get_clock_increment())?;
+    let listener =
+        std::net::TcpListener::bind(address.trim()).map_err(|e| format!("Could not listen on {}: {}", address, e))?;
+    let local_address = listener.local_addr().map_err(|e| e.to_string())?;
+    connect(state, true, move || listener.accept().map(|(stream, _)| stream));
+    Ok(format!("Waiting for the opponent on {}", local_address))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn join(state: &Rc<State>, address: &str) -> Result<String, String> {
+    let address = address.trim().to_string();
+    let message = format!("Connecting to {}", address);
+    connect(state, false, move || std::net::TcpStream::connect(&address));
+    Ok(message)
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn host(_state: &Rc<State>, _address: &str) -> Result<String, String> {
+    Err(String::from("Online play needs the desktop version"))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn join(_state: &Rc<State>, _address: &str) -> Result<String, String> {
+    Err(String::from("Online play needs the desktop version"))
+}
+
+/// Opens the connection on a thread that passes the messages of the opponent to the event loop, tagged with the
+/// generation of the connection.
+#[cfg(not(target_arch = "wasm32"))]
+fn connect(state: &Rc<State>, host: bool, open: impl FnOnce() -> io::Result<std::net::TcpStream> + Send + 'static) {
+    leave(state);
+    let (sender, outgoing) = std::sync::mpsc::channel();
+    *state.online.borrow_mut() = Some(Link {
+        sender,
+        host,
+        color: None,
+        draw_offer: None,
+    });
+    state.main_ui.set_online_active(true);
+    let generation = state.online_generation.get();
+    let ui_weak = state.main_ui.as_weak();
+    std::thread::spawn(move || {
+        let post = |event: Box<dyn FnOnce(&crate::MainWindow) + Send>| {
+            let ui_weak = ui_weak.clone();
+            let _ = slint::invoke_from_event_loop(move || {
+                if let Some(ui) = ui_weak.upgrade() {
+                    event(&ui);
+                }
+            });
+        };
+        let result = open().and_then(|stream| {
+            post(Box::new(move |ui| ui.invoke_online_opened(generation)));
+            run_connection(stream, outgoing, |message| {
+                let json = serde_json::to_string(&message).unwrap_or_default();
+                post(Box::new(move |ui| {
+                    ui.invoke_online_message(generation, SharedString::from(json))
+                }));
+            })
+        });
+        let reason = match result {
+            Ok(()) => String::from("The connection was closed"),
+            Err(e) => format!("The connection failed: {}", e),
+        };
+        post(Box::new(move |ui| {
+            ui.invoke_online_closed(generation, SharedString::from(reason))
+        }));
+    });
+}
+
+/// Closes the connection, a running game ends.
+pub fn leave(state: &Rc<State>) {
+    state.online_generation.set(state.online_generation.get() + 1);
+    let Some(link) = state.online.borrow_mut().take() else {
+        return;
+    };
+    state.main_ui.set_online_active(false);
+    if link.color.is_some() && !state.game_over.get() {
+        end_game(state, "You left the online game");
+    }
+}
+
+/// The connection is open, the host starts the game.
+pub fn opened(state: &Rc<State>) {
+    let host = state.online.borrow().as_ref().is_some_and(|link| link.host);
+    if !host {
+        state
+            .main_ui
+            .set_status_message(SharedString::from("Connected, waiting for the game to start"));
+        return;
+    }
+    let time_control = parse_time_control(&state.main_ui.get_clock_minutes(), &state.main_ui.get_clock_increment());
+    let clock = time_control.ok().map(|(base_time, increment)| ClockSnapshot {
+        white_ms: base_time.as_millis() as u64,
+        black_ms: base_time.as_millis() as u64,
+        increment_ms: increment.as_millis() as u64,
+        running: true,
+    });
+    let board = state.chess_board.borrow().clone();
+    send(
+        state,
+        Message::Start {
+            version: PROTOCOL_VERSION,
+            fen: board.to_fen(),
+            guest_color: opponent(board.active_color),
+            clock,
+        },
+    );
+    if let Err(e) = start_game(state, &board.to_fen(), board.active_color, clock) {
+        state.main_ui.set_status_message(SharedString::from(e));
+    }
+}
+
+/// A message of the opponent arrived. A message that breaks the protocol or the rules closes the connection.
+pub fn received(state: &Rc<State>, json: &str) {
+    let result = serde_json::from_str(json)
+        .map_err(|e| e.to_string())
+        .and_then(|message| handle_message(state, message));
+    if let Err(e) = result {
+        leave(state);
+        state
+            .main_ui
+            .set_status_message(SharedString::from(format!("Disconnected: {}", e)));
+    }
+}
+
+/// The connection closed or could not be opened.
+pub fn closed(state: &Rc<State>, reason: &str) {
+    let playing = is_playing(state) && !state.game_over.get();
+    state.online.borrow_mut().take();
+    state.main_ui.set_online_active(false);
+    if playing {
+        end_game(state, &format!("The opponent is gone, {}", reason.to_lowercase()));
+    } else {
+        state.main_ui.set_status_message(SharedString::from(reason));
+    }
+}
+
+fn start_game(state: &Rc<State>, fen: &str, color: Color, clock: Option<ClockSnapshot>) -> Result<(), String> {
+    // The analysis would show the user the best moves
+    state.analysis_mode.set(false);
+    state.main_ui.set_analysis_mode(false);
+    set_game(state, Game::new(fen)?)?;
+    let to_move = state.chess_board.borrow().active_color;
+    *state.clock.borrow_mut() = clock.map(|snapshot| ChessClock::from_snapshot(&snapshot, to_move, Instant::now()));
+    if let Some(link) = state.online.borrow_mut().as_mut() {
+        link.color = Some(color);
+    }
+    state.engine_thinking.set(to_move != color);
+    update_draw_claim(state);
+    update_clock(state);
+    let side = match color {
+        Color::White => "White",
+        Color::Black => "Black",
+    };
+    state
+        .main_ui
+        .set_status_message(SharedString::from(format!("Online game started, you play {}", side)));
+    Ok(())
+}
+
+fn handle_message(state: &Rc<State>, message: Message) -> Result<(), String> {
+    let (host, color, draw_offer) = match state.online.borrow().as_ref() {
+        Some(link) => (link.host, link.color, link.draw_offer),
+        None => return Ok(()),
+    };
+    match message {
+        Message::Start {
+            version,
+            fen,
+            guest_color,
+            clock,
+        } => {
+            if host || color.is_some() {
+                return Err(String::from("The game was started twice"));
+            }
+            if version != PROTOCOL_VERSION {
+                return Err(format!("The opponent speaks protocol version {}", version));
+            }
+            start_game(state, &fen, guest_color, clock)
+        }
+        Message::Move { mv, clock } => {
+            let Some(color) = color.filter(|_| !state.game_over.get()) else {
+                return Err(String::from("A move arrived outside of a game"));
+            };
+            let chess_board = state.chess_board.borrow().clone();
+            if chess_board.active_color == color || !chess_board.generate_legal_moves().contains(&mv) {
+                return Err(format!("The opponent played the illegal move {}", mv.as_algebraic()));
+            }
+            set_draw_offer(state, None);
+            state.engine_thinking.set(false);
+            apply_move(state, mv);
+            if !state.game_over.get() {
+                // The clock of the opponent counts its time, the delay of the network is the user's
+                if let Some(snapshot) = clock {
+                    let clock = ChessClock::from_snapshot(&snapshot, color, Instant::now());
+                    *state.clock.borrow_mut() = Some(clock);
+                }
+                update_clock(state);
+                play_premove(state);
+            }
+            Ok(())
+        }
+        Message::OfferDraw => {
+            if let Some(color) = color.filter(|_| !state.game_over.get()) {
+                set_draw_offer(state, Some(opponent(color)));
+                state.main_ui.set_status_message(SharedString::from(
+                    "The opponent offers a draw, offer a draw to accept it",
+                ));
+            }
+            Ok(())
+        }
+        Message::AcceptDraw => {
+            if color.is_some() && draw_offer == color && !state.game_over.get() {
+                end_game(state, "Draw agreed");
+            }
+            Ok(())
+        }
+        Message::Resign => {
+            if color.is_some() && !state.game_over.get() {
+                end_game(state, "The opponent resigned");
+            }
+            Ok(())
+        }
+    }
+}
+
+fn set_draw_offer(state: &State, side: Option<Color>) {
+    if let Some(link) = state.online.borrow_mut().as_mut() {
+        link.draw_offer = side;
+    }
+}
+
+/// Sends the move the user just played with the clock after it.
+pub fn send_move(state: &Rc<State>, mv: Move) {
+    set_draw_offer(state, None);
+    let clock = state
+        .clock
+        .borrow()
+        .as_ref()
+        .map(|clock| clock.snapshot(Instant::now()));
+    send(state, Message::Move { mv, clock });
+}
+
+/// Offers a draw, or accepts the open offer of the opponent.
+pub fn offer_draw(state: &Rc<State>) {
+    let (color, draw_offer) = match state.online.borrow().as_ref() {
+        Some(link) => (link.color, link.draw_offer),
+        None => return,
+    };
+    let Some(color) = color else {
+        return;
+    };
+    if draw_offer == Some(opponent(color)) {
+        send(state, Message::AcceptDraw);
+        end_game(state, "Draw agreed");
+    } else if draw_offer.is_none() {
+        send(state, Message::OfferDraw);
+        set_draw_offer(state, Some(color));
+        state.main_ui.set_status_message(SharedString::from("Draw offered"));
+    }
+}
+
+pub fn resign(state: &Rc<State>) {
+    if is_playing(state) && !state.game_over.get() {
+        send(state, Message::Resign);
+        end_game(state, "You resigned");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc::{channel, Receiver};
+
+    #[test]
+    fn test_message_format() {
+        let message = Message::Move {
+            mv: Move::from_algebraic("e7e8q"),
+            clock: None,
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, r#"{"type":"move","mv":"e7e8q","clock":null}"#);
+        assert_eq!(
+            read_message(&mut io::Cursor::new(format!("{}\n", json))).unwrap(),
+            Some(message)
+        );
+        let start =
+            r#"{"type":"start","version":1,"fen":"8/8/8/8/8/8/8/K6k w - - 0 1","guestColor":"Black","clock":null}"#;
+        assert!(matches!(
+            read_message(&mut io::Cursor::new(start)).unwrap(),
+            Some(Message::Start {
+                guest_color: Color::Black,
+                ..
+            })
+        ));
+        assert_eq!(read_message(&mut io::Cursor::new("")).unwrap(), None);
+        assert!(read_message(&mut io::Cursor::new(r#"{"type":"move","mv":"e9e8"}"#)).is_err());
+    }
+
+    fn run(
+        stream: TcpStream,
+    ) -> (
+        Sender<Message>,
+        Receiver<Message>,
+        std::thread::JoinHandle<io::Result<()>>,
+    ) {
+        let (sender, outgoing) = channel();
+        let (received, incoming) = channel();
+        let thread =
+            std::thread::spawn(move || run_connection(stream, outgoing, |message| received.send(message).unwrap()));
+        (sender, incoming, thread)
+    }
+
+    #[test]
+    fn test_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let guest = TcpStream::connect(address).unwrap();
+        let (host_sender, host_incoming, host_thread) = run(listener.accept().unwrap().0);
+        let (guest_sender, guest_incoming, guest_thread) = run(guest);
+
+        host_sender.send(Message::OfferDraw).unwrap();
+        assert_eq!(guest_incoming.recv().unwrap(), Message::OfferDraw);
+        guest_sender.send(Message::AcceptDraw).unwrap();
+        assert_eq!(host_incoming.recv().unwrap(), Message::AcceptDraw);
+
+        // The guest leaves, both connections end
+        drop(guest_sender);
+        assert!(guest_thread.join().unwrap().is_ok());
+        assert!(host_thread.join().unwrap().is_ok());
+        assert!(host_incoming.recv().is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::chess_board::{Color, PieceType};
+use slint::Image;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// The SVGs are embedded, so the binary does not depend on the working directory and runs in wasm
+fn classic_svg(color: Color, kind: PieceType) -> &'static [u8] {
+    match (color, kind) {
+        (Color::White, PieceType::Pawn) => include_bytes!("../../ui/icons/Piece_White_Pawn.svg"),
+        (Color::White, PieceType::Knight) => include_bytes!("../../ui/icons/Piece_White_Knight.svg"),
+        (Color::White, PieceType::Bishop) => include_bytes!("../../ui/icons/Piece_White_Bishop.svg"),
+        (Color::White, PieceType::Rook) => include_bytes!("../../ui/icons/Piece_White_Rock.svg"),
+        (Color::White, PieceType::Queen) => include_bytes!("../../ui/icons/Piece_White_Queen.svg"),
+        (Color::White, PieceType::King) => include_bytes!("../../ui/icons/Piece_White_King.svg"),
+        (Color::Black, PieceType::Pawn) => include_bytes!("../../ui/icons/Piece_Black_Pawn.svg"),
+        (Color::Black, PieceType::Knight) => include_bytes!("../../ui/icons/Piece_Black_Knight.svg"),
+        (Color::Black, PieceType::Bishop) => include_bytes!("../../ui/icons/Piece_Black_Bishop.svg"),
+        (Color::Black, PieceType::Rook) => include_bytes!("../../ui/icons/Piece_Black_Rock.svg"),
+        (Color::Black, PieceType::Queen) => include_bytes!("../../ui/icons/Piece_Black_Queen.svg"),
+        (Color::Black, PieceType::King) => include_bytes!("../../ui/icons/Piece_Black_King.svg"),
+    }
+}
+
+thread_local! {
+    // Parsing an SVG is expensive, so every piece image is only created once
+    static IMAGE_CACHE: RefCell<HashMap<(PieceSet, Color, PieceType), Image>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceSet {
+    Classic,
+    Letters,
+}
+
+impl PieceSet {
+    pub const ALL: [PieceSet; 2] = [PieceSet::Classic, PieceSet::Letters];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PieceSet::Classic => "Classic",
+            PieceSet::Letters => "Letters",
+        }
+    }
+
+    pub fn image(&self, color: Color, kind: PieceType) -> Image {
+        IMAGE_CACHE.with(|cache| {
+            cache
+                .borrow_mut()
+                .entry((*self, color, kind))
+                .or_insert_with(|| Image::load_from_svg_data(&self.svg(color, kind)).unwrap())
+                .clone()
+        })
+    }
+
+    fn svg(&self, color: Color, kind: PieceType) -> Vec<u8> {
+        match self {
+            PieceSet::Classic => classic_svg(color, kind).to_vec(),
+            PieceSet::Letters => letter_svg(color, kind).into_bytes(),
+        }
+    }
+}
+
+/// A disc with the piece letter, drawn without any image assets.
+fn letter_svg(color: Color, kind: PieceType) -> String {
+    let (fill, stroke) = match color {
+        Color::White => ("#FFFFFF", "#000000"),
+        Color::Black => ("#202020", "#FFFFFF"),
+    };
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 45 45"><circle cx="22.5" cy="22.5" r="20" fill="{fill}" stroke="{stroke}" stroke-width="2"/><text x="22.5" y="30.5" font-size="22" font-family="sans-serif" font-weight="bold" text-anchor="middle" fill="{stroke}">{kind}</text></svg>"##
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_piece_images_load() {
+        let kinds = [
+            PieceType::Pawn,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+            PieceType::King,
+        ];
+        for piece_set in PieceSet::ALL {
+            for color in [Color::White, Color::Black] {
+                for kind in kinds {
+                    assert!(piece_set.image(color, kind).size().width > 0);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! The session of the browser version: the game, the clock and the settings are saved to the local storage of the
+//! browser after every move and restored when the page is loaded again. The session is JSON with the game as PGN,
+//! which keeps the start position, the moves with their variations and comments, and so every position of the game.
+use super::clock::{ChessClock, ClockSnapshot};
+use super::piece_set::PieceSet;
+use super::{make_engine_move, set_game, update_clock, update_draw_claim, update_engine_options, State};
+use crate::chess_board::game::Game;
+use crate::config::EngineConfig;
+use serde::{Deserialize, Serialize};
+use slint::SharedString;
+use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Key of the session in the local storage.
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY: &str = "chic-session";
+
+/// Sessions saved in another format are dropped instead of being restored.
+const SESSION_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub version: u32,
+    pub pgn: String,
+    /// None in untimed games
+    pub clock: Option<ClockSnapshot>,
+    pub game_over: bool,
+    /// The engine was searching its reply, it searches again after the restore
+    pub engine_thinking: bool,
+    pub analysis_mode: bool,
+    pub engine: EngineConfig,
+    pub piece_set: String,
+    pub show_threats: bool,
+}
+
+impl Session {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Could not save the session: {}", e))
+    }
+
+    pub fn from_json(text: &str) -> Result<Session, String> {
+        let session: Session =
+            serde_json::from_str(text).map_err(|e| format!("Could not restore the session: {}", e))?;
+        if session.version != SESSION_VERSION {
+            return Err(format!("Unknown session version {}", session.version));
+        }
+        Ok(session)
+    }
+
+    fn capture(state: &State) -> Result<Session, String> {
+        Ok(Session {
+            version: SESSION_VERSION,
+            pgn: state.game.borrow().to_pgn()?,
+            clock: state
+                .clock
+                .borrow()
+                .as_ref()
+                .map(|clock| clock.snapshot(Instant::now())),
+            game_over: state.game_over.get(),
+            // A watched game is restored for the user to play on
+            engine_thinking: state.engine_thinking.get() && !super::watch::is_watching(state),
+            analysis_mode: state.analysis_mode.get(),
+            engine: state.engine_config.borrow().clone(),
+            piece_set: String::from(state.piece_set.get().name()),
+            show_threats: state.show_threats.get(),
+        })
+    }
+}
+
+/// Saves the session to the local storage. Only the browser version keeps a session, the desktop version starts
+/// with the position and the configuration it is given.
+pub fn save(state: &State) {
+    if cfg!(not(target_arch = "wasm32")) {
+        return;
+    }
+    // A session that cannot be saved leaves the previous one, the game goes on either way
+    if let Ok(text) = Session::capture(state).and_then(|session| session.to_json()) {
+        write_storage(&text);
+    }
+}
+
+/// Restores the session of the last visit of the page, if there is one. A session that cannot be restored is
+/// reported and the initial position stays.
+pub fn restore(state: &Rc<State>) {
+    let Some(text) = read_storage() else {
+        return;
+    };
+    let message = match Session::from_json(&text).and_then(|session| apply(state, session)) {
+        Ok(()) => String::from("Session restored"),
+        Err(e) => e,
+    };
+    state.main_ui.set_status_message(SharedString::from(message));
+}
+
+fn apply(state: &Rc<State>, session: Session) -> Result<(), String> {
+    let game = Game::from_pgn(&session.pgn)?;
+    // The browser has no evaluation files
+    *state.engine_config.borrow_mut() = EngineConfig {
+        eval_file: None,
+        ..session.engine
+    };
+    update_engine_options(state);
+    if let Some(index) = PieceSet::ALL
+        .iter()
+        .position(|piece_set| piece_set.name() == session.piece_set)
+    {
+        state.piece_set.set(PieceSet::ALL[index]);
+        state.main_ui.set_piece_set_index(index as i32);
+    }
+    state.show_threats.set(session.show_threats);
+    state.main_ui.set_show_threats(session.show_threats);
+    state.analysis_mode.set(session.analysis_mode);
+    state.main_ui.set_analysis_mode(session.analysis_mode);
+    set_game(state, game)?;
+
+    let to_move = state.chess_board.borrow().active_color;
+    *state.clock.borrow_mut() = session
+        .clock
+        .map(|snapshot| ChessClock::from_snapshot(&snapshot, to_move, Instant::now()));
+    update_clock(state);
+    state.game_over.set(session.game_over);
+    if session.engine_thinking && !session.game_over && !session.analysis_mode {
+        state.engine_thinking.set(true);
+        make_engine_move(state);
+    }
+    update_draw_claim(state);
+    save(state);
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_storage() -> Option<String> {
+    local_storage()?.get_item(STORAGE_KEY).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_storage(text: &str) {
+    if let Some(storage) = local_storage() {
+        // The storage may be full or disabled, the game goes on without a saved session
+        let _ = storage.set_item(STORAGE_KEY, text);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_storage() -> Option<String> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_storage(_text: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess_board::fen::INITIAL_POSITION;
+    use crate::chess_board::Move;
+
+    #[test]
+    fn test_session_round_trip() {
+        let mut game = Game::new("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        for mv in ["e1g1", "e8c8", "a1a8"] {
+            game.push_move(Move::from_algebraic(mv));
+        }
+        let session = Session {
+            version: SESSION_VERSION,
+            pgn: game.to_pgn().unwrap(),
+            clock: Some(ClockSnapshot {
+                white_ms: 61_500,
+                black_ms: 3_200,
+                increment_ms: 2_000,
+                running: true,
+            }),
+            game_over: false,
+            engine_thinking: true,
+            analysis_mode: false,
+            engine: EngineConfig {
+                hash: 64,
+                ..EngineConfig::default()
+            },
+            piece_set: String::from("Letters"),
+            show_threats: true,
+        };
+        let restored = Session::from_json(&session.to_json().unwrap()).unwrap();
+        assert_eq!(restored, session);
+        let restored_game = Game::from_pgn(&restored.pgn).unwrap();
+        assert_eq!(
+            restored_game.final_position().unwrap().to_fen(),
+            game.final_position().unwrap().to_fen()
+        );
+    }
+
+    #[test]
+    fn test_invalid_session() {
+        assert!(Session::from_json("not a session").is_err());
+        let session = Session {
+            version: SESSION_VERSION + 1,
+            pgn: Game::new(INITIAL_POSITION).unwrap().to_pgn().unwrap(),
+            clock: None,
+            game_over: false,
+            engine_thinking: false,
+            analysis_mode: false,
+            engine: EngineConfig::default(),
+            piece_set: String::from("Classic"),
+            show_threats: false,
+        };
+        assert_eq!(
+            Session::from_json(&session.to_json().unwrap()),
+            Err(format!("Unknown session version {}", SESSION_VERSION + 1))
+        );
+    }
+}
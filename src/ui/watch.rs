@@ -0,0 +1,187 @@
+//! Watching two engines play each other on the board. Every side has its own engine, the board, the clocks and the
+//! evaluation bar follow the game as in a game against the user, who only watches until the game ends or is stopped.
+use super::clock::{parse_time_control, ChessClock};
+use super::{abort_engine_search, external, make_engine_move, set_game, update_clock, State};
+use crate::chess_board::game::Game;
+use crate::chess_board::Color;
+use crate::config::EngineConfig;
+use crate::engines::preset::Preset;
+use slint::SharedString;
+use std::rc::Rc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// An engine that can play a side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEngine {
+    /// The built-in engine with the settings of the user and the search settings of the preset
+    Builtin(Preset),
+    /// The loaded external engine with its name
+    External(String),
+}
+
+impl WatchEngine {
+    /// The engines offered for both sides, in the order of the choices in the user interface, the external engine
+    /// last if one is loaded.
+    pub fn choices(external: Option<&str>) -> Vec<WatchEngine> {
+        let mut choices: Vec<WatchEngine> = Preset::ALL.into_iter().map(WatchEngine::Builtin).collect();
+        choices.extend(external.map(|name| WatchEngine::External(name.to_string())));
+        choices
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            WatchEngine::Builtin(preset) => format!("chic {}", preset.name()),
+            WatchEngine::External(name) => name.clone(),
+        }
+    }
+
+    /// The configuration of the built-in engine, which otherwise plays with the settings of the user. None for the
+    /// external engine.
+    pub fn config(&self, user_config: &EngineConfig) -> Option<EngineConfig> {
+        match self {
+            WatchEngine::Builtin(preset) => Some(EngineConfig {
+                preset: *preset,
+                ..user_config.clone()
+            }),
+            WatchEngine::External(_) => None,
+        }
+    }
+}
+
+/// The engines of a watched game.
+pub struct Watch {
+    white: WatchEngine,
+    black: WatchEngine,
+}
+
+impl Watch {
+    pub fn engine(&self, color: Color) -> &WatchEngine {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+}
+
+pub fn is_watching(state: &State) -> bool {
+    state.watch.borrow().is_some()
+}
+
+/// The configuration of the engine to move in a watched game, None if no game is watched.
+pub fn engine_config(state: &State) -> Option<EngineConfig> {
+    let color = state.chess_board.borrow().active_color;
+    let watch = state.watch.borrow();
+    watch.as_ref()?.engine(color).config(&state.engine_config.borrow())
+}
+
+/// Whether the external engine plays a side of the watched game.
+pub fn plays_external(state: &State) -> bool {
+    let watch = state.watch.borrow();
+    watch.as_ref().is_some_and(|watch| {
+        [Color::White, Color::Black]
+            .iter()
+            .any(|&color| matches!(watch.engine(color), WatchEngine::External(_)))
+    })
+}
+
+/// Starts a game of the engines of the choices from the current position, with the clock of the clock fields if
+/// they hold a valid time control.
+pub fn start(state: &Rc<State>, white: usize, black: usize) -> Result<String, String> {
+    let choices = WatchEngine::choices(external::name(state).as_deref());
+    let (Some(white), Some(black)) = (choices.get(white).cloned(), choices.get(black).cloned()) else {
+        return Err(String::from("Choose an engine for both sides"));
+    };
+    if state.main_ui.get_edit_mode() {
+        return Err(String::from("Finish editing the position first"));
+    }
+    let board = state.chess_board.borrow().clone();
+    if !board.has_legal_move() || board.automatic_draw().is_some() {
+        return Err(String::from("The game is over, set up a position to watch"));
+    }
+    // The analysis mode would analyze instead of letting the engines play
+    state.analysis_mode.set(false);
+    state.main_ui.set_analysis_mode(false);
+    let mut game = Game::new(&board.to_fen())?;
+    game.set_tag("White", &white.name());
+    game.set_tag("Black", &black.name());
+    set_game(state, game)?;
+
+    let time_control = parse_time_control(&state.main_ui.get_clock_minutes(), &state.main_ui.get_clock_increment());
+    if let Ok((base_time, increment)) = time_control {
+        let mut clock = ChessClock::new(base_time, increment);
+        clock.start(board.active_color, Instant::now());
+        *state.clock.borrow_mut() = Some(clock);
+        update_clock(state);
+    }
+    let message = format!("{} against {}", white.name(), black.name());
+    *state.watch.borrow_mut() = Some(Watch { white, black });
+    state.main_ui.set_watching(true);
+    next_move(state);
+    Ok(message)
+}
+
+/// Lets the engine to move search its move, unless the game is over.
+pub fn next_move(state: &Rc<State>) {
+    if state.game_over.get() {
+        finish(state);
+        return;
+    }
+    state.engine_thinking.set(true);
+    make_engine_move(state);
+}
+
+/// Stops the engines, the position stays on the board for the user.
+pub fn stop(state: &Rc<State>) {
+    if !is_watching(state) {
+        return;
+    }
+    finish(state);
+    abort_engine_search(state);
+    state.engine_thinking.set(false);
+    if let Some(clock) = state.clock.borrow_mut().as_mut() {
+        clock.stop(Instant::now());
+    }
+    update_clock(state);
+    state.main_ui.set_status_message(SharedString::from("Stopped watching"));
+}
+
+/// The watched game ended, the user may play on from the position.
+pub fn finish(state: &State) {
+    state.watch.borrow_mut().take();
+    state.main_ui.set_watching(false);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_engines() {
+        let choices = WatchEngine::choices(None);
+        assert_eq!(choices.len(), Preset::ALL.len());
+        assert_eq!(choices[2].name(), "chic Strong");
+        let choices = WatchEngine::choices(Some("Stockfish 16"));
+        assert_eq!(choices.len(), Preset::ALL.len() + 1);
+        assert_eq!(choices.last().unwrap().name(), "Stockfish 16");
+        assert_eq!(choices.last().unwrap().config(&EngineConfig::default()), None);
+
+        let user_config = EngineConfig {
+            hash: 64,
+            elo: Some(1500),
+            ..EngineConfig::default()
+        };
+        let config = WatchEngine::Builtin(Preset::Fast).config(&user_config).unwrap();
+        assert_eq!(config.preset, Preset::Fast);
+        assert_eq!(config.hash, 64);
+        assert_eq!(config.elo, Some(1500));
+
+        let watch = Watch {
+            white: choices[0].clone(),
+            black: choices[3].clone(),
+        };
+        assert_eq!(watch.engine(Color::Black), &WatchEngine::Builtin(Preset::Analysis));
+    }
+}
@@ -0,0 +1,457 @@
+//! Drives the chic-engine binary with scripted UCI sessions as a GUI would and checks its output against the
+//! protocol: every go gets exactly one legal bestmove, and every line is a UCI message.
+use chic::chess_board::fen::INITIAL_POSITION;
+use chic::chess_board::ChessBoard;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Generous for debug builds on a busy machine.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The engine process with its output read on a thread, so a missing line fails the test instead of blocking it.
+struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    output: Receiver<String>,
+}
+
+impl Engine {
+    fn start() -> Self {
+        // An empty configuration directory, a configuration file of the user would change the defaults
+        let config_directory = std::env::temp_dir().join(format!("chic-uci-test-{}", std::process::id()));
+        let mut child = Command::new(env!("CARGO_BIN_EXE_chic-engine"))
+            .env("XDG_CONFIG_HOME", config_directory)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("chic-engine starts");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, output) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Engine { child, stdin, output }
+    }
+
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin, "{}", command).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    /// The lines up to and including the first one that is done.
+    fn read_until(&self, done: impl Fn(&str) -> bool) -> Vec<String> {
+        let deadline = Instant::now() + TIMEOUT;
+        let mut lines = Vec::new();
+        loop {
+            match self
+                .output
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(line) => {
+                    assert_uci_output(&line);
+                    let finished = done(&line);
+                    lines.push(line);
+                    if finished {
+                        return lines;
+                    }
+                }
+                Err(e) => panic!("{} waiting for the engine after {:?}", e, lines),
+            }
+        }
+    }
+
+    fn read_best_move(&self) -> Vec<String> {
+        self.read_until(|line| line.starts_with("bestmove"))
+    }
+
+    /// All lines sent in the duration.
+    fn read_for(&self, duration: Duration) -> Vec<String> {
+        let deadline = Instant::now() + duration;
+        let mut lines = Vec::new();
+        while let Ok(line) = self
+            .output
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            assert_uci_output(&line);
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Waits for readyok and returns the lines before it.
+    fn sync(&mut self) -> Vec<String> {
+        self.send("isready");
+        let mut lines = self.read_until(|line| line == "readyok");
+        lines.pop();
+        lines
+    }
+
+    fn quit(mut self) {
+        self.send("quit");
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                assert!(status.success(), "{}", status);
+                return;
+            }
+            assert!(Instant::now() < deadline, "The engine did not quit");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+fn assert_uci_output(line: &str) {
+    let command = line.split_whitespace().next().unwrap_or_default();
+    assert!(
+        ["id", "uciok", "readyok", "bestmove", "info", "option"].contains(&command),
+        "Not a UCI message: {:?}",
+        line
+    );
+}
+
+fn board(start_fen: &str, moves: &[&str]) -> ChessBoard {
+    let mut board = ChessBoard::from_fen(start_fen).unwrap();
+    for mv in moves {
+        play(&mut board, mv);
+    }
+    board
+}
+
+fn play(board: &mut ChessBoard, mv: &str) {
+    let legal = board
+        .legal_move_from_algebraic(mv)
+        .unwrap_or_else(|e| panic!("{} in {}", e, board.to_fen()));
+    board.make_move(legal);
+}
+
+/// Checks that the lines of a search end with their only bestmove, a legal move with a legal ponder move, and that
+/// the info lines are well formed with legal principal variations.
+fn assert_search_output(lines: &[String], position: &ChessBoard) {
+    let (best_move, infos) = lines.split_last().unwrap();
+    assert!(
+        infos.iter().all(|line| !line.starts_with("bestmove")),
+        "More than one bestmove in {:?}",
+        lines
+    );
+    for info in infos {
+        assert_info(info, position);
+    }
+    let tokens: Vec<&str> = best_move.split_whitespace().collect();
+    let mut board = position.clone();
+    match tokens[..] {
+        ["bestmove", mv] => play(&mut board, mv),
+        ["bestmove", mv, "ponder", ponder] => {
+            play(&mut board, mv);
+            play(&mut board, ponder);
+        }
+        _ => panic!("Malformed {:?}", best_move),
+    }
+}
+
+fn assert_info(line: &str, position: &ChessBoard) {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let mut i = 1;
+    while i < tokens.len() {
+        match tokens[i] {
+            "string" => return,
+            "pv" => {
+                let mut board = position.clone();
+                assert!(i + 1 < tokens.len(), "Empty pv in {:?}", line);
+                for mv in &tokens[i + 1..] {
+                    play(&mut board, mv);
+                }
+                return;
+            }
+            "score" => {
+                assert!(matches!(tokens.get(i + 1), Some(&"cp") | Some(&"mate")), "{:?}", line);
+                assert!(
+                    tokens.get(i + 2).is_some_and(|score| score.parse::<i32>().is_ok()),
+                    "{:?}",
+                    line
+                );
+                i += 3;
+            }
+            "currmove" => {
+                play(&mut position.clone(), tokens[i + 1]);
+                i += 2;
+            }
+            "depth" | "seldepth" | "time" | "nodes" | "nps" | "hashfull" | "currmovenumber" => {
+                assert!(
+                    tokens.get(i + 1).is_some_and(|value| value.parse::<u64>().is_ok()),
+                    "{:?}",
+                    line
+                );
+                i += 2;
+            }
+            token => panic!("Unexpected {} in {:?}", token, line),
+        }
+    }
+}
+
+#[test]
+fn test_handshake() {
+    let mut engine = Engine::start();
+    engine.send("uci");
+    let lines = engine.read_until(|line| line == "uciok");
+    assert!(lines[0].starts_with("id name "), "{:?}", lines);
+    assert!(lines[1].starts_with("id author "), "{:?}", lines);
+    for option in &lines[2..lines.len() - 1] {
+        assert!(
+            option.starts_with("option name ") && option.contains(" type "),
+            "{:?}",
+            option
+        );
+    }
+    assert!(engine.sync().is_empty());
+    engine.quit();
+}
+
+#[test]
+fn test_go_movetime() {
+    let mut engine = Engine::start();
+    engine.send("uci");
+    engine.read_until(|line| line == "uciok");
+    engine.send("ucinewgame");
+    engine.send("position startpos moves e2e4 e7e5");
+    engine.send("go movetime 200");
+    let lines = engine.read_best_move();
+    assert_search_output(&lines, &board(INITIAL_POSITION, &["e2e4", "e7e5"]));
+    assert!(lines.iter().any(|line| line.contains(" pv ")), "{:?}", lines);
+    // No second bestmove follows
+    let late = engine.read_for(Duration::from_millis(300));
+    assert!(late.iter().all(|line| !line.starts_with("bestmove")), "{:?}", late);
+    engine.quit();
+}
+
+#[test]
+fn test_stop_infinite_search() {
+    let mut engine = Engine::start();
+    engine.send("position fen r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+    engine.send("go infinite");
+    let early = engine.read_for(Duration::from_millis(300));
+    assert!(early.iter().all(|line| !line.starts_with("bestmove")), "{:?}", early);
+    engine.send("stop");
+    let mut lines = early;
+    lines.extend(engine.read_best_move());
+    let position = board("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3", &[]);
+    assert_search_output(&lines, &position);
+    // A stop without a search is ignored
+    engine.send("stop");
+    assert!(engine.sync().iter().all(|line| !line.starts_with("bestmove")));
+    engine.quit();
+}
+
+#[test]
+fn test_rapid_stop() {
+    const SEARCHES: usize = 20;
+    let mut engine = Engine::start();
+    engine.send("position startpos moves d2d4");
+    for _ in 0..SEARCHES {
+        engine.send("go infinite");
+        engine.send("stop");
+    }
+    let position = board(INITIAL_POSITION, &["d2d4"]);
+    for _ in 0..SEARCHES {
+        let lines = engine.read_best_move();
+        assert_search_output(&lines, &position);
+    }
+    let late = engine.read_for(Duration::from_millis(500));
+    assert!(late.iter().all(|line| !line.starts_with("bestmove")), "{:?}", late);
+    engine.quit();
+}
+
+#[test]
+fn test_ponder() {
+    let mut engine = Engine::start();
+    // The predicted reply e7e5 is already on the board while pondering
+    let position = board(INITIAL_POSITION, &["e2e4", "e7e5"]);
+    engine.send("position startpos moves e2e4 e7e5");
+    engine.send("go ponder wtime 3000 btime 3000");
+    let early = engine.read_for(Duration::from_millis(300));
+    assert!(early.iter().all(|line| !line.starts_with("bestmove")), "{:?}", early);
+    engine.send("ponderhit");
+    let lines = engine.read_best_move();
+    assert_search_output(&lines, &position);
+
+    // A ponder miss: the GUI stops the ponder search and ignores its bestmove
+    engine.send("position startpos moves e2e4 e7e5");
+    engine.send("go ponder wtime 3000 btime 3000");
+    engine.send("stop");
+    let lines = engine.read_best_move();
+    assert_search_output(&lines, &position);
+    engine.send("ponderhit");
+    let lines = engine.sync();
+    assert!(lines.iter().all(|line| !line.starts_with("bestmove")), "{:?}", lines);
+    engine.quit();
+}
+
+#[test]
+fn test_ponder_before_reply() {
+    let mut engine = Engine::start();
+    let position = board(INITIAL_POSITION, &["e2e4", "e7e5", "g1f3"]);
+    // Ten ponder misses switch to pondering on the position before the predicted reply
+    for _ in 0..11 {
+        engine.send("position startpos moves e2e4 e7e5 g1f3");
+        engine.send("go ponder wtime 3000 btime 3000");
+        engine.read_until(|line| line.starts_with("info depth"));
+        engine.send("stop");
+        let lines = engine.read_best_move();
+        // Only the bestmove is checked, the info lines of the ponder search are about the position before the reply
+        assert_search_output(&lines[lines.len() - 1..], &position);
+    }
+    engine.send("stats");
+    let lines = engine.sync();
+    assert!(
+        lines.iter().any(|line| line.ends_with("MultipleCandidates")),
+        "{:?}",
+        lines
+    );
+    engine.quit();
+}
+
+#[test]
+fn test_go_depth_and_nodes() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4");
+    let position = board(INITIAL_POSITION, &["e2e4"]);
+    // Both searches end by themselves
+    engine.send("go depth 3");
+    let lines = engine.read_best_move();
+    assert_search_output(&lines, &position);
+    for line in &lines {
+        if let Some(depth) = line.strip_prefix("info depth ") {
+            let depth: u32 = depth.split_whitespace().next().unwrap().parse().unwrap();
+            assert!(depth <= 3, "{:?}", line);
+        }
+    }
+    engine.send("go nodes 2000");
+    assert_search_output(&engine.read_best_move(), &position);
+    // The limits do not carry over to the next go
+    engine.send("go infinite");
+    engine.read_until(|line| line.starts_with("info depth 5"));
+    engine.send("stop");
+    assert_search_output(&engine.read_best_move(), &position);
+    engine.quit();
+}
+
+#[test]
+fn test_malformed_input() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4");
+    for garbage in [
+        "",
+        "   ",
+        "hello engine",
+        "position",
+        "position fen not a fen",
+        "position startpos moves e2e4 e2e4",
+        "position startpos moves e7e5x",
+        "setoption",
+        "setoption name",
+        "setoption name Hash value many",
+        "setoption name NoSuchOption value 1",
+        "debug",
+        "ponderhit",
+        "stop",
+    ] {
+        engine.send(garbage);
+    }
+    let lines = engine.sync();
+    assert!(lines.iter().all(|line| line.starts_with("info string")), "{:?}", lines);
+
+    // Searches with malformed limits still end with a bestmove, in the position of the last valid command
+    let position = board(INITIAL_POSITION, &["e2e4"]);
+    for go in [
+        "go depth",
+        "go depth deep nodes many",
+        "go movetime",
+        "go wtime -5 btime x",
+    ] {
+        engine.send(go);
+        engine.send("stop");
+        let lines = engine.read_best_move();
+        assert_search_output(&lines, &position);
+    }
+    engine.send("go movetime 100");
+    assert_search_output(&engine.read_best_move(), &position);
+    engine.quit();
+}
+
+#[test]
+fn test_no_legal_move() {
+    let mut engine = Engine::start();
+    for fen in [
+        // Checkmate and stalemate
+        "1k6/8/8/8/8/8/PPn5/KN6 w - - 0 1",
+        "1k6/8/8/8/8/1r6/7r/K7 w - - 0 1",
+    ] {
+        engine.send(&format!("position fen {}", fen));
+        engine.send("go movetime 100");
+        let lines = engine.read_best_move();
+        assert_eq!(lines.last().unwrap(), "bestmove 0000");
+    }
+    engine.quit();
+}
+
+#[test]
+fn test_quit_during_search() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go infinite");
+    engine.read_until(|line| line.starts_with("info depth"));
+    engine.quit();
+}
+
+#[test]
+fn test_go_during_search() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4");
+    engine.send("go infinite");
+    engine.read_until(|line| line.starts_with("info depth"));
+    // The infinite search still gets its bestmove, then the second go searches
+    engine.send("go movetime 300");
+    let position = board(INITIAL_POSITION, &["e2e4"]);
+    assert_search_output(&engine.read_best_move(), &position);
+    engine.send("stop");
+    assert_search_output(&engine.read_best_move(), &position);
+    let late = engine.read_for(Duration::from_millis(500));
+    assert!(late.iter().all(|line| !line.starts_with("bestmove")), "{:?}", late);
+    engine.quit();
+}
+
+#[test]
+fn test_debug_during_search() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go infinite");
+    engine.read_until(|line| line.starts_with("info depth"));
+    engine.send("debug on");
+    engine.send("stop");
+    assert_search_output(&engine.read_best_move(), &board(INITIAL_POSITION, &[]));
+    // The engine of the next search sends its diagnostics
+    engine.send("go movetime 200");
+    let lines = engine.read_best_move();
+    assert!(
+        lines
+            .iter()
+            .any(|line| line.starts_with("info string Search time limit")),
+        "{:?}",
+        lines
+    );
+    engine.quit();
+}